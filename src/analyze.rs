@@ -10,12 +10,27 @@
 // is strictly forbidden unless prior written permission is obtained
 // from Nuwaira.
 
-use crate::refs_finder::{analyze_frappe_field_usage, Output as RefsFinderOutput};
+use crate::cache::{FileRecord, FileTracker};
+use crate::config::Config;
+use crate::py_refs::{doctype_json_files, DocTypeIndex};
+use crate::refs_finder::{
+    analyze_file_field_usage, analyze_frappe_field_usage, occurrence_path, source_files,
+    Output as RefsFinderOutput, Stats as RefsStats,
+};
 use crate::stringutil::to_snakec_var;
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
 use std::fs;
 use std::io::{BufRead, BufReader};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+lazy_static::lazy_static! {
+    static ref RX_DOCTYPE_NAME: regex::Regex =
+        regex::Regex::new(r#""name"\s*:\s*"([^"]+)""#).expect("rx_doctype_name bad");
+}
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DocType {
@@ -25,49 +40,310 @@ pub struct DocType {
     pub meta_file: Option<String>,
     pub test_file: Option<String>,
     pub module: String,
+    /// App directory the DocType lives in (paths above are relative to it)
+    #[serde(default)]
+    pub app: String,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Module {
     pub name: String,
     pub location: String,
+    #[serde(default)]
+    pub app: String,
 }
 
-#[derive(Serialize, Deserialize)]
-struct Analysis {
-    doctypes: Vec<DocType>,
-    modules: Vec<Module>,
-    symbol_refs: Option<RefsFinderOutput>,
-}
-
-#[derive(Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct AnalyzedData {
     pub doctypes: Vec<DocType>,
     pub modules: Vec<Module>,
     pub symbol_refs: Option<RefsFinderOutput>,
+    /// Content hash of every analyzed file, for incremental analysis
+    #[serde(default)]
+    pub files: BTreeMap<String, FileRecord>,
+    /// Table fields and controller classes of every DocType JSON
+    #[serde(default)]
+    pub doctype_index: DocTypeIndex,
 }
 
 impl AnalyzedData {
-    pub fn from_toml_str(toml_str: &str) -> Result<AnalyzedData, serde_json::Error> {
-        // toml::from_str(toml_str)
-        serde_json::from_str(toml_str)
+    /// Find a DocType by name (case-insensitive), optionally only in `app`.
+    pub fn find_doctype(&self, name: &str, app: Option<&str>) -> Option<&DocType> {
+        self.doctypes.iter().find(|dt| {
+            dt.name.to_lowercase() == name.to_lowercase()
+                && app.is_none_or(|app| dt.app.is_empty() || dt.app == app)
+        })
     }
+}
+
+/// Wall time and volume of each analysis phase, shown by `analyze --stats`.
+#[derive(Debug, Default, Clone)]
+pub struct AnalysisStats {
+    pub threads: usize,
+    pub apps: usize,
+    pub module_scan: Duration,
+    pub modules: usize,
+    pub doctype_scan: Duration,
+    pub doctypes: usize,
+    /// DocType JSON files (re)parsed into the index
+    pub json_parsed: usize,
+    pub reference_scan: Duration,
+    pub source_files: usize,
+    /// Source files parsed; the others were unchanged since the previous analysis
+    pub source_parsed: usize,
+}
+
+impl AnalysisStats {
+    pub fn total(&self) -> Duration {
+        self.module_scan + self.doctype_scan + self.reference_scan
+    }
+}
 
-    pub fn from_file(file_path: &str) -> Result<AnalyzedData, Box<dyn std::error::Error>> {
-        let content = std::fs::read_to_string(file_path)?;
-        let data = Self::from_toml_str(&content)?;
-        Ok(data)
+impl fmt::Display for AnalysisStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ms = |d: Duration| format!("{:>10.1} ms", d.as_secs_f64() * 1000.0);
+        writeln!(f, "Analysis phases (worker threads: {}):", self.threads)?;
+        writeln!(
+            f,
+            "  module scan    {}  ({} apps, {} modules)",
+            ms(self.module_scan),
+            self.apps,
+            self.modules
+        )?;
+        writeln!(
+            f,
+            "  DocType scan   {}  ({} DocTypes, {} JSON files parsed)",
+            ms(self.doctype_scan),
+            self.doctypes,
+            self.json_parsed
+        )?;
+        writeln!(
+            f,
+            "  reference scan {}  ({} of {} source files parsed)",
+            ms(self.reference_scan),
+            self.source_parsed,
+            self.source_files
+        )?;
+        write!(f, "  total          {}", ms(self.total()))
     }
 }
 
+/// Analyze a single app directory, reporting the time spent in each phase.
 pub fn analyze_frappe_app(
     root: &str,
     relative_path: &str,
-    output_file: &str,
-) -> anyhow::Result<()> {
+) -> anyhow::Result<(AnalyzedData, AnalysisStats)> {
     let root_path = Path::new(root);
-    let leaf = root_path.file_name().and_then(|s| s.to_str()).unwrap_or("");
-    let root_sub_path = root_path.join(leaf);
+    let mut stats = AnalysisStats {
+        threads: rayon::current_num_threads(),
+        apps: 1,
+        ..Default::default()
+    };
+
+    let started = Instant::now();
+    let modules = scan_modules(root_path, relative_path)?;
+    stats.module_scan = started.elapsed();
+    stats.modules = modules.len();
+
+    let started = Instant::now();
+    let index = DocTypeIndex::scan(&[root_path]);
+    let doctypes = scan_doctypes(root_path, relative_path, &modules, &index)?;
+    stats.doctype_scan = started.elapsed();
+    stats.doctypes = doctypes.len();
+    stats.json_parsed = index.files().count();
+
+    let started = Instant::now();
+    let symbol_refs = analyze_frappe_field_usage(&root_path.to_string_lossy(), &index);
+    stats.reference_scan = started.elapsed();
+    if let Ok(refs) = &symbol_refs {
+        stats.source_files = refs.stats.py_files + refs.stats.js_files;
+        stats.source_parsed = stats.source_files;
+    }
+
+    let data = AnalyzedData {
+        doctypes,
+        modules,
+        symbol_refs: symbol_refs.ok(),
+        doctype_index: index,
+        ..Default::default()
+    };
+    Ok((data, stats))
+}
+
+/// Analyze every app in `config.app_dirs` together.
+///
+/// With the `previous` analysis only files whose content hash changed are
+/// parsed again; `None` rebuilds everything.
+pub fn analyze_bench(
+    config: &Config,
+    previous: Option<&AnalyzedData>,
+) -> anyhow::Result<AnalyzedData> {
+    analyze_bench_with_stats(config, previous).map(|(data, _)| data)
+}
+
+/// `analyze_bench` that also reports the time spent in each phase.
+///
+/// Apps and files are scanned on rayon's worker pool; results are merged in
+/// app and path order so the output doesn't depend on scheduling.
+pub fn analyze_bench_with_stats(
+    config: &Config,
+    previous: Option<&AnalyzedData>,
+) -> anyhow::Result<(AnalyzedData, AnalysisStats)> {
+    let mut stats = AnalysisStats {
+        threads: rayon::current_num_threads(),
+        ..Default::default()
+    };
+    let mut tracker = FileTracker::new(previous.map(|p| p.files.clone()).unwrap_or_default());
+
+    // Module scan
+    let started = Instant::now();
+    let scanned: Vec<(String, anyhow::Result<Vec<Module>>)> = config
+        .app_dirs
+        .par_iter()
+        .map(|app| {
+            (
+                app.clone(),
+                scan_modules(Path::new(&config.app_path(app)), app),
+            )
+        })
+        .collect();
+    let mut apps: Vec<(String, Vec<Module>)> = Vec::new();
+    for (app, result) in scanned {
+        match result {
+            Ok(app_modules) => apps.push((app, app_modules)),
+            Err(e) if app != config.app_relative_path => {
+                tracing::warn!("Skipping app '{}': {}", app, e);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    stats.module_scan = started.elapsed();
+    stats.apps = apps.len();
+    stats.modules = apps.iter().map(|(_, m)| m.len()).sum();
+
+    // DocType scan. Child tables may live in another app (e.g. rows of an
+    // ERPNext DocType), so the index covers every app
+    let started = Instant::now();
+    let app_paths: Vec<String> = config.app_dirs.iter().map(|a| config.app_path(a)).collect();
+    let mut index = previous
+        .map(|p| p.doctype_index.clone())
+        .unwrap_or_default();
+    let json_files = doctype_json_files(&app_paths);
+    let changed_json: Vec<&PathBuf> = json_files
+        .iter()
+        .zip(tracker.check_all(&json_files)?)
+        .filter(|(_, changed)| *changed)
+        .map(|(path, _)| path)
+        .collect();
+    stats.json_parsed = changed_json.len();
+    index.add_files(&changed_json);
+    let json_keys: BTreeSet<String> = json_files
+        .iter()
+        .map(|p| p.to_string_lossy().to_string())
+        .collect();
+    let removed: Vec<String> = index
+        .files()
+        .filter(|f| !json_keys.contains(*f))
+        .map(|f| f.to_string())
+        .collect();
+    for file in removed {
+        index.remove_file(Path::new(&file));
+    }
+
+    let scanned: Vec<anyhow::Result<Vec<DocType>>> = apps
+        .par_iter()
+        .map(|(app, app_modules)| {
+            scan_doctypes(Path::new(&config.app_path(app)), app, app_modules, &index)
+        })
+        .collect();
+    let mut doctypes = Vec::new();
+    for result in scanned {
+        doctypes.extend(result?);
+    }
+    stats.doctype_scan = started.elapsed();
+    stats.doctypes = doctypes.len();
+
+    // Reference scan. References found in unchanged files stay valid as long
+    // as child tables and controller classes resolve the same way
+    let started = Instant::now();
+    let mut symbol_refs = match previous {
+        Some(AnalyzedData {
+            symbol_refs: Some(refs),
+            doctype_index,
+            ..
+        }) if doctype_index.resolves_like(&index) => refs.clone(),
+        _ => RefsFinderOutput::new(),
+    };
+    let rescan_all = symbol_refs.stats.files_scanned == 0;
+
+    let mut refs_stats = RefsStats::default();
+    let mut files = Vec::new();
+    for (app, _) in &apps {
+        match source_files(Path::new(&config.app_path(app)), &mut refs_stats) {
+            Ok(app_files) => files.extend(app_files),
+            Err(e) => tracing::warn!("Field usage analysis failed for '{}': {}", app, e),
+        }
+    }
+    let to_parse: Vec<&PathBuf> = files
+        .iter()
+        .zip(tracker.check_all(&files)?)
+        .filter(|(_, changed)| *changed || rescan_all)
+        .map(|(path, _)| path)
+        .collect();
+
+    // Drop what the changed files and the files that disappeared contributed
+    let mut stale: BTreeSet<String> = to_parse.iter().map(|f| occurrence_path(f)).collect();
+    stale.extend(tracker.unseen().map(|f| occurrence_path(Path::new(f))));
+    symbol_refs.remove_files(&stale);
+
+    let parsed: Vec<RefsFinderOutput> = to_parse
+        .par_iter()
+        .map(|file| {
+            analyze_file_field_usage(file, &index).unwrap_or_else(|e| {
+                tracing::warn!("Field usage analysis failed for {}: {}", file.display(), e);
+                RefsFinderOutput::new()
+            })
+        })
+        .collect();
+    for refs in parsed {
+        symbol_refs.merge(refs);
+    }
+    symbol_refs.sort();
+    symbol_refs.stats = refs_stats;
+    symbol_refs.recount();
+    stats.reference_scan = started.elapsed();
+    stats.source_files = files.len();
+    stats.source_parsed = to_parse.len();
+    tracing::info!(
+        "Parsed {} of {} source files",
+        stats.source_parsed,
+        stats.source_files
+    );
+
+    let data = AnalyzedData {
+        doctypes,
+        modules: apps.into_iter().flat_map(|(_, m)| m).collect(),
+        symbol_refs: Some(symbol_refs),
+        files: tracker.into_records(),
+        doctype_index: index,
+    };
+    Ok((data, stats))
+}
+
+/// Scan `modules.txt` and every `<module>/doctype/<dt>/` directory of an app.
+pub fn scan_app(
+    root_path: &Path,
+    relative_path: &str,
+    index: &DocTypeIndex,
+) -> anyhow::Result<(Vec<Module>, Vec<DocType>)> {
+    let modules = scan_modules(root_path, relative_path)?;
+    let doctypes = scan_doctypes(root_path, relative_path, &modules, index)?;
+    Ok((modules, doctypes))
+}
+
+/// Modules listed in the app's `modules.txt` that have a directory.
+pub fn scan_modules(root_path: &Path, relative_path: &str) -> anyhow::Result<Vec<Module>> {
+    let root_sub_path = app_package_path(root_path);
     let modules_txt = root_sub_path.join("modules.txt");
 
     // println!("Modules file: {:?}", modules_txt);
@@ -82,7 +358,6 @@ pub fn analyze_frappe_app(
     let file = fs::File::open(&modules_txt)?;
     let reader = BufReader::new(file);
     let mut modules = Vec::new();
-    let mut doctypes = Vec::new();
 
     for line in reader.lines() {
         let line = line?;
@@ -103,103 +378,125 @@ pub fn analyze_frappe_app(
                     &root_sub_path.to_string_lossy().to_string(),
                     relative_path,
                 ),
+                app: relative_path.to_string(),
             });
+        }
+    }
+
+    Ok(modules)
+}
+
+/// Every `<module>/doctype/<dt>/` directory of the given modules, in path order.
+pub fn scan_doctypes(
+    root_path: &Path,
+    relative_path: &str,
+    modules: &[Module],
+    index: &DocTypeIndex,
+) -> anyhow::Result<Vec<DocType>> {
+    let root_sub_path = app_package_path(root_path);
+    let mut doctypes = Vec::new();
+
+    for module in modules {
+        let doctype_path = root_sub_path
+            .join(to_snakec_var(&module.name))
+            .join("doctype");
+        tracing::debug!("Scanning doctype in {:?}", doctype_path);
+        if !doctype_path.is_dir() {
+            continue;
+        }
+
+        let mut dirs = Vec::new();
+        for entry in fs::read_dir(&doctype_path)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                dirs.push(entry.path());
+            }
+        }
+        dirs.sort();
 
-            // scan doctypes
-            let doctype_path = module_path.join("doctype");
-            println!("Scanning doctype in {:?}", doctype_path);
-            if doctype_path.exists() && doctype_path.is_dir() {
-                for entry in fs::read_dir(&doctype_path)? {
-                    let entry = entry?;
-                    if entry.file_type()?.is_dir() {
-                        let doctype_name = entry.file_name().to_string_lossy().to_string();
-                        if doctype_name.is_empty() {
-                            continue;
-                        }
-                        if ["__pycache__", ".git"].contains(&doctype_name.as_str()) {
-                            continue;
-                        }
-                        let doctype_dir = entry.path();
-
-                        let backend_file = doctype_dir.join(format!("{}.py", &doctype_name));
-                        let frontend_file = doctype_dir.join(format!("{}.js", &doctype_name));
-                        let meta_file = doctype_dir.join(format!("{}.json", &doctype_name));
-                        let test_file = doctype_dir.join(format!("test_{}.py", &doctype_name));
-
-                        if !meta_file.exists() {
-                            continue;
-                        }
-
-                        // get real doctype name by regex match in meta_file, looking for
-                        // text like: `"name": "SHU Period"`
-                        let meta_content = fs::read_to_string(&meta_file)?;
-                        let real_doctype_name = if let Some(caps) =
-                            regex::Regex::new(r#""name"\s*:\s*"([^"]+)""#)
-                                .unwrap()
-                                .captures(&meta_content)
-                        {
-                            caps.get(1)
-                                .map_or(doctype_name.clone(), |m| m.as_str().to_string())
-                        } else {
-                            capitalize_words(&doctype_name)
-                        };
-
-                        doctypes.push(DocType {
-                            name: real_doctype_name,
-                            backend_file: to_relative_path(
-                                &backend_file.to_string_lossy(),
-                                &root_sub_path.to_string_lossy(),
-                                relative_path,
-                            ),
-                            frontend_file: if frontend_file.exists() {
-                                Some(to_relative_path(
-                                    &frontend_file.to_string_lossy(),
-                                    &root_sub_path.to_string_lossy(),
-                                    relative_path,
-                                ))
-                            } else {
-                                None
-                            },
-                            meta_file: if meta_file.exists() {
-                                Some(to_relative_path(
-                                    &meta_file.to_string_lossy(),
-                                    &root_sub_path.to_string_lossy(),
-                                    relative_path,
-                                ))
-                            } else {
-                                None
-                            },
-                            test_file: if test_file.exists() {
-                                Some(to_relative_path(
-                                    &test_file.to_string_lossy(),
-                                    &root_sub_path.to_string_lossy(),
-                                    relative_path,
-                                ))
-                            } else {
-                                None
-                            },
-                            module: module_title.to_string(),
-                        });
-                    }
-                }
+        for dir in dirs {
+            if let Some(doctype) =
+                scan_doctype_dir(&dir, &module.name, &root_sub_path, relative_path, index)?
+            {
+                doctypes.push(doctype);
             }
         }
     }
 
-    let symbol_refs = analyze_frappe_field_usage(&root_path.to_string_lossy());
-    let analysis = Analysis {
-        doctypes,
-        modules,
-        symbol_refs: symbol_refs.ok(),
+    Ok(doctypes)
+}
+
+/// Build a `DocType` entry from a single `<module>/doctype/<dt>/` directory.
+///
+/// Returns `None` when the directory is not a DocType (no `<dt>.json`).
+/// The DocType name is taken from `index` when the JSON is indexed.
+pub fn scan_doctype_dir(
+    doctype_dir: &Path,
+    module_title: &str,
+    root_sub_path: &Path,
+    relative_path: &str,
+    index: &DocTypeIndex,
+) -> anyhow::Result<Option<DocType>> {
+    let doctype_name = doctype_dir
+        .file_name()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    if doctype_name.is_empty() {
+        return Ok(None);
+    }
+    if ["__pycache__", ".git"].contains(&doctype_name.as_str()) {
+        return Ok(None);
+    }
+
+    let backend_file = doctype_dir.join(format!("{}.py", &doctype_name));
+    let frontend_file = doctype_dir.join(format!("{}.js", &doctype_name));
+    let meta_file = doctype_dir.join(format!("{}.json", &doctype_name));
+    let test_file = doctype_dir.join(format!("test_{}.py", &doctype_name));
+
+    if !meta_file.exists() {
+        return Ok(None);
+    }
+
+    // get real doctype name by regex match in meta_file, looking for
+    // text like: `"name": "SHU Period"`
+    let real_doctype_name = if let Some(name) = index.doctype_name(&meta_file) {
+        name.to_string()
+    } else if let Some(caps) = RX_DOCTYPE_NAME.captures(&fs::read_to_string(&meta_file)?) {
+        caps.get(1)
+            .map_or(doctype_name.clone(), |m| m.as_str().to_string())
+    } else {
+        capitalize_words(&doctype_name)
     };
 
-    let json_str = serde_json::to_string_pretty(&analysis)?;
-    fs::write(output_file, json_str)?;
+    let root_sub_path = root_sub_path.to_string_lossy();
+    let rel = |p: &Path| to_relative_path(&p.to_string_lossy(), &root_sub_path, relative_path);
 
-    Ok(())
+    Ok(Some(DocType {
+        name: real_doctype_name,
+        backend_file: rel(&backend_file),
+        frontend_file: if frontend_file.exists() {
+            Some(rel(&frontend_file))
+        } else {
+            None
+        },
+        meta_file: Some(rel(&meta_file)),
+        test_file: if test_file.exists() {
+            Some(rel(&test_file))
+        } else {
+            None
+        },
+        module: module_title.to_string(),
+        app: relative_path.to_string(),
+    }))
+}
+
+/// The python package directory inside an app, e.g. `apps/erpnext/erpnext`.
+pub fn app_package_path(root_path: &Path) -> std::path::PathBuf {
+    let leaf = root_path.file_name().and_then(|s| s.to_str()).unwrap_or("");
+    root_path.join(leaf)
 }
 
-fn to_relative_path(full_path: &str, base_path: &str, relative_path: &str) -> String {
+pub fn to_relative_path(full_path: &str, base_path: &str, relative_path: &str) -> String {
     if let Some(pos) = full_path.find(base_path) {
         let rel_path = &full_path[pos + base_path.len()..];
         format!("{}{}", relative_path, rel_path)
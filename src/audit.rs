@@ -0,0 +1,326 @@
+// Copyright (C) 2025 Nuwaira
+// All Rights Reserved.
+//
+// NOTICE: All information contained herein is, and remains
+// the property of Nuwaira.
+// The intellectual and technical concepts contained
+// herein are proprietary to Nuwaira
+// and are protected by trade secret or copyright law.
+// Dissemination of this information or reproduction of this material
+// is strictly forbidden unless prior written permission is obtained
+// from Nuwaira.
+#![allow(dead_code)]
+
+//! Append-only JSONL audit log of the tool calls that can change the bench:
+//! the `create_*` generators, the shell tools and the database tools.
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use rmcp::{model::*, ErrorData as McpError};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::policy::ToolGroup;
+
+lazy_static::lazy_static! {
+    /// Serializes appends from concurrent sessions
+    static ref APPEND_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// One line of the audit log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub timestamp: DateTime<Utc>,
+    /// Client session of the call, 0 for stdio
+    pub session: u64,
+    pub tool: String,
+    pub arguments: Value,
+    /// Command line of the `bench` process the tool ran
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    pub is_error: bool,
+    pub duration_ms: u64,
+    /// Size of the text returned to the agent, in bytes
+    pub output_bytes: usize,
+    /// Files written by a `create_*` tool
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<String>,
+    /// Error message, or the first line of a failed result
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Whether calls to `tool` are audited.
+pub fn is_audited(tool: &str) -> bool {
+    ToolGroup::of(tool).is_some()
+}
+
+impl AuditEntry {
+    /// Entry for a finished call, taking the command, exit code and files
+    /// from the result's structured content.
+    pub fn from_call(
+        session: u64,
+        tool: &str,
+        arguments: Option<&JsonObject>,
+        result: &Result<CallToolResult, McpError>,
+        duration: Duration,
+    ) -> Self {
+        let mut entry = AuditEntry {
+            timestamp: Utc::now(),
+            session,
+            tool: tool.to_string(),
+            arguments: arguments
+                .map(|a| Value::Object(a.clone()))
+                .unwrap_or(Value::Null),
+            command: None,
+            exit_code: None,
+            is_error: true,
+            duration_ms: duration.as_millis() as u64,
+            output_bytes: 0,
+            files: Vec::new(),
+            error: None,
+        };
+        let result = match result {
+            Ok(result) => result,
+            Err(e) => {
+                entry.error = Some(e.message.to_string());
+                return entry;
+            }
+        };
+
+        entry.is_error = result.is_error.unwrap_or(false);
+        let texts: Vec<&str> = result
+            .content
+            .iter()
+            .filter_map(|c| c.as_text().map(|t| t.text.as_str()))
+            .collect();
+        entry.output_bytes = texts.iter().map(|t| t.len()).sum();
+        if entry.is_error {
+            entry.error = texts
+                .first()
+                .and_then(|t| t.lines().next())
+                .map(|l| l.chars().take(200).collect());
+        }
+
+        if let Some(data) = &result.structured_content {
+            // Database tools nest the bench output
+            let run = data.get("output").filter(|o| o.is_object()).unwrap_or(data);
+            entry.command = run
+                .get("command")
+                .and_then(|c| c.as_str())
+                .map(|c| c.to_string());
+            entry.exit_code = run
+                .get("exit_code")
+                .and_then(|c| c.as_i64())
+                .map(|c| c as i32);
+            entry.files = data
+                .get("files")
+                .and_then(|f| f.as_array())
+                .map(|files| {
+                    files
+                        .iter()
+                        .filter_map(|f| f.get("path").and_then(|p| p.as_str()))
+                        .map(|p| p.to_string())
+                        .collect()
+                })
+                .unwrap_or_default();
+        }
+        entry
+    }
+
+    /// One-line summary followed by the written files.
+    pub fn render(&self) -> String {
+        let status = match (self.exit_code, self.is_error) {
+            (Some(code), _) => format!("exit {}", code),
+            (None, true) => "error".to_string(),
+            (None, false) => "ok".to_string(),
+        };
+        let mut line = format!(
+            "{}  {:<22} {:<8} {:>7}ms {:>8}B",
+            self.timestamp.format("%Y-%m-%d %H:%M:%S"),
+            self.tool,
+            status,
+            self.duration_ms,
+            self.output_bytes
+        );
+        match (&self.command, &self.error) {
+            (Some(command), _) => line.push_str(&format!("  {}", command)),
+            (None, Some(error)) => line.push_str(&format!("  {}", error)),
+            (None, None) => line.push_str(&format!("  {}", self.arguments)),
+        }
+        for file in &self.files {
+            line.push_str(&format!("\n    + {}", file));
+        }
+        line
+    }
+}
+
+/// Append an entry as one JSON line, creating the log if needed.
+pub fn append(path: &Path, entry: &AuditEntry) -> std::io::Result<()> {
+    let line = serde_json::to_string(entry)?;
+    let _guard = APPEND_LOCK.lock().unwrap();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", line)
+}
+
+/// All entries of the log, skipping lines that don't parse.
+pub fn read(path: &Path) -> std::io::Result<Vec<AuditEntry>> {
+    let content = fs::read_to_string(path)?;
+    Ok(content
+        .lines()
+        .filter_map(|l| serde_json::from_str(l).ok())
+        .collect())
+}
+
+/// Which entries `frappe-mcp audit` prints.
+#[derive(Debug, Default)]
+pub struct AuditFilter {
+    pub tool: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub errors_only: bool,
+    /// Substring of the arguments, command or files
+    pub contains: Option<String>,
+}
+
+impl AuditFilter {
+    pub fn matches(&self, entry: &AuditEntry) -> bool {
+        if self.tool.as_ref().is_some_and(|t| *t != entry.tool) {
+            return false;
+        }
+        if self.since.is_some_and(|since| entry.timestamp < since) {
+            return false;
+        }
+        if self.errors_only && !entry.is_error {
+            return false;
+        }
+        if let Some(needle) = &self.contains {
+            let haystack = format!(
+                "{} {} {}",
+                entry.arguments,
+                entry.command.as_deref().unwrap_or_default(),
+                entry.files.join(" ")
+            );
+            if !haystack.contains(needle.as_str()) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// `--since` value: an RFC 3339 timestamp, a date, or an age like `30m`, `2h`, `7d`.
+pub fn parse_since(value: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(t) = DateTime::parse_from_rfc3339(value) {
+        return Ok(t.with_timezone(&Utc));
+    }
+    if let Ok(d) = chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(d.and_hms_opt(0, 0, 0).unwrap().and_utc());
+    }
+    let invalid = || format!("Invalid --since value '{}'", value);
+    let unit = value.chars().last().ok_or_else(invalid)?;
+    let number: i64 = value[..value.len() - unit.len_utf8()]
+        .parse()
+        .map_err(|_| invalid())?;
+    let age = match unit {
+        'm' => chrono::Duration::minutes(number),
+        'h' => chrono::Duration::hours(number),
+        'd' => chrono::Duration::days(number),
+        _ => return Err(invalid()),
+    };
+    Ok(Utc::now() - age)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_from_call() {
+        let mut args = JsonObject::new();
+        args.insert("args".to_string(), serde_json::json!(["migrate"]));
+        let mut result = CallToolResult::success(vec![Content::text("done")]);
+        result.structured_content = Some(serde_json::json!({
+            "command": "bench --site dev.local migrate",
+            "exit_code": 0,
+            "success": true,
+        }));
+        let entry = AuditEntry::from_call(
+            0,
+            "run_bench_command",
+            Some(&args),
+            &Ok(result),
+            Duration::from_millis(1500),
+        );
+        assert_eq!(
+            entry.command.as_deref(),
+            Some("bench --site dev.local migrate")
+        );
+        assert_eq!(entry.exit_code, Some(0));
+        assert_eq!(entry.output_bytes, 4);
+        assert_eq!(entry.duration_ms, 1500);
+        assert!(!entry.is_error);
+
+        let mut result = CallToolResult::success(vec![Content::text("✓ Created")]);
+        result.structured_content = Some(serde_json::json!({
+            "name": "Anggota",
+            "files": [{"kind": "metadata", "path": "/b/apps/k/anggota.json"}],
+        }));
+        let entry = AuditEntry::from_call(3, "create_doctype", None, &Ok(result), Duration::ZERO);
+        assert_eq!(entry.files, vec!["/b/apps/k/anggota.json"]);
+        assert!(entry.command.is_none());
+
+        let err = McpError::invalid_params("bad args", None);
+        let entry = AuditEntry::from_call(0, "run_tests", None, &Err(err), Duration::ZERO);
+        assert!(entry.is_error);
+        assert_eq!(entry.error.as_deref(), Some("bad args"));
+    }
+
+    #[test]
+    fn test_append_read_and_filter() {
+        let path = std::env::temp_dir().join(format!(
+            "frappe-mcp-audit-test-{}/audit.jsonl",
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        let failed = CallToolResult::error(vec![Content::text("Denied by policy: no\nmore")]);
+        for (tool, result) in [
+            ("run_db_command", Ok(failed)),
+            ("run_tests", Ok(CallToolResult::success(vec![]))),
+        ] {
+            let entry = AuditEntry::from_call(0, tool, None, &result, Duration::ZERO);
+            append(&path, &entry).unwrap();
+        }
+        let entries = read(&path).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].error.as_deref(), Some("Denied by policy: no"));
+
+        let filter = AuditFilter {
+            errors_only: true,
+            ..Default::default()
+        };
+        let matched: Vec<_> = entries.iter().filter(|e| filter.matches(e)).collect();
+        assert_eq!(matched.len(), 1);
+        assert_eq!(matched[0].tool, "run_db_command");
+
+        let filter = AuditFilter {
+            since: Some(parse_since("1h").unwrap()),
+            tool: Some("run_tests".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(entries.iter().filter(|e| filter.matches(e)).count(), 1);
+        assert!(parse_since("2025-01-31").is_ok());
+        assert!(parse_since("soon").is_err());
+
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
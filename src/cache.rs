@@ -0,0 +1,453 @@
+// Copyright (C) 2025 Nuwaira
+// All Rights Reserved.
+//
+// NOTICE: All information contained herein is, and remains
+// the property of Nuwaira.
+// The intellectual and technical concepts contained
+// herein are proprietary to Nuwaira
+// and are protected by trade secret or copyright law.
+// Dissemination of this information or reproduction of this material
+// is strictly forbidden unless prior written permission is obtained
+// from Nuwaira.
+#![allow(dead_code)]
+
+//! Versioned analysis cache stored per bench in `<bench>/.frappe-mcp/`.
+//!
+//! Every cache file carries the schema version and the fingerprint of the
+//! config that produced it, so a cache written by another config (or by an
+//! incompatible version) is never loaded. It also records a content hash per
+//! analyzed file so the next analysis only re-parses what changed.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use anyhow::{bail, Context, Result};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::analyze::AnalyzedData;
+use crate::config::Config;
+
+/// Bump when the layout of `AnalyzedData` changes incompatibly, and teach
+/// `migrate` how to upgrade the previous version if that is possible.
+pub const SCHEMA_VERSION: u32 = 5;
+
+/// Directory (relative to the bench) holding frappe-mcp's state.
+pub const STATE_DIR: &str = ".frappe-mcp";
+
+/// What the analysis was computed for.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Fingerprint {
+    pub bench_dir: String,
+    pub app: String,
+    pub apps: Vec<String>,
+    pub site: String,
+}
+
+impl Fingerprint {
+    pub fn from_config(config: &Config) -> Self {
+        let bench_dir = fs::canonicalize(&config.frappe_bench_dir)
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_else(|_| config.frappe_bench_dir.clone());
+        Fingerprint {
+            bench_dir,
+            app: config.app_relative_path.clone(),
+            apps: config.app_dirs.clone(),
+            site: config.site.clone(),
+        }
+    }
+
+    /// Short stable id used in the cache file name.
+    fn id(&self) -> String {
+        // FNV-1a, stable across builds unlike std's hasher
+        let mut hash: u64 = 0xcbf29ce484222325;
+        let key = format!(
+            "{}\0{}\0{}\0{}",
+            self.bench_dir,
+            self.app,
+            self.apps.join(","),
+            self.site
+        );
+        for b in key.bytes() {
+            hash ^= b as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        format!("{:016x}", hash)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheFile<T> {
+    schema_version: u32,
+    generator: String,
+    created_at: chrono::DateTime<chrono::Utc>,
+    fingerprint: Fingerprint,
+    data: T,
+}
+
+/// Analysis cache of one config.
+#[derive(Debug, Clone)]
+pub struct AnalysisCache {
+    path: PathBuf,
+    fingerprint: Fingerprint,
+}
+
+impl AnalysisCache {
+    pub fn for_config(config: &Config) -> Self {
+        let fingerprint = Fingerprint::from_config(config);
+        let path = state_dir(config).join(format!("analysis-{}.json", fingerprint.id()));
+        AnalysisCache { path, fingerprint }
+    }
+
+    /// Cache of an analysis of a single directory, kept apart from the
+    /// config's: its fingerprint names the directory instead of the apps, so
+    /// the server never loads it as the bench's analysis.
+    pub fn for_app_dir(config: &Config, app_dir: &str) -> Self {
+        let mut fingerprint = Fingerprint::from_config(config);
+        let app_dir = fs::canonicalize(app_dir)
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_else(|_| app_dir.to_string());
+        fingerprint.apps = vec![app_dir];
+        let path = state_dir(config).join(format!("analysis-dir-{}.json", fingerprint.id()));
+        AnalysisCache { path, fingerprint }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn exists(&self) -> bool {
+        self.path.is_file()
+    }
+
+    /// Load the cached analysis, failing with the reason when it is missing,
+    /// foreign or of an unsupported version.
+    pub fn load(&self) -> Result<AnalyzedData> {
+        let content = fs::read_to_string(&self.path)
+            .with_context(|| format!("read analysis cache {}", self.path.display()))?;
+        let doc: Value = serde_json::from_str(&content)
+            .with_context(|| format!("parse analysis cache {}", self.path.display()))?;
+
+        let doc = migrate(doc)?;
+        let file: CacheFile<AnalyzedData> =
+            serde_json::from_value(doc).context("decode analysis cache")?;
+
+        if file.fingerprint != self.fingerprint {
+            bail!(
+                "analysis cache was built for another configuration ({} / {} / {})",
+                file.fingerprint.bench_dir,
+                file.fingerprint.app,
+                file.fingerprint.site
+            );
+        }
+        Ok(file.data)
+    }
+
+    /// Atomically replace the cache with `data`.
+    pub fn save(&self, data: &AnalyzedData) -> Result<()> {
+        let dir = self.path.parent().unwrap_or_else(|| Path::new("."));
+        fs::create_dir_all(dir).with_context(|| format!("create {}", dir.display()))?;
+
+        let file = CacheFile {
+            schema_version: SCHEMA_VERSION,
+            generator: format!("frappe-mcp {}", env!("CARGO_PKG_VERSION")),
+            created_at: chrono::Utc::now(),
+            fingerprint: self.fingerprint.clone(),
+            data,
+        };
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_vec(&file)?)
+            .with_context(|| format!("write {}", tmp.display()))?;
+        fs::rename(&tmp, &self.path)
+            .with_context(|| format!("write analysis cache {}", self.path.display()))?;
+        Ok(())
+    }
+
+    /// Remove the cache file, if any.
+    pub fn invalidate(&self) -> Result<()> {
+        if self.path.exists() {
+            fs::remove_file(&self.path)
+                .with_context(|| format!("remove {}", self.path.display()))?;
+        }
+        Ok(())
+    }
+}
+
+/// Content hash of an analyzed file, with the size and mtime it had when
+/// hashed so unchanged files don't have to be read again.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FileRecord {
+    pub hash: String,
+    pub len: u64,
+    pub mtime_ns: u64,
+}
+
+/// Compares files against the records of the previous analysis and collects
+/// the records of the current one.
+#[derive(Debug, Default)]
+pub struct FileTracker {
+    previous: BTreeMap<String, FileRecord>,
+    current: BTreeMap<String, FileRecord>,
+}
+
+impl FileTracker {
+    pub fn new(previous: BTreeMap<String, FileRecord>) -> Self {
+        FileTracker {
+            previous,
+            current: BTreeMap::new(),
+        }
+    }
+
+    /// Record `path` and tell whether its content differs from the previous
+    /// analysis (unknown files count as changed).
+    pub fn check(&mut self, path: &Path) -> Result<bool> {
+        let key = path.to_string_lossy().to_string();
+        let (record, changed) = probe(self.previous.get(&key), path)?;
+        self.current.insert(key, record);
+        Ok(changed)
+    }
+
+    /// `check` for many files at once, hashing them on the worker pool.
+    pub fn check_all(&mut self, paths: &[PathBuf]) -> Result<Vec<bool>> {
+        let probed: Vec<(String, FileRecord, bool)> = paths
+            .par_iter()
+            .map(|path| {
+                let key = path.to_string_lossy().to_string();
+                let (record, changed) = probe(self.previous.get(&key), path)?;
+                Ok((key, record, changed))
+            })
+            .collect::<Result<_>>()?;
+
+        let mut changed = Vec::with_capacity(probed.len());
+        for (key, record, c) in probed {
+            self.current.insert(key, record);
+            changed.push(c);
+        }
+        Ok(changed)
+    }
+
+    /// Files known to the previous analysis that haven't been checked (yet).
+    pub fn unseen(&self) -> impl Iterator<Item = &str> {
+        self.previous
+            .keys()
+            .filter(move |k| !self.current.contains_key(*k))
+            .map(|k| k.as_str())
+    }
+
+    pub fn into_records(self) -> BTreeMap<String, FileRecord> {
+        self.current
+    }
+}
+
+/// Current record of `path`, `None` when it can't be read (e.g. deleted).
+pub fn file_record(path: &Path) -> Option<FileRecord> {
+    probe(None, path).ok().map(|(record, _)| record)
+}
+
+/// Current record of `path`, and whether its content differs from `previous`.
+fn probe(previous: Option<&FileRecord>, path: &Path) -> Result<(FileRecord, bool)> {
+    let meta = fs::metadata(path).with_context(|| format!("stat {}", path.display()))?;
+    let mtime_ns = meta
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_nanos() as u64);
+
+    if let Some(record) = previous {
+        if record.len == meta.len() && record.mtime_ns == mtime_ns && mtime_ns != 0 {
+            return Ok((record.clone(), false));
+        }
+    }
+
+    let content = fs::read(path).with_context(|| format!("read {}", path.display()))?;
+    let record = FileRecord {
+        hash: format!("{:032x}", xxhash_rust::xxh3::xxh3_128(&content)),
+        len: content.len() as u64,
+        mtime_ns,
+    };
+    let changed = previous.is_none_or(|p| p.hash != record.hash);
+    Ok((record, changed))
+}
+
+/// `<bench>/.frappe-mcp`
+pub fn state_dir(config: &Config) -> PathBuf {
+    Path::new(&config.frappe_bench_dir).join(STATE_DIR)
+}
+
+/// Upgrade a cache document to `SCHEMA_VERSION`.
+fn migrate(doc: Value) -> Result<Value> {
+    let version = match doc.get("schema_version").and_then(|v| v.as_u64()) {
+        Some(v) => v as u32,
+        // Version 1 was the unversioned `analyzed_output.dat`, which carried
+        // no fingerprint and therefore can't be trusted
+        None => bail!("analysis cache has no schema version"),
+    };
+
+    match version {
+        SCHEMA_VERSION => Ok(doc),
+        v if v > SCHEMA_VERSION => bail!(
+            "analysis cache schema {} is newer than supported ({})",
+            v,
+            SCHEMA_VERSION
+        ),
+        // Upgradable layouts get an arm here: `v => migrate(upgrade_vN(doc)?)`
+        2 => migrate(upgrade_v2(doc)),
+        3 => migrate(upgrade_v3(doc)),
+        4 => migrate(upgrade_v4(doc)),
+        v => bail!("analysis cache schema {} can't be migrated", v),
+    }
+}
+
+/// v3 added file hashes and the DocType index; a v2 cache loads without them
+/// and the next analysis re-scans everything once.
+fn upgrade_v2(mut doc: Value) -> Value {
+    doc["schema_version"] = Value::from(3);
+    doc
+}
+
+/// v4 added the link fields to the DocType index. Unchanged DocType JSONs
+/// aren't parsed again, so the index and file hashes are dropped and the
+/// next analysis re-scans everything once.
+fn upgrade_v3(mut doc: Value) -> Value {
+    if let Some(data) = doc.get_mut("data").and_then(|d| d.as_object_mut()) {
+        data.remove("files");
+        data.remove("doctype_index");
+    }
+    doc["schema_version"] = Value::from(4);
+    doc
+}
+
+/// v5 added the DocTypes a Dynamic Link may point at to the link fields,
+/// re-scanned the same way as for v4.
+fn upgrade_v4(mut doc: Value) -> Value {
+    if let Some(data) = doc.get_mut("data").and_then(|d| d.as_object_mut()) {
+        data.remove("files");
+        data.remove("doctype_index");
+    }
+    doc["schema_version"] = Value::from(5);
+    doc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(bench: &Path, site: &str) -> Config {
+        Config {
+            frappe_bench_dir: bench.to_string_lossy().to_string(),
+            app_relative_path: "koperasi".to_string(),
+            app_dirs: vec!["koperasi".to_string()],
+            site: site.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn bench_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("frappe-mcp-cache-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_roundtrip_and_foreign_config() {
+        let bench = bench_dir("roundtrip");
+        let cache = AnalysisCache::for_config(&config(&bench, "frontend"));
+        assert!(cache.path().starts_with(bench.join(STATE_DIR)));
+
+        let data = AnalyzedData::default();
+        cache.save(&data).unwrap();
+        assert!(cache.load().is_ok());
+
+        // Another site gets its own cache file
+        let other = AnalysisCache::for_config(&config(&bench, "other"));
+        assert_ne!(cache.path(), other.path());
+        assert!(other.load().is_err());
+
+        // A copied file with a foreign fingerprint is rejected
+        fs::copy(cache.path(), other.path()).unwrap();
+        assert!(other.load().is_err());
+
+        // An analysis of a single directory never becomes the config's
+        let dir = AnalysisCache::for_app_dir(&config(&bench, "frontend"), "/tmp/koperasi");
+        assert_ne!(cache.path(), dir.path());
+        dir.save(&data).unwrap();
+        assert!(dir.load().is_ok());
+        fs::copy(dir.path(), cache.path()).unwrap();
+        assert!(cache.load().is_err());
+
+        fs::remove_dir_all(&bench).unwrap();
+    }
+
+    #[test]
+    fn test_rejects_unversioned_and_newer_schema() {
+        let bench = bench_dir("schema");
+        let cache = AnalysisCache::for_config(&config(&bench, "frontend"));
+        fs::create_dir_all(cache.path().parent().unwrap()).unwrap();
+
+        fs::write(cache.path(), r#"{"doctypes": [], "modules": []}"#).unwrap();
+        assert!(cache.load().is_err());
+
+        cache.save(&AnalyzedData::default()).unwrap();
+        let mut doc: Value =
+            serde_json::from_str(&fs::read_to_string(cache.path()).unwrap()).unwrap();
+        doc["schema_version"] = Value::from(SCHEMA_VERSION + 1);
+        fs::write(cache.path(), doc.to_string()).unwrap();
+        let err = cache.load().unwrap_err().to_string();
+        assert!(err.contains("newer"), "{}", err);
+
+        fs::remove_dir_all(&bench).unwrap();
+    }
+
+    #[test]
+    fn test_migrates_v2_schema() {
+        let bench = bench_dir("v2");
+        let cache = AnalysisCache::for_config(&config(&bench, "frontend"));
+        cache.save(&AnalyzedData::default()).unwrap();
+
+        let mut doc: Value =
+            serde_json::from_str(&fs::read_to_string(cache.path()).unwrap()).unwrap();
+        doc["schema_version"] = Value::from(2);
+        let data = doc["data"].as_object_mut().unwrap();
+        data.remove("files");
+        data.remove("doctype_index");
+        fs::write(cache.path(), doc.to_string()).unwrap();
+
+        let data = cache.load().unwrap();
+        assert!(data.files.is_empty());
+
+        fs::remove_dir_all(&bench).unwrap();
+    }
+
+    #[test]
+    fn test_file_tracker_detects_content_changes() {
+        let dir = bench_dir("tracker");
+        let file = dir.join("api.py");
+        fs::write(&file, "a = 1\n").unwrap();
+
+        let mut tracker = FileTracker::default();
+        assert!(tracker.check(&file).unwrap());
+        let records = tracker.into_records();
+
+        // Same content, even when rewritten, is not a change
+        fs::write(&file, "a = 1\n").unwrap();
+        let mut tracker = FileTracker::new(records.clone());
+        assert!(!tracker.check(&file).unwrap());
+
+        fs::write(&file, "a = 2\n").unwrap();
+        let mut tracker = FileTracker::new(records);
+        assert!(tracker.check(&file).unwrap());
+        assert_eq!(tracker.unseen().count(), 0);
+
+        // The watcher records the files it re-scans the same way
+        let records = tracker.into_records();
+        assert_eq!(file_record(&file).as_ref(), records.values().next());
+        assert_eq!(file_record(&dir.join("gone.py")), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -10,8 +10,48 @@
 // is strictly forbidden unless prior written permission is obtained
 // from Nuwaira.
 use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
+use crate::policy::Policy;
+
+/// Which apps of the bench to analyze besides the main app.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum AppSelection {
+    /// `apps = "all"`: every app listed in `sites/apps.txt`
+    Keyword(String),
+    /// `apps = ["erpnext", "hrms"]`
+    List(Vec<String>),
+}
+
+/// Time limits of the tools running `bench`, in seconds, 0 for none.
+///
+/// ```toml
+/// [timeouts]
+/// default = 600
+/// run_tests = 1800
+/// ```
 #[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Timeouts {
+    pub default: u64,
+    /// Limits of single tools, by tool name
+    #[serde(flatten)]
+    pub tools: HashMap<String, u64>,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Timeouts {
+            default: 600,
+            tools: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
 pub struct Config {
     pub frappe_bench_dir: String,
     pub app_relative_path: String,
@@ -24,6 +64,26 @@ pub struct Config {
 
     #[serde(default)]
     pub site: String,
+
+    /// Additional apps to analyze, a list of app directories or "all"
+    #[serde(default)]
+    pub apps: Option<AppSelection>,
+
+    /// JSONL audit log of write, shell and database tool calls, relative to the bench
+    #[serde(default)]
+    pub audit_log: Option<String>,
+
+    /// Which tools may run and what they may do, the `[policy]` section
+    #[serde(default)]
+    pub policy: Policy,
+
+    /// Timeouts of the tools running `bench`, the `[timeouts]` section
+    #[serde(default)]
+    pub timeouts: Timeouts,
+
+    /// Resolved app directories to analyze, the main app always comes first
+    #[serde(skip)]
+    pub app_dirs: Vec<String>,
 }
 
 impl Config {
@@ -40,6 +100,195 @@ impl Config {
         if config.site.is_empty() {
             config.site = "frontend".to_string();
         }
+        config.app_dirs = config.resolve_app_dirs()?;
         Ok(config)
     }
+
+    fn resolve_app_dirs(&self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let mut dirs = vec![self.app_relative_path.clone()];
+        let extra = match &self.apps {
+            None => Vec::new(),
+            Some(AppSelection::List(apps)) => apps.clone(),
+            Some(AppSelection::Keyword(k)) if k == "all" => self.bench_apps(),
+            Some(AppSelection::Keyword(k)) => {
+                return Err(
+                    format!("Invalid apps value '{}', expected a list or \"all\"", k).into(),
+                )
+            }
+        };
+        for app in extra {
+            if !dirs.contains(&app) {
+                dirs.push(app);
+            }
+        }
+        Ok(dirs)
+    }
+
+    /// Apps installed in the bench, from `sites/apps.txt` (falls back to `apps/*`).
+    pub fn bench_apps(&self) -> Vec<String> {
+        let apps_txt = Path::new(&self.frappe_bench_dir)
+            .join("sites")
+            .join("apps.txt");
+        if let Ok(content) = std::fs::read_to_string(apps_txt) {
+            return content
+                .lines()
+                .map(|l| l.trim().to_string())
+                .filter(|l| !l.is_empty())
+                .collect();
+        }
+        let mut apps: Vec<String> =
+            std::fs::read_dir(Path::new(&self.frappe_bench_dir).join("apps"))
+                .map(|entries| {
+                    entries
+                        .flatten()
+                        .filter(|e| e.path().is_dir())
+                        .map(|e| e.file_name().to_string_lossy().to_string())
+                        .collect()
+                })
+                .unwrap_or_default();
+        apps.sort();
+        apps
+    }
+
+    /// Absolute path of an app directory, e.g. `<bench>/apps/erpnext`.
+    ///
+    /// An empty `app` (data analyzed before multi-app support) means the main app.
+    pub fn app_path(&self, app: &str) -> String {
+        if app.is_empty() || app == self.app_relative_path {
+            self.app_absolute_path.clone()
+        } else {
+            format!("{}/apps/{}", self.frappe_bench_dir, app)
+        }
+    }
+
+    /// Absolute path of the audit log, when one is configured.
+    pub fn audit_log_path(&self) -> Option<PathBuf> {
+        let path = Path::new(self.audit_log.as_deref()?);
+        Some(Path::new(&self.frappe_bench_dir).join(path))
+    }
+
+    /// How long `tool` may run `bench` before it is killed, `None` for no limit.
+    pub fn tool_timeout(&self, tool: &str) -> Option<Duration> {
+        let secs = self
+            .timeouts
+            .tools
+            .get(tool)
+            .copied()
+            .unwrap_or(self.timeouts.default);
+        (secs > 0).then(|| Duration::from_secs(secs))
+    }
+
+    /// Whether more than the main app is analyzed.
+    pub fn is_multi_app(&self) -> bool {
+        self.app_dirs.len() > 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apps_selection() {
+        let config = Config::from_toml_str(
+            r#"
+            frappe_bench_dir = "/tmp/bench"
+            app_relative_path = "koperasi"
+            app_name = "Koperasi"
+            apps = ["erpnext", "koperasi", "hrms"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.resolve_app_dirs().unwrap(),
+            vec!["koperasi", "erpnext", "hrms"]
+        );
+
+        let config = Config::from_toml_str(
+            r#"
+            frappe_bench_dir = "/tmp/bench"
+            app_relative_path = "koperasi"
+            app_name = "Koperasi"
+            apps = "everything"
+            "#,
+        )
+        .unwrap();
+        assert!(config.resolve_app_dirs().is_err());
+    }
+
+    #[test]
+    fn test_policy_section() {
+        let config = Config::from_toml_str(
+            r#"
+            frappe_bench_dir = "/tmp/bench"
+            app_relative_path = "koperasi"
+            app_name = "Koperasi"
+
+            [policy]
+            read_only = true
+            disabled_groups = ["shell"]
+            sql_allow = ["select", "show"]
+            "#,
+        )
+        .unwrap();
+        assert!(config.policy.read_only);
+        assert!(!config.policy.tool_enabled("run_bench_command"));
+        assert!(config.policy.check_sql("SHOW TABLES").is_ok());
+        // Unset lists keep their defaults
+        assert!(config.policy.check_bench(&["drop-site"]).is_err());
+
+        let config = Config::from_toml_str(
+            r#"
+            frappe_bench_dir = "/tmp/bench"
+            app_relative_path = "koperasi"
+            app_name = "Koperasi"
+            "#,
+        )
+        .unwrap();
+        assert!(!config.policy.read_only);
+        assert!(config.policy.check_sql("DROP TABLE tabUser").is_err());
+        assert_eq!(
+            config.tool_timeout("run_tests"),
+            Some(Duration::from_secs(600))
+        );
+    }
+
+    #[test]
+    fn test_timeouts_section() {
+        let config = Config::from_toml_str(
+            r#"
+            frappe_bench_dir = "/tmp/bench"
+            app_relative_path = "koperasi"
+            app_name = "Koperasi"
+
+            [timeouts]
+            default = 120
+            run_tests = 1800
+            bench_execute = 0
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.tool_timeout("run_tests"),
+            Some(Duration::from_secs(1800))
+        );
+        assert_eq!(
+            config.tool_timeout("run_db_command"),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(config.tool_timeout("bench_execute"), None);
+    }
+
+    #[test]
+    fn test_app_path() {
+        let config = Config {
+            frappe_bench_dir: "/tmp/bench".to_string(),
+            app_relative_path: "koperasi".to_string(),
+            app_absolute_path: "/tmp/bench/apps/koperasi".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(config.app_path(""), "/tmp/bench/apps/koperasi");
+        assert_eq!(config.app_path("koperasi"), "/tmp/bench/apps/koperasi");
+        assert_eq!(config.app_path("erpnext"), "/tmp/bench/apps/erpnext");
+    }
 }
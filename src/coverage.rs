@@ -0,0 +1,495 @@
+// Copyright (C) 2025 Nuwaira
+// All Rights Reserved.
+//
+// NOTICE: All information contained herein is, and remains
+// the property of Nuwaira.
+// The intellectual and technical concepts contained
+// herein are proprietary to Nuwaira
+// and are protected by trade secret or copyright law.
+// Dissemination of this information or reproduction of this material
+// is strictly forbidden unless prior written permission is obtained
+// from Nuwaira.
+#![allow(dead_code)]
+
+//! Line coverage of a `bench run-tests --coverage` run, mapped back to the
+//! analyzed DocTypes and modules.
+//!
+//! Frappe saves the report as Cobertura XML in `sites/coverage.xml`; the
+//! functions of each controller come from parsing it with tree-sitter.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+use rmcp::schemars;
+use serde::Serialize;
+use tree_sitter::{Node, Parser};
+
+use crate::analyze::AnalyzedData;
+use crate::config::Config;
+use crate::testutil;
+
+lazy_static::lazy_static! {
+    static ref RX_SOURCE: Regex = Regex::new(r"<source>([^<]*)</source>").expect("rx_source bad");
+    static ref RX_CLASS: Regex = Regex::new(r#"<class\s[^>]*filename="([^"]+)""#).expect("rx_class bad");
+    static ref RX_LINE: Regex =
+        Regex::new(r#"<line\s[^>]*number="(\d+)"[^>]*hits="(\d+)""#).expect("rx_line bad");
+}
+
+/// Where Frappe writes the report, relative to the bench.
+const COVERAGE_XML: &str = "sites/coverage.xml";
+
+/// DocTypes listed in the text view.
+const MAX_RENDERED_DOCTYPES: usize = 30;
+
+/// The DocTypes and modules a report is mapped to, taken from the analyzed
+/// data before the tests run.
+#[derive(Debug, Clone, Default)]
+pub struct CoverageTargets {
+    /// (DocType, module, controller path relative to the bench's `apps/`)
+    doctypes: Vec<(String, String, String)>,
+    /// (module, app, directory relative to the bench's `apps/`)
+    modules: Vec<(String, String, String)>,
+    /// Absolute path of the bench's `apps/`
+    apps_dir: PathBuf,
+}
+
+/// Line hits of one measured file, by line number.
+pub type FileHits = BTreeMap<usize, u64>;
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct CoverageReport {
+    /// Measured lines executed by the tests, over every file of the app
+    pub lines_covered: usize,
+    pub lines_total: usize,
+    pub percent: f64,
+    /// Modules with measured files, least covered first
+    pub modules: Vec<ModuleCoverage>,
+    /// DocTypes whose controller was measured, least covered first
+    pub doctypes: Vec<DocTypeCoverage>,
+}
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct ModuleCoverage {
+    pub module: String,
+    pub app: String,
+    pub lines_covered: usize,
+    pub lines_total: usize,
+    pub percent: f64,
+}
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct DocTypeCoverage {
+    pub doctype: String,
+    pub module: String,
+    /// Controller path relative to the bench's `apps/`
+    pub controller: String,
+    pub lines_covered: usize,
+    pub lines_total: usize,
+    pub percent: f64,
+    /// Controller functions none of whose lines ran
+    pub uncovered_functions: Vec<UncoveredFunction>,
+}
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct UncoveredFunction {
+    /// `Class.method` or the function name
+    pub name: String,
+    /// Line of the `def`
+    pub line: usize,
+    /// Measured lines in its body
+    pub lines: usize,
+}
+
+/// A function definition of a Python file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionSpan {
+    pub name: String,
+    pub line: usize,
+    /// First and last line of the body, 1-based
+    pub body: (usize, usize),
+}
+
+impl CoverageReport {
+    pub fn render(&self) -> String {
+        let mut out = format!(
+            "COVERAGE: {}% ({}/{} lines)\n",
+            self.percent, self.lines_covered, self.lines_total
+        );
+        for module in &self.modules {
+            out.push_str(&format!(
+                "  {:>5}%  {} ({})\n",
+                module.percent, module.module, module.app
+            ));
+        }
+        // Least covered first; the structured report has every DocType
+        let partial: Vec<_> = self
+            .doctypes
+            .iter()
+            .filter(|dt| dt.percent < 100.0)
+            .collect();
+        for dt in partial.iter().take(MAX_RENDERED_DOCTYPES) {
+            out.push_str(&format!(
+                "\n{} {}% ({}/{} lines) {}\n",
+                dt.doctype, dt.percent, dt.lines_covered, dt.lines_total, dt.controller
+            ));
+            for function in &dt.uncovered_functions {
+                out.push_str(&format!(
+                    "  not run: {} (line {})\n",
+                    function.name, function.line
+                ));
+            }
+        }
+        if partial.len() > MAX_RENDERED_DOCTYPES {
+            out.push_str(&format!(
+                "\n... {} more DocTypes below 100%\n",
+                partial.len() - MAX_RENDERED_DOCTYPES
+            ));
+        }
+        out
+    }
+}
+
+/// `sites/coverage.xml` of the bench.
+pub fn xml_path(bench_path: &str) -> PathBuf {
+    Path::new(bench_path).join(COVERAGE_XML)
+}
+
+pub fn targets(config: &Config, anal: &AnalyzedData) -> CoverageTargets {
+    let app_dir = |app: &str| {
+        if app.is_empty() {
+            config.app_relative_path.clone()
+        } else {
+            app.to_string()
+        }
+    };
+    CoverageTargets {
+        doctypes: anal
+            .doctypes
+            .iter()
+            .map(|dt| {
+                (
+                    dt.name.clone(),
+                    dt.module.clone(),
+                    format!("{}/{}", app_dir(&dt.app), dt.backend_file),
+                )
+            })
+            .collect(),
+        modules: anal
+            .modules
+            .iter()
+            .map(|m| {
+                (
+                    m.name.clone(),
+                    app_dir(&m.app),
+                    format!("{}/{}", app_dir(&m.app), m.location),
+                )
+            })
+            .collect(),
+        apps_dir: Path::new(&config.frappe_bench_dir).join("apps"),
+    }
+}
+
+/// Line hits per file of a Cobertura report, keyed by the path relative to
+/// the bench's `apps/`.
+pub fn parse_cobertura(xml: &str, bench_dir: &str) -> BTreeMap<String, FileHits> {
+    let source = RX_SOURCE
+        .captures(xml)
+        .map(|c| c[1].trim().to_string())
+        .unwrap_or_default();
+    let mut files: BTreeMap<String, FileHits> = BTreeMap::new();
+    let mut current: Option<String> = None;
+    for line in xml.lines() {
+        if let Some(caps) = RX_CLASS.captures(line) {
+            let path = Path::new(&source).join(&caps[1]);
+            let (relative, _) = testutil::app_relative(&path.to_string_lossy(), bench_dir);
+            files.entry(relative.clone()).or_default();
+            current = Some(relative);
+        } else if let (Some(caps), Some(file)) = (RX_LINE.captures(line), &current) {
+            let number: usize = caps[1].parse().unwrap_or(0);
+            let hits: u64 = caps[2].parse().unwrap_or(0);
+            *files.get_mut(file).unwrap().entry(number).or_default() += hits;
+        }
+    }
+    files
+}
+
+/// Map the measured files to the DocTypes and modules of `targets`.
+pub fn report(targets: &CoverageTargets, files: &BTreeMap<String, FileHits>) -> CoverageReport {
+    let count = |hits: &mut dyn Iterator<Item = &u64>| {
+        hits.fold((0, 0), |(covered, total), h| {
+            (covered + usize::from(*h > 0), total + 1)
+        })
+    };
+
+    let (lines_covered, lines_total) = count(&mut files.values().flat_map(|f| f.values()));
+
+    let mut modules: Vec<ModuleCoverage> = targets
+        .modules
+        .iter()
+        .filter_map(|(module, app, dir)| {
+            let prefix = format!("{}/", dir.trim_end_matches('/'));
+            let mut hits = files
+                .iter()
+                .filter(|(path, _)| path.starts_with(&prefix))
+                .flat_map(|(_, f)| f.values())
+                .peekable();
+            hits.peek()?;
+            let (covered, total) = count(&mut hits);
+            Some(ModuleCoverage {
+                module: module.clone(),
+                app: app.clone(),
+                lines_covered: covered,
+                lines_total: total,
+                percent: percent(covered, total),
+            })
+        })
+        .collect();
+    modules.sort_by(|a, b| {
+        a.percent
+            .total_cmp(&b.percent)
+            .then(a.module.cmp(&b.module))
+    });
+
+    let mut doctypes: Vec<DocTypeCoverage> = targets
+        .doctypes
+        .iter()
+        .filter_map(|(doctype, module, controller)| {
+            let hits = files.get(controller)?;
+            let (covered, total) = count(&mut hits.values());
+            let source = fs::read_to_string(targets.apps_dir.join(controller)).unwrap_or_default();
+            Some(DocTypeCoverage {
+                doctype: doctype.clone(),
+                module: module.clone(),
+                controller: controller.clone(),
+                lines_covered: covered,
+                lines_total: total,
+                percent: percent(covered, total),
+                uncovered_functions: uncovered_functions(&python_functions(&source), hits),
+            })
+        })
+        .collect();
+    doctypes.sort_by(|a, b| {
+        a.percent
+            .total_cmp(&b.percent)
+            .then(a.doctype.cmp(&b.doctype))
+    });
+
+    CoverageReport {
+        lines_covered,
+        lines_total,
+        percent: percent(lines_covered, lines_total),
+        modules,
+        doctypes,
+    }
+}
+
+/// Functions with measured lines in their body, none of which ran.
+///
+/// The `def` line itself runs when the module is imported, so only the body counts.
+fn uncovered_functions(functions: &[FunctionSpan], hits: &FileHits) -> Vec<UncoveredFunction> {
+    functions
+        .iter()
+        .filter_map(|f| {
+            let body: Vec<u64> = hits.range(f.body.0..=f.body.1).map(|(_, h)| *h).collect();
+            (!body.is_empty() && body.iter().all(|h| *h == 0)).then(|| UncoveredFunction {
+                name: f.name.clone(),
+                line: f.line,
+                lines: body.len(),
+            })
+        })
+        .collect()
+}
+
+/// Top-level functions and methods of top-level classes.
+pub fn python_functions(source: &str) -> Vec<FunctionSpan> {
+    let mut parser = Parser::new();
+    if parser
+        .set_language(&tree_sitter_python::LANGUAGE.into())
+        .is_err()
+    {
+        return Vec::new();
+    }
+    let Some(tree) = parser.parse(source, None) else {
+        return Vec::new();
+    };
+    let mut functions = Vec::new();
+    collect_functions(tree.root_node(), source, None, &mut functions);
+    functions
+}
+
+fn collect_functions(block: Node, source: &str, class: Option<&str>, out: &mut Vec<FunctionSpan>) {
+    let mut cursor = block.walk();
+    for child in block.named_children(&mut cursor) {
+        let node = if child.kind() == "decorated_definition" {
+            match child.child_by_field_name("definition") {
+                Some(definition) => definition,
+                None => continue,
+            }
+        } else {
+            child
+        };
+        let name = node
+            .child_by_field_name("name")
+            .and_then(|n| n.utf8_text(source.as_bytes()).ok())
+            .unwrap_or_default();
+        match (node.kind(), class) {
+            ("function_definition", _) => {
+                let Some(body) = node.child_by_field_name("body") else {
+                    continue;
+                };
+                out.push(FunctionSpan {
+                    name: match class {
+                        Some(class) => format!("{}.{}", class, name),
+                        None => name.to_string(),
+                    },
+                    line: node.start_position().row + 1,
+                    body: (body.start_position().row + 1, body.end_position().row + 1),
+                });
+            }
+            ("class_definition", None) => {
+                if let Some(body) = node.child_by_field_name("body") {
+                    collect_functions(body, source, Some(name), out);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn percent(covered: usize, total: usize) -> f64 {
+    if total == 0 {
+        return 100.0;
+    }
+    (covered as f64 * 1000.0 / total as f64).round() / 10.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTROLLER: &str = r#"import frappe
+from frappe.model.document import Document
+
+
+class Anggota(Document):
+    def validate(self):
+        if not self.nama:
+            frappe.throw("Nama wajib")
+
+    @frappe.whitelist()
+    def keluar(self):
+        self.status = "Keluar"
+        self.save()
+
+
+def get_saldo(anggota):
+    return 0
+"#;
+
+    const XML: &str = r#"<?xml version="1.0" ?>
+<coverage version="7.4.0" line-rate="0.5" lines-covered="6" lines-valid="12">
+	<sources>
+		<source>/home/frappe/frappe-bench/apps/koperasi</source>
+	</sources>
+	<packages>
+		<package name="koperasi.koperasi.doctype.anggota" line-rate="0.6">
+			<classes>
+				<class name="anggota.py" filename="koperasi/koperasi/doctype/anggota/anggota.py" complexity="0" line-rate="0.6">
+					<methods/>
+					<lines>
+						<line number="1" hits="1"/>
+						<line number="2" hits="1"/>
+						<line number="5" hits="1"/>
+						<line number="6" hits="1"/>
+						<line number="7" hits="1"/>
+						<line number="8" hits="0"/>
+						<line number="10" hits="1"/>
+						<line number="11" hits="1"/>
+						<line number="12" hits="0"/>
+						<line number="13" hits="0"/>
+						<line number="16" hits="1"/>
+						<line number="17" hits="0"/>
+					</lines>
+				</class>
+				<class name="utils.py" filename="koperasi/utils.py" complexity="0" line-rate="0">
+					<lines>
+						<line number="1" hits="0"/>
+					</lines>
+				</class>
+			</classes>
+		</package>
+	</packages>
+</coverage>
+"#;
+
+    #[test]
+    fn test_python_functions() {
+        let functions = python_functions(CONTROLLER);
+        let names: Vec<_> = functions
+            .iter()
+            .map(|f| (f.name.as_str(), f.line, f.body))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                ("Anggota.validate", 6, (7, 8)),
+                ("Anggota.keluar", 11, (12, 13)),
+                ("get_saldo", 16, (17, 17)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_coverage_report() {
+        let apps = std::env::temp_dir().join(format!(
+            "frappe-mcp-coverage-test-{}/apps",
+            std::process::id()
+        ));
+        let controller = "koperasi/koperasi/koperasi/doctype/anggota/anggota.py";
+        fs::create_dir_all(apps.join(controller).parent().unwrap()).unwrap();
+        fs::write(apps.join(controller), CONTROLLER).unwrap();
+
+        let files = parse_cobertura(XML, "/home/frappe/frappe-bench");
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[controller].len(), 12);
+
+        let targets = CoverageTargets {
+            doctypes: vec![(
+                "Anggota".to_string(),
+                "Koperasi".to_string(),
+                controller.to_string(),
+            )],
+            modules: vec![
+                (
+                    "Koperasi".to_string(),
+                    "koperasi".to_string(),
+                    "koperasi/koperasi/koperasi".to_string(),
+                ),
+                (
+                    "Simpanan".to_string(),
+                    "koperasi".to_string(),
+                    "koperasi/koperasi/simpanan".to_string(),
+                ),
+            ],
+            apps_dir: apps.clone(),
+        };
+        let report = report(&targets, &files);
+        assert_eq!((report.lines_covered, report.lines_total), (8, 13));
+        assert_eq!(report.percent, 61.5);
+        assert_eq!(report.modules.len(), 1);
+        assert_eq!(report.modules[0].percent, 66.7);
+
+        let dt = &report.doctypes[0];
+        assert_eq!((dt.lines_covered, dt.lines_total), (8, 12));
+        let uncovered: Vec<_> = dt
+            .uncovered_functions
+            .iter()
+            .map(|f| f.name.as_str())
+            .collect();
+        assert_eq!(uncovered, vec!["Anggota.keluar", "get_saldo"]);
+
+        let _ = fs::remove_dir_all(apps.parent().unwrap());
+    }
+}
@@ -15,55 +15,186 @@ use std::collections::{HashMap, HashSet, VecDeque};
 use std::fs;
 use std::path::Path;
 
-use crate::analyze::AnalyzedData;
+use crate::analyze::{AnalyzedData, DocType};
 use crate::config::Config;
-use rmcp::{model::*, ErrorData as McpError};
+use rmcp::{model::*, schemars, ErrorData as McpError};
+use serde::{Deserialize, Serialize};
 
 type McpResult = Result<CallToolResult, McpError>;
 
-#[derive(Debug, Clone)]
-struct LinkInfo {
+/// DocTypes reachable from `doctype`, or the DocTypes of `module`, through
+/// link fields, up to `depth` hops.
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct LinkGraph {
+    /// The DocType the analysis started from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub doctype: Option<String>,
+    /// The module whose DocTypes the analysis started from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub module: Option<String>,
+    pub depth: usize,
+    pub summary: LinkSummary,
+    /// Every visited DocType with its outgoing links, sorted by name
+    pub doctypes: Vec<LinkNode>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, schemars::JsonSchema)]
+pub struct LinkSummary {
+    pub direct: usize,
+    pub table: usize,
+    pub select: usize,
+    pub dynamic: usize,
+    pub fetch: usize,
+}
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct LinkNode {
+    pub name: String,
+    /// Module of the DocType, when it is one of the analyzed apps'
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub module: Option<String>,
+    pub links: Vec<LinkInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct LinkInfo {
     pub target_doctype: String,
-    pub field_name: String,
+    pub fieldname: String,
+    pub label: String,
     pub field_type: String,
     pub is_required: bool,
     pub link_type: LinkType,
+    /// For a Dynamic Link, the field holding the name of the target DocType
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub doctype_field: Option<String>,
+    /// For a fetched field, its `fetch_from` expression, e.g. `customer.customer_name`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fetch_from: Option<String>,
 }
 
-#[derive(Debug, Clone)]
-enum LinkType {
-    Direct, // Direct Link field
-    Table,  // Table field (child table)
-    Select, // Select field with options referencing DocType
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkType {
+    Direct,  // Direct Link field
+    Table,   // Table or Table MultiSelect field (child table)
+    Select,  // Select field with options referencing DocType
+    Dynamic, // Dynamic Link field, one per DocType its options field allows
+    Fetch,   // Field copied from a linked DocType through fetch_from
 }
 
+/// How `analyze_links` renders the graph as text.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum GraphFormat {
+    /// Tree of the links per DocType
+    #[default]
+    Text,
+    /// Mermaid `erDiagram`
+    Mermaid,
+    /// Graphviz DOT
+    Dot,
+    /// JSON node and edge list
+    Json,
+}
+
+impl std::str::FromStr for GraphFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(GraphFormat::Text),
+            "mermaid" => Ok(GraphFormat::Mermaid),
+            "dot" => Ok(GraphFormat::Dot),
+            "json" => Ok(GraphFormat::Json),
+            _ => Err(format!(
+                "Invalid format '{}', expected text, mermaid, dot or json",
+                s
+            )),
+        }
+    }
+}
+
+/// The graph as a node and edge list, the `json` format.
+#[derive(Debug, Serialize)]
+pub struct GraphExport {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GraphNode {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub module: Option<String>,
+    /// Whether the DocType's own links were followed (false past `depth`)
+    pub expanded: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GraphEdge {
+    pub from: String,
+    pub to: String,
+    pub fieldname: String,
+    pub field_type: String,
+    pub link_type: LinkType,
+    pub is_required: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub doctype_field: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fetch_from: Option<String>,
+}
+
+impl LinkInfo {
+    /// `Label (fieldname)`, as shown in the text view.
+    fn field_name(&self) -> String {
+        format!("{} ({})", self.label, self.fieldname)
+    }
+}
+
+/// Follow the links of `doctype`, or of every DocType of `module`, up to `depth`
+/// hops (default 2 from a DocType, 0 from a module) and render them as `format`.
 pub fn analyze_links(
     config: &Config,
     anal: &AnalyzedData,
-    doctype: &str,
+    doctype: Option<&str>,
+    module: Option<&str>,
     depth: Option<usize>,
+    app: Option<String>,
+    format: Option<GraphFormat>,
 ) -> McpResult {
-    let max_depth = depth.unwrap_or(2);
-
-    // Find the target DocType
-    let target_doctype = anal
-        .doctypes
-        .iter()
-        .find(|dt| dt.name.to_lowercase() == doctype.to_lowercase());
-
-    let target_doctype = match target_doctype {
-        Some(dt) => dt,
-        None => {
-            mcp_return!(format!("DocType '{}' not found in analyzed data", doctype));
+    let (roots, max_depth): (Vec<&DocType>, usize) = match (doctype, module) {
+        (Some(doctype), None) => match anal.find_doctype(doctype, app.as_deref()) {
+            Some(dt) => (vec![dt], depth.unwrap_or(2)),
+            None => {
+                mcp_error!(format!("DocType '{}' not found in analyzed data", doctype));
+            }
+        },
+        (None, Some(module)) => {
+            let roots: Vec<&DocType> = anal
+                .doctypes
+                .iter()
+                .filter(|dt| dt.module.eq_ignore_ascii_case(module))
+                .filter(|dt| {
+                    app.as_deref()
+                        .is_none_or(|app| dt.app.is_empty() || dt.app == app)
+                })
+                .collect();
+            if roots.is_empty() {
+                mcp_error!(format!("Module '{}' has no analyzed DocTypes", module));
+            }
+            (roots, depth.unwrap_or(0))
         }
+        _ => mcp_error!("Give either a doctype or a module".to_string()),
     };
 
     let mut result = HashMap::new();
     let mut visited = HashSet::new();
     let mut queue = VecDeque::new();
 
-    // Start BFS from the target DocType
-    queue.push_back((target_doctype.name.clone(), 0));
+    // Start BFS from the target DocTypes
+    for root in &roots {
+        queue.push_back((root.name.clone(), 0));
+    }
 
     while let Some((current_doctype, current_depth)) = queue.pop_front() {
         if visited.contains(&current_doctype) || current_depth > max_depth {
@@ -86,9 +217,62 @@ pub fn analyze_links(
         }
     }
 
-    // Format the results
-    let formatted_result = format_link_analysis(doctype, &result, max_depth)?;
-    mcp_return!(formatted_result)
+    let root_doctype = doctype.map(|_| roots[0].name.as_str());
+    let module = module.map(|_| roots[0].module.as_str());
+    let graph = build_link_graph(anal, root_doctype, module, result, max_depth);
+    let formatted_result = match format.unwrap_or_default() {
+        GraphFormat::Text => format_link_analysis(&graph)?,
+        GraphFormat::Mermaid => to_mermaid(&graph),
+        GraphFormat::Dot => to_dot(&graph),
+        GraphFormat::Json => {
+            serde_json::to_string_pretty(&to_edge_list(&graph, anal)).unwrap_or_default()
+        }
+    };
+    mcp_return_data!(graph, formatted_result)
+}
+
+fn build_link_graph(
+    anal: &AnalyzedData,
+    root_doctype: Option<&str>,
+    module: Option<&str>,
+    links_map: HashMap<String, Vec<LinkInfo>>,
+    max_depth: usize,
+) -> LinkGraph {
+    let mut summary = LinkSummary::default();
+    for link in links_map.values().flatten() {
+        match link.link_type {
+            LinkType::Direct => summary.direct += 1,
+            LinkType::Table => summary.table += 1,
+            LinkType::Select => summary.select += 1,
+            LinkType::Dynamic => summary.dynamic += 1,
+            LinkType::Fetch => summary.fetch += 1,
+        }
+    }
+
+    let mut doctypes: Vec<LinkNode> = links_map
+        .into_iter()
+        .map(|(name, links)| LinkNode {
+            module: doctype_module(anal, &name),
+            name,
+            links,
+        })
+        .collect();
+    doctypes.sort_by(|a, b| a.name.cmp(&b.name));
+
+    LinkGraph {
+        doctype: root_doctype.map(|d| d.to_string()),
+        module: module.map(|m| m.to_string()),
+        depth: max_depth,
+        summary,
+        doctypes,
+    }
+}
+
+fn doctype_module(anal: &AnalyzedData, name: &str) -> Option<String> {
+    anal.doctypes
+        .iter()
+        .find(|dt| dt.name == name)
+        .map(|dt| dt.module.clone())
 }
 
 fn get_doctype_links(
@@ -97,23 +281,28 @@ fn get_doctype_links(
     doctype_name: &str,
 ) -> Result<Vec<LinkInfo>, McpError> {
     // Find DocType metadata
-    let doctype_info = anal
-        .doctypes
-        .iter()
-        .find(|dt| dt.name.to_lowercase() == doctype_name.to_lowercase());
+    let doctype_info = anal.find_doctype(doctype_name, None);
 
     let doctype_info = match doctype_info {
         Some(info) => info,
         None => return Ok(Vec::new()),
     };
 
+    doctype_links(config, doctype_info)
+}
+
+/// Outgoing links of a DocType, read from its JSON metadata.
+pub(crate) fn doctype_links(
+    config: &Config,
+    doctype_info: &DocType,
+) -> Result<Vec<LinkInfo>, McpError> {
     // Read the JSON metadata file
     let meta_file = match &doctype_info.meta_file {
         Some(path) => path,
         None => return Ok(Vec::new()),
     };
 
-    let meta_path = format!("{}/{}", config.app_absolute_path, meta_file);
+    let meta_path = format!("{}/{}", config.app_path(&doctype_info.app), meta_file);
 
     if !Path::new(&meta_path).exists() {
         return Ok(Vec::new());
@@ -139,20 +328,125 @@ fn get_doctype_links(
         )
     })?;
 
-    let mut links = Vec::new();
-
     // Extract fields array
-    if let Some(fields) = json.get("fields").and_then(|f| f.as_array()) {
-        for field in fields {
-            if let Some(field_obj) = field.as_object() {
-                if let Some(link_info) = extract_link_from_field(field_obj) {
-                    links.push(link_info);
-                }
-            }
+    let fields: Vec<&Map<String, Value>> = json
+        .get("fields")
+        .and_then(|f| f.as_array())
+        .map(|fields| fields.iter().filter_map(|f| f.as_object()).collect())
+        .unwrap_or_default();
+
+    Ok(extract_links(&fields))
+}
+
+/// Links of every field, in field order. Dynamic Links and `fetch_from`
+/// expressions refer to other fields, so they are resolved against `fields`.
+fn extract_links(fields: &[&Map<String, Value>]) -> Vec<LinkInfo> {
+    let mut links = Vec::new();
+    for field in fields {
+        if let Some(link_info) = extract_link_from_field(field) {
+            links.push(link_info);
         }
+        links.extend(extract_dynamic_links(field, fields));
+        if let Some(link_info) = extract_fetch_from(field, fields) {
+            links.push(link_info);
+        }
+    }
+    links
+}
+
+fn field_str<'a>(field: &'a Map<String, Value>, key: &str) -> Option<&'a str> {
+    field.get(key).and_then(|v| v.as_str())
+}
+
+fn find_field<'a>(
+    fields: &[&'a Map<String, Value>],
+    fieldname: &str,
+) -> Option<&'a Map<String, Value>> {
+    fields
+        .iter()
+        .find(|f| field_str(f, "fieldname") == Some(fieldname))
+        .copied()
+}
+
+/// `fieldname`, label (falling back to the fieldname), fieldtype and `reqd`.
+fn field_header(field: &Map<String, Value>) -> Option<(String, String, String, bool)> {
+    let fieldname = field_str(field, "fieldname")?.to_string();
+    let fieldtype = field_str(field, "fieldtype")?.to_string();
+    let label = field_str(field, "label").unwrap_or(&fieldname).to_string();
+    let reqd = field
+        .get("reqd")
+        .and_then(|v| v.as_bool().or_else(|| v.as_i64().map(|n| n != 0)))
+        .unwrap_or(false);
+    Some((fieldname, label, fieldtype, reqd))
+}
+
+/// One `Dynamic` link per DocType the field's `options` field allows, i.e.
+/// the options of a Select. Behind a Link to DocType the target may be any
+/// DocType, which gives no edges.
+fn extract_dynamic_links(
+    field: &Map<String, Value>,
+    fields: &[&Map<String, Value>],
+) -> Vec<LinkInfo> {
+    let Some((fieldname, label, fieldtype, reqd)) = field_header(field) else {
+        return Vec::new();
+    };
+    if fieldtype != "Dynamic Link" {
+        return Vec::new();
+    }
+    let Some(doctype_field) = field_str(field, "options").map(str::trim) else {
+        return Vec::new();
+    };
+    let Some(source) = find_field(fields, doctype_field) else {
+        return Vec::new();
+    };
+
+    if field_str(source, "fieldtype") != Some("Select") {
+        return Vec::new();
+    }
+
+    field_str(source, "options")
+        .unwrap_or_default()
+        .lines()
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(|target| LinkInfo {
+            target_doctype: target.to_string(),
+            fieldname: fieldname.clone(),
+            label: label.clone(),
+            field_type: fieldtype.clone(),
+            is_required: reqd,
+            link_type: LinkType::Dynamic,
+            doctype_field: Some(doctype_field.to_string()),
+            fetch_from: None,
+        })
+        .collect()
+}
+
+/// A `Fetch` link for a field with `fetch_from: "<link field>.<source field>"`,
+/// pointing at the DocType of the link field.
+fn extract_fetch_from(
+    field: &Map<String, Value>,
+    fields: &[&Map<String, Value>],
+) -> Option<LinkInfo> {
+    let (fieldname, label, fieldtype, reqd) = field_header(field)?;
+    let fetch_from = field_str(field, "fetch_from")?.trim();
+    let (link_fieldname, _) = fetch_from.split_once('.')?;
+    let link_field = find_field(fields, link_fieldname)?;
+    if field_str(link_field, "fieldtype")? != "Link" {
+        return None;
     }
+    let target = field_str(link_field, "options")?;
 
-    Ok(links)
+    Some(LinkInfo {
+        target_doctype: target.to_string(),
+        fieldname,
+        label,
+        field_type: fieldtype,
+        is_required: reqd,
+        link_type: LinkType::Fetch,
+        doctype_field: None,
+        fetch_from: Some(fetch_from.to_string()),
+    })
 }
 
 fn extract_link_from_field(field: &Map<String, Value>) -> Option<LinkInfo> {
@@ -161,28 +455,38 @@ fn extract_link_from_field(field: &Map<String, Value>) -> Option<LinkInfo> {
     let label = field
         .get("label")
         .and_then(|v| v.as_str())
-        .unwrap_or(&fieldname);
-    let reqd = field.get("reqd").and_then(|v| v.as_bool()).unwrap_or(false);
+        .unwrap_or(&fieldname)
+        .to_string();
+    let reqd = field
+        .get("reqd")
+        .and_then(|v| v.as_bool().or_else(|| v.as_i64().map(|n| n != 0)))
+        .unwrap_or(false);
 
     match fieldtype {
         "Link" => {
             let options = field.get("options")?.as_str()?;
             Some(LinkInfo {
                 target_doctype: options.to_string(),
-                field_name: format!("{} ({})", label, fieldname),
+                fieldname: fieldname.clone(),
+                label: label.clone(),
                 field_type: fieldtype.to_string(),
                 is_required: reqd,
                 link_type: LinkType::Direct,
+                doctype_field: None,
+                fetch_from: None,
             })
         }
-        "Table" => {
+        "Table" | "Table MultiSelect" => {
             let options = field.get("options")?.as_str()?;
             Some(LinkInfo {
                 target_doctype: options.to_string(),
-                field_name: format!("{} ({})", label, fieldname),
+                fieldname: fieldname.clone(),
+                label: label.clone(),
                 field_type: fieldtype.to_string(),
                 is_required: reqd,
                 link_type: LinkType::Table,
+                doctype_field: None,
+                fetch_from: None,
             })
         }
         "Select" => {
@@ -202,10 +506,13 @@ fn extract_link_from_field(field: &Map<String, Value>) -> Option<LinkInfo> {
             {
                 Some(LinkInfo {
                     target_doctype: options.to_string(),
-                    field_name: format!("{} ({})", label, fieldname),
+                    fieldname: fieldname.clone(),
+                    label: label.clone(),
                     field_type: fieldtype.to_string(),
                     is_required: reqd,
                     link_type: LinkType::Select,
+                    doctype_field: None,
+                    fetch_from: None,
                 })
             } else {
                 None
@@ -215,55 +522,45 @@ fn extract_link_from_field(field: &Map<String, Value>) -> Option<LinkInfo> {
     }
 }
 
-fn format_link_analysis(
-    root_doctype: &str,
-    links_map: &HashMap<String, Vec<LinkInfo>>,
-    max_depth: usize,
-) -> Result<String, McpError> {
+fn format_link_analysis(graph: &LinkGraph) -> Result<String, McpError> {
     let mut result = String::new();
 
-    result.push_str(&format!(
-        "🔗 Link Analysis for DocType: '{}'\n",
-        root_doctype
-    ));
-    result.push_str(&format!("📊 Analysis Depth: {} levels\n", max_depth));
+    match (&graph.doctype, &graph.module) {
+        (Some(doctype), _) => {
+            result.push_str(&format!("🔗 Link Analysis for DocType: '{}'\n", doctype));
+        }
+        (None, module) => {
+            result.push_str(&format!(
+                "🔗 Link Analysis for Module: '{}'\n",
+                module.as_deref().unwrap_or_default()
+            ));
+        }
+    }
+    result.push_str(&format!("📊 Analysis Depth: {} levels\n", graph.depth));
     result.push_str(&format!(
         "📈 Total DocTypes Analyzed: {}\n\n",
-        links_map.len()
+        graph.doctypes.len()
     ));
 
-    // Group by link type for summary
-    let mut total_direct = 0;
-    let mut total_table = 0;
-    let mut total_select = 0;
-
-    for links in links_map.values() {
-        for link in links {
-            match link.link_type {
-                LinkType::Direct => total_direct += 1,
-                LinkType::Table => total_table += 1,
-                LinkType::Select => total_select += 1,
-            }
-        }
-    }
-
     result.push_str("📋 SUMMARY:\n");
-    result.push_str(&format!("   • Direct Links: {}\n", total_direct));
-    result.push_str(&format!("   • Child Tables: {}\n", total_table));
-    result.push_str(&format!("   • Select References: {}\n\n", total_select));
+    result.push_str(&format!("   • Direct Links: {}\n", graph.summary.direct));
+    result.push_str(&format!("   • Child Tables: {}\n", graph.summary.table));
+    result.push_str(&format!(
+        "   • Select References: {}\n",
+        graph.summary.select
+    ));
+    result.push_str(&format!("   • Dynamic Links: {}\n", graph.summary.dynamic));
+    result.push_str(&format!("   • Fetched Fields: {}\n\n", graph.summary.fetch));
 
     result.push_str("🌳 DETAILED ANALYSIS:\n");
     result.push_str("═".repeat(60).as_str());
     result.push('\n');
 
     // Show detailed breakdown for each DocType
-    let mut sorted_doctypes: Vec<_> = links_map.keys().collect();
-    sorted_doctypes.sort();
-
-    for doctype_name in sorted_doctypes {
-        let links = &links_map[doctype_name];
+    for node in &graph.doctypes {
+        let links = &node.links;
 
-        result.push_str(&format!("\n📄 {}\n", doctype_name));
+        result.push_str(&format!("\n📄 {}\n", node.name));
         result.push_str("─".repeat(40).as_str());
         result.push('\n');
 
@@ -276,12 +573,16 @@ fn format_link_analysis(
         let mut direct_links = Vec::new();
         let mut table_links = Vec::new();
         let mut select_links = Vec::new();
+        let mut dynamic_links = Vec::new();
+        let mut fetch_links = Vec::new();
 
         for link in links {
             match link.link_type {
                 LinkType::Direct => direct_links.push(link),
                 LinkType::Table => table_links.push(link),
                 LinkType::Select => select_links.push(link),
+                LinkType::Dynamic => dynamic_links.push(link),
+                LinkType::Fetch => fetch_links.push(link),
             }
         }
 
@@ -291,7 +592,9 @@ fn format_link_analysis(
                 let req_marker = if link.is_required { "*" } else { "" };
                 result.push_str(&format!(
                     "      → {} → {}{}\n",
-                    link.field_name, link.target_doctype, req_marker
+                    link.field_name(),
+                    link.target_doctype,
+                    req_marker
                 ));
             }
         }
@@ -302,7 +605,9 @@ fn format_link_analysis(
                 let req_marker = if link.is_required { "*" } else { "" };
                 result.push_str(&format!(
                     "      → {} → {}{}\n",
-                    link.field_name, link.target_doctype, req_marker
+                    link.field_name(),
+                    link.target_doctype,
+                    req_marker
                 ));
             }
         }
@@ -313,7 +618,37 @@ fn format_link_analysis(
                 let req_marker = if link.is_required { "*" } else { "" };
                 result.push_str(&format!(
                     "      → {} → {}{}\n",
-                    link.field_name, link.target_doctype, req_marker
+                    link.field_name(),
+                    link.target_doctype,
+                    req_marker
+                ));
+            }
+        }
+
+        if !dynamic_links.is_empty() {
+            result.push_str("\n   🔀 Dynamic Links:\n");
+            for link in dynamic_links {
+                let req_marker = if link.is_required { "*" } else { "" };
+                result.push_str(&format!(
+                    "      → {} → {}{} (via {})\n",
+                    link.field_name(),
+                    link.target_doctype,
+                    req_marker,
+                    link.doctype_field.as_deref().unwrap_or_default()
+                ));
+            }
+        }
+
+        if !fetch_links.is_empty() {
+            result.push_str("\n   📥 Fetched Fields:\n");
+            for link in fetch_links {
+                let req_marker = if link.is_required { "*" } else { "" };
+                result.push_str(&format!(
+                    "      ← {}{} ← {} ({})\n",
+                    link.field_name(),
+                    req_marker,
+                    link.fetch_from.as_deref().unwrap_or_default(),
+                    link.target_doctype
                 ));
             }
         }
@@ -323,6 +658,368 @@ fn format_link_analysis(
     result.push_str("═".repeat(60).as_str());
     result.push_str("\n📝 Legend: * = Required field\n");
     result.push_str("🔗 = Direct Link, 📋 = Child Table, 📋 = Select Reference\n");
+    result.push_str("🔀 = Dynamic Link (via its DocType field), 📥 = Fetched Field (fetch_from)\n");
 
     Ok(result)
 }
+
+/// Mermaid `erDiagram` of the graph, with the link fields as attributes.
+/// A Dynamic Link is listed once, with the field holding its DocType.
+pub fn to_mermaid(graph: &LinkGraph) -> String {
+    let mut out = String::from("erDiagram\n");
+    let mut declared = HashSet::new();
+    for node in &graph.doctypes {
+        declared.insert(node.name.as_str());
+        out.push_str(&format!(
+            "    {}[\"{}\"]",
+            mermaid_id(&node.name),
+            node.name
+        ));
+        if node.links.is_empty() {
+            out.push('\n');
+            continue;
+        }
+        out.push_str(" {\n");
+        let mut attributes = HashSet::new();
+        for link in &node.links {
+            if !attributes.insert((link.fieldname.as_str(), link.link_type)) {
+                continue;
+            }
+            let key = if link.link_type == LinkType::Direct {
+                " FK"
+            } else {
+                ""
+            };
+            let comment = match link.link_type {
+                LinkType::Dynamic => {
+                    format!("via {}", link.doctype_field.as_deref().unwrap_or_default())
+                }
+                LinkType::Fetch => link.fetch_from.clone().unwrap_or_default(),
+                _ => link.target_doctype.clone(),
+            };
+            out.push_str(&format!(
+                "        {} {}{} \"{}\"\n",
+                mermaid_id(&link.field_type),
+                link.fieldname,
+                key,
+                comment
+            ));
+        }
+        out.push_str("    }\n");
+    }
+    // DocTypes past `depth`
+    for link in graph.doctypes.iter().flat_map(|n| &n.links) {
+        if declared.insert(link.target_doctype.as_str()) {
+            out.push_str(&format!(
+                "    {}[\"{}\"]\n",
+                mermaid_id(&link.target_doctype),
+                link.target_doctype
+            ));
+        }
+    }
+    for node in &graph.doctypes {
+        for link in &node.links {
+            let relation = match (link.link_type, link.is_required) {
+                (LinkType::Table, _) => "||--o{",
+                (LinkType::Direct, true) => "}o--||",
+                (LinkType::Direct, false) => "}o--o|",
+                (LinkType::Select | LinkType::Dynamic | LinkType::Fetch, _) => "}o..o|",
+            };
+            out.push_str(&format!(
+                "    {} {} {} : \"{}\"\n",
+                mermaid_id(&node.name),
+                relation,
+                mermaid_id(&link.target_doctype),
+                edge_label(link)
+            ));
+        }
+    }
+    out
+}
+
+/// Graphviz DOT of the graph. The starting DocTypes are filled, those past
+/// `depth` dashed; child tables get bold edges, Select references and Dynamic
+/// Links dashed ones and fetched fields dotted ones.
+pub fn to_dot(graph: &LinkGraph) -> String {
+    let mut out = String::from("digraph doctypes {\n    rankdir=LR;\n    node [shape=box];\n");
+    let mut declared = HashSet::new();
+    for node in &graph.doctypes {
+        declared.insert(node.name.as_str());
+        let root = graph.doctype.as_deref() == Some(node.name.as_str())
+            || (graph.module.is_some() && node.module == graph.module);
+        let style = if root {
+            " [style=filled, fillcolor=lightblue]"
+        } else {
+            ""
+        };
+        out.push_str(&format!("    {}{};\n", dot_id(&node.name), style));
+    }
+    for link in graph.doctypes.iter().flat_map(|n| &n.links) {
+        if declared.insert(link.target_doctype.as_str()) {
+            out.push_str(&format!(
+                "    {} [style=dashed];\n",
+                dot_id(&link.target_doctype)
+            ));
+        }
+    }
+    for node in &graph.doctypes {
+        for link in &node.links {
+            let req_marker = if link.is_required { "*" } else { "" };
+            let style = match link.link_type {
+                LinkType::Direct => "",
+                LinkType::Table => ", style=bold",
+                LinkType::Select => ", style=dashed",
+                LinkType::Dynamic => ", style=dashed, color=blue",
+                LinkType::Fetch => ", style=dotted, arrowhead=empty",
+            };
+            out.push_str(&format!(
+                "    {} -> {} [label={}{}];\n",
+                dot_id(&node.name),
+                dot_id(&link.target_doctype),
+                dot_id(&format!("{}{}", edge_label(link), req_marker)),
+                style
+            ));
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// Node and edge list of the graph, nodes sorted by name.
+pub fn to_edge_list(graph: &LinkGraph, anal: &AnalyzedData) -> GraphExport {
+    let mut nodes: Vec<GraphNode> = graph
+        .doctypes
+        .iter()
+        .map(|node| GraphNode {
+            name: node.name.clone(),
+            module: node.module.clone(),
+            expanded: true,
+        })
+        .collect();
+    let mut declared: HashSet<&str> = graph.doctypes.iter().map(|n| n.name.as_str()).collect();
+    let mut edges = Vec::new();
+    for node in &graph.doctypes {
+        for link in &node.links {
+            if declared.insert(link.target_doctype.as_str()) {
+                nodes.push(GraphNode {
+                    name: link.target_doctype.clone(),
+                    module: doctype_module(anal, &link.target_doctype),
+                    expanded: false,
+                });
+            }
+            edges.push(GraphEdge {
+                from: node.name.clone(),
+                to: link.target_doctype.clone(),
+                fieldname: link.fieldname.clone(),
+                field_type: link.field_type.clone(),
+                link_type: link.link_type,
+                is_required: link.is_required,
+                doctype_field: link.doctype_field.clone(),
+                fetch_from: link.fetch_from.clone(),
+            });
+        }
+    }
+    nodes.sort_by(|a, b| a.name.cmp(&b.name));
+    GraphExport { nodes, edges }
+}
+
+/// The fieldname, and for a fetched field where its value comes from.
+fn edge_label(link: &LinkInfo) -> String {
+    match &link.fetch_from {
+        Some(fetch_from) => format!("{} = {}", link.fieldname, fetch_from),
+        None => link.fieldname.clone(),
+    }
+}
+
+/// Mermaid entity names can't contain spaces; the DocType name is the alias.
+fn mermaid_id(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+fn dot_id(name: &str) -> String {
+    format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn link(target: &str, fieldname: &str, link_type: LinkType, is_required: bool) -> LinkInfo {
+        LinkInfo {
+            target_doctype: target.to_string(),
+            fieldname: fieldname.to_string(),
+            label: fieldname.to_string(),
+            field_type: match link_type {
+                LinkType::Direct => "Link",
+                LinkType::Table => "Table",
+                LinkType::Select => "Select",
+                LinkType::Dynamic => "Dynamic Link",
+                LinkType::Fetch => "Data",
+            }
+            .to_string(),
+            is_required,
+            link_type,
+            doctype_field: None,
+            fetch_from: None,
+        }
+    }
+
+    fn graph() -> LinkGraph {
+        LinkGraph {
+            doctype: Some("Pinjaman".to_string()),
+            module: None,
+            depth: 1,
+            summary: LinkSummary::default(),
+            doctypes: vec![
+                LinkNode {
+                    name: "Angsuran Pinjaman".to_string(),
+                    module: Some("Simpanan".to_string()),
+                    links: vec![],
+                },
+                LinkNode {
+                    name: "Pinjaman".to_string(),
+                    module: Some("Simpanan".to_string()),
+                    links: vec![
+                        link("Anggota", "anggota", LinkType::Direct, true),
+                        link("Angsuran Pinjaman", "angsuran", LinkType::Table, false),
+                        link("Jenis Pinjaman", "jenis", LinkType::Select, false),
+                    ],
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_to_mermaid() {
+        assert_eq!(
+            to_mermaid(&graph()),
+            r#"erDiagram
+    Angsuran_Pinjaman["Angsuran Pinjaman"]
+    Pinjaman["Pinjaman"] {
+        Link anggota FK "Anggota"
+        Table angsuran "Angsuran Pinjaman"
+        Select jenis "Jenis Pinjaman"
+    }
+    Anggota["Anggota"]
+    Jenis_Pinjaman["Jenis Pinjaman"]
+    Pinjaman }o--|| Anggota : "anggota"
+    Pinjaman ||--o{ Angsuran_Pinjaman : "angsuran"
+    Pinjaman }o..o| Jenis_Pinjaman : "jenis"
+"#
+        );
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let dot = to_dot(&graph());
+        assert!(dot.starts_with("digraph doctypes {\n"));
+        assert!(dot.contains("    \"Pinjaman\" [style=filled, fillcolor=lightblue];\n"));
+        assert!(dot.contains("    \"Anggota\" [style=dashed];\n"));
+        assert!(dot.contains("    \"Pinjaman\" -> \"Anggota\" [label=\"anggota*\"];\n"));
+        assert!(dot.contains(
+            "    \"Pinjaman\" -> \"Angsuran Pinjaman\" [label=\"angsuran\", style=bold];\n"
+        ));
+        assert!(dot.ends_with("}\n"));
+    }
+
+    #[test]
+    fn test_to_edge_list() {
+        let export = to_edge_list(&graph(), &AnalyzedData::default());
+        let nodes: Vec<_> = export
+            .nodes
+            .iter()
+            .map(|n| (n.name.as_str(), n.expanded))
+            .collect();
+        assert_eq!(
+            nodes,
+            vec![
+                ("Anggota", false),
+                ("Angsuran Pinjaman", true),
+                ("Jenis Pinjaman", false),
+                ("Pinjaman", true),
+            ]
+        );
+        assert_eq!(export.edges.len(), 3);
+        assert_eq!(export.edges[1].from, "Pinjaman");
+        assert_eq!(export.edges[1].link_type, LinkType::Table);
+    }
+
+    #[test]
+    fn test_extract_links() {
+        let json = serde_json::json!([
+            {"fieldname": "customer", "fieldtype": "Link", "options": "Customer", "reqd": 1},
+            {"fieldname": "customer_name", "fieldtype": "Data", "fetch_from": "customer.customer_name"},
+            {"fieldname": "party_type", "fieldtype": "Select", "options": "\nCustomer\nSupplier"},
+            {"fieldname": "party", "fieldtype": "Dynamic Link", "options": "party_type"},
+            {"fieldname": "ref_doctype", "fieldtype": "Link", "options": "DocType"},
+            {"fieldname": "ref_name", "fieldtype": "Dynamic Link", "options": "ref_doctype"},
+            {"fieldname": "tags", "fieldtype": "Table MultiSelect", "options": "Pinjaman Tag"},
+            {"fieldname": "stale", "fieldtype": "Data", "fetch_from": "missing.field"}
+        ]);
+        let fields: Vec<&Map<String, Value>> = json
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|f| f.as_object())
+            .collect();
+        let links: Vec<_> = extract_links(&fields)
+            .into_iter()
+            .map(|l| {
+                (
+                    l.fieldname,
+                    l.target_doctype,
+                    l.link_type,
+                    l.doctype_field,
+                    l.fetch_from,
+                )
+            })
+            .collect();
+        let expected: Vec<(String, String, LinkType, Option<String>, Option<String>)> = vec![
+            (
+                "customer".into(),
+                "Customer".into(),
+                LinkType::Direct,
+                None,
+                None,
+            ),
+            (
+                "customer_name".into(),
+                "Customer".into(),
+                LinkType::Fetch,
+                None,
+                Some("customer.customer_name".into()),
+            ),
+            (
+                "party".into(),
+                "Customer".into(),
+                LinkType::Dynamic,
+                Some("party_type".into()),
+                None,
+            ),
+            (
+                "party".into(),
+                "Supplier".into(),
+                LinkType::Dynamic,
+                Some("party_type".into()),
+                None,
+            ),
+            (
+                "ref_doctype".into(),
+                "DocType".into(),
+                LinkType::Direct,
+                None,
+                None,
+            ),
+            (
+                "tags".into(),
+                "Pinjaman Tag".into(),
+                LinkType::Table,
+                None,
+                None,
+            ),
+        ];
+        assert_eq!(links, expected);
+    }
+}
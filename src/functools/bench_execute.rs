@@ -11,19 +11,19 @@
 // from Nuwaira.
 #![allow(dead_code)]
 
-use crate::analyze::AnalyzedData;
+use super::output::command_result;
 use crate::config::Config;
-use crate::shellutil;
+use crate::shellutil::{self, RunOptions};
 use rmcp::{model::*, ErrorData as McpError};
 
 type McpResult = Result<CallToolResult, McpError>;
 
-pub fn bench_execute(
+pub async fn bench_execute(
     config: &Config,
-    _anal: &AnalyzedData,
     frappe_function: &str,
     args: Option<&str>,
     kwargs: Option<&str>,
+    options: &RunOptions,
 ) -> McpResult {
     let mut command_args = vec!["execute".to_string(), frappe_function.to_string()];
 
@@ -37,9 +37,8 @@ pub fn bench_execute(
         command_args.push(kwargs_str.to_string());
     }
 
-    let args_refs: Vec<&str> = command_args.iter().map(|s| s.as_str()).collect();
-
-    shellutil::run_bench_command(config, &args_refs, 5000)
+    shellutil::run_bench_async(config, &command_args, 5000, options)
+        .await
         .map_err(|e| McpError::new(ErrorCode::INTERNAL_ERROR, format!("{}", e), None))
-        .and_then(|output| mcp_return!(output))
+        .map(|output| command_result(&output))
 }
@@ -13,6 +13,7 @@
 use std::fs;
 use std::path::Path;
 
+use super::output::GeneratedFiles;
 use crate::config::Config;
 use crate::{
     analyze::AnalyzedData,
@@ -54,7 +55,7 @@ pub fn create_custom_page(
     let init_file = base_dir.join("__init__.py");
 
     if json_file.exists() || py_file.exists() || js_file.exists() {
-        mcp_return!(format!(
+        mcp_error!(format!(
             "Custom page '{}' already exists at: {}",
             page_name,
             base_dir.display()
@@ -64,7 +65,7 @@ pub fn create_custom_page(
     // Create parent directories if they don't exist
     if !base_dir.exists() {
         if let Err(e) = fs::create_dir_all(base_dir) {
-            mcp_return!(format!(
+            mcp_error!(format!(
                 "Failed to create directory {}: {}",
                 base_dir.display(),
                 e
@@ -72,34 +73,38 @@ pub fn create_custom_page(
         }
     }
 
-    let mut result = Vec::new();
+    let mut generated = GeneratedFiles {
+        name: page_name.to_string(),
+        module: Some(module.to_string()),
+        ..Default::default()
+    };
 
     // Create __init__.py
     if let Err(e) = fs::write(&init_file, "") {
-        mcp_return!(format!("Failed to write __init__.py file: {}", e));
+        mcp_error!(format!("Failed to write __init__.py file: {}", e));
     }
-    result.push(format!("✓ Created __init__.py: {}", init_file.display()));
+    generated.push("__init__.py", init_file.display().to_string());
 
     // Create JSON configuration file
     let json_content = create_json_boilerplate(&page_name_kebab, module, &page_title, &page_roles);
     if let Err(e) = fs::write(&json_file, json_content) {
-        mcp_return!(format!("Failed to write JSON file: {}", e));
+        mcp_error!(format!("Failed to write JSON file: {}", e));
     }
-    result.push(format!("✓ Created JSON: {}", json_file.display()));
+    generated.push("JSON", json_file.display().to_string());
 
     // Create Python backend file
     let py_content = create_python_boilerplate(&page_title);
     if let Err(e) = fs::write(&py_file, py_content) {
-        mcp_return!(format!("Failed to write Python file: {}", e));
+        mcp_error!(format!("Failed to write Python file: {}", e));
     }
-    result.push(format!("✓ Created Python: {}", py_file.display()));
+    generated.push("Python", py_file.display().to_string());
 
     // Create JavaScript frontend file
     let js_content = create_js_boilerplate(&page_name_kebab, &page_title, config);
     if let Err(e) = fs::write(&js_file, js_content) {
-        mcp_return!(format!("Failed to write JavaScript file: {}", e));
+        mcp_error!(format!("Failed to write JavaScript file: {}", e));
     }
-    result.push(format!("✓ Created JavaScript: {}", js_file.display()));
+    generated.push("JavaScript", js_file.display().to_string());
 
     let summary = format!(
         "Custom page '{}' created successfully:\n\n{}\n\nNext steps:\n1. Create the Page doctype record in the database:\n   \
@@ -110,7 +115,7 @@ pub fn create_custom_page(
                 * Standard: Yes\n   \
             - OR use: bench execute \"frappe.get_doc({{'doctype': 'Page', 'name': '{}', 'title': '{}', 'page_name': '{}', 'module': '{}', 'standard': 'Yes'}}).insert()\"\n\n2. Clear cache and reload:\n   - bench clear-cache\n   - Refresh your browser\n\n3. Access your page at: /app/{}\n\n4. Customize the form fields in the JavaScript file\n5. Add backend API methods in the Python file",
         page_title,
-        result.join("\n"),
+        generated.render(),
         page_name_kebab,
         module,
         page_name_kebab,
@@ -120,7 +125,7 @@ pub fn create_custom_page(
         page_name_kebab
     );
 
-    mcp_return!(summary)
+    mcp_return_data!(generated, summary)
 }
 
 fn create_json_boilerplate(page_name: &str, module: &str, title: &str, roles: &[String]) -> String {
@@ -622,6 +627,7 @@ mod tests {
             app_absolute_path: "/tmp/test".to_string(),
             app_relative_path: "test_app".to_string(),
             site: "frontend".to_string(),
+            ..Default::default()
         }
     }
 
@@ -681,14 +687,11 @@ mod tests {
             app_absolute_path: app_path.clone(),
             app_relative_path: "test_app".to_string(),
             site: "frontend".to_string(),
+            ..Default::default()
         };
 
         // Create a minimal AnalyzedData instance
-        let anal = AnalyzedData {
-            doctypes: vec![],
-            modules: vec![],
-            symbol_refs: None,
-        };
+        let anal = AnalyzedData::default();
 
         // Test 1: Create custom page
         let result = create_custom_page(
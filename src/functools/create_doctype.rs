@@ -15,6 +15,7 @@ use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::Path;
 
+use super::output::GeneratedFiles;
 use crate::analyze::{AnalyzedData, DocType};
 use crate::config::Config;
 use crate::serdeutil::deserialize_bool_from_int_or_bool_to_int;
@@ -71,7 +72,7 @@ pub fn create_doctype(
     if Path::new(&doctype_dir).exists() {
         // check is py file already exists
         if Path::new(&format!("{}/{}.py", doctype_dir, snake_name)).exists() {
-            mcp_return!(format!(
+            mcp_error!(format!(
                 "DocType '{}' already exists at: {}",
                 name, doctype_dir
             ));
@@ -79,11 +80,15 @@ pub fn create_doctype(
     } else {
         // Create directory structure
         if let Err(e) = fs::create_dir_all(&doctype_dir) {
-            mcp_return!(format!("Failed to create directory {}: {}", doctype_dir, e));
+            mcp_error!(format!("Failed to create directory {}: {}", doctype_dir, e));
         }
     }
 
-    let mut result = Vec::new();
+    let mut generated = GeneratedFiles {
+        name: name.to_string(),
+        module: Some(module.to_string()),
+        ..Default::default()
+    };
 
     let fields = fields.unwrap_or_default();
 
@@ -97,32 +102,32 @@ pub fn create_doctype(
     let json_content = create_json_metadata(name, &fields, &module, &settings);
     let json_path = format!("{}/{}.json", doctype_dir, snake_name);
     if let Err(e) = fs::write(&json_path, json_content) {
-        mcp_return!(format!("Failed to write JSON file: {}", e));
+        mcp_error!(format!("Failed to write JSON file: {}", e));
     }
-    result.push(format!("✓ Created metadata: {}", json_path));
+    generated.push("metadata", json_path);
 
     // 2. Create Python controller file
     let py_content = create_python_controller(config, name, &camel_name, &fields, &settings);
     let py_path = format!("{}/{}.py", doctype_dir, snake_name);
     if let Err(e) = fs::write(&py_path, py_content) {
-        mcp_return!(format!("Failed to write Python file: {}", e));
+        mcp_error!(format!("Failed to write Python file: {}", e));
     }
-    result.push(format!("✓ Created controller: {}", py_path));
+    generated.push("controller", py_path);
 
     // 3. Create JavaScript form file
     let js_content = create_javascript_form(config, name, &snake_name);
     let js_path = format!("{}/{}.js", doctype_dir, snake_name);
     if let Err(e) = fs::write(&js_path, js_content) {
-        mcp_return!(format!("Failed to write JavaScript file: {}", e));
+        mcp_error!(format!("Failed to write JavaScript file: {}", e));
     }
-    result.push(format!("✓ Created form script: {}", js_path));
+    generated.push("form script", js_path);
 
     // 4. Create __init__.py file
     let init_path = format!("{}/__init__.py", doctype_dir);
     if let Err(e) = fs::write(&init_path, "") {
-        mcp_return!(format!("Failed to write __init__.py: {}", e));
+        mcp_error!(format!("Failed to write __init__.py: {}", e));
     }
-    result.push(format!("✓ Created __init__.py: {}", init_path));
+    generated.push("__init__.py", init_path);
 
     // Update analyzed data so subsequent queries can find this DocType without re-analyzing
     anal.doctypes.push(DocType {
@@ -141,16 +146,17 @@ pub fn create_doctype(
         )),
         test_file: None,
         module: module.to_string(),
+        app: config.app_relative_path.clone(),
     });
 
     let summary = format!(
         "DocType '{}' template created successfully in module '{}':\n\n{}\n\nNext steps:\n- Run 'bench migrate' to install the DocType\n- Customize fields in the JSON metadata\n- Add business logic in the Python controller",
         name,
         module,
-        result.join("\n")
+        generated.render()
     );
 
-    mcp_return!(summary)
+    mcp_return_data!(generated, summary)
 }
 
 fn get_current_year() -> i32 {
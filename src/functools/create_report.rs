@@ -14,6 +14,7 @@ use chrono::Utc;
 use std::fs;
 use std::path::Path;
 
+use super::output::GeneratedFiles;
 use crate::analyze::AnalyzedData;
 use crate::config::Config;
 use crate::stringutil::to_snakec_var;
@@ -41,20 +42,24 @@ pub fn create_report(
     // Create report directory if it doesn't exist
     if !Path::new(&report_dir).exists() {
         if let Err(e) = fs::create_dir_all(&report_dir) {
-            mcp_return!(format!("Failed to create report directory: {}", e));
+            mcp_error!(format!("Failed to create report directory: {}", e));
         }
     }
 
-    let mut result = Vec::new();
+    let mut generated = GeneratedFiles {
+        name: report_name.to_string(),
+        module: Some(module.to_string()),
+        ..Default::default()
+    };
     let report_type_str = report_type.unwrap_or_else(|| "Script Report".to_string());
 
     // 1. Create __init__.py
     let init_path = format!("{}/__init__.py", report_dir);
     if !Path::new(&init_path).exists() {
         if let Err(e) = fs::write(&init_path, "") {
-            mcp_return!(format!("Failed to write __init__.py: {}", e));
+            mcp_error!(format!("Failed to write __init__.py: {}", e));
         }
-        result.push(format!("✓ Created __init__.py: {}", init_path));
+        generated.push("__init__.py", init_path);
     } else {
         tracing::info!("__init__.py already exists at: {}", init_path);
     }
@@ -65,9 +70,9 @@ pub fn create_report(
 
     if !Path::new(&py_path).exists() {
         if let Err(e) = fs::write(&py_path, py_content) {
-            mcp_return!(format!("Failed to write {}.py: {}", snake_name, e));
+            mcp_error!(format!("Failed to write {}.py: {}", snake_name, e));
         }
-        result.push(format!("✓ Created {}.py: {}", snake_name, py_path));
+        generated.push(&format!("{}.py", snake_name), py_path);
     } else {
         tracing::info!("{}.py already exists at: {}", snake_name, py_path);
     }
@@ -78,9 +83,9 @@ pub fn create_report(
 
     if !Path::new(&js_path).exists() {
         if let Err(e) = fs::write(&js_path, js_content) {
-            mcp_return!(format!("Failed to write {}.js: {}", snake_name, e));
+            mcp_error!(format!("Failed to write {}.js: {}", snake_name, e));
         }
-        result.push(format!("✓ Created {}.js: {}", snake_name, js_path));
+        generated.push(&format!("{}.js", snake_name), js_path);
     } else {
         tracing::info!("{}.js already exists at: {}", snake_name, js_path);
     }
@@ -91,9 +96,9 @@ pub fn create_report(
 
     if !Path::new(&json_path).exists() {
         if let Err(e) = fs::write(&json_path, json_content) {
-            mcp_return!(format!("Failed to write {}.json: {}", snake_name, e));
+            mcp_error!(format!("Failed to write {}.json: {}", snake_name, e));
         }
-        result.push(format!("✓ Created {}.json: {}", snake_name, json_path));
+        generated.push(&format!("{}.json", snake_name), json_path);
     } else {
         tracing::info!("{}.json already exists at: {}", snake_name, json_path);
     }
@@ -106,13 +111,13 @@ pub fn create_report(
         - Test the report in Frappe: /app/query-report/{}",
         report_name,
         module,
-        result.join("\n"),
+        generated.render(),
         snake_name,
         snake_name,
         snake_name
     );
 
-    mcp_return!(summary)
+    mcp_return_data!(generated, summary)
 }
 
 fn find_module_path(config: &Config, module: &str) -> Result<String, McpError> {
@@ -15,6 +15,7 @@ use serde_json::Value;
 use std::fs;
 use std::path::Path;
 
+use super::output::GeneratedFiles;
 use crate::analyze::AnalyzedData;
 use crate::config::Config;
 use crate::stringutil::{generate_abbrev, to_pascalc, to_snakec_var};
@@ -33,7 +34,10 @@ pub fn create_test_template(
     // Find the DocType directory by searching for the JSON metadata file
     let doctype_path = find_doctype_path(config, doctype)?;
 
-    let mut result = Vec::new();
+    let mut generated = GeneratedFiles {
+        name: doctype.to_string(),
+        ..Default::default()
+    };
     let dependencies = doctype_dependencies.unwrap_or_default();
 
     // 1. Create test_records.json
@@ -41,38 +45,32 @@ pub fn create_test_template(
     let test_records_path = format!("{}/test_records.json", doctype_path);
 
     if Path::new(&test_records_path).exists() {
-        mcp_return!(format!(
+        mcp_error!(format!(
             "test_records.json already exists at: {}",
             test_records_path
         ));
     }
 
     if let Err(e) = fs::write(&test_records_path, test_records_content) {
-        mcp_return!(format!("Failed to write test_records.json: {}", e));
+        mcp_error!(format!("Failed to write test_records.json: {}", e));
     }
-    result.push(format!(
-        "✓ Created test_records.json: {}",
-        test_records_path
-    ));
+    generated.push("test_records.json", test_records_path);
 
     // 2. Create test_[doctype_name].py
     let test_py_content = generate_test_python_file(config, doctype, &snake_name, &dependencies);
     let test_py_path = format!("{}/test_{}.py", doctype_path, snake_name);
 
     if Path::new(&test_py_path).exists() {
-        // mcp_return!(format!(
+        // mcp_error!(format!(
         //     "test_{}.py already exists at: {}",
         //     snake_name, test_py_path
         // ));
         tracing::info!("test_{}.py already exists at: {}", snake_name, test_py_path)
     } else {
         if let Err(e) = fs::write(&test_py_path, test_py_content) {
-            mcp_return!(format!("Failed to write test_{}.py: {}", snake_name, e));
+            mcp_error!(format!("Failed to write test_{}.py: {}", snake_name, e));
         }
-        result.push(format!(
-            "✓ Created test_{}.py: {}",
-            snake_name, test_py_path
-        ));
+        generated.push(&format!("test_{}.py", snake_name), test_py_path);
     }
 
     let summary = format!(
@@ -82,12 +80,12 @@ pub fn create_test_template(
             - Add test methods in test_{}.py\n\
             - Run tests using: bench run-tests --doctype \"{}\"",
         doctype,
-        result.join("\n"),
+        generated.render(),
         snake_name,
         doctype,
     );
 
-    mcp_return!(summary)
+    mcp_return_data!(generated, summary)
 }
 
 fn find_doctype_path(config: &Config, doctype: &str) -> Result<String, McpError> {
@@ -13,6 +13,7 @@
 use std::fs;
 use std::path::Path;
 
+use super::output::GeneratedFiles;
 use crate::config::Config;
 use crate::{
     analyze::AnalyzedData,
@@ -46,13 +47,13 @@ pub fn create_web_page(
 
     // Check if file already exists
     if index_html.exists() {
-        mcp_return!(format!("File already exists at: {}", index_html.display()));
+        mcp_error!(format!("File already exists at: {}", index_html.display()));
     }
 
     // Create parent directories if they don't exist
     if !base_dir.exists() {
         if let Err(e) = fs::create_dir_all(base_dir) {
-            mcp_return!(format!(
+            mcp_error!(format!(
                 "Failed to create directory {}: {}",
                 base_dir.display(),
                 e
@@ -62,7 +63,10 @@ pub fn create_web_page(
 
     let filename = slug.split('/').last().unwrap_or("index").to_string();
 
-    let mut result = Vec::new();
+    let mut generated = GeneratedFiles {
+        name: slug.to_string(),
+        ..Default::default()
+    };
 
     // Create HTML file
     let html_content = create_html_boilerplate(
@@ -73,18 +77,18 @@ pub fn create_web_page(
         &to_kebabc(&filename),
     );
     if let Err(e) = fs::write(&index_html, html_content) {
-        mcp_return!(format!("Failed to write HTML file: {}", e));
+        mcp_error!(format!("Failed to write HTML file: {}", e));
     }
-    result.push(format!("✓ Created HTML: {}", index_html.display()));
+    generated.push("HTML", index_html.display().to_string());
 
     // Create CSS file if requested
     if css_enabled {
         let css_path = base_dir.join(format!("{}.css", filename));
         let css_content = create_css_boilerplate(&page_title);
         if let Err(e) = fs::write(&css_path, css_content) {
-            mcp_return!(format!("Failed to write CSS file: {}", e));
+            mcp_error!(format!("Failed to write CSS file: {}", e));
         }
-        result.push(format!("✓ Created CSS: {}", css_path.display()));
+        generated.push("CSS", css_path.display().to_string());
     }
 
     // Create JavaScript file if requested
@@ -92,18 +96,18 @@ pub fn create_web_page(
         let js_path = base_dir.join(format!("{}.js", filename));
         let js_content = create_js_boilerplate(&page_title);
         if let Err(e) = fs::write(&js_path, js_content) {
-            mcp_return!(format!("Failed to write JavaScript file: {}", e));
+            mcp_error!(format!("Failed to write JavaScript file: {}", e));
         }
-        result.push(format!("✓ Created JavaScript: {}", js_path.display()));
+        generated.push("JavaScript", js_path.display().to_string());
     }
 
     let summary = format!(
         "Web page '{}' created successfully:\n\n{}\n\nNext steps:\n- Customize the HTML structure as needed\n- Add your own styles to the CSS file\n- Implement interactive features in the JavaScript file",
         page_title,
-        result.join("\n")
+        generated.render()
     );
 
-    mcp_return!(summary)
+    mcp_return_data!(generated, summary)
 }
 
 fn create_html_boilerplate(
@@ -219,6 +223,7 @@ mod tests {
             app_absolute_path: "/tmp/test".to_string(),
             app_relative_path: "test_app".to_string(),
             site: "frontend".to_string(),
+            ..Default::default()
         }
     }
 
@@ -274,14 +279,11 @@ mod tests {
             app_absolute_path: app_path.clone(),
             app_relative_path: "test_app".to_string(),
             site: "frontend".to_string(),
+            ..Default::default()
         };
 
         // Create a minimal AnalyzedData instance
-        let anal = AnalyzedData {
-            doctypes: vec![],
-            modules: vec![],
-            symbol_refs: None,
-        };
+        let anal = AnalyzedData::default();
 
         // Test 1: Create web page with CSS and JS
         let result = create_web_page(
@@ -293,6 +295,9 @@ mod tests {
             Some(true),
         );
         assert!(result.is_ok());
+        let files = &result.unwrap().structured_content.unwrap()["files"];
+        assert_eq!(files.as_array().map(|f| f.len()), Some(3));
+        assert_eq!(files[0]["kind"], "HTML");
 
         // Verify files were created
         // The create_web_page function uses to_snakec on the app_name
@@ -320,6 +325,7 @@ mod tests {
         // Test 3: Try to create duplicate page
         let result = create_web_page(&config, &anal, "about", None, None, None);
         assert!(result.is_ok());
+        assert_eq!(result.as_ref().unwrap().is_error, Some(true));
         if let Ok(tool_result) = result {
             if let Some(first_content) = tool_result.content.first() {
                 if let RawContent::Text(text_content) = &first_content.raw {
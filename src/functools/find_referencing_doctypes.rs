@@ -0,0 +1,244 @@
+// Copyright (C) 2025 Nuwaira
+// All Rights Reserved.
+//
+// NOTICE: All information contained herein is, and remains
+// the property of Nuwaira.
+// The intellectual and technical concepts contained
+// herein are proprietary to Nuwaira
+// and are protected by trade secret or copyright law.
+// Dissemination of this information or reproduction of this material
+// is strictly forbidden unless prior written permission is obtained
+// from Nuwaira.
+#![allow(dead_code)]
+use std::collections::BTreeSet;
+
+use crate::analyze::AnalyzedData;
+use crate::config::Config;
+use crate::py_refs::{DocTypeIndex, LinkField};
+use crate::testutil;
+use rmcp::{model::*, schemars, ErrorData as McpError};
+use serde::Serialize;
+
+type McpResult = Result<CallToolResult, McpError>;
+
+/// Fields of the analyzed DocTypes pointing at a DocType.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub struct DocTypeReferences {
+    pub doctype: String,
+    /// Link, Table and Table MultiSelect fields pointing at the DocType
+    pub references: Vec<Reference>,
+    /// Dynamic Link fields whose Select DocType field offers the DocType; listed
+    /// with `include_dynamic`
+    pub dynamic_links: Vec<Reference>,
+    /// Number of those Dynamic Link fields, listed or not
+    pub dynamic_total: usize,
+}
+
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub struct Reference {
+    /// The referencing DocType
+    pub doctype: String,
+    pub app: String,
+    pub fieldname: String,
+    pub field_type: String,
+    /// DocTypes having the referencing DocType as a child table
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub parents: Vec<String>,
+    /// For a Dynamic Link, the field holding the name of the target DocType
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub doctype_field: Option<String>,
+}
+
+impl DocTypeReferences {
+    fn render(&self) -> String {
+        let doctypes: BTreeSet<&str> = self.references.iter().map(|r| r.doctype.as_str()).collect();
+        let mut out = format!(
+            "🔗 References to DocType '{}': {} fields in {} DocTypes\n\n",
+            self.doctype,
+            self.references.len(),
+            doctypes.len()
+        );
+        if self.references.is_empty() {
+            out.push_str(
+                "   No Link, Table or Table MultiSelect field of the analyzed apps points at it.\n",
+            );
+        }
+        for reference in &self.references {
+            out.push_str(&format!("   {}\n", reference.render()));
+        }
+        if !self.dynamic_links.is_empty() {
+            out.push_str("\n🔀 Dynamic Links (may point at it, depending on the DocType field):\n");
+            for reference in &self.dynamic_links {
+                out.push_str(&format!("   {}\n", reference.render()));
+            }
+        } else if self.dynamic_total > 0 {
+            out.push_str(&format!(
+                "\n🔀 {} Dynamic Link fields may also point at it, list them with include_dynamic: true\n",
+                self.dynamic_total
+            ));
+        }
+        out
+    }
+}
+
+impl Reference {
+    fn render(&self) -> String {
+        let mut owner = self.app.clone();
+        if !self.parents.is_empty() {
+            owner.push_str(&format!(", child of {}", self.parents.join(", ")));
+        }
+        let target = match &self.doctype_field {
+            Some(field) => format!(" → DocType in {}", field),
+            None => String::new(),
+        };
+        format!(
+            "{} ({}) → {} ({}){}",
+            self.doctype, owner, self.fieldname, self.field_type, target
+        )
+    }
+}
+
+/// Which DocTypes point at `doctype`, through which fields.
+pub fn find_referencing_doctypes(
+    config: &Config,
+    anal: &AnalyzedData,
+    doctype: &str,
+    include_dynamic: Option<bool>,
+    app: Option<String>,
+) -> McpResult {
+    let index = &anal.doctype_index;
+    if index.files().next().is_none() {
+        mcp_error!("No DocType index available. Run analysis first.".to_string());
+    }
+    // Case-insensitive like the other tools, when the DocType is one of the app's
+    let doctype = anal
+        .find_doctype(doctype, None)
+        .map_or(doctype, |dt| dt.name.as_str());
+
+    let to_reference = |(path, name, field): (&str, &str, &LinkField)| {
+        let reference = Reference {
+            doctype: name.to_string(),
+            app: app_of(config, path),
+            fieldname: field.fieldname.clone(),
+            field_type: field.fieldtype.clone(),
+            parents: parents(index, name),
+            doctype_field: (field.fieldtype == "Dynamic Link").then(|| field.options.clone()),
+        };
+        let wanted = app.as_ref().is_none_or(|app| &reference.app == app);
+        wanted.then_some(reference)
+    };
+
+    let mut references: Vec<Reference> = index
+        .referencing(doctype)
+        .into_iter()
+        .filter_map(to_reference)
+        .collect();
+    references.sort_by(|a, b| (&a.doctype, &a.fieldname).cmp(&(&b.doctype, &b.fieldname)));
+    if references.is_empty() && !index.contains(doctype) {
+        mcp_error!(format!("DocType '{}' not found in analyzed data", doctype));
+    }
+
+    let mut dynamic_links: Vec<Reference> = index
+        .dynamic_links(doctype)
+        .into_iter()
+        .filter_map(to_reference)
+        .collect();
+    dynamic_links.sort_by(|a, b| (&a.doctype, &a.fieldname).cmp(&(&b.doctype, &b.fieldname)));
+    let dynamic_total = dynamic_links.len();
+    if !include_dynamic.unwrap_or(false) {
+        dynamic_links.clear();
+    }
+
+    let result = DocTypeReferences {
+        doctype: doctype.to_string(),
+        references,
+        dynamic_links,
+        dynamic_total,
+    };
+    mcp_return_data!(result, result.render())
+}
+
+/// App directory of an indexed DocType JSON.
+fn app_of(config: &Config, path: &str) -> String {
+    let (relative, in_apps) = testutil::app_relative(path, &config.frappe_bench_dir);
+    match relative.split_once('/') {
+        Some((app, _)) if in_apps => app.to_string(),
+        _ => config.app_relative_path.clone(),
+    }
+}
+
+fn parents(index: &DocTypeIndex, doctype: &str) -> Vec<String> {
+    index
+        .parent_doctypes(doctype)
+        .into_iter()
+        .map(|p| p.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_referencing_doctypes() {
+        let config = Config {
+            frappe_bench_dir: "/home/frappe/frappe-bench".to_string(),
+            app_relative_path: "koperasi".to_string(),
+            ..Default::default()
+        };
+        let mut anal = AnalyzedData::default();
+        for (name, fields) in [
+            (
+                "Pinjaman",
+                serde_json::json!([
+                    {"fieldname": "anggota", "fieldtype": "Link", "options": "Anggota"},
+                    {"fieldname": "angsuran", "fieldtype": "Table", "options": "Angsuran"},
+                    {"fieldname": "saksi", "fieldtype": "Table MultiSelect", "options": "Anggota"},
+                    {"fieldname": "pihak_tipe", "fieldtype": "Select", "options": "\nAnggota\nSupplier"},
+                    {"fieldname": "pihak", "fieldtype": "Dynamic Link", "options": "pihak_tipe"},
+                ]),
+            ),
+            (
+                "Angsuran",
+                serde_json::json!([{"fieldname": "penjamin", "fieldtype": "Link", "options": "Anggota"}]),
+            ),
+            (
+                "Catatan",
+                serde_json::json!([
+                    {"fieldname": "ref_doctype", "fieldtype": "Link", "options": "DocType"},
+                    {"fieldname": "ref_name", "fieldtype": "Dynamic Link", "options": "ref_doctype"},
+                ]),
+            ),
+            ("Anggota", serde_json::json!([])),
+        ] {
+            anal.doctype_index
+                .add_json(&serde_json::json!({"name": name, "fields": fields}));
+        }
+
+        let result = find_referencing_doctypes(&config, &anal, "Anggota", None, None).unwrap();
+        let data = result.structured_content.unwrap();
+        let references = data["references"].as_array().unwrap();
+        assert_eq!(references.len(), 3);
+        assert_eq!(references[0]["doctype"], "Angsuran");
+        assert_eq!(references[0]["parents"], serde_json::json!(["Pinjaman"]));
+        assert_eq!(references[1]["fieldname"], "anggota");
+        assert_eq!(references[2]["field_type"], "Table MultiSelect");
+        assert_eq!(data["dynamic_links"], serde_json::json!([]));
+        assert_eq!(data["dynamic_total"], 1);
+
+        let result =
+            find_referencing_doctypes(&config, &anal, "Anggota", Some(true), None).unwrap();
+        let data = result.structured_content.unwrap();
+        // Catatan's ref_name may point at any DocType and isn't counted
+        assert_eq!(data["dynamic_links"].as_array().unwrap().len(), 1);
+        assert_eq!(data["dynamic_links"][0]["fieldname"], "pihak");
+        assert_eq!(data["dynamic_links"][0]["doctype_field"], "pihak_tipe");
+
+        let result = find_referencing_doctypes(&config, &anal, "Pinjaman", None, None).unwrap();
+        let data = result.structured_content.unwrap();
+        assert_eq!(data["dynamic_total"], 0);
+
+        let result = find_referencing_doctypes(&config, &anal, "Nasabah", None, None).unwrap();
+        assert_eq!(result.is_error, Some(true));
+    }
+}
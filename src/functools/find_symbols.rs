@@ -11,18 +11,30 @@
 // from Nuwaira.
 #![allow(dead_code)]
 
+use super::output::SourceMatch;
 use crate::analyze::AnalyzedData;
 use crate::config::Config;
 use regex::Regex;
-use rmcp::{model::*, ErrorData as McpError};
+use rmcp::{model::*, schemars, ErrorData as McpError};
+use serde::Serialize;
 use std::fs;
-use std::io::{BufRead, BufReader};
 use walkdir::WalkDir;
 
 type McpResult = Result<CallToolResult, McpError>;
 
+/// Matches of a `find_symbols` search, best first.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub struct SymbolSearch {
+    pub query: String,
+    /// `backend`, `frontend` or `all`
+    pub search_in: String,
+    pub fuzzy: bool,
+    pub matches: Vec<SourceMatch>,
+}
+
 #[derive(Debug, Clone)]
 struct ScoredMatch {
+    root: String,
     path: String,
     line_no: usize,
     content: String,
@@ -80,6 +92,7 @@ pub fn find_symbols(
     search_in: Option<String>,
     fuzzy: Option<bool>,
     limit: Option<usize>,
+    app: Option<String>,
 ) -> McpResult {
     let search_in = search_in.unwrap_or_else(|| "all".to_string());
     let fuzzy = fuzzy.unwrap_or(false);
@@ -109,11 +122,19 @@ pub fn find_symbols(
         None
     };
 
-    // Search in the app directory
-    for entry in WalkDir::new(&config.app_absolute_path)
-        .into_iter()
-        .filter_map(|e| e.ok())
-    {
+    // Search in the app directories (or only the requested one)
+    let roots: Vec<String> = match app {
+        Some(ref app) => vec![config.app_path(app)],
+        None if config.app_dirs.is_empty() => vec![config.app_absolute_path.clone()],
+        None => config.app_dirs.iter().map(|a| config.app_path(a)).collect(),
+    };
+
+    for (root, entry) in roots.iter().flat_map(|root| {
+        WalkDir::new(root)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .map(move |e| (root, e))
+    }) {
         if !entry.file_type().is_file() {
             continue;
         }
@@ -168,7 +189,7 @@ pub fn find_symbols(
             // Get relative path from the app directory
             let relative_path = entry
                 .path()
-                .strip_prefix(&config.app_absolute_path)
+                .strip_prefix(root)
                 .unwrap_or(entry.path())
                 .display()
                 .to_string();
@@ -180,6 +201,7 @@ pub fn find_symbols(
                     if score > 20.0 {
                         // Only include matches above threshold
                         scored_matches.push(ScoredMatch {
+                            root: root.clone(),
                             path: relative_path.clone(),
                             line_no: line_idx + 1,
                             content: line.trim().to_string(),
@@ -203,6 +225,7 @@ pub fn find_symbols(
                         .trim_end_matches('\r');
 
                     scored_matches.push(ScoredMatch {
+                        root: root.clone(),
                         path: relative_path.clone(),
                         line_no,
                         content: line_content.trim().to_string(),
@@ -221,99 +244,61 @@ pub fn find_symbols(
     });
     let top_matches: Vec<_> = scored_matches.into_iter().take(limit).collect();
 
-    let out = if top_matches.is_empty() {
-        format!(
-            "No symbols matching '{}' found in {} (search: {}, fuzzy: {})",
-            name, search_in, search_in, fuzzy
-        )
-    } else {
-        let display_count = top_matches.len();
-        let header = format!("Found {} symbols matching '{}':\n", display_count, name);
-
-        let mut matches_str = Vec::new();
-        for (idx, m) in top_matches.iter().enumerate() {
-            matches_str.push(String::new());
-
-            if fuzzy {
-                matches_str.push(format!(
-                    "{}. In file '{}' at line {} (score: {:.1}):",
-                    idx + 1,
-                    m.path,
+    let search = SymbolSearch {
+        query: name.to_string(),
+        search_in,
+        fuzzy,
+        matches: top_matches
+            .iter()
+            .map(|m| {
+                SourceMatch::read(
+                    m.path.clone(),
+                    &format!("{}/{}", m.root, m.path),
                     m.line_no,
-                    m.score
-                ));
-            } else {
-                matches_str.push(format!(
-                    "{}. In file '{}' at line {}:",
-                    idx + 1,
-                    m.path,
-                    m.line_no
-                ));
-            }
-
-            // Try to read the code snippet
-            let full_path = format!("{}/{}", config.app_absolute_path, m.path);
-            if let Some(snippet_lines) = read_code_snippet(&full_path, m.line_no, 2) {
-                // Find the maximum line number width for proper alignment
-                let max_line_width = snippet_lines
-                    .iter()
-                    .map(|(line_no, _)| line_no.to_string().len())
-                    .max()
-                    .unwrap_or(1);
-
-                for (line_no, content) in &snippet_lines {
-                    let is_target_line = *line_no == m.line_no;
-                    let arrow = if is_target_line { "→" } else { " " };
-
-                    matches_str.push(format!(
-                        "   {:>width$}: {} {}",
-                        line_no,
-                        arrow,
-                        content,
-                        width = max_line_width
-                    ));
-                }
-            } else {
-                matches_str.push(format!("   [Could not read file content]"));
-            }
-        }
-
-        let matches_string = matches_str.join("\n");
-        format!("{}{}", header, matches_string)
+                    Some(m.score).filter(|_| fuzzy),
+                )
+            })
+            .collect(),
     };
-
-    mcp_return!(out)
+    mcp_return_data!(search, format_symbol_search(&search))
 }
 
-fn read_code_snippet(
-    file_path: &str,
-    target_line: usize,
-    context_lines: usize,
-) -> Option<Vec<(usize, String)>> {
-    let file = fs::File::open(file_path).ok()?;
-    let reader = BufReader::new(file);
-    let mut lines: Vec<(usize, String)> = Vec::new();
-
-    let start_line = target_line.saturating_sub(context_lines);
-    let end_line = target_line + context_lines;
-
-    for (idx, line_result) in reader.lines().enumerate() {
-        let line_number = idx + 1;
-
-        if line_number >= start_line && line_number <= end_line {
-            if let Ok(line) = line_result {
-                lines.push((line_number, line));
-            }
-        }
+fn format_symbol_search(search: &SymbolSearch) -> String {
+    if search.matches.is_empty() {
+        return format!(
+            "No symbols matching '{}' found in {} (search: {}, fuzzy: {})",
+            search.query, search.search_in, search.search_in, search.fuzzy
+        );
+    }
 
-        if line_number > end_line {
-            break;
+    let header = format!(
+        "Found {} symbols matching '{}':\n",
+        search.matches.len(),
+        search.query
+    );
+
+    let mut matches_str = Vec::new();
+    for (idx, m) in search.matches.iter().enumerate() {
+        matches_str.push(String::new());
+
+        if let Some(score) = m.score {
+            matches_str.push(format!(
+                "{}. In file '{}' at line {} (score: {:.1}):",
+                idx + 1,
+                m.file,
+                m.line,
+                score
+            ));
+        } else {
+            matches_str.push(format!(
+                "{}. In file '{}' at line {}:",
+                idx + 1,
+                m.file,
+                m.line
+            ));
         }
+        matches_str.extend(m.render_snippet());
     }
 
-    if lines.is_empty() {
-        None
-    } else {
-        Some(lines)
-    }
+    format!("{}{}", header, matches_str.join("\n"))
 }
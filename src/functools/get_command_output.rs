@@ -0,0 +1,189 @@
+// Copyright (C) 2025 Nuwaira
+// All Rights Reserved.
+//
+// NOTICE: All information contained herein is, and remains
+// the property of Nuwaira.
+// The intellectual and technical concepts contained
+// herein are proprietary to Nuwaira
+// and are protected by trade secret or copyright law.
+// Dissemination of this information or reproduction of this material
+// is strictly forbidden unless prior written permission is obtained
+// from Nuwaira.
+#![allow(dead_code)]
+
+use crate::config::Config;
+use crate::runlog;
+use regex::Regex;
+use rmcp::{model::*, schemars, ErrorData as McpError};
+use serde::Serialize;
+
+type McpResult = Result<CallToolResult, McpError>;
+
+const DEFAULT_LIMIT: usize = 200;
+
+/// A page of the full output of a `bench` run.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub struct CommandLog {
+    pub run_id: String,
+    /// The command line that was run
+    pub command: String,
+    /// Lines of output in the whole run
+    pub total_lines: usize,
+    /// Lines matching `grep`, when given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub matches: Option<usize>,
+    pub lines: Vec<LogLine>,
+    /// `offset` of the next page, if there is one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_offset: Option<usize>,
+}
+
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub struct LogLine {
+    /// 1-based line number in the run's output
+    pub line: usize,
+    pub text: String,
+}
+
+impl CommandLog {
+    fn render(&self) -> String {
+        let mut out = format!(
+            "Run {}: `{}` ({} lines",
+            self.run_id, self.command, self.total_lines
+        );
+        if let Some(matches) = self.matches {
+            out.push_str(&format!(", {} matching", matches));
+        }
+        out.push_str(")\n");
+        let width = self.lines.last().map_or(1, |l| l.line.to_string().len());
+        for line in &self.lines {
+            out.push_str(&format!(
+                "{:>width$}: {}\n",
+                line.line,
+                line.text,
+                width = width
+            ));
+        }
+        if let Some(next) = self.next_offset {
+            out.push_str(&format!("... more lines, continue with offset {}\n", next));
+        }
+        out
+    }
+}
+
+/// Page through, or search, the kept output of a run.
+///
+/// `offset` and `limit` count the matching lines when `grep` (a regex) is given.
+pub fn get_command_output(
+    config: &Config,
+    run_id: &str,
+    offset: Option<usize>,
+    limit: Option<usize>,
+    grep: Option<&str>,
+) -> McpResult {
+    let dir = runlog::runs_dir(config);
+    let Some(path) = runlog::log_path(&dir, run_id).filter(|p| p.is_file()) else {
+        mcp_error!(format!(
+            "Run '{}' not found, only the last runs of this bench are kept",
+            run_id
+        ));
+    };
+    let filter = match grep.map(Regex::new).transpose() {
+        Ok(filter) => filter,
+        Err(e) => mcp_error!(format!("Invalid grep pattern: {}", e)),
+    };
+    let content = std::fs::read(&path)
+        .map_err(|e| McpError::internal_error(format!("read {}: {}", path.display(), e), None))?;
+    let content = String::from_utf8_lossy(&content);
+
+    // The first line of a log is the command, prefixed with `$ `
+    let mut lines = content.lines();
+    let command = lines
+        .next()
+        .and_then(|l| l.strip_prefix("$ "))
+        .unwrap_or_default()
+        .to_string();
+    let output: Vec<&str> = lines.collect();
+    let matching: Vec<(usize, &str)> = output
+        .iter()
+        .enumerate()
+        .filter(|(_, text)| filter.as_ref().is_none_or(|re| re.is_match(text)))
+        .map(|(i, text)| (i + 1, *text))
+        .collect();
+
+    let offset = offset.unwrap_or(0);
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).max(1);
+    let end = matching.len().min(offset.saturating_add(limit));
+    let log = CommandLog {
+        run_id: run_id.to_string(),
+        command,
+        total_lines: output.len(),
+        matches: filter.as_ref().map(|_| matching.len()),
+        lines: matching
+            .get(offset..end)
+            .unwrap_or_default()
+            .iter()
+            .map(|(line, text)| LogLine {
+                line: *line,
+                text: text.to_string(),
+            })
+            .collect(),
+        next_offset: (end < matching.len()).then_some(end),
+    };
+    mcp_return_data!(log, log.render())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_get_command_output() {
+        let bench =
+            std::env::temp_dir().join(format!("frappe-mcp-output-test-{}", std::process::id()));
+        let config = Config {
+            frappe_bench_dir: bench.to_string_lossy().to_string(),
+            ..Default::default()
+        };
+        let (run_id, mut file) = runlog::create(&runlog::runs_dir(&config)).unwrap();
+        writeln!(file, "$ bench --site dev.local run-tests").unwrap();
+        for i in 1..=5 {
+            writeln!(
+                file,
+                "test_{} ... {}",
+                i,
+                if i % 2 == 0 { "FAIL" } else { "ok" }
+            )
+            .unwrap();
+        }
+        drop(file);
+
+        let result = get_command_output(&config, &run_id, Some(1), Some(2), None).unwrap();
+        let data = result.structured_content.unwrap();
+        assert_eq!(data["command"], "bench --site dev.local run-tests");
+        assert_eq!(data["total_lines"], 5);
+        assert_eq!(data["lines"][0]["line"], 2);
+        assert_eq!(data["lines"][1]["text"], "test_3 ... ok");
+        assert_eq!(data["next_offset"], 3);
+
+        let result = get_command_output(&config, &run_id, None, None, Some("FAIL$")).unwrap();
+        let data = result.structured_content.unwrap();
+        assert_eq!(data["matches"], 2);
+        assert_eq!(data["lines"][1]["line"], 4);
+        assert!(data.get("next_offset").is_none());
+
+        let result = get_command_output(&config, &run_id, Some(10), None, None).unwrap();
+        assert_eq!(
+            result.structured_content.unwrap()["lines"],
+            serde_json::json!([])
+        );
+
+        let result = get_command_output(&config, "../config", None, None, None).unwrap();
+        assert_eq!(result.is_error, Some(true));
+        let result = get_command_output(&config, &run_id, None, None, Some("(")).unwrap();
+        assert_eq!(result.is_error, Some(true));
+
+        let _ = std::fs::remove_dir_all(&bench);
+    }
+}
@@ -10,14 +10,15 @@
 // is strictly forbidden unless prior written permission is obtained
 // from Nuwaira.
 #![allow(dead_code)]
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::path::Path;
 
+use crate::analyze::{AnalyzedData, DocType};
 use crate::config::Config;
 use crate::serdeutil::deserialize_bool_from_int_or_bool;
+use crate::shellutil::{self, RunOptions};
 use crate::stringutil::to_snakec_var;
-use crate::{analyze::AnalyzedData, shellutil};
-use rmcp::{model::*, ErrorData as McpError};
+use rmcp::{model::*, schemars, ErrorData as McpError};
 use serde_json::Value;
 
 type McpResult = Result<CallToolResult, McpError>;
@@ -79,123 +80,220 @@ struct DocTypeStruct {
     pub fields: Vec<DocField>,
 }
 
-pub fn get_doctype(config: &Config, anal: &AnalyzedData, name: &str, json_only: bool) -> McpResult {
-    let target = name;
-    let mut result: Vec<String> = Vec::new();
+/// What is known about a DocType: its files, structure and fields.
+#[derive(Debug, Default, Serialize, schemars::JsonSchema)]
+pub struct DocTypeInfo {
+    pub name: String,
+    /// `app` when found in the analyzed apps, `database` when only the site knows it
+    pub source: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub module: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backend_file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frontend_file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta_file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub test_file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_view: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_single: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_child: Option<bool>,
+    pub fields: Vec<FieldSummary>,
+    /// The whole metadata JSON, only returned with `json_only`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// The `bench` command the metadata was read with, for `database` DocTypes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+}
 
-    let candidate = anal
-        .doctypes
-        .iter()
-        .find(|a| a.name.to_lowercase() == target.to_lowercase());
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub struct FieldSummary {
+    pub fieldname: String,
+    pub label: String,
+    pub fieldtype: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub options: Option<String>,
+    pub reqd: bool,
+}
 
-    if candidate.is_none() {
-        // try snake_case variant
-        let target_snake = to_snakec_var(target);
-        let candidate_snake = anal
-            .doctypes
-            .iter()
-            .find(|a| a.name.to_lowercase() == target_snake.to_lowercase());
-        if candidate_snake.is_some() {
-            result.push(format!(
-                "Note: DocType '{}' not found, but '{}' (snake_case) found",
-                target, target_snake
-            ));
-        } else {
-            // Try to get from database and file from other apps
-            return get_doctype_from_database(config, name, json_only);
+/// Look a DocType up in the analyzed apps; `None` when it isn't there, see
+/// [`get_doctype_from_database`].
+pub fn get_doctype(
+    config: &Config,
+    anal: &AnalyzedData,
+    name: &str,
+    json_only: bool,
+    app: Option<&str>,
+) -> Option<McpResult> {
+    let target = name;
+
+    let (doc, note) = match anal.find_doctype(target, app) {
+        Some(doc) => (doc, None),
+        None => {
+            // try snake_case variant
+            let target_snake = to_snakec_var(target);
+            match anal.find_doctype(&target_snake, app) {
+                Some(doc) => (
+                    doc,
+                    Some(format!(
+                        "DocType '{}' not found, but '{}' (snake_case) found",
+                        target, target_snake
+                    )),
+                ),
+                None => return None,
+            }
         }
-        mcp_return!(result.join("\n"));
-    }
-    let doc = candidate.unwrap();
+    };
+    Some(app_doctype_info(config, doc, note, json_only))
+}
+
+fn app_doctype_info(
+    config: &Config,
+    doc: &DocType,
+    note: Option<String>,
+    json_only: bool,
+) -> McpResult {
+    let mut info = DocTypeInfo {
+        name: doc.name.clone(),
+        source: "app".to_string(),
+        app: Some(doc.app.clone()).filter(|a| !a.is_empty()),
+        module: Some(doc.module.clone()),
+        backend_file: Some(doc.backend_file.clone()),
+        frontend_file: doc.frontend_file.clone(),
+        meta_file: doc.meta_file.clone(),
+        test_file: doc.test_file.clone(),
+        note,
+        ..Default::default()
+    };
+    let root = &config.app_path(&doc.app);
 
     if json_only {
-        if doc.meta_file.is_none() {
-            mcp_return!(format!(
+        let Some(meta_file) = doc.meta_file.as_ref() else {
+            mcp_error!(format!(
                 "DocType '{}' found, but has no metadata file",
                 doc.name
             ));
-        } else {
-            // read whole metadata file
-            let meta_path = format!(
-                "{}/{}",
-                config.app_absolute_path,
-                doc.meta_file.as_ref().unwrap()
-            );
-            if !Path::new(&meta_path).exists() {
-                mcp_return!(format!(
-                    "DocType '{}' metadata file '{}' not found",
-                    target, meta_path
-                ));
-            }
-            let content = std::fs::read_to_string(meta_path).unwrap_or_else(|_| "".to_string());
-            mcp_return!(format!("{}", content));
+        };
+        // read whole metadata file
+        let meta_path = format!("{}/{}", root, meta_file);
+        if !Path::new(&meta_path).exists() {
+            mcp_error!(format!(
+                "DocType '{}' metadata file '{}' not found",
+                doc.name, meta_path
+            ));
         }
+        let content = std::fs::read_to_string(meta_path).unwrap_or_else(|_| "".to_string());
+        info.meta = serde_json::from_str(&content).ok();
+        mcp_return_data!(info, content);
     }
 
-    result.push(format!("DocType '{}' found:\n", doc.name));
-    result.push(format!("- Module: {}", doc.module));
-    result.push(format!("- Backend: {}", doc.backend_file));
-    if let Some(front) = &doc.frontend_file {
+    if let Some(meta_file) = &doc.meta_file {
+        let json_file = format!("{}/{}", root, meta_file);
+        // deserialize json file to get more info
+        tracing::debug!("Parsing DocType metadata from {}", json_file);
+        if !Path::new(&json_file).exists() {
+            info.note = Some(format!("Metadata file '{}' not found", json_file));
+        } else if let Ok(doc_struct) = parse_doctype_metadata(&json_file) {
+            info.default_view = Some(doc_struct.default_view);
+            info.is_single = doc_struct.is_single;
+            info.is_child = doc_struct.is_child;
+            info.fields = doc_struct
+                .fields
+                .into_iter()
+                .map(|field| FieldSummary {
+                    label: field.label.unwrap_or(field.fieldname.clone()),
+                    fieldname: field.fieldname,
+                    fieldtype: field.fieldtype,
+                    options: field.options,
+                    reqd: field.reqd.unwrap_or(false),
+                })
+                .collect();
+        }
+    }
+
+    mcp_return_data!(info, format_doctype_info(config, &info))
+}
+
+fn format_doctype_info(config: &Config, info: &DocTypeInfo) -> String {
+    let mut result: Vec<String> = Vec::new();
+
+    if info.source == "database" {
+        result.push(format!(
+            "DocType '{}' found in database (not in current app '{}')\n",
+            info.name, config.app_name
+        ));
+    } else {
+        if let Some(note) = &info.note {
+            result.push(format!("Note: {}", note));
+        }
+        result.push(format!("DocType '{}' found:\n", info.name));
+    }
+    if config.is_multi_app() {
+        if let Some(app) = &info.app {
+            result.push(format!("- App: {}", app));
+        }
+    }
+    if let Some(module) = &info.module {
+        result.push(format!("- Module: {}", module));
+    }
+    if let Some(backend) = &info.backend_file {
+        result.push(format!("- Backend: {}", backend));
+    }
+    if let Some(front) = &info.frontend_file {
         result.push(format!("- Frontend: {}", front));
     }
-    if let Some(meta_file) = &doc.meta_file {
+    if let Some(meta_file) = &info.meta_file {
         result.push(format!("- Metadata: {}", meta_file));
     }
-    if let Some(test_file) = &doc.test_file {
+    if let Some(test_file) = &info.test_file {
         result.push(format!("- Test: {}", test_file));
     }
 
-    let root = &config.app_absolute_path;
-
-    if doc.meta_file.is_some() {
-        let json_file = format!("{}/{}", root, doc.meta_file.as_ref().unwrap());
-        // deserialize json file to get more info
-        tracing::debug!("Parsing DocType metadata from {}", json_file);
-        if !Path::new(&json_file).exists() {
-            result.push(format!("  Note: Metadata file '{}' not found", json_file));
-            mcp_return!(result.join("\n"));
+    if let Some(default_view) = &info.default_view {
+        result.push("\n## Basic Structure".to_string());
+        result.push(format!("- Default View: {}", default_view));
+        if let Some(is_single) = info.is_single {
+            result.push(format!("- Is Single: {}", is_single));
         }
-        if let Ok(doc_struct) = parse_doctype_metadata(&json_file) {
-            result.push("\n## Basic Structure".to_string());
-            result.push(format!("- Default View: {}", doc_struct.default_view));
-            if let Some(is_single) = doc_struct.is_single {
-                result.push(format!("- Is Single: {}", is_single));
-            }
-            if let Some(is_child) = doc_struct.is_child {
-                result.push(format!("- Is Child Table: {}", is_child));
-            }
-            result.push("- Fields:".to_string());
-            for field in doc_struct.fields {
-                result.push(format!(
-                    "  - {} - \"{}\" ({}){}",
-                    &field.fieldname,
-                    field.label.as_ref().unwrap_or(&field.fieldname),
-                    field.fieldtype,
-                    if field.reqd.unwrap_or(false) {
-                        " [Required]"
-                    } else {
-                        ""
-                    }
-                ));
-            }
+        if let Some(is_child) = info.is_child {
+            result.push(format!("- Is Child Table: {}", is_child));
+        }
+        result.push("- Fields:".to_string());
+        for field in &info.fields {
+            result.push(format!(
+                "  - {} - \"{}\" ({}){}",
+                &field.fieldname,
+                field.label,
+                field.fieldtype,
+                if field.reqd { " [Required]" } else { "" }
+            ));
         }
     }
 
-    if doc.test_file.is_some() {
+    if info.source == "database" {
+        if let Some(note) = &info.note {
+            result.push(format!("\nNote: {}", note));
+        }
+    }
+
+    if info.test_file.is_some() {
         result.push(format!(
             "\nYou can run tests for this DocType using the following command:\n\
             `frappe_mcp.run_tests({})`",
-            doc.name
+            info.name
         ));
     }
 
-    let out = if result.is_empty() {
-        format!("DocType '{}' not found under '{}'", target, root)
-    } else {
-        result.join("\n")
-    };
-
-    mcp_return!(out)
+    result.join("\n")
 }
 
 fn parse_doctype_metadata(json_file: &str) -> Result<DocTypeStruct, McpError> {
@@ -226,107 +324,115 @@ fn parse_doctype_metadata_string(json_content: &str) -> Result<DocTypeStruct, Mc
     Ok(doc_struct)
 }
 
-fn get_doctype_from_database(config: &Config, name: &str, json_only: bool) -> McpResult {
-    let mut result: Vec<String> = Vec::new();
-
-    // Get DocType metadata from database using print with frappe.get_meta().as_json()
-    let args = format!("(frappe.get_meta(\"{}\").as_json(),)", name);
-
-    // let meta_result = bench_execute(config, anal, "print", Some(&args), None);
-    let meta_result =
-        shellutil::run_bench_command(config, vec!["execute", "print", "--args", args.as_str()], 0);
-
-    if meta_result.is_err() {
-        // bench execute failed
-        mcp_return!(format!(
-            "DocType '{}' not found in current app '{}'. Database query failed.",
-            name, config.app_name
+/// Read a DocType the analyzed apps don't have from the site, with
+/// `bench execute frappe.get_meta`; checked like `bench_execute`.
+pub async fn get_doctype_from_database(
+    config: &Config,
+    name: &str,
+    json_only: bool,
+    options: &RunOptions,
+) -> McpResult {
+    let denied = if !config.policy.tool_enabled("bench_execute") {
+        Some("bench_execute is disabled".to_string())
+    } else {
+        config
+            .policy
+            .check_bench(&["execute", "frappe.get_meta"])
+            .err()
+    };
+    if let Some(reason) = denied {
+        mcp_error!(format!(
+            "DocType '{}' not found in the analyzed apps, and looking it up in the site is denied by policy: {}",
+            name, reason
         ));
     }
-    let meta_response = meta_result.unwrap();
 
-    let output = meta_response;
+    // JSON-encoded, as bench evaluates `--args` as Python
+    let args = serde_json::to_string(&[name]).unwrap_or_default();
+    let output = shellutil::run_bench_async(
+        config,
+        &["execute", "frappe.get_meta", "--args", args.as_str()],
+        0,
+        options,
+    )
+    .await
+    .map_err(|e| McpError::new(ErrorCode::INTERNAL_ERROR, format!("{}", e), None))?;
+
+    if !output.success {
+        mcp_error!(format!(
+            "DocType '{}' not found in current app '{}'. Database query failed.\n{}",
+            name,
+            config.app_name,
+            output.render()
+        ));
+    }
+    // The meta is printed as JSON on the last line
+    let json_str = output
+        .stdout
+        .lines()
+        .rev()
+        .map(str::trim)
+        .find(|l| l.starts_with('{'))
+        .unwrap_or_default()
+        .to_string();
+
+    let Ok(meta_json) = serde_json::from_str::<Value>(&json_str) else {
+        // Not a valid DocType
+        mcp_error!(format!(
+            "DocType '{}' not found in current app '{}' or in the database",
+            name, config.app_name
+        ));
+    };
 
-    let json_str = output.trim();
+    let mut info = DocTypeInfo {
+        name: name.to_string(),
+        source: "database".to_string(),
+        module: meta_json
+            .get("module")
+            .and_then(|m| m.as_str())
+            .map(|m| m.to_string()),
+        note: Some(format!(
+            "This DocType is not in the current app '{}' but is available in the site.",
+            config.app_name
+        )),
+        command: Some(output.command.clone()),
+        ..Default::default()
+    };
 
     if json_only {
-        mcp_return!(json_str.to_string());
+        info.meta = Some(meta_json);
+        mcp_return_data!(info, json_str);
     }
 
-    match serde_json::from_str::<Value>(json_str) {
-        Ok(meta_json) => {
-            result.push(format!(
-                "DocType '{}' found in database (not in current app '{}')\n",
-                name, config.app_name
-            ));
+    if let Some(module) = &info.module {
+        let doctype_snake = to_snakec_var(name);
+        let module_snake = to_snakec_var(module);
 
-            let mut potential_path = String::new();
-
-            // Extract module info
-            if let Some(module) = meta_json.get("module").and_then(|m| m.as_str()) {
-                result.push(format!("- Module: {}", module));
-
-                let doctype_snake = to_snakec_var(name);
-                let module_snake = to_snakec_var(module);
-
-                // Common apps to check
-                let common_apps = vec![
-                    "frappe",
-                    "erpnext",
-                    "hrms",
-                    "education",
-                    "lms",
-                    &config.app_name,
-                ];
-
-                for app in common_apps {
-                    potential_path = format!(
-                        "{}/apps/{}/{}/{}/doctype/{}/{}.py",
-                        config.frappe_bench_dir,
-                        app,
-                        app,
-                        module_snake,
-                        doctype_snake,
-                        doctype_snake
-                    );
-
-                    // check if exists
-                    if Path::new(&potential_path).exists() {
-                        result.push(format!("- Backend: {}", potential_path));
-                        break;
-                    }
-                }
-            }
-            // println!("Potential backend path: {}", potential_path);
+        // Look through every app installed in the bench
+        let backend = config.bench_apps().into_iter().find_map(|app| {
+            let potential_path = format!(
+                "{}/apps/{}/{}/{}/doctype/{}/{}.py",
+                config.frappe_bench_dir, app, app, module_snake, doctype_snake, doctype_snake
+            );
+            Path::new(&potential_path)
+                .exists()
+                .then_some(potential_path)
+        });
 
-            // direct relative candidate
-            if !Path::new(&potential_path).exists() {
-                mcp_return!(format!("Path not found: {}", potential_path));
-            }
-            let json_file = Path::new(&potential_path).with_extension("json");
+        if let Some(backend) = backend {
+            let json_file = Path::new(&backend).with_extension("json");
             if json_file.exists() {
-                result.push(format!("- Metadata: {}", json_file.display()));
+                info.meta_file = Some(json_file.display().to_string());
             }
-            let js_file = Path::new(&potential_path).with_extension("js");
+            let js_file = Path::new(&backend).with_extension("js");
             if js_file.exists() {
-                result.push(format!("- Frontend: {}", js_file.display()));
+                info.frontend_file = Some(js_file.display().to_string());
             }
-
-            result.push(format!(
-                "\nNote: This DocType is not in the current app '{}' but is available in the site.",
-                config.app_name
-            ));
-
-            mcp_return!(result.join("\n"));
-        }
-        Err(_) => {
-            // Not a valid DocType
-            mcp_return!(format!(
-                "DocType '{}' not found in current app '{}' or in the database",
-                name, config.app_name
-            ));
+            info.backend_file = Some(backend);
         }
     }
+
+    mcp_return_data!(info, format_doctype_info(config, &info))
 }
 
 #[cfg(test)]
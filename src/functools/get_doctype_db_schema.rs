@@ -11,17 +11,123 @@
 // from Nuwaira.
 #![allow(dead_code)]
 
-use crate::analyze::AnalyzedData;
 use crate::config::Config;
-use crate::shellutil;
-use rmcp::{model::*, ErrorData as McpError};
+use crate::shellutil::{self, CommandOutput, RunOptions};
+use rmcp::{model::*, schemars, ErrorData as McpError};
+use serde::Serialize;
 
 type McpResult = Result<CallToolResult, McpError>;
 
+/// Columns of a DocType's table, in `DESCRIBE` form.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub struct DbSchema {
+    pub doctype: String,
+    pub table: String,
+    pub columns: Vec<DbColumn>,
+    /// The raw `bench mariadb` run
+    pub output: CommandOutput,
+}
+
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub struct DbColumn {
+    pub field: String,
+    #[serde(rename = "type")]
+    pub column_type: String,
+    pub null: String,
+    pub key: String,
+    pub default: String,
+    pub extra: String,
+}
+
 /// Run a bench command to get the database schema of a specified DocType
-pub fn get_doctype_db_schema(config: &Config, _anal: &AnalyzedData, doctype: &str) -> McpResult {
-    let args = ["mariadb", "-e", &format!("DESCRIBE `tab{}`;", doctype)];
-    shellutil::run_bench_command(config, args, 5000)
-        .map_err(|e| McpError::new(ErrorCode::INTERNAL_ERROR, format!("{}", e), None))
-        .and_then(|output| mcp_return!(output))
+pub async fn get_doctype_db_schema(
+    config: &Config,
+    doctype: &str,
+    options: &RunOptions,
+) -> McpResult {
+    let table = format!("tab{}", doctype);
+    let sql = columns_sql(&table);
+    if let Err(reason) = config.policy.check_sql(&sql) {
+        mcp_error!(format!("Denied by policy: {}", reason));
+    }
+    let args = ["mariadb", "-e", &sql];
+    let output = shellutil::run_bench_async(config, &args, 5000, options)
+        .await
+        .map_err(|e| McpError::new(ErrorCode::INTERNAL_ERROR, format!("{}", e), None))?;
+    if !output.success {
+        return Ok(super::output::command_result(&output));
+    }
+
+    let schema = DbSchema {
+        doctype: doctype.to_string(),
+        columns: parse_describe(&output.stdout),
+        table,
+        output,
+    };
+    mcp_return_data!(schema, schema.output.render())
+}
+
+/// `DESCRIBE` of `table` as a SELECT over `information_schema`, with the
+/// table name as an escaped string literal rather than an identifier.
+fn columns_sql(table: &str) -> String {
+    let table = table.replace('\\', "\\\\").replace('\'', "''");
+    format!(
+        "SELECT COLUMN_NAME AS `Field`, COLUMN_TYPE AS `Type`, IS_NULLABLE AS `Null`, \
+         COLUMN_KEY AS `Key`, COLUMN_DEFAULT AS `Default`, EXTRA AS `Extra` \
+         FROM information_schema.COLUMNS \
+         WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = '{}' \
+         ORDER BY ORDINAL_POSITION;",
+        table
+    )
+}
+
+/// Rows of the tab-separated `DESCRIBE` output (the first line is the header).
+fn parse_describe(stdout: &str) -> Vec<DbColumn> {
+    stdout
+        .lines()
+        .skip_while(|l| !l.starts_with("Field\t"))
+        .skip(1)
+        .filter_map(|line| {
+            let cols: Vec<&str> = line.split('\t').collect();
+            if cols.len() < 6 {
+                return None;
+            }
+            Some(DbColumn {
+                field: cols[0].to_string(),
+                column_type: cols[1].to_string(),
+                null: cols[2].to_string(),
+                key: cols[3].to_string(),
+                default: cols[4].to_string(),
+                extra: cols[5].to_string(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_describe() {
+        let stdout = "Field\tType\tNull\tKey\tDefault\tExtra\n\
+            name\tvarchar(140)\tNO\tPRI\tNULL\t\n\
+            docstatus\tint(1)\tNO\t\t0\t";
+        let columns = parse_describe(stdout);
+        assert_eq!(columns.len(), 2);
+        assert_eq!(columns[0].field, "name");
+        assert_eq!(columns[0].key, "PRI");
+        assert_eq!(columns[1].column_type, "int(1)");
+        assert_eq!(columns[1].default, "0");
+    }
+
+    #[test]
+    fn test_columns_sql() {
+        let sql = columns_sql("tabUser`; DROP TABLE tabUser; -- ' OR 1 \\");
+        assert!(sql.contains("TABLE_NAME = 'tabUser`; DROP TABLE tabUser; -- '' OR 1 \\\\'"));
+        let statements = crate::sqlutil::split_statements(&sql);
+        assert_eq!(statements.len(), 1);
+        assert_eq!(statements[0].kind, "select");
+        assert!(crate::policy::Policy::default().check_sql(&sql).is_ok());
+    }
 }
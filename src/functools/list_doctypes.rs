@@ -13,81 +13,119 @@
 
 use crate::analyze::AnalyzedData;
 use crate::config::Config;
-use rmcp::{model::*, ErrorData as McpError};
+use rmcp::{model::*, schemars, ErrorData as McpError};
+use serde::Serialize;
+use std::collections::BTreeMap;
 
 type McpResult = Result<CallToolResult, McpError>;
 
+/// DocTypes grouped by module.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub struct DocTypeList {
+    /// Module filter, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub module: Option<String>,
+    /// App filter, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app: Option<String>,
+    /// Number of DocTypes listed
+    pub total: usize,
+    /// Modules sorted by name, each with its DocTypes sorted by name
+    pub modules: Vec<ModuleDocTypes>,
+}
+
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub struct ModuleDocTypes {
+    pub name: String,
+    pub app: String,
+    pub doctypes: Vec<String>,
+}
+
 pub fn list_doctypes(
-    _config: &Config,
+    config: &Config,
     anal: &AnalyzedData,
     module_filter: Option<String>,
+    app_filter: Option<String>,
 ) -> McpResult {
-    let mut result: Vec<String> = Vec::new();
+    // Filter doctypes by app if specified
+    let in_app = |app: &str| {
+        app_filter
+            .as_ref()
+            .is_none_or(|f| app == f || (app.is_empty() && f == &config.app_relative_path))
+    };
+    let in_module = |module: &str| {
+        module_filter
+            .as_ref()
+            .is_none_or(|m| module.to_lowercase() == m.to_lowercase())
+    };
+
+    // Group by module for better organization
+    let mut modules: BTreeMap<(&str, &str), Vec<String>> = BTreeMap::new();
+    for doctype in anal
+        .doctypes
+        .iter()
+        .filter(|dt| in_app(&dt.app) && in_module(&dt.module))
+    {
+        modules
+            .entry((&doctype.module, &doctype.app))
+            .or_default()
+            .push(doctype.name.clone());
+    }
 
-    // Filter doctypes by module if specified
-    let (doctypes, filtered_module_name) = if let Some(ref module) = module_filter {
-        let filtered = anal
-            .doctypes
-            .iter()
-            .filter(|dt| dt.module.to_lowercase() == module.to_lowercase())
-            .collect::<Vec<_>>();
-        (filtered, Some(module.clone()))
-    } else {
-        (anal.doctypes.iter().collect::<Vec<_>>(), None)
+    let list = DocTypeList {
+        module: module_filter.clone(),
+        app: app_filter.clone(),
+        total: modules.values().map(|d| d.len()).sum(),
+        modules: modules
+            .into_iter()
+            .map(|((name, app), mut doctypes)| {
+                doctypes.sort();
+                ModuleDocTypes {
+                    name: name.to_string(),
+                    app: app.to_string(),
+                    doctypes,
+                }
+            })
+            .collect(),
     };
+    mcp_return_data!(list, format_doctype_list(config, &list))
+}
 
-    if doctypes.is_empty() {
-        let msg = if let Some(module_name) = filtered_module_name {
+fn format_doctype_list(config: &Config, list: &DocTypeList) -> String {
+    if list.modules.is_empty() {
+        return if let Some(module_name) = &list.module {
             format!("No DocTypes found in module '{}'", module_name)
+        } else if let Some(app) = &list.app {
+            format!("No DocTypes found in app '{}'", app)
         } else {
             "No DocTypes found in the current app".to_string()
         };
-        mcp_return!(msg);
     }
 
-    let doctype_count = doctypes.len();
-
-    // Group by module for better organization
-    let mut modules = std::collections::HashMap::new();
-    for doctype in &doctypes {
-        modules
-            .entry(&doctype.module)
-            .or_insert_with(Vec::new)
-            .push(doctype);
-    }
-
-    // Sort modules by name
-    let mut module_names: Vec<_> = modules.keys().collect();
-    module_names.sort();
-
-    let total_count = if filtered_module_name.is_some() {
-        doctype_count
-    } else {
-        anal.doctypes.len()
-    };
-
+    let mut result: Vec<String> = Vec::new();
     result.push(format!(
         "Found {} DocType(s) across {} module(s):\n",
-        total_count,
-        modules.len()
+        list.total,
+        list.modules.len()
     ));
 
-    for module_name in module_names {
-        let mut module_doctypes = modules[module_name].clone();
-        module_doctypes.sort_by(|a, b| a.name.cmp(&b.name));
-
-        result.push(format!("## Module: {}", module_name));
+    for module in &list.modules {
+        if config.is_multi_app() {
+            result.push(format!("## Module: {} (app: {})", module.name, module.app));
+        } else {
+            result.push(format!("## Module: {}", module.name));
+        }
         result.push(format!(
             "   ({} DocType{})",
-            module_doctypes.len(),
-            if module_doctypes.len() == 1 { "" } else { "s" }
+            module.doctypes.len(),
+            if module.doctypes.len() == 1 { "" } else { "s" }
         ));
 
-        for doctype in module_doctypes {
-            result.push(format!("   - {}", doctype.name));
+        for doctype in &module.doctypes {
+            result.push(format!("   - {}", doctype));
         }
         result.push("".to_string()); // Empty line between modules
     }
 
-    mcp_return!(result.join("\n"))
+    result.join("\n")
 }
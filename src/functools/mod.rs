@@ -17,30 +17,43 @@ mod create_report;
 mod create_test_template;
 mod create_web_page;
 mod find_field_usage;
+mod find_referencing_doctypes;
 mod find_symbols;
+mod get_command_output;
 mod get_doctype;
 mod get_doctype_db_schema;
 mod get_function_signature;
 mod list_doctypes;
+mod output;
 mod run_bench_command;
 mod run_db_command;
 mod run_tests;
 mod search_frappe_docs;
+mod tests_for_changes;
 
-pub use analyze_links::analyze_links;
+pub use analyze_links::{analyze_links, GraphFormat, LinkGraph};
 pub use bench_execute::bench_execute;
 pub use create_custom_page::create_custom_page;
 pub use create_doctype::{create_doctype, DoctypeSettings, FieldDefinition};
 pub use create_report::create_report;
 pub use create_test_template::create_test_template;
 pub use create_web_page::create_web_page;
-pub use find_field_usage::find_field_usage;
-pub use find_symbols::find_symbols;
-pub use get_doctype::get_doctype;
-pub use get_doctype_db_schema::get_doctype_db_schema;
+pub use find_field_usage::{find_field_usage, FieldUsage};
+pub use find_referencing_doctypes::{find_referencing_doctypes, DocTypeReferences};
+pub use find_symbols::{find_symbols, SymbolSearch};
+pub use get_command_output::{get_command_output, CommandLog};
+pub use get_doctype::{get_doctype, get_doctype_from_database, DocTypeInfo};
+pub use get_doctype_db_schema::{get_doctype_db_schema, DbSchema};
 // pub use get_function_signature::get_function_signature;
-pub use list_doctypes::list_doctypes;
+pub use list_doctypes::{list_doctypes, DocTypeList};
+pub use output::{structured_result, GeneratedFiles};
 pub use run_bench_command::run_bench_command;
-pub use run_db_command::run_db_command;
-pub use run_tests::run_tests;
-pub use search_frappe_docs::{get_frappe_doc, search_frappe_docs, OutputFormat};
+pub use run_db_command::{run_db_command, DbQuery};
+pub use run_tests::{rerun_failed_args, run_tests, test_args, TestRun};
+pub use search_frappe_docs::{
+    frappe_doc_content, frappe_doc_entries, get_frappe_doc, search_frappe_docs, DocSearch,
+    FrappeDocPage, OutputFormat,
+};
+pub use tests_for_changes::{
+    diff_files, git_changed_files, select_tests, tests_for_changes, TestSelection,
+};
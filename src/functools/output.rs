@@ -0,0 +1,276 @@
+// Copyright (C) 2025 Nuwaira
+// All Rights Reserved.
+//
+// NOTICE: All information contained herein is, and remains
+// the property of Nuwaira.
+// The intellectual and technical concepts contained
+// herein are proprietary to Nuwaira
+// and are protected by trade secret or copyright law.
+// Dissemination of this information or reproduction of this material
+// is strictly forbidden unless prior written permission is obtained
+// from Nuwaira.
+#![allow(dead_code)]
+
+//! Structured tool output shared by several functools.
+//!
+//! Every tool returns its data as MCP `structured_content` matching the
+//! `output_schema` declared in the server, and renders the text content from
+//! the same data.
+
+use std::fs;
+use std::io::{BufRead, BufReader};
+
+use rmcp::{model::*, schemars};
+use serde::Serialize;
+
+use crate::shellutil::CommandOutput;
+
+/// Tool result carrying `data` as structured content and `text` as its view.
+pub fn structured_result<T: Serialize>(data: &T, text: String) -> CallToolResult {
+    let mut result = CallToolResult::success(vec![Content::text(text)]);
+    result.structured_content = serde_json::to_value(data).ok();
+    result
+}
+
+/// Result of a `bench` run; a non-zero exit is flagged as a tool error.
+pub fn command_result(output: &CommandOutput) -> CallToolResult {
+    let mut result = structured_result(output, output.render());
+    result.is_error = Some(!output.success);
+    result
+}
+
+/// A line of a source file matching a search.
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct SourceMatch {
+    /// File path (relative to the app directory, or absolute for field usages)
+    pub file: String,
+    /// 1-based line number of the match
+    pub line: usize,
+    /// Fuzzy match score, when fuzzy search was used
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score: Option<f64>,
+    /// The matched line and up to two lines around it
+    pub snippet: Vec<SnippetLine>,
+}
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct SnippetLine {
+    pub line: usize,
+    pub text: String,
+}
+
+impl SourceMatch {
+    /// A match at `file:line`, reading its snippet from `path`.
+    pub fn read(file: String, path: &str, line: usize, score: Option<f64>) -> Self {
+        let snippet = read_code_snippet(path, line, 2)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(line, text)| SnippetLine { line, text })
+            .collect();
+        SourceMatch {
+            file,
+            line,
+            score,
+            snippet,
+        }
+    }
+
+    /// The snippet lines, aligned and with an arrow at the matched line.
+    pub fn render_snippet(&self) -> Vec<String> {
+        if self.snippet.is_empty() {
+            return vec!["   [Could not read file content]".to_string()];
+        }
+        let width = self
+            .snippet
+            .iter()
+            .map(|l| l.line.to_string().len())
+            .max()
+            .unwrap_or(1);
+        self.snippet
+            .iter()
+            .map(|l| {
+                let arrow = if l.line == self.line { "→" } else { " " };
+                format!("   {:>width$}: {} {}", l.line, arrow, l.text, width = width)
+            })
+            .collect()
+    }
+}
+
+/// Files written by a `create_*` tool.
+#[derive(Debug, Clone, Default, Serialize, schemars::JsonSchema)]
+pub struct GeneratedFiles {
+    /// Name of the generated DocType, page, report or test
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub module: Option<String>,
+    pub files: Vec<GeneratedFile>,
+}
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct GeneratedFile {
+    /// What the file is, e.g. `metadata`, `controller`, `form script`
+    pub kind: String,
+    pub path: String,
+}
+
+impl GeneratedFiles {
+    pub fn push(&mut self, kind: &str, path: impl Into<String>) {
+        self.files.push(GeneratedFile {
+            kind: kind.to_string(),
+            path: path.into(),
+        });
+    }
+
+    /// One `✓ Created <kind>: <path>` line per file.
+    pub fn render(&self) -> String {
+        self.files
+            .iter()
+            .map(|f| format!("✓ Created {}: {}", f.kind, f.path))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+pub(crate) fn read_code_snippet(
+    file_path: &str,
+    target_line: usize,
+    context_lines: usize,
+) -> Option<Vec<(usize, String)>> {
+    let file = fs::File::open(file_path).ok()?;
+    let reader = BufReader::new(file);
+    let mut lines: Vec<(usize, String)> = Vec::new();
+
+    let start_line = target_line.saturating_sub(context_lines);
+    let end_line = target_line + context_lines;
+
+    for (idx, line_result) in reader.lines().enumerate() {
+        let line_number = idx + 1;
+
+        if line_number >= start_line && line_number <= end_line {
+            if let Ok(line) = line_result {
+                lines.push((line_number, line));
+            }
+        }
+
+        if line_number > end_line {
+            break;
+        }
+    }
+
+    if lines.is_empty() {
+        None
+    } else {
+        Some(lines)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_code_snippet_middle_of_file() {
+        let test_file = "test_data/sample_code.py";
+        let result = read_code_snippet(test_file, 9, 2);
+
+        assert!(result.is_some());
+        let lines = result.unwrap();
+
+        // println!(
+        //     "Lines: {}",
+        //     lines
+        //         .iter()
+        //         .map(|(n, l)| format!("{}: {}", n, l))
+        //         .collect::<Vec<String>>()
+        //         .join("\n")
+        // );
+
+        // Should have 5 lines total (target line 9 + 2 before + 2 after)
+        assert_eq!(lines.len(), 5);
+
+        // Check line numbers
+        assert_eq!(lines[0].0, 7);
+        assert_eq!(lines[1].0, 8);
+        assert_eq!(lines[2].0, 9); // Target line
+        assert_eq!(lines[3].0, 10);
+        assert_eq!(lines[4].0, 11);
+
+        // Check content
+        assert!(lines[2].1.contains("if task.status == \"Open\":"));
+    }
+
+    #[test]
+    fn test_read_code_snippet_beginning_of_file() {
+        let test_file = "test_data/sample_code.py";
+        let result = read_code_snippet(test_file, 2, 2);
+
+        assert!(result.is_some());
+        let lines = result.unwrap();
+
+        // Should have 4 lines (no line 0, starts at line 1)
+        assert_eq!(lines.len(), 4);
+
+        assert_eq!(lines[0].0, 1);
+        assert_eq!(lines[1].0, 2); // Target line
+        assert_eq!(lines[2].0, 3);
+        assert_eq!(lines[3].0, 4);
+
+        assert!(lines[1].1.contains("# Line 2"));
+    }
+
+    #[test]
+    fn test_read_code_snippet_end_of_file() {
+        let test_file = "test_data/sample_code.py";
+        let result = read_code_snippet(test_file, 51, 2);
+
+        assert!(result.is_some());
+        let lines = result.unwrap();
+
+        // File has 52 lines, so line 51 with 2 context would be:
+        // lines 49, 50, 51, 52 (and potentially 53 if it existed)
+        // But since file ends at 52, we get 49-52
+        assert!(lines.len() >= 4);
+
+        // Find the line with number 51
+        let target_line = lines.iter().find(|(num, _)| *num == 51);
+        assert!(target_line.is_some());
+        assert!(target_line.unwrap().1.contains("return Result(True)"));
+    }
+
+    #[test]
+    fn test_read_code_snippet_nonexistent_file() {
+        let test_file = "test_data/nonexistent.py";
+        let result = read_code_snippet(test_file, 10, 2);
+
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn test_read_code_snippet_beyond_file_end() {
+        let test_file = "test_data/sample_code.py";
+        let result = read_code_snippet(test_file, 100, 2);
+
+        // When requesting a line beyond the file, we should get None or empty result
+        if let Some(lines) = result {
+            assert!(lines.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_read_code_snippet_single_context_line() {
+        let test_file = "test_data/sample_code.py";
+        let result = read_code_snippet(test_file, 20, 1);
+
+        assert!(result.is_some());
+        let lines = result.unwrap();
+
+        // Should have 3 lines (19, 20, 21)
+        assert_eq!(lines.len(), 3);
+
+        assert_eq!(lines[0].0, 19);
+        assert_eq!(lines[1].0, 20); // Target line
+        assert_eq!(lines[2].0, 21);
+
+        assert!(lines[1].1.contains("# Line 20"));
+    }
+}
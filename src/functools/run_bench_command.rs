@@ -11,14 +11,14 @@
 // from Nuwaira.
 #![allow(dead_code)]
 
-use crate::analyze::AnalyzedData;
+use super::output::command_result;
 use crate::config::Config;
-use crate::shellutil;
+use crate::shellutil::{self, RunOptions};
 use rmcp::{model::*, ErrorData as McpError};
 
 type McpResult = Result<CallToolResult, McpError>;
 
-pub fn run_bench_command(config: &Config, _anal: &AnalyzedData, args: &[&str]) -> McpResult {
+pub async fn run_bench_command(config: &Config, args: &[&str], options: &RunOptions) -> McpResult {
     // if migrate is in args, then remove the lock file, sometimes migrate fails because of the
     // lock file in dev environment.
     if args.contains(&"migrate") {
@@ -41,7 +41,8 @@ pub fn run_bench_command(config: &Config, _anal: &AnalyzedData, args: &[&str]) -
             }
         }
     }
-    shellutil::run_bench_command(config, args, 5000)
+    shellutil::run_bench_async(config, args, 5000, options)
+        .await
         .map_err(|e| McpError::new(ErrorCode::INTERNAL_ERROR, format!("{}", e), None))
-        .and_then(|output| mcp_return!(output))
+        .map(|output| command_result(&output))
 }
@@ -11,15 +11,172 @@
 // from Nuwaira.
 #![allow(dead_code)]
 
-use crate::analyze::AnalyzedData;
+use super::output::structured_result;
 use crate::config::Config;
-use crate::shellutil;
-use rmcp::{model::*, ErrorData as McpError};
+use crate::policy::SqlVerdict;
+use crate::shellutil::{self, CommandOutput, RunOptions};
+use crate::sqlutil::{self, StatementClass};
+use rmcp::{model::*, schemars, ErrorData as McpError};
+use serde::Serialize;
 
 type McpResult = Result<CallToolResult, McpError>;
 
-pub fn run_db_command(config: &Config, _anal: &AnalyzedData, sql: &str) -> McpResult {
-    shellutil::run_db_command(config, sql)
-        .map_err(|e| McpError::new(ErrorCode::INTERNAL_ERROR, format!("{}", e), None))
-        .and_then(|output| mcp_return!(output))
+/// A query checked against the policy and, unless blocked, run on the site.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub struct DbQuery {
+    /// SQL sent to the database, with the added limits
+    pub sql: String,
+    pub statements: Vec<SqlStatement>,
+    /// The statement that stopped the query, nothing was run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blocked: Option<BlockedStatement>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<CommandOutput>,
+}
+
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub struct SqlStatement {
+    pub statement: String,
+    /// Leading keyword, e.g. `select`
+    pub kind: String,
+    pub class: StatementClass,
+    /// Whether a `LIMIT` was added to the statement
+    pub limit_added: bool,
+}
+
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub struct BlockedStatement {
+    /// 1-based position of the statement in the query
+    pub index: usize,
+    pub statement: String,
+    pub reason: String,
+    /// Run again with `confirm: true` to execute it
+    pub needs_confirmation: bool,
+}
+
+impl DbQuery {
+    fn render(&self) -> String {
+        if let Some(blocked) = &self.blocked {
+            let hint = if blocked.needs_confirmation {
+                "\nRun again with `confirm: true` to execute the query."
+            } else {
+                ""
+            };
+            return format!(
+                "Query blocked at statement {}: {}\n  {}{}",
+                blocked.index, blocked.reason, blocked.statement, hint
+            );
+        }
+        let mut out = String::new();
+        for s in self.statements.iter().filter(|s| s.limit_added) {
+            out.push_str(&format!("(LIMIT added to: {})\n", s.statement));
+        }
+        if let Some(output) = &self.output {
+            out.push_str(&output.render());
+        }
+        out
+    }
+}
+
+pub async fn run_db_command(
+    config: &Config,
+    sql: &str,
+    confirm: bool,
+    options: &RunOptions,
+) -> McpResult {
+    let policy = &config.policy;
+    let parsed = sqlutil::split_statements(sql);
+    if parsed.is_empty() {
+        mcp_error!("No SQL statement given".to_string());
+    }
+
+    let mut query = DbQuery {
+        sql: String::new(),
+        statements: Vec::new(),
+        blocked: None,
+        output: None,
+    };
+    let mut texts = Vec::new();
+    for (i, statement) in parsed.iter().enumerate() {
+        let verdict = policy.check_statement(statement);
+        let blocked = match verdict {
+            SqlVerdict::Allowed => None,
+            SqlVerdict::NeedsConfirmation if confirm => None,
+            SqlVerdict::NeedsConfirmation => Some((
+                format!(
+                    "{} statements change the database and need confirmation",
+                    statement.kind.to_uppercase()
+                ),
+                true,
+            )),
+            SqlVerdict::Denied(reason) => Some((reason, false)),
+        };
+        if let Some((reason, needs_confirmation)) = blocked {
+            query.blocked = Some(BlockedStatement {
+                index: i + 1,
+                statement: statement.text.clone(),
+                reason,
+                needs_confirmation,
+            });
+            break;
+        }
+
+        let limited = statement.with_limit(policy.select_limit);
+        query.statements.push(SqlStatement {
+            statement: statement.text.clone(),
+            kind: statement.kind.clone(),
+            class: statement.class,
+            limit_added: limited.is_some(),
+        });
+        texts.push(limited.unwrap_or_else(|| statement.text.clone()));
+    }
+
+    if query.blocked.is_some() {
+        query.sql = sql.trim().to_string();
+        let mut result = structured_result(&query, query.render());
+        result.is_error = Some(true);
+        return Ok(result);
+    }
+
+    query.sql = texts.join(";\n");
+    let output = shellutil::run_db_command(config, &query.sql, options)
+        .await
+        .map_err(|e| McpError::new(ErrorCode::INTERNAL_ERROR, format!("{}", e), None))?;
+    let success = output.success;
+    query.output = Some(output);
+    let mut result = structured_result(&query, query.render());
+    result.is_error = Some(!success);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_blocked_query_is_not_run() {
+        // The bench doesn't exist, so reaching `bench` would fail with an McpError
+        let config = Config {
+            frappe_bench_dir: "/nonexistent/bench".to_string(),
+            ..Default::default()
+        };
+        let limits = RunOptions::default();
+
+        let result = run_db_command(&config, "SELECT 1; DROP TABLE `tabUser`", true, &limits)
+            .await
+            .unwrap();
+        assert_eq!(result.is_error, Some(true));
+        let data = result.structured_content.unwrap();
+        assert_eq!(data["blocked"]["index"], 2);
+        assert_eq!(data["blocked"]["statement"], "DROP TABLE `tabUser`");
+        assert_eq!(data["blocked"]["needs_confirmation"], false);
+
+        let mut config = config;
+        config.policy.sql_allow = vec!["read".to_string(), "dml".to_string()];
+        let result = run_db_command(&config, "UPDATE tabUser SET enabled = 0", false, &limits)
+            .await
+            .unwrap();
+        let data = result.structured_content.unwrap();
+        assert_eq!(data["blocked"]["needs_confirmation"], true);
+    }
 }
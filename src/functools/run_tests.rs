@@ -10,53 +10,71 @@
 // is strictly forbidden unless prior written permission is obtained
 // from Nuwaira.
 use std::path::Path;
-use std::process::Command;
 
 use crate::analyze::AnalyzedData;
 use crate::config::Config;
+use crate::coverage::{self, CoverageReport, CoverageTargets};
+use crate::runlog::LogHandle;
+use crate::shellutil::{self, KillReason, RunOptions};
 use crate::stringutil::to_snakec_var;
-use rmcp::{model::*, ErrorData as McpError};
+use crate::testutil::{self, TestResults};
+use rmcp::{model::*, schemars, ErrorData as McpError};
+use serde::Serialize;
 
 type McpResult = Result<CallToolResult, McpError>;
 
-pub fn run_tests(
+/// Outcome of a `bench run-tests` invocation.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub struct TestRun {
+    /// The command line that was run, one per line when failed tests were re-run per module
+    pub command: String,
+    /// Process exit code (`null` when killed by a signal)
+    pub exit_code: Option<i32>,
+    /// Whether every test passed
+    pub success: bool,
+    pub stdout: String,
+    pub stderr: String,
+    /// The runner's report, when it got far enough to print one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub results: Option<TestResults>,
+    /// Line coverage per module and DocType, when run with `coverage`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub coverage: Option<CoverageReport>,
+    /// Set when the run was killed on timeout or cancellation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub killed: Option<KillReason>,
+    /// Full output of the (last) `bench` run, also streamed as progress while it ran
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log: Option<LogHandle>,
+}
+
+/// `bench` arguments running the tests of a module, DocType or the whole app.
+///
+/// Only this part needs the analyzed data; an `Err` is a message for the agent.
+pub fn test_args(
     config: &Config,
     anal: &AnalyzedData,
     module: Option<String>,
     doctype: Option<String>,
     test: Option<String>,
-) -> McpResult {
-    // let app_path = &config.app_absolute_path;
-
-    // Verify we're in a Frappe bench directory
-    let bench_path = find_bench_root(&config.frappe_bench_dir)?;
-
+    app: Option<String>,
+) -> Result<Vec<String>, String> {
     let mut cmd_args: Vec<String> = vec![];
 
-    let app_name_snake = to_snakec_var(&config.app_name);
+    // Explicit app first, then the app that owns the DocType, then the primary app
+    let target_app = app
+        .clone()
+        .or_else(|| {
+            doctype
+                .as_deref()
+                .and_then(|d| anal.find_doctype(d, None))
+                .map(|dt| dt.app.clone())
+                .filter(|a| !a.is_empty())
+        })
+        .unwrap_or_else(|| config.app_name.clone());
+    let app_name_snake = to_snakec_var(&target_app);
     let snake_doctype = to_snakec_var(doctype.as_deref().unwrap_or(""));
 
-    // Remove `.test_log` file if exists
-    // When running tests, we need to make sure file `.test_log` in
-    // `frappe-bench/sites/[site-name]/.test_log` is removed, otherwise the test runner won't create
-    // test records from test_records.json
-    let test_log_path = Path::new(&bench_path)
-        .join("sites")
-        .join(&config.site)
-        .join(".test_log");
-
-    if test_log_path.exists() {
-        if let Err(e) = std::fs::remove_file(&test_log_path) {
-            tracing::warn!(
-                "Failed to remove .test_log file at {:?}: {}",
-                test_log_path,
-                e
-            );
-        } else {
-            tracing::debug!("Removed existing .test_log file at {:?}", test_log_path);
-        }
-    }
-
     cmd_args.push("--site".to_string());
     cmd_args.push(config.site.clone());
     cmd_args.push("run-tests".to_string());
@@ -99,7 +117,7 @@ pub fn run_tests(
         }
         (None, Some(d)) => {
             // Find module for doctype and test it
-            if let Some(found_module) = find_doctype_module(anal, d) {
+            if let Some(found_module) = find_doctype_module(anal, d, app.as_deref()) {
                 let snake_doctype = d.replace(' ', "_").to_lowercase();
                 let test_path = format!(
                     "--app {} --module {}.{}.doctype.{}.test_{}",
@@ -117,13 +135,13 @@ pub fn run_tests(
                     cmd_args.push(arg);
                 }
             } else {
-                mcp_return!(format!("DocType '{}' not found in analyzed data", d));
+                return Err(format!("DocType '{}' not found in analyzed data", d));
             }
         }
         (None, None) => {
             // Test entire app
             cmd_args.push("--app".to_string());
-            cmd_args.push(config.app_name.clone());
+            cmd_args.push(target_app.clone());
         }
     }
 
@@ -132,72 +150,239 @@ pub fn run_tests(
         cmd_args.push(t);
     }
 
-    if cmd_args.len() > 1 {
-        tracing::debug!("Executing bench command: bench {}", cmd_args.join(" "));
+    Ok(cmd_args)
+}
+
+/// `bench` arguments re-running the tests that failed in the last run on the site.
+pub fn rerun_failed_args(config: &Config) -> Result<Vec<Vec<String>>, String> {
+    let failed = testutil::load_failed(config);
+    let runs = testutil::rerun_args(&config.site, &failed);
+    if runs.is_empty() {
+        return Err(format!(
+            "No failing tests remembered for site '{}', run the tests first",
+            config.site
+        ));
     }
+    Ok(runs)
+}
 
-    // Execute bench command
-    let output = Command::new("bench")
-        .current_dir(&bench_path)
-        .args(&cmd_args)
-        .output();
-
-    match output {
-        Ok(result) => {
-            let stdout = String::from_utf8_lossy(&result.stdout);
-            let stderr = String::from_utf8_lossy(&result.stderr);
-
-            let mut response = String::new();
-
-            response.push_str("COMMAND EXECUTED:\n");
-            response.push_str(&format!("bench {}\n\n", cmd_args.join(" ")));
-
-            if !stdout.is_empty() {
-                response.push_str("STDOUT:\n");
-                response.push_str("─".repeat(50).as_str());
-                response.push('\n');
-                response.push_str(&stdout);
-                response.push('\n');
-                response.push_str("─".repeat(50).as_str());
-                response.push_str("\n\n");
+/// Run `bench <args>` for each of `runs`, from [`test_args`] or [`rerun_failed_args`],
+/// and remember the tests that failed for `rerun_failed`.
+///
+/// With `coverage`, the single run gets `--coverage` and its report is mapped to the targets.
+pub async fn run_tests(
+    config: &Config,
+    mut runs: Vec<Vec<String>>,
+    coverage: Option<&CoverageTargets>,
+    options: &RunOptions,
+) -> McpResult {
+    // Verify we're in a Frappe bench directory
+    let bench_path = find_bench_root(&config.frappe_bench_dir)?;
+
+    let coverage_xml = coverage::xml_path(&bench_path);
+    if coverage.is_some() {
+        if runs.len() != 1 {
+            mcp_error!("Coverage needs a single run-tests invocation".to_string());
+        }
+        runs[0].push("--coverage".to_string());
+        // Don't report a previous run's data
+        let _ = std::fs::remove_file(&coverage_xml);
+    }
+
+    let mut merged: Option<TestRun> = None;
+    for cmd_args in runs {
+        remove_test_log(&bench_path, &config.site);
+
+        let command = shellutil::command_line("bench", &cmd_args);
+        tracing::debug!("Executing bench command: {}", command);
+
+        let mut cmd = tokio::process::Command::new("bench");
+        cmd.current_dir(&bench_path).args(&cmd_args);
+        let output = match shellutil::run_command(cmd, command.clone(), 0, options).await {
+            Ok(output) => output,
+            Err(e) => {
+                mcp_error!(format!(
+                    "Failed to execute bench command: `{}`\n\n\
+                    Error: {:#}\n\n\
+                    \n\nMake sure:\n1. You're in a Frappe bench directory\n2. 'bench' command is available in PATH\n3. The app is installed in the bench",
+                    command,
+                    e
+                ));
             }
+        };
+        // unittest reports on stderr, Frappe's own messages go to stdout
+        let results = testutil::parse_test_output(
+            &format!("{}\n{}", output.stdout, output.stderr),
+            &bench_path,
+        );
+        let run = TestRun {
+            command: output.command,
+            exit_code: output.exit_code,
+            success: output.success,
+            stdout: output.stdout,
+            stderr: output.stderr,
+            results,
+            coverage: None,
+            killed: output.killed,
+            log: output.log,
+        };
+        let killed = run.killed.is_some();
+        merged = Some(match merged {
+            Some(previous) => previous.merge(run),
+            None => run,
+        });
+        if killed {
+            break;
+        }
+    }
+    let Some(mut run) = merged else {
+        mcp_error!("No tests to run".to_string());
+    };
 
-            if !stderr.is_empty() {
-                response.push_str("STDERR:\n");
-                response.push_str("─".repeat(50).as_str());
-                response.push('\n');
-                response.push_str(&stderr);
-                response.push('\n');
-                response.push_str("─".repeat(50).as_str());
-                response.push_str("\n\n");
+    let mut coverage_note = None;
+    if let (Some(targets), None) = (coverage, run.killed) {
+        match std::fs::read_to_string(&coverage_xml) {
+            Ok(xml) => {
+                let files = coverage::parse_cobertura(&xml, &bench_path);
+                run.coverage = Some(coverage::report(targets, &files));
             }
+            Err(e) => {
+                coverage_note = Some(format!(
+                    "No coverage report at {} ({}); is `coverage` installed in the bench's env?",
+                    coverage_xml.display(),
+                    e
+                ));
+            }
+        }
+    }
 
-            // // Try to extract test summary
-            // if let Some(summary) = extract_test_summary(&stdout) {
-            //     response.push_str("TEST SUMMARY:\n");
-            //     response.push_str(&summary);
-            //     response.push('\n');
-            // }
+    // A killed run didn't report every failure
+    if let (None, Some(results)) = (run.killed, &run.results) {
+        let cases: Vec<_> = results.failures.iter().map(|f| f.test_case()).collect();
+        if let Err(e) = testutil::save_failed(config, &cases) {
+            tracing::warn!("Failed to remember the failing tests: {}", e);
+        }
+    }
 
-            response.push_str(&format!(
-                "Exit code: {}\n",
-                result.status.code().unwrap_or(-1)
-            ));
+    let mut text = format_test_run(&run);
+    if let Some(note) = coverage_note {
+        text.push_str(&format!("{}\n", note));
+    }
+    let mut result = crate::functools::structured_result(&run, text);
+    result.is_error = Some(run.killed.is_some());
+    Ok(result)
+}
 
-            mcp_return!(response)
+impl TestRun {
+    /// Append a later `bench` invocation of the same request.
+    fn merge(mut self, next: TestRun) -> TestRun {
+        let join = |a: String, b: String| match (a.is_empty(), b.is_empty()) {
+            (true, _) => b,
+            (_, true) => a,
+            _ => format!("{}\n{}", a, b),
+        };
+        self.command = join(self.command, next.command);
+        if self.success {
+            self.exit_code = next.exit_code;
         }
-        Err(e) => {
-            mcp_return!(format!(
-                "Failed to execute bench command: `bench {}`\n\n\
-                Error: {}\n\n\
-                \n\nMake sure:\n1. You're in a Frappe bench directory\n2. 'bench' command is available in PATH\n3. The app is installed in the bench",
-                cmd_args.join(" "),
+        self.success = self.success && next.success;
+        self.stdout = join(self.stdout, next.stdout);
+        self.stderr = join(self.stderr, next.stderr);
+        self.results = match (self.results, next.results) {
+            (Some(mut results), Some(next)) => {
+                results.merge(next);
+                Some(results)
+            }
+            (results, next) => results.or(next),
+        };
+        self.coverage = next.coverage.or(self.coverage);
+        self.killed = next.killed;
+        self.log = next.log.or(self.log);
+        self
+    }
+}
+
+/// Remove `.test_log` if it exists.
+///
+/// When running tests, we need to make sure file `.test_log` in
+/// `frappe-bench/sites/[site-name]/.test_log` is removed, otherwise the test runner won't create
+/// test records from test_records.json
+fn remove_test_log(bench_path: &str, site: &str) {
+    let test_log_path = Path::new(bench_path)
+        .join("sites")
+        .join(site)
+        .join(".test_log");
+
+    if test_log_path.exists() {
+        if let Err(e) = std::fs::remove_file(&test_log_path) {
+            tracing::warn!(
+                "Failed to remove .test_log file at {:?}: {}",
+                test_log_path,
                 e
-            ));
+            );
+        } else {
+            tracing::debug!("Removed existing .test_log file at {:?}", test_log_path);
         }
     }
 }
 
+fn format_test_run(run: &TestRun) -> String {
+    let mut response = String::new();
+
+    response.push_str("COMMAND EXECUTED:\n");
+    response.push_str(&format!("{}\n\n", run.command));
+
+    if let Some(results) = &run.results {
+        response.push_str("TEST SUMMARY:\n");
+        response.push_str(&results.summary());
+        response.push('\n');
+        for failure in &results.failures {
+            response.push_str(&format!("\n{:?}: {}\n", failure.kind, failure.test_id()));
+            if let Some(location) = &failure.location {
+                response.push_str(&format!("  at {}\n", location));
+            }
+            for line in failure.message.lines() {
+                response.push_str(&format!("  {}\n", line));
+            }
+        }
+        response.push('\n');
+    }
+
+    if let Some(coverage) = &run.coverage {
+        response.push_str(&coverage.render());
+        response.push('\n');
+    }
+
+    if !run.stdout.is_empty() {
+        response.push_str("STDOUT:\n");
+        response.push_str("─".repeat(50).as_str());
+        response.push('\n');
+        response.push_str(&run.stdout);
+        response.push('\n');
+        response.push_str("─".repeat(50).as_str());
+        response.push_str("\n\n");
+    }
+
+    if !run.stderr.is_empty() {
+        response.push_str("STDERR:\n");
+        response.push_str("─".repeat(50).as_str());
+        response.push('\n');
+        response.push_str(&run.stderr);
+        response.push('\n');
+        response.push_str("─".repeat(50).as_str());
+        response.push_str("\n\n");
+    }
+
+    if let Some(reason) = run.killed {
+        response.push_str(&format!("Killed: {:?}\n", reason));
+    }
+    response.push_str(&format!("Exit code: {}\n", run.exit_code.unwrap_or(-1)));
+    if let Some(log) = &run.log {
+        response.push_str(&format!("{}\n", log.render()));
+    }
+    response
+}
+
 fn find_bench_root(app_path: &str) -> Result<String, McpError> {
     let mut current = Path::new(app_path);
 
@@ -226,45 +411,11 @@ fn find_bench_root(app_path: &str) -> Result<String, McpError> {
     ))
 }
 
-fn find_doctype_module(anal: &AnalyzedData, doctype_name: &str) -> Option<String> {
-    anal.doctypes
-        .iter()
-        .find(|dt| dt.name.to_lowercase() == doctype_name.to_lowercase())
+fn find_doctype_module(
+    anal: &AnalyzedData,
+    doctype_name: &str,
+    app: Option<&str>,
+) -> Option<String> {
+    anal.find_doctype(doctype_name, app)
         .map(|dt| dt.module.clone())
 }
-
-// fn extract_test_summary(output: &str) -> Option<String> {
-//     let lines: Vec<&str> = output.lines().collect();
-//     let mut summary = Vec::new();
-//     let mut in_summary = false;
-//
-//     for line in lines {
-//         if line.contains("FAILED") || line.contains("PASSED") || line.contains("ERROR") {
-//             in_summary = true;
-//         }
-//
-//         if in_summary {
-//             if line.contains("=")
-//                 && (line.contains("passed") || line.contains("failed") || line.contains("error"))
-//             {
-//                 summary.push(line.to_string());
-//                 break;
-//             }
-//
-//             if line.contains("FAILED") || line.contains("ERROR") {
-//                 summary.push(line.to_string());
-//             }
-//         }
-//
-//         // Look for coverage information
-//         if line.contains("Total coverage:") || line.contains("TOTAL") {
-//             summary.push(line.to_string());
-//         }
-//     }
-//
-//     if summary.is_empty() {
-//         None
-//     } else {
-//         Some(summary.join("\n"))
-//     }
-// }
@@ -28,6 +28,38 @@ pub enum OutputFormat {
     Markdown,
 }
 
+/// Results of a docs search, best first.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub struct DocSearch {
+    pub query: String,
+    pub total: usize,
+    pub results: Vec<DocHit>,
+}
+
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub struct DocHit {
+    /// Document ID, for `read_frappe_doc`
+    pub id: String,
+    pub title: String,
+    pub category: String,
+    /// Relevance score, for fuzzy searches
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score: Option<i64>,
+    pub snippet: String,
+}
+
+/// A whole embedded document.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub struct FrappeDocPage {
+    pub id: String,
+    pub title: String,
+    pub category: String,
+    /// Path of the document inside the embedded docs
+    pub path: String,
+    /// Markdown content
+    pub content: String,
+}
+
 #[derive(RustEmbed)]
 #[folder = "frappe_docs/"]
 struct FrappeDocs;
@@ -156,14 +188,13 @@ pub fn search_frappe_docs(
 
         // Take top results
         for (score, doc) in scored_results.iter().take(limit) {
-            let snippet = extract_snippet(&doc.content, query, 150);
-            results.push(json!({
-                "id": doc.id,
-                "title": doc.title,
-                "category": doc.category,
-                "score": score,
-                "snippet": snippet,
-            }));
+            results.push(DocHit {
+                id: doc.id.clone(),
+                title: doc.title.clone(),
+                category: doc.category.clone(),
+                score: Some(*score),
+                snippet: extract_snippet(&doc.content, query, 150),
+            });
         }
     } else {
         // Exact search (case-insensitive)
@@ -174,13 +205,13 @@ pub fn search_frappe_docs(
             let content_lower = doc.content.to_lowercase();
 
             if title_lower.contains(&query_lower) || content_lower.contains(&query_lower) {
-                let snippet = extract_snippet(&doc.content, query, 150);
-                results.push(json!({
-                    "id": doc.id,
-                    "title": doc.title,
-                    "category": doc.category,
-                    "snippet": snippet,
-                }));
+                results.push(DocHit {
+                    id: doc.id.clone(),
+                    title: doc.title.clone(),
+                    category: doc.category.clone(),
+                    score: None,
+                    snippet: extract_snippet(&doc.content, query, 150),
+                });
 
                 if results.len() >= limit {
                     break;
@@ -189,8 +220,20 @@ pub fn search_frappe_docs(
         }
     }
 
-    // Generate output based on format
-    let response_content = match output_format {
+    let search = DocSearch {
+        query: query.to_string(),
+        total: results.len(),
+        results,
+    };
+    let response_content = format_doc_search(&search, output_format);
+    mcp_return_data!(search, response_content)
+}
+
+/// Render the search results as JSON or markdown text.
+fn format_doc_search(search: &DocSearch, output_format: OutputFormat) -> String {
+    let query = &search.query;
+    let results = &search.results;
+    match output_format {
         OutputFormat::Json => {
             // JSON format - structured data
             let response = if results.is_empty() {
@@ -220,29 +263,20 @@ pub fn search_frappe_docs(
                 );
 
                 for (index, result) in results.iter().enumerate() {
-                    let id = result["id"].as_str().unwrap_or("unknown");
-                    let title = result["title"].as_str().unwrap_or("Untitled");
-                    let category = result["category"].as_str().unwrap_or("general");
-                    let snippet = result["snippet"].as_str().unwrap_or("");
-
+                    let score_line = result
+                        .score
+                        .map(|score| format!("**Relevance Score:** {}\n", score))
+                        .unwrap_or_default();
                     markdown.push_str(&format!(
-                        "## {}. {} `[{}]`\n\n**ID:** `{}`  \n**Category:** `{}`  \n\n{}\n\n---\n\n",
+                        "## {}. {} `[{}]`\n\n**ID:** `{}`  \n**Category:** `{}`  \n{}\n{}\n\n---\n\n",
                         index + 1,
-                        title,
-                        category,
-                        id,
-                        category,
-                        snippet
+                        result.title,
+                        result.category,
+                        result.id,
+                        result.category,
+                        score_line,
+                        result.snippet
                     ));
-
-                    // Add score for fuzzy search
-                    if let Some(score) = result.get("score") {
-                        let score_line = format!("**Relevance Score:** {}\n\n", score);
-                        markdown = markdown.replace(
-                            &format!("**Category:** `{}`  \n\n", category),
-                            &format!("**Category:** `{}`  \n{}", category, score_line),
-                        );
-                    }
                 }
 
                 markdown.push_str(&format!(
@@ -251,11 +285,7 @@ pub fn search_frappe_docs(
                 markdown
             }
         }
-    };
-
-    Ok(CallToolResult::success(vec![Content::text(
-        response_content,
-    )]))
+    }
 }
 
 fn extract_title(content: &str, path: &str) -> String {
@@ -375,15 +405,21 @@ pub fn get_frappe_doc(id: &str) -> Result<CallToolResult, McpError> {
         let title = extract_title(content, &path);
         let category = extract_category(&path);
 
+        let page = FrappeDocPage {
+            id: id.to_string(),
+            title,
+            category,
+            path,
+            content: content.to_string(),
+        };
+
         // Build formatted markdown response with metadata header
         let formatted_response = format!(
             "---\n**Document ID:** `{}`  \n**Title:** {}  \n**Category:** `{}`  \n**Source Path:** `{}`  \n---\n\n{}",
-            id, title, category, path, content
+            page.id, page.title, page.category, page.path, page.content
         );
 
-        Ok(CallToolResult::success(vec![Content::text(
-            formatted_response,
-        )]))
+        mcp_return_data!(page, formatted_response)
     } else {
         Err(McpError {
             code: rmcp::model::ErrorCode(-32602),
@@ -397,6 +433,49 @@ pub fn get_frappe_doc(id: &str) -> Result<CallToolResult, McpError> {
     }
 }
 
+/// An embedded document, as listed in the docs catalogue.
+#[derive(Debug, Clone)]
+pub struct DocSummary {
+    pub id: String,
+    pub path: String,
+    pub title: String,
+    pub category: String,
+}
+
+/// Every embedded document, ordered by path.
+pub fn frappe_doc_entries() -> Vec<DocSummary> {
+    let mut entries: Vec<DocSummary> = FrappeDocs::iter()
+        .filter(|file| file.ends_with(".md"))
+        .filter_map(|file| {
+            let path = file.to_string();
+            let data = FrappeDocs::get(&path)?;
+            let content = std::str::from_utf8(data.data.as_ref()).ok()?;
+            Some(DocSummary {
+                id: path_to_id(&path),
+                title: extract_title(content, &path),
+                category: extract_category(&path),
+                path,
+            })
+        })
+        .collect();
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    entries
+}
+
+/// Summary and markdown content of the document `id`.
+pub fn frappe_doc_content(id: &str) -> Option<(DocSummary, String)> {
+    let path = resolve_id_to_path(id)?;
+    let data = FrappeDocs::get(&path)?;
+    let content = std::str::from_utf8(data.data.as_ref()).ok()?.to_string();
+    let summary = DocSummary {
+        id: id.to_string(),
+        title: extract_title(&content, &path),
+        category: extract_category(&path),
+        path,
+    };
+    Some((summary, content))
+}
+
 #[allow(dead_code)]
 pub fn list_frappe_docs(category: Option<String>) -> Result<CallToolResult, McpError> {
     let mut categories: HashMap<String, Vec<String>> = HashMap::new();
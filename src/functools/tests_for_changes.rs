@@ -0,0 +1,682 @@
+// Copyright (C) 2025 Nuwaira
+// All Rights Reserved.
+//
+// NOTICE: All information contained herein is, and remains
+// the property of Nuwaira.
+// The intellectual and technical concepts contained
+// herein are proprietary to Nuwaira
+// and are protected by trade secret or copyright law.
+// Dissemination of this information or reproduction of this material
+// is strictly forbidden unless prior written permission is obtained
+// from Nuwaira.
+#![allow(dead_code)]
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+use super::analyze_links::doctype_links;
+use crate::analyze::{AnalyzedData, DocType};
+use crate::config::Config;
+use crate::testutil;
+use rmcp::{model::*, schemars, ErrorData as McpError};
+use serde::Serialize;
+
+type McpResult = Result<CallToolResult, McpError>;
+
+/// Hops followed back through Link/Table/Select fields by default.
+const DEFAULT_LINK_DEPTH: usize = 1;
+
+/// The test modules affected by a set of changed files.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub struct TestSelection {
+    /// Changed files, relative to the bench's `apps/`
+    pub changed_files: Vec<String>,
+    /// Affected DocTypes, changed ones first, then by how they were reached
+    pub doctypes: Vec<AffectedDocType>,
+    /// Test modules to run, e.g. `koperasi.koperasi.doctype.anggota.test_anggota`
+    pub test_modules: Vec<String>,
+    /// Changed files no DocType or test module was found for
+    pub unmapped_files: Vec<String>,
+    /// Result of running `test_modules`, as returned by `run_tests`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub run: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+pub struct AffectedDocType {
+    pub doctype: String,
+    pub app: String,
+    /// `None` when the DocType has no test file
+    pub test_module: Option<String>,
+    /// Why the DocType was selected
+    pub impacts: Vec<Impact>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, schemars::JsonSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Impact {
+    /// A file in the DocType's directory changed
+    Changed { file: String },
+    /// A changed file outside DocType directories uses its fields
+    FieldUsage { file: String, fields: Vec<String> },
+    /// One of its Link, Table or Select fields points at an affected DocType
+    Linked { doctype: String, fieldname: String },
+}
+
+impl Impact {
+    fn render(&self) -> String {
+        match self {
+            Impact::Changed { file } => format!("changed {}", file),
+            Impact::FieldUsage { file, fields } => {
+                format!("{} uses {}", file, fields.join(", "))
+            }
+            Impact::Linked { doctype, fieldname } => {
+                format!("links to {} ({})", doctype, fieldname)
+            }
+        }
+    }
+}
+
+impl TestSelection {
+    /// `bench` arguments running each selected test module.
+    pub fn runs(&self, site: &str) -> Vec<Vec<String>> {
+        self.test_modules
+            .iter()
+            .map(|module| {
+                ["--site", site, "run-tests", "--module", module]
+                    .iter()
+                    .map(|a| a.to_string())
+                    .collect()
+            })
+            .collect()
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = format!(
+            "TESTS FOR {} CHANGED FILES: {} modules\n",
+            self.changed_files.len(),
+            self.test_modules.len()
+        );
+        for module in &self.test_modules {
+            out.push_str(&format!("  {}\n", module));
+        }
+        if !self.doctypes.is_empty() {
+            out.push_str("\nAFFECTED DOCTYPES:\n");
+        }
+        for dt in &self.doctypes {
+            let tests = if dt.test_module.is_some() {
+                ""
+            } else {
+                ", no tests"
+            };
+            let impacts: Vec<String> = dt.impacts.iter().map(|i| i.render()).collect();
+            out.push_str(&format!(
+                "  {} ({}{}): {}\n",
+                dt.doctype,
+                dt.app,
+                tests,
+                impacts.join("; ")
+            ));
+        }
+        if !self.unmapped_files.is_empty() {
+            out.push_str("\nNOT MAPPED (may need the whole app's tests):\n");
+            for file in &self.unmapped_files {
+                out.push_str(&format!("  {}\n", file));
+            }
+        }
+        out
+    }
+
+    /// The selection as a tool result, with the `run_tests` result of its modules if run.
+    pub fn into_result(mut self, run: Option<CallToolResult>) -> CallToolResult {
+        let Some(run) = run else {
+            let text = self.render();
+            return crate::functools::structured_result(&self, text);
+        };
+        let run_text: Vec<&str> = run
+            .content
+            .iter()
+            .filter_map(|c| c.as_text())
+            .map(|t| t.text.as_str())
+            .collect();
+        let text = format!("{}\n{}", self.render(), run_text.join("\n"));
+        self.run = run.structured_content.clone();
+        let mut result = crate::functools::structured_result(&self, text);
+        result.is_error = run.is_error;
+        result
+    }
+}
+
+/// Select the test modules affected by `files` and report them.
+pub fn tests_for_changes(
+    config: &Config,
+    anal: &AnalyzedData,
+    files: &[String],
+    link_depth: Option<usize>,
+) -> McpResult {
+    if files.is_empty() {
+        mcp_error!("No changed files".to_string());
+    }
+    let selection = select_tests(config, anal, files, link_depth);
+    Ok(selection.into_result(None))
+}
+
+/// Map changed files to test modules.
+///
+/// A DocType is affected when a file in its directory changed, when a changed
+/// file outside DocType directories uses its fields, or when it links to an
+/// affected DocType (up to `link_depth` hops). Changed `test_*.py` files outside
+/// DocType directories are run as they are.
+pub fn select_tests(
+    config: &Config,
+    anal: &AnalyzedData,
+    files: &[String],
+    link_depth: Option<usize>,
+) -> TestSelection {
+    let changed: BTreeSet<String> = files.iter().map(|f| apps_relative(config, f)).collect();
+    let dirs: Vec<(String, &DocType)> = anal
+        .doctypes
+        .iter()
+        .map(|dt| {
+            (
+                format!(
+                    "{}/",
+                    parent_dir(&doctype_path(config, dt, &dt.backend_file))
+                ),
+                dt,
+            )
+        })
+        .collect();
+
+    let mut affected: Vec<(&DocType, Vec<Impact>)> = Vec::new();
+    let mut test_modules = BTreeSet::new();
+    let mut unmapped = Vec::new();
+    let mut outside: HashMap<PathBuf, &str> = HashMap::new();
+    for file in &changed {
+        if let Some((_, dt)) = dirs.iter().find(|(dir, _)| file.starts_with(dir.as_str())) {
+            push_impact(&mut affected, dt, Impact::Changed { file: file.clone() });
+            continue;
+        }
+        let name = Path::new(file)
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("");
+        if name.starts_with("test_") && name.ends_with(".py") {
+            if let Some((_, path)) = file.split_once('/') {
+                test_modules.insert(python_module(path));
+                continue;
+            }
+        }
+        let absolute = Path::new(&config.frappe_bench_dir).join("apps").join(file);
+        outside.insert(absolute, file.as_str());
+    }
+
+    // Fields of DocTypes used by changed shared code (APIs, utils, overrides)
+    let mut used: BTreeMap<(&str, &str), BTreeSet<&str>> = BTreeMap::new();
+    if let Some(refs) = anal.symbol_refs.as_ref().filter(|_| !outside.is_empty()) {
+        for (doctype, usage) in &refs.doctypes {
+            for (field, occurrences) in &usage.fields {
+                for occ in occurrences {
+                    if let Some(file) = outside.get(Path::new(&occ.file)) {
+                        used.entry((file, doctype.as_str()))
+                            .or_default()
+                            .insert(field.as_str());
+                    }
+                }
+            }
+        }
+    }
+    let mut mapped: BTreeSet<&str> = BTreeSet::new();
+    for ((file, doctype), fields) in used {
+        let Some(dt) = anal.doctypes.iter().find(|dt| dt.name == doctype) else {
+            continue;
+        };
+        mapped.insert(file);
+        push_impact(
+            &mut affected,
+            dt,
+            Impact::FieldUsage {
+                file: file.to_string(),
+                fields: fields.into_iter().map(|f| f.to_string()).collect(),
+            },
+        );
+    }
+    for file in outside.values() {
+        if !mapped.contains(file) {
+            unmapped.push(file.to_string());
+        }
+    }
+    unmapped.sort();
+
+    // DocTypes linking to an affected one, hop by hop
+    let depth = link_depth.unwrap_or(DEFAULT_LINK_DEPTH);
+    if depth > 0 && !affected.is_empty() {
+        let mut referrers: HashMap<&str, Vec<(&DocType, String)>> = HashMap::new();
+        for dt in &anal.doctypes {
+            for link in doctype_links(config, dt).unwrap_or_default() {
+                if let Some(target) = anal.doctypes.iter().find(|t| t.name == link.target_doctype) {
+                    referrers
+                        .entry(target.name.as_str())
+                        .or_default()
+                        .push((dt, link.fieldname));
+                }
+            }
+        }
+        let mut frontier: Vec<&DocType> = affected.iter().map(|(dt, _)| *dt).collect();
+        for _ in 0..depth {
+            let mut next = Vec::new();
+            for target in frontier {
+                for (dt, fieldname) in referrers.get(target.name.as_str()).into_iter().flatten() {
+                    if affected.iter().any(|(a, _)| a.name == dt.name) {
+                        continue;
+                    }
+                    affected.push((
+                        dt,
+                        vec![Impact::Linked {
+                            doctype: target.name.clone(),
+                            fieldname: fieldname.clone(),
+                        }],
+                    ));
+                    next.push(*dt);
+                }
+            }
+            frontier = next;
+        }
+    }
+
+    let doctypes: Vec<AffectedDocType> = affected
+        .into_iter()
+        .map(|(dt, impacts)| AffectedDocType {
+            doctype: dt.name.clone(),
+            app: app_dir(config, dt).to_string(),
+            test_module: dt.test_file.as_deref().map(python_module),
+            impacts,
+        })
+        .collect();
+    test_modules.extend(doctypes.iter().filter_map(|dt| dt.test_module.clone()));
+
+    TestSelection {
+        changed_files: changed.into_iter().collect(),
+        doctypes,
+        test_modules: test_modules.into_iter().collect(),
+        unmapped_files: unmapped,
+        run: None,
+    }
+}
+
+/// Files changed in the analyzed apps' git work trees since `base` (default
+/// `HEAD`), untracked ones included, relative to the bench's `apps/`.
+///
+/// `base` comes from the client, so it is resolved to a commit first and
+/// never reaches `git diff` where it could be read as an option.
+pub async fn git_changed_files(config: &Config, base: Option<&str>) -> Result<Vec<String>, String> {
+    let base = base.unwrap_or("HEAD");
+    if base.starts_with('-') {
+        return Err(format!("Invalid base revision '{}'", base));
+    }
+    let mut files = BTreeSet::new();
+    for app in &config.app_dirs {
+        let dir = config.app_path(app);
+        if !Path::new(&dir).join(".git").exists() {
+            continue;
+        }
+        let rev = format!("{}^{{commit}}", base);
+        let commit = git(&dir, &["rev-parse", "--verify", "--end-of-options", &rev]).await?;
+        let diff = git(
+            &dir,
+            &["diff", "--name-only", "--relative", commit.trim(), "--"],
+        )
+        .await?;
+        let untracked = git(&dir, &["ls-files", "--others", "--exclude-standard"]).await?;
+        for file in diff.lines().chain(untracked.lines()) {
+            files.insert(format!("{}/{}", app, file));
+        }
+    }
+    Ok(files.into_iter().collect())
+}
+
+/// Files named in the `--- a/…` and `+++ b/…` headers of a unified diff.
+///
+/// Hunk lines are skipped by the counts of their `@@` header, so removed or
+/// added lines starting with `--`/`++` aren't taken for file headers.
+pub fn diff_files(diff: &str) -> Vec<String> {
+    let mut files = BTreeSet::new();
+    // Old and new lines of the current hunk still to come
+    let (mut old, mut new) = (0usize, 0usize);
+    for line in diff.lines() {
+        if line.starts_with("diff ") {
+            (old, new) = (0, 0);
+            continue;
+        }
+        if old > 0 || new > 0 {
+            match line.chars().next() {
+                Some('-') => old = old.saturating_sub(1),
+                Some('+') => new = new.saturating_sub(1),
+                Some('\\') => {} // `\ No newline at end of file`
+                _ => {
+                    old = old.saturating_sub(1);
+                    new = new.saturating_sub(1);
+                }
+            }
+            continue;
+        }
+        if let Some(range) = line.strip_prefix("@@ ") {
+            (old, new) = hunk_lengths(range);
+            continue;
+        }
+        let Some(path) = line
+            .strip_prefix("+++ ")
+            .or_else(|| line.strip_prefix("--- "))
+        else {
+            continue;
+        };
+        // `diff -u` appends a tab and the timestamp
+        let path = path.split('\t').next().unwrap_or("").trim();
+        if path == "/dev/null" || path.is_empty() {
+            continue;
+        }
+        let path = path
+            .strip_prefix("a/")
+            .or_else(|| path.strip_prefix("b/"))
+            .unwrap_or(path);
+        files.insert(path.to_string());
+    }
+    files.into_iter().collect()
+}
+
+/// Old and new line counts of a `-l,s +l,s @@` hunk range (`s` defaults to 1).
+fn hunk_lengths(range: &str) -> (usize, usize) {
+    let (mut old, mut new) = (0, 0);
+    for part in range.split_whitespace().take_while(|p| *p != "@@") {
+        let len = |r: &str| {
+            r.split_once(',')
+                .map_or(Some(1), |(_, len)| len.parse().ok())
+                .unwrap_or(0)
+        };
+        if let Some(r) = part.strip_prefix('-') {
+            old = len(r);
+        } else if let Some(r) = part.strip_prefix('+') {
+            new = len(r);
+        }
+    }
+    (old, new)
+}
+
+async fn git(dir: &str, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run git in {}: {}", dir, e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "`git {}` failed in {}: {}",
+            args.join(" "),
+            dir,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+fn push_impact<'a>(
+    affected: &mut Vec<(&'a DocType, Vec<Impact>)>,
+    dt: &'a DocType,
+    impact: Impact,
+) {
+    match affected.iter_mut().find(|(a, _)| a.name == dt.name) {
+        Some((_, impacts)) => impacts.push(impact),
+        None => affected.push((dt, vec![impact])),
+    }
+}
+
+/// A changed file's path relative to the bench's `apps/`; relative paths are
+/// taken from the bench, `apps/`, or the main app.
+///
+/// An app's package has the app's name, so `koperasi/api.py` may be either
+/// `apps/koperasi/api.py` or `apps/koperasi/koperasi/api.py`: the one that
+/// exists wins, then the one whose directory exists (for deleted files).
+fn apps_relative(config: &Config, file: &str) -> String {
+    let file = file.trim().trim_start_matches("./");
+    if Path::new(file).is_absolute() {
+        return testutil::app_relative(file, &config.frappe_bench_dir).0;
+    }
+    if let Some(rest) = file.strip_prefix("apps/") {
+        return rest.to_string();
+    }
+    let in_main = format!("{}/{}", config.app_relative_path, file);
+    let first = file.split('/').next().unwrap_or("");
+    if !file.contains('/') || !config.app_dirs.iter().any(|app| app == first) {
+        return in_main;
+    }
+    let apps = Path::new(&config.frappe_bench_dir).join("apps");
+    let exists = |path: &str| apps.join(path).exists();
+    let dir_exists = |path: &str| apps.join(parent_dir(path)).is_dir();
+    if exists(file) || (!exists(&in_main) && !dir_exists(&in_main)) {
+        file.to_string()
+    } else {
+        in_main
+    }
+}
+
+/// App directory of a DocType, the main app for data analyzed before multi-app support.
+fn app_dir<'a>(config: &'a Config, dt: &'a DocType) -> &'a str {
+    if dt.app.is_empty() {
+        &config.app_relative_path
+    } else {
+        &dt.app
+    }
+}
+
+/// A DocType file's path relative to the bench's `apps/`.
+fn doctype_path(config: &Config, dt: &DocType, file: &str) -> String {
+    format!("{}/{}", app_dir(config, dt), file)
+}
+
+fn parent_dir(path: &str) -> &str {
+    path.rsplit_once('/').map_or("", |(dir, _)| dir)
+}
+
+/// `koperasi/simpanan/doctype/anggota/test_anggota.py` → `koperasi.simpanan.doctype.anggota.test_anggota`
+fn python_module(path: &str) -> String {
+    path.trim_end_matches(".py").replace('/', ".")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::refs_finder::{DoctypeUsage, Occurrence, Output as RefsFinderOutput};
+    use std::fs;
+
+    fn doctype(name: &str, links: &[(&str, &str, &str)], tests: bool, dir: &Path) -> DocType {
+        let snake = name.to_lowercase();
+        let rel = format!("koperasi/simpanan/doctype/{}", snake);
+        let fields: Vec<_> = links
+            .iter()
+            .map(|(fieldname, fieldtype, options)| {
+                serde_json::json!({"fieldname": fieldname, "fieldtype": fieldtype, "options": options})
+            })
+            .collect();
+        fs::create_dir_all(dir.join(&rel)).unwrap();
+        fs::write(
+            dir.join(&rel).join(format!("{}.json", snake)),
+            serde_json::json!({"name": name, "fields": fields}).to_string(),
+        )
+        .unwrap();
+        DocType {
+            name: name.to_string(),
+            backend_file: format!("{}/{}.py", rel, snake),
+            frontend_file: None,
+            meta_file: Some(format!("{}/{}.json", rel, snake)),
+            test_file: tests.then(|| format!("{}/test_{}.py", rel, snake)),
+            module: "Simpanan".to_string(),
+            app: "koperasi".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_select_tests() {
+        let bench =
+            std::env::temp_dir().join(format!("frappe-mcp-changes-test-{}", std::process::id()));
+        let app = bench.join("apps/koperasi");
+        let config = Config {
+            frappe_bench_dir: bench.to_string_lossy().to_string(),
+            app_relative_path: "koperasi".to_string(),
+            app_absolute_path: app.to_string_lossy().to_string(),
+            app_dirs: vec!["koperasi".to_string()],
+            ..Default::default()
+        };
+
+        let mut refs = RefsFinderOutput::new();
+        let mut usage = DoctypeUsage::default();
+        usage.fields.insert(
+            "saldo".to_string(),
+            vec![Occurrence {
+                file: app.join("koperasi/api.py").to_string_lossy().to_string(),
+                line: 12,
+                var: "doc".to_string(),
+                kind: "attr".to_string(),
+            }],
+        );
+        refs.doctypes.insert("Simpanan".to_string(), usage);
+        let anal = AnalyzedData {
+            doctypes: vec![
+                doctype("Anggota", &[], true, &app),
+                doctype("Pinjaman", &[("anggota", "Link", "Anggota")], true, &app),
+                doctype("Angsuran", &[("pinjaman", "Link", "Pinjaman")], false, &app),
+                doctype("Simpanan", &[], true, &app),
+            ],
+            symbol_refs: Some(refs),
+            ..Default::default()
+        };
+
+        let files = vec![
+            "koperasi/simpanan/doctype/anggota/anggota.py".to_string(),
+            "apps/koperasi/koperasi/api.py".to_string(),
+            app.join("koperasi/tests/test_utils.py")
+                .to_string_lossy()
+                .to_string(),
+            "koperasi/koperasi/hooks.py".to_string(),
+        ];
+        let selection = select_tests(&config, &anal, &files, None);
+        assert_eq!(
+            selection.test_modules,
+            vec![
+                "koperasi.simpanan.doctype.anggota.test_anggota",
+                "koperasi.simpanan.doctype.pinjaman.test_pinjaman",
+                "koperasi.simpanan.doctype.simpanan.test_simpanan",
+                "koperasi.tests.test_utils",
+            ]
+        );
+        let names: Vec<_> = selection
+            .doctypes
+            .iter()
+            .map(|d| d.doctype.as_str())
+            .collect();
+        assert_eq!(names, vec!["Anggota", "Simpanan", "Pinjaman"]);
+        assert_eq!(
+            selection.doctypes[1].impacts,
+            vec![Impact::FieldUsage {
+                file: "koperasi/koperasi/api.py".to_string(),
+                fields: vec!["saldo".to_string()],
+            }]
+        );
+        assert_eq!(
+            selection.doctypes[2].impacts,
+            vec![Impact::Linked {
+                doctype: "Anggota".to_string(),
+                fieldname: "anggota".to_string(),
+            }]
+        );
+        assert_eq!(selection.unmapped_files, vec!["koperasi/koperasi/hooks.py"]);
+        assert_eq!(
+            selection.runs("dev.local")[0],
+            vec![
+                "--site",
+                "dev.local",
+                "run-tests",
+                "--module",
+                "koperasi.simpanan.doctype.anggota.test_anggota"
+            ]
+        );
+
+        // A second hop reaches Angsuran, which has no tests to run
+        let selection = select_tests(&config, &anal, &files[..1], Some(2));
+        let names: Vec<_> = selection
+            .doctypes
+            .iter()
+            .map(|d| d.doctype.as_str())
+            .collect();
+        assert_eq!(names, vec!["Anggota", "Pinjaman", "Angsuran"]);
+        assert_eq!(selection.test_modules.len(), 2);
+
+        let _ = fs::remove_dir_all(&bench);
+    }
+
+    #[tokio::test]
+    async fn test_git_changed_files_base() {
+        let app = std::env::temp_dir().join(format!("frappe-mcp-git-base-{}", std::process::id()));
+        fs::create_dir_all(&app).unwrap();
+        let dir = app.to_string_lossy().to_string();
+        git(&dir, &["init", "-q"]).await.unwrap();
+        git(
+            &dir,
+            &[
+                "-c",
+                "user.name=t",
+                "-c",
+                "user.email=t@t",
+                "commit",
+                "-q",
+                "--allow-empty",
+                "-m",
+                "init",
+            ],
+        )
+        .await
+        .unwrap();
+        fs::write(app.join("api.py"), "").unwrap();
+        let config = Config {
+            app_relative_path: "koperasi".to_string(),
+            app_absolute_path: dir.clone(),
+            app_dirs: vec!["koperasi".to_string()],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            git_changed_files(&config, None).await.unwrap(),
+            vec!["koperasi/api.py"]
+        );
+        // An option in place of the revision must not reach git
+        let output = app.join("overwritten");
+        let base = format!("--output={}", output.display());
+        assert!(git_changed_files(&config, Some(&base)).await.is_err());
+        assert!(!output.exists());
+        assert!(git_changed_files(&config, Some("no-such-rev"))
+            .await
+            .is_err());
+
+        let _ = fs::remove_dir_all(&app);
+    }
+
+    #[test]
+    fn test_diff_files() {
+        let diff = "diff --git a/koperasi/api.py b/koperasi/api.py\n\
+            --- a/koperasi/api.py\n\
+            +++ b/koperasi/api.py\n\
+            @@ -1,3 +1,3 @@\n\
+            \x20import frappe\n\
+            --- comment.sql\n\
+            +++ counter\n\
+            \x20x = 1\n\
+            --- /dev/null\n\
+            +++ b/koperasi/utils.py\t2025-01-01 10:00:00\n\
+            @@ -0,0 +1 @@\n\
+            +-- select 1\n";
+        assert_eq!(
+            diff_files(diff),
+            vec!["koperasi/api.py", "koperasi/utils.py"]
+        );
+    }
+}
@@ -0,0 +1,793 @@
+// Copyright (C) 2025 Nuwaira
+// All Rights Reserved.
+//
+// NOTICE: All information contained herein is, and remains
+// the property of Nuwaira.
+// The intellectual and technical concepts contained
+// herein are proprietary to Nuwaira
+// and are protected by trade secret or copyright law.
+// Dissemination of this information or reproduction of this material
+// is strictly forbidden unless prior written permission is obtained
+// from Nuwaira.
+#![allow(dead_code)]
+
+//! Field reference analysis for form scripts (`frappe.ui.form.on(...)`).
+//!
+//! The DocType of `frm` comes from the enclosing `frappe.ui.form.on` call (or
+//! the file's DocType for `cur_frm`); rows are followed through `locals[cdt][cdn]`,
+//! `frm.add_child(...)` and loops over table fields.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use tree_sitter::{Node, Parser};
+
+use crate::py_refs::{DocTypeIndex, FieldRef};
+use crate::refs_finder::Occurrence;
+
+/// Form events that are not field triggers.
+const FORM_EVENTS: [&str; 22] = [
+    "setup",
+    "onload",
+    "onload_post_render",
+    "refresh",
+    "validate",
+    "before_save",
+    "after_save",
+    "before_submit",
+    "on_submit",
+    "before_cancel",
+    "after_cancel",
+    "before_discard",
+    "after_discard",
+    "before_workflow_action",
+    "after_workflow_action",
+    "timeline_refresh",
+    "before_load",
+    "form_render",
+    "before_print",
+    "on_update",
+    "after_delete",
+    "on_cancel",
+];
+
+/// `frm` methods taking a fieldname (or a list of them) as first argument.
+const FORM_FIELD_METHODS: [&str; 9] = [
+    "set_df_property",
+    "toggle_display",
+    "toggle_reqd",
+    "toggle_enable",
+    "set_query",
+    "refresh_field",
+    "get_field",
+    "add_child",
+    "clear_table",
+];
+
+/// Array methods whose callback receives the rows of a table.
+const ROW_CALLBACKS: [&str; 7] = [
+    "forEach", "map", "filter", "some", "every", "find", "reduce",
+];
+
+thread_local! {
+    /// One parser per worker thread, reused for every file it scans.
+    static PARSER: RefCell<Parser> = {
+        let mut parser = Parser::new();
+        parser
+            .set_language(&tree_sitter_javascript::LANGUAGE.into())
+            .expect("tree-sitter-javascript grammar");
+        RefCell::new(parser)
+    };
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Ty {
+    /// A form (`frm`, `cur_frm`) of the DocType
+    Form(String),
+    /// `frm.fields_dict` of the DocType
+    FieldsDict(String),
+    /// A document or a child table row of the DocType
+    Doc(String),
+    /// The rows of a child table of the DocType
+    Rows(String),
+    /// The `cdt` argument of a child table event, naming the DocType
+    Cdt(String),
+}
+
+/// Find every DocType field reference in a form script.
+///
+/// `file_doctype` is the DocType of `doctype/<dt>/<dt>.js`, used for `cur_frm`.
+pub fn scan_source(
+    file: &str,
+    source: &str,
+    file_doctype: Option<&str>,
+    index: &DocTypeIndex,
+) -> Vec<FieldRef> {
+    let tree = match PARSER.with(|parser| parser.borrow_mut().parse(source, None)) {
+        Some(tree) => tree,
+        None => return Vec::new(),
+    };
+
+    let mut scanner = Scanner {
+        src: source.as_bytes(),
+        file,
+        file_doctype: file_doctype.map(|s| s.to_string()),
+        index,
+        scopes: vec![HashMap::new()],
+        refs: Vec::new(),
+    };
+    scanner.visit(tree.root_node());
+    scanner.refs
+}
+
+struct Scanner<'a> {
+    src: &'a [u8],
+    file: &'a str,
+    file_doctype: Option<String>,
+    index: &'a DocTypeIndex,
+    /// Innermost scope last; `None` shadows an outer binding with an unknown type.
+    scopes: Vec<HashMap<String, Option<Ty>>>,
+    refs: Vec<FieldRef>,
+}
+
+impl<'a> Scanner<'a> {
+    fn text(&self, node: Node) -> &'a str {
+        node.utf8_text(self.src).unwrap_or("")
+    }
+
+    fn lookup(&self, var: &str) -> Option<Ty> {
+        let found = self.scopes.iter().rev().find_map(|scope| scope.get(var));
+        match found {
+            Some(ty) => ty.clone(),
+            None if var == "cur_frm" => self.file_doctype.clone().map(Ty::Form),
+            None => None,
+        }
+    }
+
+    fn bind(&mut self, var: &str, ty: Option<Ty>) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(var.to_string(), ty);
+        }
+    }
+
+    fn record(&mut self, doctype: &str, field: &str, node: Node, var: &str, kind: &str) {
+        if field.is_empty() {
+            return;
+        }
+        self.refs.push(FieldRef {
+            doctype: doctype.to_string(),
+            field: field.to_string(),
+            occurrence: Occurrence {
+                file: self.file.to_string(),
+                line: node.start_position().row + 1,
+                var: var.to_string(),
+                kind: kind.to_string(),
+            },
+        });
+    }
+
+    fn visit_children(&mut self, node: Node) {
+        let mut cursor = node.walk();
+        let children: Vec<Node> = node.named_children(&mut cursor).collect();
+        for child in children {
+            self.visit(child);
+        }
+    }
+
+    fn visit(&mut self, node: Node) {
+        match node.kind() {
+            "function_declaration"
+            | "function_expression"
+            | "arrow_function"
+            | "method_definition" => self.visit_function(node, &[]),
+            "variable_declarator" => {
+                let value = node.child_by_field_name("value");
+                if let Some(value) = value {
+                    self.visit(value);
+                }
+                if let Some(name) = node.child_by_field_name("name") {
+                    let ty = value.and_then(|v| self.expr_type(v));
+                    self.bind_pattern(name, ty);
+                }
+            }
+            "assignment_expression" => {
+                let right = node.child_by_field_name("right");
+                if let Some(right) = right {
+                    self.visit(right);
+                }
+                if let Some(left) = node.child_by_field_name("left") {
+                    if left.kind() == "identifier" {
+                        let ty = right.and_then(|r| self.expr_type(r));
+                        let name = self.text(left);
+                        self.bind(name, ty);
+                    } else {
+                        self.visit(left);
+                    }
+                }
+            }
+            "for_in_statement" => {
+                let right = node.child_by_field_name("right");
+                if let Some(right) = right {
+                    self.visit(right);
+                }
+                let is_of = node
+                    .child_by_field_name("operator")
+                    .is_some_and(|op| self.text(op) == "of");
+                let ty = match right.and_then(|r| self.expr_type(r)) {
+                    Some(Ty::Rows(child)) if is_of => Some(Ty::Doc(child)),
+                    _ => None,
+                };
+                if let Some(left) = node.child_by_field_name("left") {
+                    self.bind_pattern(left, ty);
+                }
+                if let Some(body) = node.child_by_field_name("body") {
+                    self.visit(body);
+                }
+            }
+            "member_expression" => self.visit_member(node),
+            "subscript_expression" => self.visit_subscript(node),
+            "call_expression" => self.visit_call(node),
+            _ => self.visit_children(node),
+        }
+    }
+
+    /// Visit a function, typing its parameters positionally with `params`.
+    fn visit_function(&mut self, node: Node, params: &[Option<Ty>]) {
+        let mut scope = HashMap::new();
+
+        let mut names = Vec::new();
+        if let Some(param) = node.child_by_field_name("parameter") {
+            names.push(param);
+        }
+        if let Some(formal) = node.child_by_field_name("parameters") {
+            let mut cursor = formal.walk();
+            names.extend(formal.named_children(&mut cursor));
+        }
+        for (i, param) in names.into_iter().enumerate() {
+            let name = match param.kind() {
+                "identifier" => Some(param),
+                "assignment_pattern" => param
+                    .child_by_field_name("left")
+                    .filter(|n| n.kind() == "identifier"),
+                _ => None,
+            };
+            if let Some(name) = name {
+                let ty = params.get(i).cloned().flatten();
+                scope.insert(self.text(name).to_string(), ty);
+            }
+        }
+
+        self.scopes.push(scope);
+        if let Some(body) = node.child_by_field_name("body") {
+            self.visit(body);
+        }
+        self.scopes.pop();
+    }
+
+    fn bind_pattern(&mut self, pattern: Node, ty: Option<Ty>) {
+        if pattern.kind() == "identifier" {
+            let name = self.text(pattern);
+            self.bind(name, ty);
+        }
+    }
+
+    fn visit_member(&mut self, node: Node) {
+        let (Some(object), Some(property)) = (
+            node.child_by_field_name("object"),
+            node.child_by_field_name("property"),
+        ) else {
+            return;
+        };
+        let field = self.text(property);
+        match self.expr_type(object) {
+            Some(Ty::Doc(dt)) => {
+                let (var, kind) = self.doc_access(object);
+                self.record(&dt, field, property, &var, kind);
+            }
+            Some(Ty::FieldsDict(dt)) => {
+                let var = self.text(object).to_string();
+                self.record(&dt, field, property, &var, "form_api");
+            }
+            _ => {}
+        }
+        self.visit(object);
+    }
+
+    fn visit_subscript(&mut self, node: Node) {
+        if let (Some(object), Some(index)) = (
+            node.child_by_field_name("object"),
+            node.child_by_field_name("index"),
+        ) {
+            if let Some(field) = self.string_value(index) {
+                match self.expr_type(object) {
+                    Some(Ty::Doc(dt)) => {
+                        let (var, kind) = self.doc_access(object);
+                        self.record(&dt, &field, node, &var, kind);
+                    }
+                    Some(Ty::FieldsDict(dt)) => {
+                        let var = self.text(object).to_string();
+                        self.record(&dt, &field, node, &var, "form_api");
+                    }
+                    _ => {}
+                }
+            }
+        }
+        self.visit_children(node);
+    }
+
+    /// `frm.doc.x` is a `frm_doc` access, anything else on a document is `attr`.
+    fn doc_access(&self, object: Node) -> (String, &'static str) {
+        let var = self.text(object).to_string();
+        let via_form = object.kind() == "member_expression"
+            && object
+                .child_by_field_name("object")
+                .is_some_and(|o| matches!(self.expr_type(o), Some(Ty::Form(_))));
+        if via_form {
+            (var, "frm_doc")
+        } else {
+            (var, "attr")
+        }
+    }
+
+    fn visit_call(&mut self, node: Node) {
+        let Some(function) = node.child_by_field_name("function") else {
+            return self.visit_children(node);
+        };
+        let args = node
+            .child_by_field_name("arguments")
+            .map(|a| self.call_args(a))
+            .unwrap_or_default();
+        let fn_text = self.text(function);
+
+        if fn_text == "frappe.ui.form.on" {
+            return self.visit_form_on(&args);
+        }
+        if fn_text == "frappe.model.set_value" {
+            self.record_model_set_value(node, &args);
+        }
+
+        if function.kind() == "member_expression" {
+            let object = function.child_by_field_name("object");
+            let method = function
+                .child_by_field_name("property")
+                .map(|p| self.text(p))
+                .unwrap_or("");
+
+            if let Some(object) = object {
+                match self.expr_type(object) {
+                    Some(Ty::Form(dt)) => self.record_form_call(node, object, &dt, method, &args),
+                    Some(Ty::Rows(child)) if ROW_CALLBACKS.contains(&method) => {
+                        self.visit(object);
+                        let row = Some(Ty::Doc(child));
+                        let params = if method == "reduce" {
+                            vec![None, row]
+                        } else {
+                            vec![row]
+                        };
+                        for arg in args {
+                            if is_function(arg) {
+                                self.visit_function(arg, &params);
+                            } else {
+                                self.visit(arg);
+                            }
+                        }
+                        return;
+                    }
+                    _ => {}
+                }
+                // The method name itself is not a field
+                self.visit(object);
+            }
+        } else {
+            self.visit(function);
+        }
+
+        for arg in args {
+            self.visit(arg);
+        }
+    }
+
+    /// `frappe.ui.form.on("DocType", { refresh(frm) {...}, customer(frm) {...} })`
+    fn visit_form_on(&mut self, args: &[Node]) {
+        let Some(doctype) = args.first().and_then(|a| self.string_value(*a)) else {
+            for arg in args {
+                self.visit(*arg);
+            }
+            return;
+        };
+
+        // In child table scripts `frm` is the parent's form
+        let parents = self.index.parent_doctypes(&doctype);
+        let form_dt = match parents.as_slice() {
+            [] => Some(doctype.clone()),
+            [parent] => Some(parent.to_string()),
+            _ => None,
+        };
+        if parents.is_empty() && self.file_doctype.is_none() {
+            self.file_doctype = Some(doctype.clone());
+        }
+        let params = vec![form_dt.map(Ty::Form), Some(Ty::Cdt(doctype.clone()))];
+
+        // Legacy form: frappe.ui.form.on("DocType", "field", function(frm) {...})
+        if let Some(event) = args.get(1).and_then(|a| self.string_value(*a)) {
+            self.record_trigger(&doctype, &event, args[1]);
+            for handler in &args[2..] {
+                self.visit_function(*handler, &params);
+            }
+            return;
+        }
+
+        let Some(handlers) = args.get(1).filter(|a| a.kind() == "object") else {
+            for arg in &args[1..] {
+                self.visit(*arg);
+            }
+            return;
+        };
+        let mut cursor = handlers.walk();
+        let members: Vec<Node> = handlers.named_children(&mut cursor).collect();
+        for member in members {
+            let (key, handler) = match member.kind() {
+                "method_definition" => (member.child_by_field_name("name"), Some(member)),
+                "pair" => (
+                    member.child_by_field_name("key"),
+                    member
+                        .child_by_field_name("value")
+                        .filter(|v| is_function(*v)),
+                ),
+                _ => (None, None),
+            };
+            if let Some(key) = key {
+                let event = self
+                    .string_value(key)
+                    .unwrap_or_else(|| self.text(key).to_string());
+                self.record_trigger(&doctype, &event, key);
+            }
+            match handler {
+                Some(handler) => self.visit_function(handler, &params),
+                None => self.visit(member),
+            }
+        }
+    }
+
+    fn record_trigger(&mut self, doctype: &str, event: &str, node: Node) {
+        if FORM_EVENTS.contains(&event) {
+            return;
+        }
+        // `items_add(frm, cdt, cdn)`, `items_remove`, `items_move` concern the table
+        // field, either of this DocType or of the parent the rows belong to
+        let mut target = (doctype.to_string(), event.to_string());
+        for suffix in ["_add", "_remove", "_move", "_on_form_rendered"] {
+            let Some(table) = event.strip_suffix(suffix) else {
+                continue;
+            };
+            if self.index.child_doctype(doctype, table).is_some() {
+                target = (doctype.to_string(), table.to_string());
+                break;
+            }
+            let parent = self
+                .index
+                .parent_doctypes(doctype)
+                .into_iter()
+                .find(|p| self.index.child_doctype(p, table) == Some(doctype));
+            if let Some(parent) = parent {
+                target = (parent.to_string(), table.to_string());
+                break;
+            }
+        }
+        self.record(
+            &target.0,
+            &target.1,
+            node,
+            "frappe.ui.form.on",
+            "field_trigger",
+        );
+    }
+
+    /// `frm.set_value(...)`, `frm.set_query(...)`, `frm.toggle_display(...)`, ...
+    fn record_form_call(
+        &mut self,
+        node: Node,
+        object: Node,
+        doctype: &str,
+        method: &str,
+        args: &[Node],
+    ) {
+        let var = self.text(object).to_string();
+        let Some(first) = args.first() else {
+            return;
+        };
+
+        if method == "set_value" {
+            for field in self.field_names(*first) {
+                self.record(doctype, &field, node, &var, "set_value");
+            }
+            return;
+        }
+        if !FORM_FIELD_METHODS.contains(&method) {
+            return;
+        }
+
+        // frm.set_query("item_code", "items", ...) targets a field of the child table
+        let child = args
+            .get(1)
+            .and_then(|a| self.string_value(*a))
+            .filter(|_| method == "set_query")
+            .and_then(|table| {
+                self.index
+                    .child_doctype(doctype, &table)
+                    .map(|c| c.to_string())
+            });
+        let target = child.as_deref().unwrap_or(doctype).to_string();
+        for field in self.field_names(*first) {
+            self.record(&target, &field, node, &var, "form_api");
+        }
+    }
+
+    /// `frappe.model.set_value(cdt, cdn, "qty", 1)` or `(cdt, cdn, { qty: 1 })`
+    fn record_model_set_value(&mut self, node: Node, args: &[Node]) {
+        let Some(first) = args.first() else {
+            return;
+        };
+        let doctype = match self.expr_type(*first) {
+            Some(Ty::Cdt(dt)) => Some(dt),
+            _ => self.string_value(*first),
+        };
+        let (Some(doctype), Some(fields)) = (doctype, args.get(2)) else {
+            return;
+        };
+        for field in self.field_names(*fields) {
+            self.record(&doctype, &field, node, "frappe.model", "set_value");
+        }
+    }
+
+    /// Fieldnames from `"field"`, `["a", "b"]` or `{ a: 1, "b": 2 }`.
+    fn field_names(&self, node: Node) -> Vec<String> {
+        match node.kind() {
+            "string" => self.string_value(node).into_iter().collect(),
+            "array" => {
+                let mut cursor = node.walk();
+                let items: Vec<Node> = node.named_children(&mut cursor).collect();
+                items
+                    .into_iter()
+                    .filter_map(|n| self.string_value(n))
+                    .collect()
+            }
+            "object" => {
+                let mut cursor = node.walk();
+                let members: Vec<Node> = node.named_children(&mut cursor).collect();
+                members
+                    .into_iter()
+                    .filter_map(|m| match m.kind() {
+                        "pair" => m.child_by_field_name("key").map(|k| {
+                            self.string_value(k)
+                                .unwrap_or_else(|| self.text(k).to_string())
+                        }),
+                        "shorthand_property_identifier" => Some(self.text(m).to_string()),
+                        _ => None,
+                    })
+                    .collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn call_args<'t>(&self, arguments: Node<'t>) -> Vec<Node<'t>> {
+        let mut cursor = arguments.walk();
+        arguments
+            .named_children(&mut cursor)
+            .filter(|n| n.kind() != "comment")
+            .collect()
+    }
+
+    /// Type of an expression, without side effects.
+    fn expr_type(&self, node: Node) -> Option<Ty> {
+        match node.kind() {
+            "identifier" => self.lookup(self.text(node)),
+            "parenthesized_expression" => node.named_child(0).and_then(|n| self.expr_type(n)),
+            "binary_expression" => {
+                // `frm.doc.items || []`
+                let op = node.child_by_field_name("operator").map(|o| self.text(o));
+                if matches!(op, Some("||") | Some("??")) {
+                    self.expr_type(node.child_by_field_name("left")?)
+                } else {
+                    None
+                }
+            }
+            "member_expression" => {
+                let object = node.child_by_field_name("object")?;
+                let property = self.text(node.child_by_field_name("property")?);
+                match self.expr_type(object)? {
+                    Ty::Form(dt) if property == "doc" => Some(Ty::Doc(dt)),
+                    Ty::Form(dt) if property == "fields_dict" => Some(Ty::FieldsDict(dt)),
+                    Ty::Doc(dt) => self
+                        .index
+                        .child_doctype(&dt, property)
+                        .map(|c| Ty::Rows(c.to_string())),
+                    _ => None,
+                }
+            }
+            "subscript_expression" => {
+                let object = node.child_by_field_name("object")?;
+                let index = node.child_by_field_name("index")?;
+
+                // locals[cdt][cdn]
+                if object.kind() == "subscript_expression" {
+                    let inner = object.child_by_field_name("object")?;
+                    if self.text(inner) == "locals" {
+                        let cdt = object.child_by_field_name("index")?;
+                        return match self.expr_type(cdt) {
+                            Some(Ty::Cdt(dt)) => Some(Ty::Doc(dt)),
+                            _ => self.string_value(cdt).map(Ty::Doc),
+                        };
+                    }
+                }
+
+                match self.expr_type(object)? {
+                    Ty::Rows(child) => Some(Ty::Doc(child)),
+                    Ty::Doc(dt) => {
+                        let field = self.string_value(index)?;
+                        self.index
+                            .child_doctype(&dt, &field)
+                            .map(|c| Ty::Rows(c.to_string()))
+                    }
+                    _ => None,
+                }
+            }
+            "call_expression" => {
+                let function = node.child_by_field_name("function")?;
+                let args = self.call_args(node.child_by_field_name("arguments")?);
+                match self.text(function) {
+                    // frappe.get_doc(cdt, cdn)
+                    "frappe.get_doc" | "locals.get_doc" => match self.expr_type(*args.first()?) {
+                        Some(Ty::Cdt(dt)) => Some(Ty::Doc(dt)),
+                        _ => self.string_value(*args.first()?).map(Ty::Doc),
+                    },
+                    _ if function.kind() == "member_expression" => {
+                        let object = function.child_by_field_name("object")?;
+                        let method = self.text(function.child_by_field_name("property")?);
+                        match self.expr_type(object)? {
+                            // frm.add_child("items")
+                            Ty::Form(dt) if method == "add_child" => {
+                                let table = self.string_value(*args.first()?)?;
+                                self.index
+                                    .child_doctype(&dt, &table)
+                                    .map(|c| Ty::Doc(c.to_string()))
+                            }
+                            Ty::Rows(child) if method == "find" => Some(Ty::Doc(child)),
+                            Ty::Rows(child) if method == "filter" => Some(Ty::Rows(child)),
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Value of a plain string literal (templates without substitutions too).
+    fn string_value(&self, node: Node) -> Option<String> {
+        let text = self.text(node);
+        match node.kind() {
+            "string" => {
+                let inner = text.get(1..text.len().saturating_sub(1))?;
+                Some(inner.to_string())
+            }
+            "template_string" if !text.contains("${") => {
+                let inner = text.get(1..text.len().saturating_sub(1))?;
+                Some(inner.to_string())
+            }
+            _ => None,
+        }
+    }
+}
+
+fn is_function(node: Node) -> bool {
+    matches!(
+        node.kind(),
+        "function_expression" | "arrow_function" | "function_declaration" | "method_definition"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index() -> DocTypeIndex {
+        let mut index = DocTypeIndex::default();
+        index.add_json(&serde_json::json!({
+            "name": "Sales Invoice",
+            "fields": [
+                {"fieldname": "customer", "fieldtype": "Link", "options": "Customer"},
+                {"fieldname": "items", "fieldtype": "Table", "options": "Sales Invoice Item"},
+            ]
+        }));
+        index.add_json(&serde_json::json!({
+            "name": "Sales Invoice Item",
+            "istable": 1,
+            "fields": [{"fieldname": "qty", "fieldtype": "Float"}]
+        }));
+        index
+    }
+
+    fn refs(source: &str, file_doctype: Option<&str>) -> Vec<(String, String, usize, String)> {
+        scan_source("test.js", source, file_doctype, &index())
+            .into_iter()
+            .map(|r| (r.doctype, r.field, r.occurrence.line, r.occurrence.kind))
+            .collect()
+    }
+
+    fn has(
+        refs: &[(String, String, usize, String)],
+        dt: &str,
+        field: &str,
+        line: usize,
+        kind: &str,
+    ) -> bool {
+        refs.iter()
+            .any(|(d, f, l, k)| d == dt && f == field && *l == line && k == kind)
+    }
+
+    #[test]
+    fn test_form_handlers() {
+        let source = r#"
+frappe.ui.form.on("Sales Invoice", {
+    refresh(frm) {
+        if (frm.doc.customer) {
+            frm.set_value("customer", null);
+        }
+        frm.set_query("qty", "items", () => ({}));
+    },
+    customer: function (frm) {
+        (frm.doc.items || []).forEach((row) => {
+            console.log(row.qty);
+        });
+        let row = frm.add_child("items");
+        row.qty = 1;
+    },
+});
+"#;
+        let refs = refs(source, None);
+        assert!(has(&refs, "Sales Invoice", "customer", 4, "frm_doc"));
+        assert!(has(&refs, "Sales Invoice", "customer", 5, "set_value"));
+        assert!(has(&refs, "Sales Invoice Item", "qty", 7, "form_api"));
+        assert!(has(&refs, "Sales Invoice", "customer", 9, "field_trigger"));
+        assert!(!refs.iter().any(|(_, f, _, _)| f == "refresh"));
+        assert!(has(&refs, "Sales Invoice", "items", 10, "frm_doc"));
+        assert!(has(&refs, "Sales Invoice Item", "qty", 11, "attr"));
+        assert!(has(&refs, "Sales Invoice Item", "qty", 14, "attr"));
+    }
+
+    #[test]
+    fn test_child_table_events() {
+        let source = r#"
+frappe.ui.form.on("Sales Invoice Item", {
+    qty(frm, cdt, cdn) {
+        const row = locals[cdt][cdn];
+        frappe.model.set_value(cdt, cdn, "qty", row.qty * 2);
+        frm.refresh_field("items");
+    },
+    items_remove(frm) {},
+});
+"#;
+        let refs = refs(source, None);
+        assert!(has(&refs, "Sales Invoice Item", "qty", 3, "field_trigger"));
+        assert!(has(&refs, "Sales Invoice Item", "qty", 5, "set_value"));
+        assert!(has(&refs, "Sales Invoice Item", "qty", 5, "attr"));
+        assert!(has(&refs, "Sales Invoice", "items", 6, "form_api"));
+        assert!(has(&refs, "Sales Invoice", "items", 8, "field_trigger"));
+    }
+
+    #[test]
+    fn test_cur_frm_uses_file_doctype() {
+        let source = r#"
+function update() {
+    cur_frm.set_value({ customer: "A" });
+    return cur_frm.doc["customer"];
+}
+"#;
+        let refs = refs(source, Some("Sales Invoice"));
+        assert!(has(&refs, "Sales Invoice", "customer", 3, "set_value"));
+        assert!(has(&refs, "Sales Invoice", "customer", 4, "frm_doc"));
+    }
+}
@@ -13,11 +13,23 @@
 #[macro_use]
 pub mod macros;
 pub mod analyze;
+pub mod audit;
+pub mod cache;
 pub mod config;
+pub mod coverage;
 pub mod fileutil;
 pub mod functools;
+pub mod js_refs;
+pub mod policy;
+pub mod prompts;
+pub mod py_refs;
 pub mod refs_finder;
+pub mod resources;
+pub mod runlog;
 pub mod serdeutil;
 pub mod server;
 pub mod shellutil;
+pub mod sqlutil;
 pub mod stringutil;
+pub mod testutil;
+pub mod watcher;
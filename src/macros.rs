@@ -14,3 +14,17 @@ macro_rules! mcp_return {
         return Ok(CallToolResult::success(vec![Content::text($expr)]))
     };
 }
+
+/// Return `$data` as the tool's structured content, with `$text` as its text view.
+macro_rules! mcp_return_data {
+    ($data:expr, $text:expr) => {
+        return Ok(crate::functools::structured_result(&$data, $text))
+    };
+}
+
+/// Return a tool-level error (`is_error: true`) with a message for the agent.
+macro_rules! mcp_error {
+    ($expr:expr) => {
+        return Ok(CallToolResult::error(vec![Content::text($expr)]))
+    };
+}
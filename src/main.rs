@@ -15,14 +15,26 @@ use std::process::exit;
 #[macro_use]
 mod macros;
 mod analyze;
+mod audit;
+mod cache;
 mod config;
+mod coverage;
 mod fileutil;
 mod functools;
+mod js_refs;
+mod policy;
+mod prompts;
+mod py_refs;
 mod refs_finder;
+mod resources;
+mod runlog;
 mod serdeutil;
 mod server;
 mod shellutil;
+mod sqlutil;
 mod stringutil;
+mod testutil;
+mod watcher;
 
 use config::Config;
 use rmcp::model::{CallToolResult, ErrorCode, RawTextContent};
@@ -67,20 +79,36 @@ struct Args {
 /// Enum of subcommands
 #[derive(Subcommand, Debug)]
 enum CommandEnum {
-    /// Analyze the codebase and output a analyzed_output.dat file.
+    /// Analyze the codebase into the bench's analysis cache (`<bench>/.frappe-mcp/`).
     Analyze {
         #[arg(
             short,
             long,
             default_value = "",
-            help = "Directory/codebase to analyze"
+            help = "Directory/codebase to analyze into a cache of its own (default: all configured apps)"
         )]
         app_dir: String,
+        #[arg(
+            long,
+            help = "Rebuild everything instead of re-parsing only the files changed since the last analysis"
+        )]
+        full: bool,
+        #[arg(long, help = "Print the time spent in each analysis phase")]
+        stats: bool,
         // #[arg(short, long, help = "relative path from root")]
         // relative_path: String,
     },
     /// Run the MCP server
     Run,
+    /// Run the MCP server, optionally over HTTP so it can be shared by several clients
+    Serve {
+        #[arg(
+            long,
+            value_name = "ADDR",
+            help = "Serve over streamable HTTP (with SSE fallback) on this address, e.g. 127.0.0.1:8080"
+        )]
+        http: Option<String>,
+    },
     /// Search Frappe documentation
     SearchDocs {
         #[arg(help = "Search query")]
@@ -102,12 +130,60 @@ enum CommandEnum {
     /// Execute functool functions for testing
     Functool {
         #[arg(
-            help = "Function name: get-doctype, list-doctypes, run-bench-command, find-field-usage, find-symbols"
+            help = "Function name: get-doctype, list-doctypes, run-bench-command, find-field-usage, find-symbols, get-command-output, tests-for-changes, find-referencing-doctypes"
         )]
         function: String,
         #[arg(help = "Function arguments (use functool <function> --help for details)", num_args = 0..)]
         args: Vec<String>,
     },
+    /// Export the DocType relationship graph as Mermaid, DOT or JSON
+    ExportGraph {
+        #[arg(help = "DocType to start from (or use --module)")]
+        doctype: Option<String>,
+        #[arg(short, long, help = "Export every DocType of this module instead")]
+        module: Option<String>,
+        #[arg(
+            short,
+            long,
+            help = "Link hops to follow (default: 2 from a DocType, 0 from a module)"
+        )]
+        depth: Option<usize>,
+        #[arg(long, help = "Only DocTypes of this app")]
+        app: Option<String>,
+        #[arg(
+            short,
+            long,
+            help = "Output format: mermaid, dot, json or text",
+            default_value = "mermaid"
+        )]
+        format: String,
+    },
+    /// Print the audit log of write, shell and database tool calls
+    Audit {
+        #[arg(long, help = "Only calls of this tool, e.g. run_bench_command")]
+        tool: Option<String>,
+        #[arg(
+            long,
+            help = "Only calls since a time: RFC 3339, YYYY-MM-DD, or an age like 30m, 2h, 7d"
+        )]
+        since: Option<String>,
+        #[arg(long, help = "Only failed or denied calls")]
+        errors: bool,
+        #[arg(
+            long,
+            help = "Only calls whose arguments, command or files contain this text"
+        )]
+        grep: Option<String>,
+        #[arg(
+            short,
+            long,
+            help = "Print the last N matching calls (0 for all)",
+            default_value_t = 50
+        )]
+        limit: usize,
+        #[arg(long, help = "Print the matching entries as JSON lines")]
+        json: bool,
+    },
     /// Print version info
     Version,
 }
@@ -130,15 +206,18 @@ async fn execute_functool(
     function: &str,
     args: &[String],
 ) -> Result<CallToolResult, rmcp::ErrorData> {
-    // Use default analysis file path
-    let analysis_file = "analyzed_output.dat";
-    let analyzed_data = analyze::AnalyzedData::from_file(analysis_file).map_err(|_| {
-        rmcp::ErrorData::new(
-            ErrorCode::INVALID_REQUEST,
-            "Failed to load analyzed data. Run 'frappe-mcp analyze' first.",
-            None,
-        )
-    })?;
+    let analyzed_data = cache::AnalysisCache::for_config(config)
+        .load()
+        .map_err(|e| {
+            rmcp::ErrorData::new(
+                ErrorCode::INVALID_REQUEST,
+                format!(
+                    "Failed to load analyzed data ({:#}). Run 'frappe-mcp analyze' first.",
+                    e
+                ),
+                None,
+            )
+        })?;
 
     match function {
         "get-doctype" | "get_doctype" => {
@@ -153,11 +232,21 @@ async fn execute_functool(
                 .get(1)
                 .map(|s| s == "true" || s == "json")
                 .unwrap_or(false);
-            functools::get_doctype(config, &analyzed_data, &args[0], json_only)
+            match functools::get_doctype(config, &analyzed_data, &args[0], json_only, None) {
+                Some(result) => result,
+                None => {
+                    let options = shellutil::RunOptions {
+                        timeout: config.tool_timeout("get_doctype"),
+                        ..Default::default()
+                    };
+                    functools::get_doctype_from_database(config, &args[0], json_only, &options)
+                        .await
+                }
+            }
         }
         "list-doctypes" | "list_doctypes" => {
             let module_filter = args.get(0).cloned();
-            functools::list_doctypes(config, &analyzed_data, module_filter)
+            functools::list_doctypes(config, &analyzed_data, module_filter, None)
         }
         "run-bench-command" | "run_bench_command" => {
             if args.is_empty() {
@@ -169,7 +258,25 @@ async fn execute_functool(
             }
             // Convert Vec<String> to Vec<&str>
             let str_args: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
-            functools::run_bench_command(config, &analyzed_data, &str_args)
+            let options = shellutil::RunOptions {
+                timeout: config.tool_timeout("run_bench_command"),
+                log_dir: Some(runlog::runs_dir(config)),
+                ..Default::default()
+            };
+            functools::run_bench_command(config, &str_args, &options).await
+        }
+        "get-command-output" | "get_command_output" => {
+            if args.is_empty() {
+                return Err(rmcp::ErrorData::new(
+                    ErrorCode::INVALID_REQUEST,
+                    "get-command-output requires a run id",
+                    None,
+                ));
+            }
+            let grep = args.get(1).filter(|g| !g.is_empty()).map(|g| g.as_str());
+            let offset = args.get(2).and_then(|s| s.parse::<usize>().ok());
+            let limit = args.get(3).and_then(|s| s.parse::<usize>().ok());
+            functools::get_command_output(config, &args[0], offset, limit, grep)
         }
         "find-field-usage" | "find_field_usage" => {
             if args.len() < 2 {
@@ -180,7 +287,7 @@ async fn execute_functool(
                 ));
             }
             let limit = args.get(2).and_then(|s| s.parse::<usize>().ok());
-            functools::find_field_usage(config, &analyzed_data, &args[0], &args[1], limit)
+            functools::find_field_usage(config, &analyzed_data, &args[0], &args[1], limit, None)
         }
         "find-symbols" | "find_symbols" => {
             if args.is_empty() {
@@ -193,7 +300,43 @@ async fn execute_functool(
             let search_in = args.get(1).cloned();
             let fuzzy = args.get(2).map(|s| s == "true").or(Some(false));
             let limit = args.get(3).and_then(|s| s.parse::<usize>().ok());
-            functools::find_symbols(config, &analyzed_data, &args[0], search_in, fuzzy, limit)
+            functools::find_symbols(
+                config,
+                &analyzed_data,
+                &args[0],
+                search_in,
+                fuzzy,
+                limit,
+                None,
+            )
+        }
+        "find-referencing-doctypes" | "find_referencing_doctypes" => {
+            if args.is_empty() {
+                return Err(rmcp::ErrorData::new(
+                    ErrorCode::INVALID_REQUEST,
+                    "find-referencing-doctypes requires a doctype name",
+                    None,
+                ));
+            }
+            let include_dynamic = args.get(1).map(|s| s == "true");
+            functools::find_referencing_doctypes(
+                config,
+                &analyzed_data,
+                &args[0],
+                include_dynamic,
+                None,
+            )
+        }
+        "tests-for-changes" | "tests_for_changes" => {
+            // Without files, the apps' uncommitted git changes
+            let files = if args.is_empty() {
+                functools::git_changed_files(config, None)
+                    .await
+                    .map_err(|e| rmcp::ErrorData::new(ErrorCode::INVALID_REQUEST, e, None))?
+            } else {
+                args.to_vec()
+            };
+            functools::tests_for_changes(config, &analyzed_data, &files, None)
         }
         _ => Err(rmcp::ErrorData::new(
             ErrorCode::INVALID_REQUEST,
@@ -208,18 +351,51 @@ async fn main() {
     let (args, config) = parse_args();
 
     match args.command {
-        CommandEnum::Analyze { app_dir } => {
-            // Perform analysis and output to the specified file
-            let output = "analyzed_output.dat";
-            let relative_path = config.app_relative_path.to_string();
-            if let Err(e) = analyze::analyze_frappe_app(&app_dir, &relative_path, output) {
-                eprintln!("Analysis error: {}", e);
-                exit(1);
+        CommandEnum::Analyze {
+            app_dir,
+            full,
+            stats,
+        } => {
+            // Perform analysis and store it in the bench's cache; a single
+            // directory gets a cache of its own, never loaded by the server
+            let cache = if app_dir.is_empty() {
+                cache::AnalysisCache::for_config(&config)
+            } else {
+                cache::AnalysisCache::for_app_dir(&config, &app_dir)
+            };
+            let result = if app_dir.is_empty() {
+                // Unless asked otherwise, only files changed since the cached analysis are parsed
+                let previous = if full { None } else { cache.load().ok() };
+                analyze::analyze_bench_with_stats(&config, previous.as_ref())
+            } else {
+                let relative_path = config.app_relative_path.to_string();
+                analyze::analyze_frappe_app(&app_dir, &relative_path)
+            };
+            let phase_stats = match result.and_then(|(data, s)| cache.save(&data).map(|_| s)) {
+                Ok(s) => s,
+                Err(e) => {
+                    eprintln!("Analysis error: {}", e);
+                    exit(1);
+                }
+            };
+            if stats {
+                println!("{}", phase_stats);
             }
-            println!("Analysis completed. Output written to {}", output);
+            println!(
+                "Analysis completed. Output written to {}",
+                cache.path().display()
+            );
             exit(1);
         }
         CommandEnum::Run => {}
+        CommandEnum::Serve { http: Some(addr) } => {
+            if let Err(e) = server::run_http(config, &addr).await {
+                eprintln!("Server error: {}", e);
+                exit(1);
+            }
+            return;
+        }
+        CommandEnum::Serve { http: None } => {}
         CommandEnum::SearchDocs {
             query,
             category,
@@ -247,6 +423,48 @@ async fn main() {
             }
             return;
         }
+        CommandEnum::ExportGraph {
+            doctype,
+            module,
+            depth,
+            app,
+            format,
+        } => {
+            let format: functools::GraphFormat = format.parse().unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                exit(1);
+            });
+            let analyzed_data = cache::AnalysisCache::for_config(&config)
+                .load()
+                .unwrap_or_else(|e| {
+                    eprintln!(
+                        "Failed to load analyzed data ({:#}). Run 'frappe-mcp analyze' first.",
+                        e
+                    );
+                    exit(1);
+                });
+            let result = functools::analyze_links(
+                &config,
+                &analyzed_data,
+                doctype.as_deref(),
+                module.as_deref(),
+                depth,
+                app,
+                Some(format),
+            );
+            match result {
+                Ok(result) if result.is_error != Some(true) => print_tool_result(result),
+                Ok(result) => {
+                    print_tool_result(result);
+                    exit(1);
+                }
+                Err(e) => {
+                    eprintln!("Export error: {}", e.message);
+                    exit(1);
+                }
+            }
+            return;
+        }
         CommandEnum::ReadDoc { id } => {
             match functools::get_frappe_doc(&id) {
                 Ok(result) => {
@@ -271,6 +489,50 @@ async fn main() {
             }
             return;
         }
+        CommandEnum::Audit {
+            tool,
+            since,
+            errors,
+            grep,
+            limit,
+            json,
+        } => {
+            let Some(path) = config.audit_log_path() else {
+                eprintln!("No audit_log configured in {}", args.config);
+                exit(1);
+            };
+            let since = since
+                .map(|s| audit::parse_since(&s))
+                .transpose()
+                .unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    exit(1);
+                });
+            let filter = audit::AuditFilter {
+                tool,
+                since,
+                errors_only: errors,
+                contains: grep,
+            };
+            let entries = audit::read(&path).unwrap_or_else(|e| {
+                eprintln!("Error reading audit log {}: {}", path.display(), e);
+                exit(1);
+            });
+            let matched: Vec<_> = entries.iter().filter(|e| filter.matches(e)).collect();
+            let skip = if limit == 0 {
+                0
+            } else {
+                matched.len().saturating_sub(limit)
+            };
+            for entry in &matched[skip..] {
+                if json {
+                    println!("{}", serde_json::to_string(entry).unwrap_or_default());
+                } else {
+                    println!("{}", entry.render());
+                }
+            }
+            return;
+        }
         CommandEnum::Version => {
             println!("Version {}", env!("CARGO_PKG_VERSION"));
             return;
@@ -0,0 +1,368 @@
+// Copyright (C) 2025 Nuwaira
+// All Rights Reserved.
+//
+// NOTICE: All information contained herein is, and remains
+// the property of Nuwaira.
+// The intellectual and technical concepts contained
+// herein are proprietary to Nuwaira
+// and are protected by trade secret or copyright law.
+// Dissemination of this information or reproduction of this material
+// is strictly forbidden unless prior written permission is obtained
+// from Nuwaira.
+#![allow(dead_code)]
+
+//! Tool permission policy, the `[policy]` section of `frappe-mcp.conf`.
+//!
+//! Disabled tools are not registered at all; the shell and database tools
+//! that stay enabled have their bench subcommand or SQL checked before
+//! anything is run.
+
+use serde::Deserialize;
+
+use crate::sqlutil::{self, Statement};
+
+/// Tools grouped by what they can touch.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolGroup {
+    /// `create_*` tools writing files into the app
+    Write,
+    /// Tools spawning `bench`
+    Shell,
+    /// Tools querying the site database
+    Db,
+}
+
+const WRITE_TOOLS: &[&str] = &[
+    "create_doctype",
+    "create_web_page",
+    "create_custom_page",
+    "create_test_template",
+    "create_report",
+];
+const SHELL_TOOLS: &[&str] = &[
+    "run_bench_command",
+    "bench_execute",
+    "run_tests",
+    "tests_for_changes",
+];
+const DB_TOOLS: &[&str] = &["run_db_command", "get_doctype_db_schema"];
+
+/// Shell tools that can change the site even with a checked subcommand.
+const READ_ONLY_BLOCKED: &[&str] = &["bench_execute", "run_tests", "tests_for_changes"];
+
+/// Bench subcommands allowed in read-only mode.
+const READ_ONLY_BENCH: &[&str] = &[
+    "version",
+    "list-apps",
+    "show-config",
+    "doctor",
+    "show-pending-jobs",
+    "mariadb",
+    "db-console",
+];
+
+/// Subcommands opening a database console; their `-e` SQL is checked too.
+/// They pass any other argument on to the client, so only `<sub> -e <sql>` is accepted.
+const SQL_CONSOLES: &[&str] = &["mariadb", "db-console", "postgres"];
+
+impl ToolGroup {
+    pub fn tools(self) -> &'static [&'static str] {
+        match self {
+            ToolGroup::Write => WRITE_TOOLS,
+            ToolGroup::Shell => SHELL_TOOLS,
+            ToolGroup::Db => DB_TOOLS,
+        }
+    }
+
+    /// The group of a tool, `None` for the analysis tools which are always on.
+    pub fn of(tool: &str) -> Option<ToolGroup> {
+        [ToolGroup::Write, ToolGroup::Shell, ToolGroup::Db]
+            .iter()
+            .copied()
+            .find(|g| g.tools().contains(&tool))
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Policy {
+    /// Block every tool that can write files or change the site
+    pub read_only: bool,
+
+    /// Tool groups to turn off entirely
+    pub disabled_groups: Vec<ToolGroup>,
+
+    /// Bench subcommands allowed, empty means any not denied
+    pub bench_allow: Vec<String>,
+
+    /// Bench subcommands always denied
+    pub bench_deny: Vec<String>,
+
+    /// SQL statements allowed, by leading keyword (`select`) or class (`read`, `dml`, `ddl`)
+    pub sql_allow: Vec<String>,
+
+    /// SQL statements always denied, by leading keyword or class
+    pub sql_deny: Vec<String>,
+
+    /// Allowed writes still need `confirm: true` on `run_db_command`
+    pub confirm_writes: bool,
+
+    /// `LIMIT` added to SELECTs over DocType tables without one, 0 to turn off
+    pub select_limit: usize,
+}
+
+/// Outcome of checking a single SQL statement.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SqlVerdict {
+    Allowed,
+    /// A write allowed by the lists, run only when the caller confirms it
+    NeedsConfirmation,
+    Denied(String),
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Policy {
+            read_only: false,
+            disabled_groups: Vec::new(),
+            bench_allow: Vec::new(),
+            bench_deny: [
+                "drop-site",
+                "reinstall",
+                "restore",
+                "partial-restore",
+                "remove-app",
+                "uninstall-app",
+                "trim-database",
+                "trim-tables",
+            ]
+            .iter()
+            .map(|s| s.to_string())
+            .collect(),
+            sql_allow: vec!["select".to_string()],
+            sql_deny: Vec::new(),
+            confirm_writes: true,
+            select_limit: 100,
+        }
+    }
+}
+
+impl Policy {
+    /// Whether a tool is registered at all.
+    pub fn tool_enabled(&self, tool: &str) -> bool {
+        let Some(group) = ToolGroup::of(tool) else {
+            return true;
+        };
+        if self.disabled_groups.contains(&group) {
+            return false;
+        }
+        !(self.read_only && (group == ToolGroup::Write || READ_ONLY_BLOCKED.contains(&tool)))
+    }
+
+    /// Names of the tools turned off by this policy.
+    pub fn disabled_tools(&self) -> Vec<&'static str> {
+        [WRITE_TOOLS, SHELL_TOOLS, DB_TOOLS]
+            .concat()
+            .into_iter()
+            .filter(|t| !self.tool_enabled(t))
+            .collect()
+    }
+
+    /// Check `bench --site <site> <args>` against the subcommand lists.
+    pub fn check_bench<S: AsRef<str>>(&self, args: &[S]) -> Result<(), String> {
+        let args: Vec<&str> = args.iter().map(|a| a.as_ref()).collect();
+        if args
+            .iter()
+            .any(|a| *a == "--site" || a.starts_with("--site="))
+        {
+            return Err("`--site` is set by the server configuration".to_string());
+        }
+        let Some(sub) = args.iter().find(|a| !a.starts_with('-')) else {
+            return Ok(());
+        };
+        let sub = sub.to_lowercase();
+        if contains(&self.bench_deny, &sub) {
+            return Err(format!("bench subcommand `{}` is denied", sub));
+        }
+        if !self.bench_allow.is_empty() && !contains(&self.bench_allow, &sub) {
+            return Err(format!(
+                "bench subcommand `{}` is not in the allow list",
+                sub
+            ));
+        }
+        if self.read_only && !READ_ONLY_BENCH.contains(&sub.as_str()) {
+            return Err(format!(
+                "bench subcommand `{}` is not allowed in read-only mode",
+                sub
+            ));
+        }
+        if SQL_CONSOLES.contains(&sub.as_str()) {
+            let [_, "-e" | "--execute", sql] = args.as_slice() else {
+                return Err(format!(
+                    "`bench {}` takes only the SQL, passed with a single `-e`",
+                    sub
+                ));
+            };
+            // Also denies client commands like `\!`, which the console would run
+            return self.check_sql(sql);
+        }
+        Ok(())
+    }
+
+    /// Check every statement of `sql`; writes can't be confirmed here.
+    pub fn check_sql(&self, sql: &str) -> Result<(), String> {
+        for (i, statement) in sqlutil::split_statements(sql).iter().enumerate() {
+            let reason = match self.check_statement(statement) {
+                SqlVerdict::Allowed => continue,
+                SqlVerdict::NeedsConfirmation => {
+                    "writes need confirmation, use `run_db_command` with `confirm`".to_string()
+                }
+                SqlVerdict::Denied(reason) => reason,
+            };
+            return Err(format!(
+                "statement {} `{}`: {}",
+                i + 1,
+                statement.text,
+                reason
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check a statement against the SQL lists and the read-only switch.
+    pub fn check_statement(&self, statement: &Statement) -> SqlVerdict {
+        // The client would run it, whatever the statement is classified as
+        if let Some(command) = statement.client_command() {
+            return SqlVerdict::Denied(format!("client command `{}` is not allowed", command));
+        }
+        let kind = statement.kind.as_str();
+        let class = statement.class.as_str();
+        let matches = |list: &[String]| contains(list, kind) || contains(list, class);
+        if matches(&self.sql_deny) {
+            return SqlVerdict::Denied(format!("{} statements are denied", kind.to_uppercase()));
+        }
+        if !matches(&self.sql_allow) {
+            return SqlVerdict::Denied(format!(
+                "{} statements are not allowed, allowed: {}",
+                kind.to_uppercase(),
+                self.sql_allow.join(", ").to_uppercase()
+            ));
+        }
+        if statement.is_read() {
+            return SqlVerdict::Allowed;
+        }
+        if self.read_only {
+            return SqlVerdict::Denied(format!(
+                "{} statements are not allowed in read-only mode",
+                kind.to_uppercase()
+            ));
+        }
+        if self.confirm_writes {
+            return SqlVerdict::NeedsConfirmation;
+        }
+        SqlVerdict::Allowed
+    }
+}
+
+fn contains(list: &[String], value: &str) -> bool {
+    list.iter().any(|v| v.eq_ignore_ascii_case(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_policy() {
+        let policy = Policy::default();
+        assert!(policy.disabled_tools().is_empty());
+
+        assert!(policy.check_bench(&["migrate"]).is_ok());
+        assert!(policy.check_bench(&["drop-site", "--force"]).is_err());
+        assert!(policy.check_bench(&["--site", "prod", "migrate"]).is_err());
+
+        assert!(policy.check_sql("SELECT name FROM tabUser").is_ok());
+        assert!(policy.check_sql("select 1; DROP TABLE tabUser;").is_err());
+        assert!(policy.check_sql("UPDATE tabUser SET enabled = 0").is_err());
+        assert!(policy.check_sql("SELECT ';DROP TABLE x' AS s").is_ok());
+        assert!(policy.check_sql("select 1 \\! id").is_err());
+        assert!(policy.check_sql("select '\\! id'").is_ok());
+        assert!(policy
+            .check_bench(&["mariadb", "-e", "DELETE FROM tabUser"])
+            .is_err());
+        assert!(policy.check_bench(&["mariadb"]).is_err());
+        assert!(policy.check_bench(&["mariadb", "-e", "select 1"]).is_ok());
+        // Client commands are denied through the shell tool as well
+        assert!(policy
+            .check_bench(&["mariadb", "-e", "select 1 \\! id"])
+            .is_err());
+        assert!(policy
+            .check_bench(&["db-console", "-e", "select 1 \\T /tmp/out"])
+            .is_err());
+        assert!(policy
+            .check_bench(&["mariadb", "-e", "select 1", "-e", "DROP TABLE tabUser"])
+            .is_err());
+        assert!(policy
+            .check_bench(&[
+                "mariadb",
+                "--init-command=DROP TABLE tabUser",
+                "-e",
+                "select 1"
+            ])
+            .is_err());
+        assert!(policy
+            .check_bench(&[
+                "db-console",
+                "-e",
+                "select 1",
+                "--init-command",
+                "DROP TABLE x"
+            ])
+            .is_err());
+    }
+
+    #[test]
+    fn test_read_only_and_groups() {
+        let policy = Policy {
+            read_only: true,
+            sql_allow: vec!["select".to_string(), "update".to_string()],
+            ..Default::default()
+        };
+        let disabled = policy.disabled_tools();
+        assert!(disabled.contains(&"create_doctype"));
+        assert!(disabled.contains(&"bench_execute"));
+        assert!(disabled.contains(&"tests_for_changes"));
+        assert!(!disabled.contains(&"run_db_command"));
+        assert!(policy.tool_enabled("get_doctype"));
+        assert!(policy.check_bench(&["list-apps"]).is_ok());
+        assert!(policy.check_bench(&["migrate"]).is_err());
+        assert!(policy.check_sql("UPDATE tabUser SET enabled = 0").is_err());
+
+        let policy = Policy {
+            sql_allow: vec!["read".to_string(), "dml".to_string()],
+            sql_deny: vec!["delete".to_string()],
+            ..Default::default()
+        };
+        let verdicts: Vec<SqlVerdict> = sqlutil::split_statements(
+            "SHOW TABLES; UPDATE tabUser SET x = 1; DELETE FROM tabUser; DROP TABLE tabUser",
+        )
+        .iter()
+        .map(|s| policy.check_statement(s))
+        .collect();
+        assert_eq!(verdicts[0], SqlVerdict::Allowed);
+        assert_eq!(verdicts[1], SqlVerdict::NeedsConfirmation);
+        assert!(matches!(verdicts[2], SqlVerdict::Denied(_)));
+        assert!(matches!(verdicts[3], SqlVerdict::Denied(_)));
+        assert!(policy.check_sql("UPDATE tabUser SET x = 1").is_err());
+
+        let policy = Policy {
+            disabled_groups: vec![ToolGroup::Db],
+            bench_allow: vec!["migrate".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(policy.disabled_tools(), DB_TOOLS.to_vec());
+        assert!(policy.check_bench(&["migrate"]).is_ok());
+        assert!(policy.check_bench(&["clear-cache"]).is_err());
+    }
+}
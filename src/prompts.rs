@@ -0,0 +1,458 @@
+// Copyright (C) 2025 Nuwaira
+// All Rights Reserved.
+//
+// NOTICE: All information contained herein is, and remains
+// the property of Nuwaira.
+// The intellectual and technical concepts contained
+// herein are proprietary to Nuwaira
+// and are protected by trade secret or copyright law.
+// Dissemination of this information or reproduction of this material
+// is strictly forbidden unless prior written permission is obtained
+// from Nuwaira.
+#![allow(dead_code)]
+
+//! Built-in prompts for common Frappe workflows.
+//!
+//! Every prompt is a plan the agent can follow, grounded with the `get_doctype`
+//! metadata and `analyze_links` graph of the DocType involved and a few
+//! matching snippets from the embedded Frappe docs.
+
+use rmcp::{model::*, ErrorData as McpError};
+use serde_json::json;
+
+use crate::analyze::{AnalyzedData, DocType};
+use crate::config::Config;
+use crate::functools::{self, OutputFormat};
+
+type McpResult = Result<GetPromptResult, McpError>;
+
+/// Docs search hits inlined per query.
+const DOC_SNIPPETS: usize = 2;
+
+/// Prompt: add a field to a DocType, from the JSON down to tests.
+pub fn add_doctype_field(
+    config: &Config,
+    anal: &AnalyzedData,
+    doctype: &str,
+    fieldname: &str,
+    fieldtype: &str,
+    options: Option<&str>,
+    app: Option<&str>,
+) -> McpResult {
+    let dt = find_doctype(anal, doctype, app)?;
+    let options_note = match options {
+        Some(options) => format!(" with options `{}`", options),
+        None if matches!(fieldtype, "Link" | "Table" | "Table MultiSelect" | "Select") => {
+            format!(" (a {} field also needs `options`)", fieldtype)
+        }
+        None => String::new(),
+    };
+
+    let mut plan = vec![
+        format!(
+            "Add the field `{}` ({}){} to the DocType **{}** end to end.",
+            fieldname, fieldtype, options_note, dt.name
+        ),
+        String::new(),
+        "## Plan".to_string(),
+        format!(
+            "1. Edit the metadata `{}`: append the field to `fields` and put `{}` at the right place in `field_order`. Keep `modified` bumped so `migrate` picks it up.",
+            dt.meta_file.as_deref().unwrap_or("(no metadata file found)"),
+            fieldname
+        ),
+        format!(
+            "2. Update the controller `{}`: add `{}: DF.{}` to the auto-generated type hints and any validation in `validate()`.",
+            dt.backend_file,
+            fieldname,
+            fieldtype.replace(' ', "")
+        ),
+    ];
+    if let Some(frontend) = &dt.frontend_file {
+        plan.push(format!(
+            "3. Update the form script `{}` if the field needs triggers, `set_query` filters or `depends_on` logic.",
+            frontend
+        ));
+    } else {
+        plan.push(
+            "3. Add a form script only if the field needs triggers or `set_query` filters."
+                .to_string(),
+        );
+    }
+    plan.push(
+        "4. Check reports, APIs and child DocTypes that read this DocType (see the link graph; `find_field_usage` on neighbouring fields helps) and update them."
+            .to_string(),
+    );
+    plan.push(format!(
+        "5. Run `run_bench_command` with `migrate`, then confirm the column with `get_doctype_db_schema(\"{}\")`.",
+        dt.name
+    ));
+    plan.push(match &dt.test_file {
+        Some(test_file) => format!(
+            "6. Cover the field in `{}` and run `run_tests(\"{}\")`.",
+            test_file, dt.name
+        ),
+        None => format!(
+            "6. Create a test with `create_test_template(\"{}\")`, cover the field and run `run_tests(\"{}\")`.",
+            dt.name, dt.name
+        ),
+    });
+
+    let context = grounding(
+        config,
+        anal,
+        dt,
+        &[(fieldtype, Some("doctypes")), ("field_order", None)],
+    );
+    Ok(prompt_result(
+        format!("Add field `{}` to {}", fieldname, dt.name),
+        plan,
+        context,
+    ))
+}
+
+/// Prompt: write a Script Report over a DocType.
+pub fn write_script_report(
+    config: &Config,
+    anal: &AnalyzedData,
+    doctype: &str,
+    report_name: Option<&str>,
+    app: Option<&str>,
+) -> McpResult {
+    let dt = find_doctype(anal, doctype, app)?;
+    let report_name = report_name
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| format!("{} Report", dt.name));
+
+    let plan = vec![
+        format!(
+            "Write a Script Report **{}** over the DocType **{}**.",
+            report_name, dt.name
+        ),
+        String::new(),
+        "## Plan".to_string(),
+        format!(
+            "1. Scaffold it with `create_report` (report_name: \"{}\", module: \"{}\", report_type: \"Script Report\", ref_doctype: \"{}\").",
+            report_name, dt.module, dt.name
+        ),
+        "2. Pick the columns from the fields below; Link fields become `Link` columns with `options` set to the target DocType.".to_string(),
+        "3. Add filters in the report's `.js` for the fields users narrow by (dates, Link fields, status).".to_string(),
+        format!(
+            "4. Implement `execute(filters)` in the report's `.py` returning `columns, data`; query `tab{}` with `frappe.qb` or `frappe.get_all` and join linked DocTypes from the link graph only when needed.",
+            dt.name
+        ),
+        format!(
+            "5. Respect permissions: the report inherits the roles of `{}`; avoid raw SQL that bypasses them.",
+            dt.name
+        ),
+        "6. Try it through `bench_execute` with the report's `execute` and sample filters.".to_string(),
+    ];
+
+    let context = grounding(
+        config,
+        anal,
+        dt,
+        &[
+            ("Script Report", Some("report")),
+            ("filters", Some("report")),
+        ],
+    );
+    Ok(prompt_result(
+        format!("Script Report over {}", dt.name),
+        plan,
+        context,
+    ))
+}
+
+/// Prompt: find out why a DocType's test fails.
+pub fn debug_doctype_test(
+    config: &Config,
+    anal: &AnalyzedData,
+    doctype: &str,
+    test: Option<&str>,
+    app: Option<&str>,
+) -> McpResult {
+    let dt = find_doctype(anal, doctype, app)?;
+    let target = match test {
+        Some(test) => format!("the test `{}` of", test),
+        None => "the failing test of".to_string(),
+    };
+
+    let plan = vec![
+        format!("Debug {} the DocType **{}**.", target, dt.name),
+        String::new(),
+        "## Plan".to_string(),
+        format!(
+            "1. Reproduce it with `run_tests` (doctype: \"{}\"{}) and read the traceback from the innermost app frame outwards.",
+            dt.name,
+            test.map(|t| format!(", test: \"{}\"", t)).unwrap_or_default()
+        ),
+        format!(
+            "2. Open the test `{}` and check its fixtures: every Link and Table target in the link graph must have a record the test can use.",
+            dt.test_file.as_deref().unwrap_or("(no test file found)")
+        ),
+        format!(
+            "3. Walk the controller `{}` (`validate`, `before_save`, `on_submit`) for the code path the assertion exercises.",
+            dt.backend_file
+        ),
+        "4. Check required fields below against the values the test inserts; a missing `reqd` value fails with `MandatoryError`.".to_string(),
+        "5. Fix the cause (code or fixture, not the assertion) and rerun the same test.".to_string(),
+    ];
+
+    let context = grounding(
+        config,
+        anal,
+        dt,
+        &[("test", Some("tutorial")), ("frappe.throw", None)],
+    );
+    Ok(prompt_result(
+        format!("Debug failing test of {}", dt.name),
+        plan,
+        context,
+    ))
+}
+
+/// Prompt: add a `@frappe.whitelist()` API method, optionally around a DocType.
+pub fn add_whitelisted_api(
+    config: &Config,
+    anal: &AnalyzedData,
+    method: &str,
+    doctype: Option<&str>,
+    app: Option<&str>,
+) -> McpResult {
+    let dt = match doctype {
+        Some(doctype) => Some(find_doctype(anal, doctype, app)?),
+        None => None,
+    };
+    let app_name = dt
+        .map(|dt| dt.app.clone())
+        .filter(|a| !a.is_empty())
+        .or_else(|| app.map(|a| a.to_string()))
+        .unwrap_or_else(|| config.app_relative_path.clone());
+    let module_path = match dt {
+        Some(dt) => format!(
+            "{}.{}.api",
+            app_name,
+            crate::stringutil::to_snakec_var(&dt.module)
+        ),
+        None => format!("{}.api", app_name),
+    };
+
+    let mut plan = vec![
+        format!(
+            "Add the whitelisted API method `{}`{}.",
+            method,
+            dt.map(|dt| format!(" working on the DocType **{}**", dt.name))
+                .unwrap_or_default()
+        ),
+        String::new(),
+        "## Plan".to_string(),
+        format!(
+            "1. Put it in `{}` (create the file if needed) and decorate it with `@frappe.whitelist()`; add `methods=[\"POST\"]` when it changes data, and `allow_guest=True` only if it must work without login.",
+            module_path.replace('.', "/") + ".py"
+        ),
+        "2. Treat every argument as untrusted input: cast types, validate values, and never format them into SQL.".to_string(),
+    ];
+    match dt {
+        Some(dt) => plan.push(format!(
+            "3. Check permissions with `frappe.has_permission(\"{}\", ptype)` or `doc.check_permission()` before reading or writing; use `frappe.get_doc`/`frappe.get_list` so user permissions apply.",
+            dt.name
+        )),
+        None => plan.push(
+            "3. Check permissions explicitly (`frappe.has_permission`, `frappe.only_for`) before doing any work.".to_string(),
+        ),
+    }
+    plan.push(format!(
+        "4. Return plain dicts/lists; the endpoint is `/api/method/{}.{}`.",
+        module_path, method
+    ));
+    plan.push(format!(
+        "5. Call it with `bench_execute` (`{}.{}`) and add a test that covers both the allowed and the denied case.",
+        module_path, method
+    ));
+
+    let mut context = Vec::new();
+    if let Some(dt) = dt {
+        context.extend(doctype_context(config, anal, dt));
+    }
+    context.extend(docs_context(&[
+        ("whitelist", Some("api")),
+        ("has_permission", None),
+    ]));
+    Ok(prompt_result(
+        format!("Whitelisted API `{}`", method),
+        plan,
+        context,
+    ))
+}
+
+fn find_doctype<'a>(
+    anal: &'a AnalyzedData,
+    doctype: &str,
+    app: Option<&str>,
+) -> Result<&'a DocType, McpError> {
+    anal.find_doctype(doctype, app).ok_or_else(|| {
+        McpError::invalid_params(
+            format!("DocType '{}' not found in the analyzed apps", doctype),
+            Some(json!({ "doctype": doctype, "app": app })),
+        )
+    })
+}
+
+/// DocType metadata, link graph and docs snippets for the prompt.
+fn grounding(
+    config: &Config,
+    anal: &AnalyzedData,
+    dt: &DocType,
+    queries: &[(&str, Option<&str>)],
+) -> Vec<String> {
+    let mut context = doctype_context(config, anal, dt);
+    context.extend(docs_context(queries));
+    context
+}
+
+fn doctype_context(config: &Config, anal: &AnalyzedData, dt: &DocType) -> Vec<String> {
+    let app = Some(dt.app.as_str()).filter(|a| !a.is_empty());
+    let mut context = Vec::new();
+    if let Some(text) =
+        functools::get_doctype(config, anal, &dt.name, false, app).and_then(tool_text)
+    {
+        context.push(format!("## DocType metadata\n\n{}", text));
+    }
+    if let Some(text) = tool_text(functools::analyze_links(
+        config,
+        anal,
+        Some(&dt.name),
+        None,
+        Some(1),
+        app.map(|a| a.to_string()),
+        None,
+    )) {
+        context.push(format!("## Link graph\n\n{}", text));
+    }
+    context
+}
+
+fn docs_context(queries: &[(&str, Option<&str>)]) -> Vec<String> {
+    let snippets: Vec<String> = queries
+        .iter()
+        .filter_map(|(query, category)| {
+            let result = functools::search_frappe_docs(
+                query,
+                category.map(|c| c.to_string()),
+                false,
+                DOC_SNIPPETS,
+                OutputFormat::Markdown,
+            );
+            tool_text(result).filter(|text| !text.contains("No documentation found"))
+        })
+        .collect();
+    if snippets.is_empty() {
+        return vec![];
+    }
+    vec![format!("## Frappe docs\n\n{}", snippets.join("\n\n"))]
+}
+
+/// Text of a successful tool call.
+fn tool_text(result: Result<CallToolResult, McpError>) -> Option<String> {
+    let result = result.ok()?;
+    if result.is_error == Some(true) {
+        return None;
+    }
+    let text: Vec<String> = result
+        .content
+        .iter()
+        .filter_map(|c| c.as_text().map(|t| t.text.clone()))
+        .collect();
+    Some(text.join("\n")).filter(|t| !t.trim().is_empty())
+}
+
+fn prompt_result(description: String, plan: Vec<String>, context: Vec<String>) -> GetPromptResult {
+    let mut text = plan.join("\n");
+    if !context.is_empty() {
+        text.push_str("\n\n# Context\n\n");
+        text.push_str(&context.join("\n\n"));
+    }
+    GetPromptResult {
+        description: Some(description),
+        messages: vec![PromptMessage::new_text(PromptMessageRole::User, text)],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyze::Module;
+
+    fn fixture() -> (Config, AnalyzedData) {
+        let bench = std::env::temp_dir().join(format!("frappe-mcp-prompts-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&bench);
+        let dt_dir = bench.join("apps/koperasi/koperasi/simpanan/doctype/anggota");
+        std::fs::create_dir_all(&dt_dir).unwrap();
+        std::fs::write(
+            dt_dir.join("anggota.json"),
+            r#"{"name": "Anggota", "default_view": "List", "fields": [
+                {"fieldname": "nama", "fieldtype": "Data", "label": "Nama", "reqd": 1}
+            ]}"#,
+        )
+        .unwrap();
+
+        let config = Config {
+            frappe_bench_dir: bench.display().to_string(),
+            app_relative_path: "koperasi".to_string(),
+            app_absolute_path: bench.join("apps/koperasi").display().to_string(),
+            ..Default::default()
+        };
+        let mut anal = AnalyzedData::default();
+        anal.doctypes.push(DocType {
+            name: "Anggota".to_string(),
+            backend_file: "koperasi/simpanan/doctype/anggota/anggota.py".to_string(),
+            frontend_file: None,
+            meta_file: Some("koperasi/simpanan/doctype/anggota/anggota.json".to_string()),
+            test_file: Some("koperasi/simpanan/doctype/anggota/test_anggota.py".to_string()),
+            module: "Simpanan".to_string(),
+            app: "koperasi".to_string(),
+        });
+        anal.modules.push(Module {
+            name: "Simpanan".to_string(),
+            location: "koperasi/simpanan".to_string(),
+            app: "koperasi".to_string(),
+        });
+        (config, anal)
+    }
+
+    fn prompt_text(result: &GetPromptResult) -> &str {
+        match &result.messages[0].content {
+            PromptMessageContent::Text { text } => text,
+            _ => panic!("expected a text message"),
+        }
+    }
+
+    #[test]
+    fn test_prompts_inline_doctype_context() {
+        let (config, anal) = fixture();
+
+        let result = add_doctype_field(
+            &config,
+            &anal,
+            "anggota",
+            "alamat",
+            "Small Text",
+            None,
+            None,
+        )
+        .unwrap();
+        let text = prompt_text(&result);
+        assert!(text.contains("`alamat` (Small Text)"));
+        assert!(text.contains("alamat: DF.SmallText"));
+        assert!(text.contains("## DocType metadata"));
+        assert!(text.contains("nama - \"Nama\" (Data) [Required]"));
+        assert!(text.contains("## Link graph"));
+
+        let result = add_whitelisted_api(&config, &anal, "daftar", Some("Anggota"), None).unwrap();
+        let text = prompt_text(&result);
+        assert!(text.contains("/api/method/koperasi.simpanan.api.daftar"));
+        assert!(text.contains("## Frappe docs"));
+
+        assert!(debug_doctype_test(&config, &anal, "Nasabah", None, None).is_err());
+    }
+}
@@ -0,0 +1,1298 @@
+// Copyright (C) 2025 Nuwaira
+// All Rights Reserved.
+//
+// NOTICE: All information contained herein is, and remains
+// the property of Nuwaira.
+// The intellectual and technical concepts contained
+// herein are proprietary to Nuwaira
+// and are protected by trade secret or copyright law.
+// Dissemination of this information or reproduction of this material
+// is strictly forbidden unless prior written permission is obtained
+// from Nuwaira.
+#![allow(dead_code)]
+
+//! Syntax tree based field reference analysis for Python sources.
+//!
+//! Variables are typed through their scopes (`frappe.get_doc(...)`, type hinted
+//! parameters, controller `self`, reassignments) and child table rows are
+//! inferred from the `Table` field options of the parent DocType.
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tree_sitter::{Node, Parser};
+use walkdir::WalkDir;
+
+use crate::refs_finder::Occurrence;
+
+/// Calls returning a document of the DocType given as first argument.
+const DOC_CONSTRUCTORS: [&str; 5] = [
+    "get_doc",
+    "new_doc",
+    "get_cached_doc",
+    "get_last_doc",
+    "get_single",
+];
+
+/// Document methods taking a fieldname as first argument.
+const FIELD_METHODS: [&str; 5] = ["get", "set", "append", "get_value", "db_set"];
+
+/// Attributes of `Document` that are not DocType fields.
+const DOCUMENT_ATTRS: [&str; 2] = ["flags", "meta"];
+
+thread_local! {
+    /// One parser per worker thread, reused for every file it scans.
+    static PARSER: RefCell<Parser> = {
+        let mut parser = Parser::new();
+        parser
+            .set_language(&tree_sitter_python::LANGUAGE.into())
+            .expect("tree-sitter-python grammar");
+        RefCell::new(parser)
+    };
+}
+
+/// Fieldtypes pointing at other documents.
+const LINK_FIELDTYPES: [&str; 4] = ["Link", "Table", "Table MultiSelect", "Dynamic Link"];
+
+/// Name, table and link fields of a single DocType JSON.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct IndexEntry {
+    pub name: String,
+    /// Table fieldname -> child DocType
+    pub tables: BTreeMap<String, String>,
+    /// Link, Table, Table MultiSelect and Dynamic Link fields, in field order
+    #[serde(default)]
+    pub links: Vec<LinkField>,
+}
+
+/// A field of a DocType pointing at other documents.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct LinkField {
+    pub fieldname: String,
+    pub fieldtype: String,
+    /// Target DocType; for a Dynamic Link, the field holding the target's name
+    pub options: String,
+    /// For a Dynamic Link, the DocTypes offered by the Select field named in
+    /// `options`; empty when that field isn't a Select
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub targets: Vec<String>,
+}
+
+/// Table fields, link fields and controller class names of every DocType found on disk.
+///
+/// Only the entries (keyed by their JSON path) are serialized; the lookup
+/// maps are rebuilt from them.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(
+    from = "BTreeMap<String, IndexEntry>",
+    into = "BTreeMap<String, IndexEntry>"
+)]
+pub struct DocTypeIndex {
+    /// DocType JSON path -> entry
+    entries: BTreeMap<String, IndexEntry>,
+    /// DocType -> table fieldname -> child DocType
+    tables: HashMap<String, HashMap<String, String>>,
+    /// Controller class name (e.g. `SalesInvoice`) -> DocType
+    classes: HashMap<String, String>,
+    /// Target DocType -> (JSON path, fieldname) of the fields linking to it
+    referrers: HashMap<String, BTreeSet<(String, String)>>,
+}
+
+impl From<BTreeMap<String, IndexEntry>> for DocTypeIndex {
+    fn from(entries: BTreeMap<String, IndexEntry>) -> Self {
+        let mut index = DocTypeIndex::default();
+        for (key, entry) in entries {
+            index.insert(key, entry);
+        }
+        index
+    }
+}
+
+impl From<DocTypeIndex> for BTreeMap<String, IndexEntry> {
+    fn from(index: DocTypeIndex) -> Self {
+        index.entries
+    }
+}
+
+impl DocTypeIndex {
+    /// Index every `doctype/<dt>/<dt>.json` below the given roots.
+    pub fn scan<P: AsRef<Path>>(roots: &[P]) -> Self {
+        let mut index = DocTypeIndex::default();
+        index.add_files(&doctype_json_files(roots));
+        index
+    }
+
+    /// (Re)index a single DocType JSON file, dropping it when it is gone.
+    pub fn add_file(&mut self, path: &Path) {
+        match read_index_entry(path) {
+            Some(entry) => self.insert(path.to_string_lossy().to_string(), entry),
+            None => self.remove_file(path),
+        }
+    }
+
+    /// `add_file` for many files, parsed on the worker pool.
+    pub fn add_files<P: AsRef<Path> + Sync>(&mut self, paths: &[P]) {
+        let entries: Vec<Option<IndexEntry>> = paths
+            .par_iter()
+            .map(|path| read_index_entry(path.as_ref()))
+            .collect();
+        for (path, entry) in paths.iter().zip(entries) {
+            let path = path.as_ref();
+            match entry {
+                Some(entry) => self.insert(path.to_string_lossy().to_string(), entry),
+                None => self.remove_file(path),
+            }
+        }
+    }
+
+    /// Forget the DocType JSON at `path`.
+    pub fn remove_file(&mut self, path: &Path) {
+        if self
+            .entries
+            .remove(path.to_string_lossy().as_ref())
+            .is_some()
+        {
+            *self = DocTypeIndex::from(std::mem::take(&mut self.entries));
+        }
+    }
+
+    /// Index a DocType JSON that doesn't come from a file (keyed by its name).
+    pub fn add_json(&mut self, json: &Value) {
+        if let Some(entry) = index_entry(json) {
+            self.insert(entry.name.clone(), entry);
+        }
+    }
+
+    fn insert(&mut self, key: String, entry: IndexEntry) {
+        if let Some(old) = self.entries.get(&key) {
+            for link in &old.links {
+                if let Some(referrers) = self.referrers.get_mut(&link.options) {
+                    referrers.remove(&(key.clone(), link.fieldname.clone()));
+                }
+            }
+            if old.name != entry.name {
+                // The JSON now defines another DocType; the old one stays only
+                // if a different file still defines it
+                let name = &old.name;
+                match self
+                    .entries
+                    .iter()
+                    .find(|(k, e)| **k != key && &e.name == name)
+                {
+                    Some((_, other)) => {
+                        self.tables
+                            .insert(name.clone(), other.tables.clone().into_iter().collect());
+                    }
+                    None => {
+                        self.tables.remove(name);
+                        let class = controller_class_name(name);
+                        if self.classes.get(&class) == Some(name) {
+                            self.classes.remove(&class);
+                        }
+                    }
+                }
+            }
+        }
+        for link in entry.links.iter().filter(|l| l.fieldtype != "Dynamic Link") {
+            self.referrers
+                .entry(link.options.clone())
+                .or_default()
+                .insert((key.clone(), link.fieldname.clone()));
+        }
+        self.classes
+            .insert(controller_class_name(&entry.name), entry.name.clone());
+        self.tables.insert(
+            entry.name.clone(),
+            entry.tables.clone().into_iter().collect(),
+        );
+        self.entries.insert(key, entry);
+    }
+
+    /// JSON files currently indexed.
+    pub fn files(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(|k| k.as_str())
+    }
+
+    /// DocType name read from the JSON at `path`, if it is indexed.
+    pub fn doctype_name(&self, path: &Path) -> Option<&str> {
+        self.entries
+            .get(path.to_string_lossy().as_ref())
+            .map(|e| e.name.as_str())
+    }
+
+    /// Whether both indexes resolve child tables and controller classes the
+    /// same way, i.e. references found with one are still valid with the other.
+    ///
+    /// Link fields don't take part in resolving references.
+    pub fn resolves_like(&self, other: &DocTypeIndex) -> bool {
+        self.resolving() == other.resolving()
+    }
+
+    /// Names and table fields of the entries, sorted.
+    fn resolving(&self) -> Vec<(&str, &BTreeMap<String, String>)> {
+        let mut entries: Vec<_> = self
+            .entries
+            .values()
+            .map(|e| (e.name.as_str(), &e.tables))
+            .collect();
+        entries.sort_unstable();
+        entries
+    }
+
+    /// Child DocType of the table field `field` of `doctype`, if any.
+    pub fn child_doctype(&self, doctype: &str, field: &str) -> Option<&str> {
+        self.tables
+            .get(doctype)
+            .and_then(|t| t.get(field))
+            .map(|s| s.as_str())
+    }
+
+    /// DocTypes having a table field whose rows are `child`.
+    pub fn parent_doctypes(&self, child: &str) -> Vec<&str> {
+        let mut parents: Vec<&str> = self
+            .tables
+            .iter()
+            .filter(|(_, tables)| tables.values().any(|c| c == child))
+            .map(|(dt, _)| dt.as_str())
+            .collect();
+        parents.sort_unstable();
+        parents
+    }
+
+    /// Link, Table and Table MultiSelect fields pointing at `doctype`, as
+    /// (JSON path, referencing DocType, field), in path order.
+    pub fn referencing(&self, doctype: &str) -> Vec<(&str, &str, &LinkField)> {
+        let Some(referrers) = self.referrers.get(doctype) else {
+            return Vec::new();
+        };
+        referrers
+            .iter()
+            .filter_map(|(key, fieldname)| {
+                let entry = self.entries.get(key)?;
+                let field = entry.links.iter().find(|l| &l.fieldname == fieldname)?;
+                Some((key.as_str(), entry.name.as_str(), field))
+            })
+            .collect()
+    }
+
+    /// Dynamic Link fields whose Select DocType field offers `doctype`, as
+    /// (JSON path, DocType, field), in path order.
+    pub fn dynamic_links(&self, doctype: &str) -> Vec<(&str, &str, &LinkField)> {
+        self.entries
+            .iter()
+            .flat_map(|(key, entry)| {
+                entry
+                    .links
+                    .iter()
+                    .filter(|l| l.fieldtype == "Dynamic Link")
+                    .filter(move |l| l.targets.iter().any(|t| t == doctype))
+                    .map(move |l| (key.as_str(), entry.name.as_str(), l))
+            })
+            .collect()
+    }
+
+    /// Whether a DocType named `doctype` is indexed.
+    pub fn contains(&self, doctype: &str) -> bool {
+        self.tables.contains_key(doctype)
+    }
+
+    /// DocType whose controller class is named `class_name`.
+    pub fn doctype_for_class(&self, class_name: &str) -> Option<&str> {
+        self.classes.get(class_name).map(|s| s.as_str())
+    }
+}
+
+/// Every `doctype/<dt>/<dt>.json` below the given roots, in path order.
+pub fn doctype_json_files<P: AsRef<Path>>(roots: &[P]) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for root in roots {
+        for entry in WalkDir::new(root.as_ref())
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|e| {
+                let name = e.file_name().to_string_lossy();
+                !(name.starts_with('.') || name == "node_modules" || name == "__pycache__")
+            })
+            .filter_map(|e| e.ok())
+        {
+            if is_doctype_json(entry.path()) {
+                files.push(entry.into_path());
+            }
+        }
+    }
+    files
+}
+
+fn read_index_entry(path: &Path) -> Option<IndexEntry> {
+    let content = fs::read_to_string(path).ok()?;
+    index_entry(&serde_json::from_str::<Value>(&content).ok()?)
+}
+
+fn index_entry(json: &Value) -> Option<IndexEntry> {
+    let name = json.get("name").and_then(|v| v.as_str())?;
+
+    let mut tables = BTreeMap::new();
+    let mut links = Vec::new();
+    if let Some(fields) = json.get("fields").and_then(|v| v.as_array()) {
+        // Select fields by name, resolving the DocTypes a Dynamic Link may point at
+        let selects: HashMap<&str, &str> = fields
+            .iter()
+            .filter(|f| f.get("fieldtype").and_then(|v| v.as_str()) == Some("Select"))
+            .filter_map(|f| {
+                let fieldname = f.get("fieldname").and_then(|v| v.as_str())?;
+                Some((fieldname, f.get("options").and_then(|v| v.as_str())?))
+            })
+            .collect();
+        for field in fields {
+            let fieldtype = field
+                .get("fieldtype")
+                .and_then(|v| v.as_str())
+                .unwrap_or("");
+            if !LINK_FIELDTYPES.contains(&fieldtype) {
+                continue;
+            }
+            let fieldname = field.get("fieldname").and_then(|v| v.as_str());
+            let options = field.get("options").and_then(|v| v.as_str());
+            let (Some(fieldname), Some(options)) = (fieldname, options) else {
+                continue;
+            };
+            let options = options.trim();
+            if options.is_empty() {
+                continue;
+            }
+            if matches!(fieldtype, "Table" | "Table MultiSelect") {
+                tables.insert(fieldname.to_string(), options.to_string());
+            }
+            let targets = match selects.get(options) {
+                Some(select) if fieldtype == "Dynamic Link" => select
+                    .lines()
+                    .map(str::trim)
+                    .filter(|t| !t.is_empty())
+                    .map(str::to_string)
+                    .collect(),
+                _ => Vec::new(),
+            };
+            links.push(LinkField {
+                fieldname: fieldname.to_string(),
+                fieldtype: fieldtype.to_string(),
+                options: options.to_string(),
+                targets,
+            });
+        }
+    }
+
+    Some(IndexEntry {
+        name: name.to_string(),
+        tables,
+        links,
+    })
+}
+
+/// A field of `doctype` referenced somewhere in the source.
+#[derive(Debug, Clone)]
+pub struct FieldRef {
+    pub doctype: String,
+    pub field: String,
+    pub occurrence: Occurrence,
+}
+
+/// Static type of an expression, as far as we can tell.
+#[derive(Debug, Clone, PartialEq)]
+enum Ty {
+    /// A document (or child table row) of the DocType
+    Doc(String),
+    /// The rows of a child table of the DocType
+    Rows(String),
+}
+
+/// Find every DocType field reference in `source`.
+///
+/// `primary_doctype` is the DocType whose controller the file is: its
+/// `Document` subclasses bind `self` and a bare `doc` defaults to it.
+pub fn scan_source(
+    file: &str,
+    source: &str,
+    primary_doctype: Option<&str>,
+    index: &DocTypeIndex,
+) -> Vec<FieldRef> {
+    let tree = match PARSER.with(|parser| parser.borrow_mut().parse(source, None)) {
+        Some(tree) => tree,
+        None => return Vec::new(),
+    };
+
+    let mut module_scope = HashMap::new();
+    if let Some(dt) = primary_doctype {
+        module_scope.insert("doc".to_string(), Some(Ty::Doc(dt.to_string())));
+    }
+
+    let mut scanner = Scanner {
+        src: source.as_bytes(),
+        file,
+        primary_doctype,
+        index,
+        scopes: vec![module_scope],
+        class_stack: Vec::new(),
+        refs: Vec::new(),
+    };
+    scanner.visit(tree.root_node());
+    scanner.refs
+}
+
+struct Scanner<'a> {
+    src: &'a [u8],
+    file: &'a str,
+    primary_doctype: Option<&'a str>,
+    index: &'a DocTypeIndex,
+    /// Innermost scope last; `None` shadows an outer binding with an unknown type.
+    scopes: Vec<HashMap<String, Option<Ty>>>,
+    /// DocType of the class whose methods are being visited, if any.
+    class_stack: Vec<Option<String>>,
+    refs: Vec<FieldRef>,
+}
+
+impl<'a> Scanner<'a> {
+    fn text(&self, node: Node) -> &'a str {
+        node.utf8_text(self.src).unwrap_or("")
+    }
+
+    fn lookup(&self, var: &str) -> Option<Ty> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(var))
+            .cloned()
+            .flatten()
+    }
+
+    fn bind(&mut self, var: &str, ty: Option<Ty>) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(var.to_string(), ty);
+        }
+    }
+
+    fn record(&mut self, doctype: &str, field: &str, node: Node, var: &str, kind: &str) {
+        if field.is_empty() {
+            return;
+        }
+        self.refs.push(FieldRef {
+            doctype: doctype.to_string(),
+            field: field.to_string(),
+            occurrence: Occurrence {
+                file: self.file.to_string(),
+                line: node.start_position().row + 1,
+                var: var.to_string(),
+                kind: kind.to_string(),
+            },
+        });
+    }
+
+    fn visit_children(&mut self, node: Node) {
+        let mut cursor = node.walk();
+        let children: Vec<Node> = node.named_children(&mut cursor).collect();
+        for child in children {
+            self.visit(child);
+        }
+    }
+
+    fn visit(&mut self, node: Node) {
+        match node.kind() {
+            "function_definition" => self.visit_function(node),
+            "class_definition" => self.visit_class(node),
+            "assignment" => self.visit_assignment(node),
+            "for_statement" => {
+                if let Some(right) = node.child_by_field_name("right") {
+                    self.visit(right);
+                    let ty = self.iter_item_type(right);
+                    if let Some(left) = node.child_by_field_name("left") {
+                        self.bind_target(left, right, ty);
+                    }
+                }
+                for field in ["body", "alternative"] {
+                    if let Some(child) = node.child_by_field_name(field) {
+                        self.visit(child);
+                    }
+                }
+            }
+            "list_comprehension"
+            | "set_comprehension"
+            | "dictionary_comprehension"
+            | "generator_expression" => self.visit_comprehension(node),
+            "named_expression" => {
+                if let Some(value) = node.child_by_field_name("value") {
+                    self.visit(value);
+                    let ty = self.expr_type(value);
+                    if let Some(name) = node.child_by_field_name("name") {
+                        let name = self.text(name);
+                        self.bind(name, ty);
+                    }
+                }
+            }
+            "attribute" => self.visit_attribute(node),
+            "subscript" => self.visit_subscript(node),
+            "call" => self.visit_call(node),
+            _ => self.visit_children(node),
+        }
+    }
+
+    fn visit_function(&mut self, node: Node) {
+        let class_dt = self
+            .class_stack
+            .last()
+            .cloned()
+            .flatten()
+            .filter(|_| !self.is_class_or_static_method(node));
+        let mut scope = HashMap::new();
+
+        if let Some(params) = node.child_by_field_name("parameters") {
+            let mut cursor = params.walk();
+            for (i, param) in params.named_children(&mut cursor).enumerate() {
+                let (name, annotation, default) = match param.kind() {
+                    "identifier" => (Some(param), None, None),
+                    "typed_parameter" => (
+                        param.named_child(0).filter(|n| n.kind() == "identifier"),
+                        param.child_by_field_name("type"),
+                        None,
+                    ),
+                    "default_parameter" => (
+                        param.child_by_field_name("name"),
+                        None,
+                        param.child_by_field_name("value"),
+                    ),
+                    "typed_default_parameter" => (
+                        param.child_by_field_name("name"),
+                        param.child_by_field_name("type"),
+                        param.child_by_field_name("value"),
+                    ),
+                    _ => (None, None, None),
+                };
+                if let Some(default) = default {
+                    self.visit(default);
+                }
+                let Some(name) = name else {
+                    continue;
+                };
+
+                let mut ty = annotation
+                    .and_then(|a| self.annotation_doctype(a))
+                    .map(Ty::Doc);
+                if ty.is_none() && i == 0 {
+                    // `self` of a DocType controller method
+                    ty = class_dt.clone().map(Ty::Doc);
+                }
+                scope.insert(self.text(name).to_string(), ty);
+            }
+        }
+
+        self.scopes.push(scope);
+        // Nested functions are not methods of the enclosing class
+        self.class_stack.push(None);
+        if let Some(body) = node.child_by_field_name("body") {
+            self.visit(body);
+        }
+        self.class_stack.pop();
+        self.scopes.pop();
+    }
+
+    /// Whether the function is decorated with `@classmethod` or `@staticmethod`,
+    /// so that its first parameter isn't the document.
+    fn is_class_or_static_method(&self, node: Node) -> bool {
+        let Some(parent) = node.parent().filter(|p| p.kind() == "decorated_definition") else {
+            return false;
+        };
+        let mut cursor = parent.walk();
+        for decorator in parent.named_children(&mut cursor) {
+            let expr = decorator
+                .named_child(0)
+                .filter(|_| decorator.kind() == "decorator");
+            if expr.is_some_and(|e| matches!(self.text(e), "classmethod" | "staticmethod")) {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn visit_class(&mut self, node: Node) {
+        let name = node
+            .child_by_field_name("name")
+            .map(|n| self.text(n))
+            .unwrap_or("");
+        let bases = node
+            .child_by_field_name("superclasses")
+            .map(|n| self.text(n))
+            .unwrap_or("");
+
+        let doctype = self
+            .index
+            .doctype_for_class(name)
+            .map(|s| s.to_string())
+            .or_else(|| {
+                self.primary_doctype
+                    .filter(|_| bases.contains("Document"))
+                    .map(|s| s.to_string())
+            });
+
+        self.class_stack.push(doctype);
+        self.scopes.push(HashMap::new());
+        if let Some(body) = node.child_by_field_name("body") {
+            self.visit(body);
+        }
+        self.scopes.pop();
+        self.class_stack.pop();
+    }
+
+    fn visit_assignment(&mut self, node: Node) {
+        let right = node.child_by_field_name("right");
+        if let Some(right) = right {
+            self.visit(right);
+        }
+        let mut ty = right.and_then(|r| self.expr_type(r));
+        if ty.is_none() {
+            ty = node
+                .child_by_field_name("type")
+                .and_then(|t| self.annotation_doctype(t))
+                .map(Ty::Doc);
+        }
+
+        let Some(left) = node.child_by_field_name("left") else {
+            return;
+        };
+        match left.kind() {
+            "identifier" => {
+                let name = self.text(left);
+                self.bind(name, ty);
+            }
+            // `doc.status = ...`, `doc.items[0].qty = ...`
+            "attribute" | "subscript" => self.visit(left),
+            _ => self.unbind_names(left),
+        }
+    }
+
+    fn visit_comprehension(&mut self, node: Node) {
+        self.scopes.push(HashMap::new());
+
+        let mut cursor = node.walk();
+        let children: Vec<Node> = node.named_children(&mut cursor).collect();
+        // Bindings first, in order, so the body sees the loop variables
+        for child in children.iter().filter(|c| c.kind() == "for_in_clause") {
+            if let Some(right) = child.child_by_field_name("right") {
+                self.visit(right);
+                let ty = self.iter_item_type(right);
+                if let Some(left) = child.child_by_field_name("left") {
+                    self.bind_target(left, right, ty);
+                }
+            }
+        }
+        for child in children.iter().filter(|c| c.kind() != "for_in_clause") {
+            self.visit(*child);
+        }
+
+        self.scopes.pop();
+    }
+
+    fn visit_attribute(&mut self, node: Node) {
+        if let (Some(object), Some(attr)) = (
+            node.child_by_field_name("object"),
+            node.child_by_field_name("attribute"),
+        ) {
+            if let Some(Ty::Doc(dt)) = self.expr_type(object) {
+                let field = self.text(attr);
+                if !DOCUMENT_ATTRS.contains(&field) {
+                    let var = self.var_label(object);
+                    self.record(&dt, field, attr, &var, "attr");
+                }
+            }
+            self.visit(object);
+        }
+    }
+
+    fn visit_subscript(&mut self, node: Node) {
+        if let Some(value) = node.child_by_field_name("value") {
+            if let Some(Ty::Doc(dt)) = self.expr_type(value) {
+                if let Some(field) = node
+                    .child_by_field_name("subscript")
+                    .and_then(|s| self.string_value(s))
+                {
+                    let var = self.var_label(value);
+                    self.record(&dt, &field, node, &var, "subscript");
+                }
+            }
+        }
+        self.visit_children(node);
+    }
+
+    fn visit_call(&mut self, node: Node) {
+        let function = node.child_by_field_name("function");
+        let arguments = node.child_by_field_name("arguments");
+        let args = arguments.map(|a| self.call_args(a)).unwrap_or_default();
+
+        if let Some(function) = function {
+            let fn_text = self.text(function);
+            match fn_text {
+                "frappe.db.get_value" | "frappe.db.get_single_value" | "frappe.db.set_value" => {
+                    self.record_db_call(node, fn_text, &args);
+                }
+                _ => {}
+            }
+
+            if function.kind() == "attribute" {
+                let object = function.child_by_field_name("object");
+                let method = function
+                    .child_by_field_name("attribute")
+                    .map(|n| self.text(n))
+                    .unwrap_or("");
+
+                if let Some(object) = object {
+                    if FIELD_METHODS.contains(&method) {
+                        if let Some(Ty::Doc(dt)) = self.expr_type(object) {
+                            if let Some(field) = args.first().and_then(|a| self.string_value(*a)) {
+                                let (var, kind) = if object.kind() == "call" {
+                                    ("<inline>".to_string(), "inline")
+                                } else {
+                                    (self.var_label(object), method)
+                                };
+                                self.record(&dt, &field, node, &var, kind);
+                            }
+                        }
+                    }
+                    // The method name itself is not a field
+                    self.visit(object);
+                }
+            } else {
+                self.visit(function);
+            }
+        }
+
+        if let Some(arguments) = arguments {
+            self.visit(arguments);
+        }
+    }
+
+    /// `frappe.db.get_value("DT", name, "field" | ["f1", "f2"])` and friends.
+    fn record_db_call(&mut self, node: Node, fn_text: &str, args: &[Node]) {
+        let Some(dt) = args.first().and_then(|a| self.string_value(*a)) else {
+            return;
+        };
+        let (field_arg, kind) = match fn_text {
+            "frappe.db.get_single_value" => (args.get(1), "db_get_value"),
+            "frappe.db.set_value" => (args.get(2), "db_set_value"),
+            _ => (args.get(2), "db_get_value"),
+        };
+        let Some(field_arg) = field_arg else {
+            return;
+        };
+
+        let mut fields = Vec::new();
+        match field_arg.kind() {
+            "string" => fields.extend(self.string_value(*field_arg)),
+            "list" | "tuple" => {
+                let mut cursor = field_arg.walk();
+                for item in field_arg.named_children(&mut cursor) {
+                    fields.extend(self.string_value(item));
+                }
+            }
+            "dictionary" => {
+                let mut cursor = field_arg.walk();
+                for pair in field_arg.named_children(&mut cursor) {
+                    if let Some(key) = pair.child_by_field_name("key") {
+                        fields.extend(self.string_value(key));
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        for field in fields {
+            // Expressions like "sum(qty)" or "*" are not plain fieldnames
+            if field.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                self.record(&dt, &field, node, "frappe.db", kind);
+            }
+        }
+    }
+
+    /// Positional arguments of a call (keyword arguments are skipped).
+    fn call_args<'t>(&self, arguments: Node<'t>) -> Vec<Node<'t>> {
+        let mut cursor = arguments.walk();
+        arguments
+            .named_children(&mut cursor)
+            .filter(|n| n.kind() != "keyword_argument" && n.kind() != "comment")
+            .collect()
+    }
+
+    /// Type of an expression, without side effects.
+    fn expr_type(&self, node: Node) -> Option<Ty> {
+        match node.kind() {
+            "identifier" => self.lookup(self.text(node)),
+            "parenthesized_expression" => node.named_child(0).and_then(|n| self.expr_type(n)),
+            "call" => self.call_type(node),
+            "attribute" => {
+                let object = node.child_by_field_name("object")?;
+                let attr = self.text(node.child_by_field_name("attribute")?);
+                match self.expr_type(object)? {
+                    Ty::Doc(dt) => self
+                        .index
+                        .child_doctype(&dt, attr)
+                        .map(|c| Ty::Rows(c.into())),
+                    Ty::Rows(_) => None,
+                }
+            }
+            "subscript" => {
+                let value = node.child_by_field_name("value")?;
+                match self.expr_type(value)? {
+                    Ty::Rows(child) => Some(Ty::Doc(child)),
+                    Ty::Doc(dt) => {
+                        let field = self.string_value(node.child_by_field_name("subscript")?)?;
+                        self.index
+                            .child_doctype(&dt, &field)
+                            .map(|c| Ty::Rows(c.into()))
+                    }
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn call_type(&self, node: Node) -> Option<Ty> {
+        let function = node.child_by_field_name("function")?;
+        let args = node
+            .child_by_field_name("arguments")
+            .map(|a| self.call_args(a))
+            .unwrap_or_default();
+        let fn_text = self.text(function);
+
+        let ctor = fn_text.strip_prefix("frappe.").unwrap_or(fn_text);
+        if DOC_CONSTRUCTORS.contains(&ctor) {
+            let first = args.first()?;
+            return self.doctype_arg(*first).map(Ty::Doc);
+        }
+        if fn_text == "frappe.copy_doc" {
+            return args.first().and_then(|a| self.expr_type(*a));
+        }
+
+        if function.kind() != "attribute" {
+            return None;
+        }
+        let object = function.child_by_field_name("object")?;
+        let method = self.text(function.child_by_field_name("attribute")?);
+        let Ty::Doc(dt) = self.expr_type(object)? else {
+            return None;
+        };
+        match method {
+            "get_doc_before_save" | "reload" => Some(Ty::Doc(dt)),
+            "append" | "get" => {
+                let field = self.string_value(*args.first()?)?;
+                let child = self.index.child_doctype(&dt, &field)?.to_string();
+                if method == "append" {
+                    Some(Ty::Doc(child))
+                } else {
+                    Some(Ty::Rows(child))
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Type of the items produced when iterating over `node`.
+    fn iter_item_type(&self, node: Node) -> Option<Ty> {
+        match self.expr_type(node)? {
+            Ty::Rows(child) => Some(Ty::Doc(child)),
+            Ty::Doc(_) => None,
+        }
+    }
+
+    /// Bind the loop target `left` of `for left in right`.
+    fn bind_target(&mut self, left: Node, right: Node, ty: Option<Ty>) {
+        if left.kind() == "identifier" {
+            let name = self.text(left);
+            self.bind(name, ty);
+            return;
+        }
+
+        // `for i, row in enumerate(doc.items)`
+        if matches!(left.kind(), "pattern_list" | "tuple_pattern") && right.kind() == "call" {
+            let is_enumerate = right
+                .child_by_field_name("function")
+                .is_some_and(|f| self.text(f) == "enumerate");
+            let second = left.named_child(1).filter(|n| n.kind() == "identifier");
+            if let (true, Some(second)) = (is_enumerate, second) {
+                let inner = right
+                    .child_by_field_name("arguments")
+                    .and_then(|a| self.call_args(a).first().copied());
+                let item_ty = inner.and_then(|n| self.iter_item_type(n));
+                self.unbind_names(left);
+                let name = self.text(second);
+                self.bind(name, item_ty);
+                return;
+            }
+        }
+
+        self.unbind_names(left);
+    }
+
+    /// Forget the types of every identifier assigned in `node`.
+    fn unbind_names(&mut self, node: Node) {
+        if node.kind() == "identifier" {
+            let name = self.text(node);
+            self.bind(name, None);
+            return;
+        }
+        if matches!(
+            node.kind(),
+            "pattern_list" | "tuple_pattern" | "list_pattern" | "list_splat_pattern"
+        ) {
+            let mut cursor = node.walk();
+            let children: Vec<Node> = node.named_children(&mut cursor).collect();
+            for child in children {
+                self.unbind_names(child);
+            }
+        }
+    }
+
+    /// DocType named by a `get_doc` style first argument: `"DT"` or `{"doctype": "DT", ...}`.
+    fn doctype_arg(&self, node: Node) -> Option<String> {
+        match node.kind() {
+            "string" => self.string_value(node),
+            "dictionary" => {
+                let mut cursor = node.walk();
+                let pairs: Vec<Node> = node.named_children(&mut cursor).collect();
+                pairs.into_iter().find_map(|pair| {
+                    let key = self.string_value(pair.child_by_field_name("key")?)?;
+                    if key == "doctype" {
+                        self.string_value(pair.child_by_field_name("value")?)
+                    } else {
+                        None
+                    }
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// DocType referenced by a type annotation like `SalesInvoice`,
+    /// `"SalesInvoice"` or `Optional[SalesInvoice]`.
+    fn annotation_doctype(&self, node: Node) -> Option<String> {
+        self.text(node)
+            .split(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+            .filter_map(|token| token.rsplit('.').next())
+            .find_map(|name| self.index.doctype_for_class(name))
+            .map(|s| s.to_string())
+    }
+
+    /// Value of a plain (non f-) string literal.
+    fn string_value(&self, node: Node) -> Option<String> {
+        if node.kind() != "string" {
+            return None;
+        }
+        let text = self.text(node);
+        let prefix_len = text.find(['"', '\'']).unwrap_or(0);
+        if text[..prefix_len].to_ascii_lowercase().contains('f') {
+            return None;
+        }
+        let body = &text[prefix_len..];
+        for quote in ["\"\"\"", "'''", "\"", "'"] {
+            if let Some(inner) = body.strip_prefix(quote).and_then(|b| b.strip_suffix(quote)) {
+                return Some(inner.to_string());
+            }
+        }
+        None
+    }
+
+    /// How the variable holding a document is shown in occurrences.
+    fn var_label(&self, object: Node) -> String {
+        if object.kind() == "call" {
+            "<inline>".to_string()
+        } else {
+            self.text(object).to_string()
+        }
+    }
+}
+
+fn is_doctype_json(path: &Path) -> bool {
+    if path.extension().and_then(|e| e.to_str()) != Some("json") {
+        return false;
+    }
+    let parts: Vec<_> = path.iter().collect();
+    let n = parts.len();
+    n >= 3 && parts[n - 3] == "doctype" && path.file_stem().is_some_and(|stem| stem == parts[n - 2])
+}
+
+/// Frappe's controller class naming: "Sales Invoice" -> `SalesInvoice`.
+fn controller_class_name(doctype: &str) -> String {
+    doctype.replace([' ', '-'], "")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index() -> DocTypeIndex {
+        let mut index = DocTypeIndex::default();
+        index.add_json(&serde_json::json!({
+            "name": "Sales Invoice",
+            "fields": [
+                {"fieldname": "customer", "fieldtype": "Link", "options": "Customer"},
+                {"fieldname": "items", "fieldtype": "Table", "options": "Sales Invoice Item"},
+            ]
+        }));
+        index.add_json(&serde_json::json!({
+            "name": "Sales Invoice Item",
+            "istable": 1,
+            "fields": [{"fieldname": "qty", "fieldtype": "Float"}]
+        }));
+        index
+    }
+
+    fn refs(source: &str, primary: Option<&str>) -> Vec<(String, String, usize, String)> {
+        scan_source("test.py", source, primary, &index())
+            .into_iter()
+            .map(|r| (r.doctype, r.field, r.occurrence.line, r.occurrence.kind))
+            .collect()
+    }
+
+    fn has(refs: &[(String, String, usize, String)], dt: &str, field: &str, line: usize) -> bool {
+        refs.iter()
+            .any(|(d, f, l, _)| d == dt && f == field && *l == line)
+    }
+
+    #[test]
+    fn test_index_roundtrip_and_removal() {
+        let mut index = DocTypeIndex::default();
+        index.insert(
+            "sales_invoice.json".to_string(),
+            index_entry(&serde_json::json!({
+                "name": "Sales Invoice",
+                "fields": [{"fieldname": "items", "fieldtype": "Table", "options": "Sales Invoice Item"}]
+            }))
+            .unwrap(),
+        );
+
+        let restored: DocTypeIndex =
+            serde_json::from_str(&serde_json::to_string(&index).unwrap()).unwrap();
+        assert!(restored.resolves_like(&index));
+        assert_eq!(
+            restored.child_doctype("Sales Invoice", "items"),
+            Some("Sales Invoice Item")
+        );
+        assert_eq!(
+            restored.doctype_for_class("SalesInvoice"),
+            Some("Sales Invoice")
+        );
+
+        let mut removed = restored.clone();
+        removed.remove_file(Path::new("sales_invoice.json"));
+        assert!(!removed.resolves_like(&index));
+        assert_eq!(removed.child_doctype("Sales Invoice", "items"), None);
+    }
+
+    #[test]
+    fn test_index_rename() {
+        let entry = |name: &str, child: &str| {
+            index_entry(&serde_json::json!({
+                "name": name,
+                "fields": [{"fieldname": "items", "fieldtype": "Table", "options": child}]
+            }))
+            .unwrap()
+        };
+        let mut index = DocTypeIndex::default();
+        index.insert(
+            "sales_invoice.json".to_string(),
+            entry("Sales Invoice", "Sales Invoice Item"),
+        );
+        index.insert(
+            "sales_invoice.json".to_string(),
+            entry("Sales Bill", "Sales Bill Item"),
+        );
+
+        assert!(!index.contains("Sales Invoice"));
+        assert_eq!(index.child_doctype("Sales Invoice", "items"), None);
+        assert_eq!(index.doctype_for_class("SalesInvoice"), None);
+        assert!(index.parent_doctypes("Sales Invoice Item").is_empty());
+        assert_eq!(index.doctype_for_class("SalesBill"), Some("Sales Bill"));
+        assert_eq!(index.parent_doctypes("Sales Bill Item"), vec!["Sales Bill"]);
+
+        let mut rebuilt = DocTypeIndex::default();
+        rebuilt.insert(
+            "sales_invoice.json".to_string(),
+            entry("Sales Bill", "Sales Bill Item"),
+        );
+        assert!(index.resolves_like(&rebuilt));
+        assert_eq!(
+            index.parent_doctypes("Sales Bill Item"),
+            rebuilt.parent_doctypes("Sales Bill Item")
+        );
+
+        // A DocType still defined by another file keeps that file's tables
+        index.insert(
+            "other.json".to_string(),
+            entry("Sales Order", "Sales Order Item"),
+        );
+        index.insert("copy.json".to_string(), entry("Sales Order", "Order Line"));
+        index.insert(
+            "other.json".to_string(),
+            entry("Purchase Order", "Purchase Order Item"),
+        );
+        assert_eq!(
+            index.child_doctype("Sales Order", "items"),
+            Some("Order Line")
+        );
+        assert_eq!(index.doctype_for_class("SalesOrder"), Some("Sales Order"));
+    }
+
+    #[test]
+    fn test_link_index() {
+        let entry = |fields: serde_json::Value| {
+            index_entry(&serde_json::json!({"name": "Sales Invoice", "fields": fields})).unwrap()
+        };
+        let mut index = DocTypeIndex::default();
+        index.insert(
+            "sales_invoice.json".to_string(),
+            entry(serde_json::json!([
+                {"fieldname": "customer", "fieldtype": "Link", "options": "Customer"},
+                {"fieldname": "items", "fieldtype": "Table", "options": "Sales Invoice Item"},
+                {"fieldname": "party_type", "fieldtype": "Select", "options": "\nCustomer\nSupplier"},
+                {"fieldname": "party", "fieldtype": "Dynamic Link", "options": "party_type"},
+                {"fieldname": "ref_doctype", "fieldtype": "Link", "options": "DocType"},
+                {"fieldname": "ref_name", "fieldtype": "Dynamic Link", "options": "ref_doctype"},
+                {"fieldname": "status", "fieldtype": "Select", "options": "Draft\nPaid"},
+            ])),
+        );
+        let referencing: Vec<_> = index
+            .referencing("Customer")
+            .into_iter()
+            .map(|(key, dt, field)| (key, dt, field.fieldname.as_str()))
+            .collect();
+        assert_eq!(
+            referencing,
+            vec![("sales_invoice.json", "Sales Invoice", "customer")]
+        );
+        assert_eq!(
+            index.referencing("Sales Invoice Item")[0].2.fieldtype,
+            "Table"
+        );
+        // Only the Dynamic Link whose Select field offers the DocType
+        let dynamic = index.dynamic_links("Supplier");
+        assert_eq!(dynamic.len(), 1);
+        assert_eq!(dynamic[0].2.options, "party_type");
+        assert_eq!(dynamic[0].2.targets, vec!["Customer", "Supplier"]);
+        assert!(index.dynamic_links("Item").is_empty());
+        assert!(index.referencing("party_type").is_empty());
+
+        // Re-indexing the JSON drops the links it no longer has
+        index.insert(
+            "sales_invoice.json".to_string(),
+            entry(serde_json::json!([
+                {"fieldname": "items", "fieldtype": "Table", "options": "Sales Invoice Item"},
+            ])),
+        );
+        assert!(index.referencing("Customer").is_empty());
+        assert_eq!(index.referencing("Sales Invoice Item").len(), 1);
+        assert!(index.dynamic_links("Customer").is_empty());
+    }
+
+    #[test]
+    fn test_multiline_get_doc_and_child_rows() {
+        let source = r#"
+def make(name):
+    si = frappe.get_doc(
+        "Sales Invoice",
+        name,
+    )
+    print(si.customer)
+    for row in si.items:
+        row.qty += 1
+    for i, item in enumerate(si.get("items")):
+        print(item["qty"])
+    new_row = si.append("items", {})
+    new_row.qty = 2
+"#;
+        let refs = refs(source, None);
+        assert!(has(&refs, "Sales Invoice", "customer", 7));
+        assert!(has(&refs, "Sales Invoice", "items", 8));
+        assert!(has(&refs, "Sales Invoice Item", "qty", 9));
+        assert!(has(&refs, "Sales Invoice Item", "qty", 11));
+        assert!(has(&refs, "Sales Invoice", "items", 12));
+        assert!(has(&refs, "Sales Invoice Item", "qty", 13));
+    }
+
+    #[test]
+    fn test_reassignment_and_scopes() {
+        let source = r#"
+doc = frappe.get_doc({"doctype": "Sales Invoice"})
+doc.customer = "A"
+
+def other(doc):
+    return doc.customer
+
+doc = get_something()
+doc.customer
+"#;
+        let refs = refs(source, None);
+        assert!(has(&refs, "Sales Invoice", "customer", 3));
+        assert!(!has(&refs, "Sales Invoice", "customer", 6));
+        assert!(!has(&refs, "Sales Invoice", "customer", 9));
+    }
+
+    #[test]
+    fn test_type_hints_and_controller_self() {
+        let source = r#"
+def on_submit(doc: "SalesInvoice", method=None):
+    doc.customer
+    doc.save()
+
+class SalesInvoice(Document):
+    def validate(self):
+        if self.get("customer"):
+            self.db_set("customer", None)
+        rows = [r.qty for r in self.items]
+
+    @frappe.whitelist()
+    def make_return(self):
+        return self.customer
+
+    @classmethod
+    def from_row(cls, row):
+        return cls.customer
+
+    @staticmethod
+    def total(row):
+        return row.customer
+"#;
+        let refs = refs(source, Some("Sales Invoice"));
+        assert!(has(&refs, "Sales Invoice", "customer", 3));
+        assert!(!refs.iter().any(|(_, f, _, _)| f == "save"));
+        assert!(has(&refs, "Sales Invoice", "customer", 8));
+        assert!(has(&refs, "Sales Invoice", "customer", 9));
+        assert!(has(&refs, "Sales Invoice Item", "qty", 10));
+        assert!(has(&refs, "Sales Invoice", "customer", 14));
+        assert!(!has(&refs, "Sales Invoice", "customer", 18));
+        assert!(!has(&refs, "Sales Invoice", "customer", 22));
+    }
+
+    #[test]
+    fn test_inline_and_db_calls() {
+        let source = r#"
+frappe.get_doc("Sales Invoice", name).get("customer")
+frappe.db.get_value("Sales Invoice", name, ["customer", "sum(qty)"])
+frappe.db.set_value("Sales Invoice Item", row, "qty", 1)
+"#;
+        let refs = refs(source, None);
+        assert!(refs.contains(&(
+            "Sales Invoice".into(),
+            "customer".into(),
+            2,
+            "inline".into()
+        )));
+        assert!(refs.contains(&(
+            "Sales Invoice".into(),
+            "customer".into(),
+            3,
+            "db_get_value".into()
+        )));
+        assert!(!refs.iter().any(|(_, f, _, _)| f.starts_with("sum")));
+        assert!(has(&refs, "Sales Invoice Item", "qty", 4));
+    }
+}
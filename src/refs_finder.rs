@@ -10,21 +10,25 @@
 // is strictly forbidden unless prior written permission is obtained
 // from Nuwaira.
 use anyhow::{bail, Context, Result};
+use rayon::prelude::*;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::{
     collections::{BTreeMap, BTreeSet},
     fs,
-    path::Path,
+    path::{Path, PathBuf},
 };
 use walkdir::WalkDir;
 
+use crate::js_refs;
+use crate::py_refs::{self, DocTypeIndex};
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Occurrence {
     pub file: String,
     pub line: usize,
     pub var: String,
-    pub kind: String, // "attr" | "subscript" | "get" | "set" | "append" | "get_value" | "inline"
+    pub kind: String, // "attr" | "subscript" | "get" | "set" | "append" | "get_value" | "db_set" | "inline" | "db_get_value" | "db_set_value"
 }
 
 #[derive(Debug, Serialize, Default, Deserialize, Clone)]
@@ -44,461 +48,260 @@ pub struct Output {
 pub struct Stats {
     pub files_scanned: usize,
     pub py_files: usize,
+    #[serde(default)]
+    pub js_files: usize,
     pub doctypes_detected: usize,
     pub total_field_hits: usize,
 }
 
-pub fn analyze_frappe_field_usage(root: &str) -> Result<Output> {
-    let root = Path::new(root);
-    if !root.exists() {
-        bail!("Root path does not exist: {}", root.display());
-    }
-
-    // Sebelumnya: let rx_bind_str = Regex::new(r#" ... "#).unwrap();
-    let rx_bind_str = Regex::new(
-        r#"(?x)
-    (?P<var>[A-Za-z_]\w*)            # nama variabel di kiri
-    \s*=\s*
-    frappe\.(?P<fn1>get_doc|new_doc|get_cached_doc)
-    \s*\(
-        \s*
-        (?:
-            ["'](?P<dt1>[^"']+)["']                 # argumen doctype sebagai string
-          |
-            \{\s*["']doctype["']\s*:\s*["'](?P<dt2>[^"']+)["']  # atau dict dengan key doctype
-        )
-        [^)]*                                       # argumen tambahan apa pun
-    \)                                              # TUTUP PAREN LITERAL (harus di-escape)
-    "#,
-    )
-    .expect("rx_bind_str bad");
-
-    let rx_inline_call = Regex::new(
-        r#"(?x)
-    frappe\.(?P<fn>get_doc|new_doc|get_cached_doc)\s*\(
-        \s*(?:
-            ["'](?P<dt_inline>[^"']+)["']
-          |
-            \{\s*["']doctype["']\s*:\s*["'](?P<dt_inline2>[^"']+)["']
-        )
-        [^)]*
-    \)                                              # TUTUP PAREN LITERAL
-    \.(?P<method>append|get|set|get_value)
-    \s*\(\s*["'](?P<field>[^"']+)["']
-    "#,
-    )
-    .expect("rx_inline_call bad");
-
-    // Field access (we’ll run per known var name)
-    // attr:   var.customer
-    // sub:    var["customer"]
-    // get:    var.get("customer")
-    // set:    var.set("customer", ...)
-    // append: var.append("items", {...})
-    // get_value: var.get_value("field")
-    let rx_attr_tpl = r#"(?x)\b{var}\.(?P<field>[A-Za-z_]\w*)\b"#;
-    let rx_sub_tpl = r#"(?x)\b{var}\s*\[\s*["'](?P<field>[^"']+)["']\s*\]"#;
-    let rx_get_tpl = r#"(?x)\b{var}\.get\s*\(\s*["'](?P<field>[^"']+)["']"#;
-    let rx_set_tpl = r#"(?x)\b{var}\.set\s*\(\s*["'](?P<field>[^"']+)["']"#;
-    let rx_app_tpl = r#"(?x)\b{var}\.append\s*\(\s*["'](?P<field>[^"']+)["']"#;
-    let rx_gv_tpl = r#"(?x)\b{var}\.get_value\s*\(\s*["'](?P<field>[^"']+)["']"#;
-
-    let mut out = Output {
-        doctypes: BTreeMap::new(),
-        unknown: BTreeMap::new(),
-        stats: Stats::default(),
-    };
-
-    let mut files_scanned = 0usize;
-    let mut py_files = 0usize;
-    let mut doctypes_detected: BTreeSet<String> = BTreeSet::new();
-    let mut total_hits = 0usize;
+lazy_static::lazy_static! {
+    static ref RX_DT_NAME: Regex = Regex::new(r#""name"\s*:\s*"([^"]+)""#).expect("rx_dt_name bad");
+}
 
-    for entry in WalkDir::new(root).into_iter().filter_map(|e| e.ok()) {
-        let path = entry.path();
-        if !path.is_file() {
-            continue;
+impl Output {
+    pub fn new() -> Self {
+        Output {
+            doctypes: BTreeMap::new(),
+            unknown: BTreeMap::new(),
+            stats: Stats::default(),
         }
-        files_scanned += 1;
+    }
 
-        if path.extension().and_then(|e| e.to_str()) != Some("py") {
-            continue;
+    /// Drop every occurrence that was found in `file`.
+    pub fn remove_file(&mut self, file: &str) {
+        self.remove_files(&std::iter::once(file.to_string()).collect());
+    }
+
+    /// Drop every occurrence that was found in one of `files`.
+    pub fn remove_files(&mut self, files: &BTreeSet<String>) {
+        if files.is_empty() {
+            return;
         }
-        py_files += 1;
-
-        let content =
-            fs::read_to_string(path).with_context(|| format!("read {}", path.display()))?;
-        let lines: Vec<&str> = content.lines().collect();
-
-        let pstr = normalize_sep(path);
-        let primary_dt = infer_primary_doctype_from_path(path); // Some(dt) jika di dalam doctype/<dt>/<dt>.py
-
-        if primary_dt.is_some() {
-            // get DocType name from it json file
-            let json_file = path.with_extension("json");
-            // loking for pattern like: `"name": "Sales Invoice",`
-            if json_file.exists() && json_file.is_file() {
-                if let Ok(json_content) = fs::read_to_string(&json_file) {
-                    let rx_dt_name =
-                        Regex::new(r#""name"\s*:\s*"([^"]+)""#).expect("rx_dt_name bad");
-                    if let Some(caps) = rx_dt_name.captures(&json_content) {
-                        if let Some(m) = caps.get(1) {
-                            let dt_name = m.as_str();
-                            scan_type_hints_in_doctype_py(
-                                &pstr,
-                                dt_name,
-                                &content,
-                                &mut out,
-                                &mut total_hits,
-                            );
-                        }
-                    }
-                }
+        for usage in self.doctypes.values_mut() {
+            for occs in usage.fields.values_mut() {
+                occs.retain(|o| !files.contains(&o.file));
             }
+            usage.fields.retain(|_, occs| !occs.is_empty());
         }
+        self.doctypes.retain(|_, usage| !usage.fields.is_empty());
+        self.unknown.retain(|file, _| !files.contains(file));
+    }
 
-        // 1) Temukan binding var -> doctype (satu file)
-        let mut var_to_dt: BTreeMap<String, String> = BTreeMap::new();
-        for (_i, line) in lines.iter().enumerate() {
-            for cap in rx_bind_str.captures_iter(line) {
-                let var = cap.name("var").unwrap().as_str().to_string();
-                let dt = cap
-                    .name("dt1")
-                    .map(|m| m.as_str())
-                    .or_else(|| cap.name("dt2").map(|m| m.as_str()))
-                    .unwrap_or("")
-                    .to_string();
-                if !dt.is_empty() {
-                    var_to_dt.insert(var, dt.clone());
-                    doctypes_detected.insert(dt);
-                }
+    /// Order occurrences by file and line, so patched and fresh outputs compare equal.
+    pub fn sort(&mut self) {
+        let key = |o: &Occurrence| (o.file.clone(), o.line, o.kind.clone(), o.var.clone());
+        for usage in self.doctypes.values_mut() {
+            for occs in usage.fields.values_mut() {
+                occs.sort_by_cached_key(key);
             }
         }
-
-        // 2) Kumpulkan field usage dari var yang diketahui tipenya
-        for (var, dt) in var_to_dt.clone() {
-            let rx_attr = Regex::new(&rx_attr_tpl.replace("{var}", &regex::escape(&var))).unwrap();
-            let rx_sub = Regex::new(&rx_sub_tpl.replace("{var}", &regex::escape(&var))).unwrap();
-            let rx_get = Regex::new(&rx_get_tpl.replace("{var}", &regex::escape(&var))).unwrap();
-            let rx_set = Regex::new(&rx_set_tpl.replace("{var}", &regex::escape(&var))).unwrap();
-            let rx_app = Regex::new(&rx_app_tpl.replace("{var}", &regex::escape(&var))).unwrap();
-            let rx_gv = Regex::new(&rx_gv_tpl.replace("{var}", &regex::escape(&var))).unwrap();
-
-            for (ln, line) in lines.iter().enumerate() {
-                collect_hits(
-                    &mut out.doctypes,
-                    &dt,
-                    &pstr,
-                    ln + 1,
-                    &var,
-                    "attr",
-                    &rx_attr,
-                    line,
-                    &mut total_hits,
-                );
-                collect_hits(
-                    &mut out.doctypes,
-                    &dt,
-                    &pstr,
-                    ln + 1,
-                    &var,
-                    "subscript",
-                    &rx_sub,
-                    line,
-                    &mut total_hits,
-                );
-                collect_hits(
-                    &mut out.doctypes,
-                    &dt,
-                    &pstr,
-                    ln + 1,
-                    &var,
-                    "get",
-                    &rx_get,
-                    line,
-                    &mut total_hits,
-                );
-                collect_hits(
-                    &mut out.doctypes,
-                    &dt,
-                    &pstr,
-                    ln + 1,
-                    &var,
-                    "set",
-                    &rx_set,
-                    line,
-                    &mut total_hits,
-                );
-                collect_hits(
-                    &mut out.doctypes,
-                    &dt,
-                    &pstr,
-                    ln + 1,
-                    &var,
-                    "append",
-                    &rx_app,
-                    line,
-                    &mut total_hits,
-                );
-                collect_hits(
-                    &mut out.doctypes,
-                    &dt,
-                    &pstr,
-                    ln + 1,
-                    &var,
-                    "get_value",
-                    &rx_gv,
-                    line,
-                    &mut total_hits,
-                );
+        for fields in self.unknown.values_mut() {
+            for occs in fields.values_mut() {
+                occs.sort_by_cached_key(key);
             }
         }
+    }
 
-        // 3) Inline call: frappe.get_doc("X", ...).append("items", ...)
-        for (ln, line) in lines.iter().enumerate() {
-            for cap in rx_inline_call.captures_iter(line) {
-                let dt = cap
-                    .name("dt_inline")
-                    .map(|m| m.as_str())
-                    .or_else(|| cap.name("dt_inline2").map(|m| m.as_str()))
-                    .unwrap_or("")
-                    .to_string();
-                let field = cap.name("field").unwrap().as_str().to_string();
-                if !dt.is_empty() && !field.is_empty() {
-                    let entry = out.doctypes.entry(dt.clone()).or_default();
-                    entry.fields.entry(field).or_default().push(Occurrence {
-                        file: pstr.clone(),
-                        line: ln + 1,
-                        var: "<inline>".into(),
-                        kind: "inline".into(),
-                    });
-                    doctypes_detected.insert(dt);
-                    total_hits += 1;
-                }
+    /// Append all occurrences of `other` into this output.
+    pub fn merge(&mut self, other: Output) {
+        for (dt, usage) in other.doctypes {
+            let entry = self.doctypes.entry(dt).or_default();
+            for (field, occs) in usage.fields {
+                entry.fields.entry(field).or_default().extend(occs);
             }
         }
-
-        // 4) Heuristik untuk `doc` tanpa tipe:
-        //    Jika file ini di doctype/<dt>/<dt>.py, maka asumsikan var 'doc' bertipe dt.
-        //    Scan akses field dari 'doc'.
-        if let Some(dt) = primary_dt {
-            let var = "doc";
-            let rx_attr = Regex::new(&rx_attr_tpl.replace("{var}", var)).unwrap();
-            let rx_sub = Regex::new(&rx_sub_tpl.replace("{var}", var)).unwrap();
-            let rx_get = Regex::new(&rx_get_tpl.replace("{var}", var)).unwrap();
-            let rx_set = Regex::new(&rx_set_tpl.replace("{var}", var)).unwrap();
-            let rx_app = Regex::new(&rx_app_tpl.replace("{var}", var)).unwrap();
-            let rx_gv = Regex::new(&rx_gv_tpl.replace("{var}", var)).unwrap();
-
-            for (ln, line) in lines.iter().enumerate() {
-                collect_hits(
-                    &mut out.doctypes,
-                    &dt,
-                    &pstr,
-                    ln + 1,
-                    var,
-                    "attr",
-                    &rx_attr,
-                    line,
-                    &mut total_hits,
-                );
-                collect_hits(
-                    &mut out.doctypes,
-                    &dt,
-                    &pstr,
-                    ln + 1,
-                    var,
-                    "subscript",
-                    &rx_sub,
-                    line,
-                    &mut total_hits,
-                );
-                collect_hits(
-                    &mut out.doctypes,
-                    &dt,
-                    &pstr,
-                    ln + 1,
-                    var,
-                    "get",
-                    &rx_get,
-                    line,
-                    &mut total_hits,
-                );
-                collect_hits(
-                    &mut out.doctypes,
-                    &dt,
-                    &pstr,
-                    ln + 1,
-                    var,
-                    "set",
-                    &rx_set,
-                    line,
-                    &mut total_hits,
-                );
-                collect_hits(
-                    &mut out.doctypes,
-                    &dt,
-                    &pstr,
-                    ln + 1,
-                    var,
-                    "append",
-                    &rx_app,
-                    line,
-                    &mut total_hits,
-                );
-                collect_hits(
-                    &mut out.doctypes,
-                    &dt,
-                    &pstr,
-                    ln + 1,
-                    var,
-                    "get_value",
-                    &rx_gv,
-                    line,
-                    &mut total_hits,
-                );
-            }
-        } else {
-            // Skip for now
-            continue;
-            // Kalau tidak bisa infer doctype, simpan sebagai unknown (per file) untuk 'doc'
-            #[allow(unreachable_code)]
-            let var = "doc";
-            let rx_attr = Regex::new(&rx_attr_tpl.replace("{var}", var)).unwrap();
-            let rx_sub = Regex::new(&rx_sub_tpl.replace("{var}", var)).unwrap();
-            let rx_get = Regex::new(&rx_get_tpl.replace("{var}", var)).unwrap();
-            let rx_set = Regex::new(&rx_set_tpl.replace("{var}", var)).unwrap();
-            let rx_app = Regex::new(&rx_app_tpl.replace("{var}", var)).unwrap();
-            let rx_gv = Regex::new(&rx_gv_tpl.replace("{var}", var)).unwrap();
-
-            for (ln, line) in lines.iter().enumerate() {
-                collect_unknown(
-                    &mut out.unknown,
-                    &pstr,
-                    ln + 1,
-                    var,
-                    "attr",
-                    &rx_attr,
-                    line,
-                    &mut total_hits,
-                );
-                collect_unknown(
-                    &mut out.unknown,
-                    &pstr,
-                    ln + 1,
-                    var,
-                    "subscript",
-                    &rx_sub,
-                    line,
-                    &mut total_hits,
-                );
-                collect_unknown(
-                    &mut out.unknown,
-                    &pstr,
-                    ln + 1,
-                    var,
-                    "get",
-                    &rx_get,
-                    line,
-                    &mut total_hits,
-                );
-                collect_unknown(
-                    &mut out.unknown,
-                    &pstr,
-                    ln + 1,
-                    var,
-                    "set",
-                    &rx_set,
-                    line,
-                    &mut total_hits,
-                );
-                collect_unknown(
-                    &mut out.unknown,
-                    &pstr,
-                    ln + 1,
-                    var,
-                    "append",
-                    &rx_app,
-                    line,
-                    &mut total_hits,
-                );
-                collect_unknown(
-                    &mut out.unknown,
-                    &pstr,
-                    ln + 1,
-                    var,
-                    "get_value",
-                    &rx_gv,
-                    line,
-                    &mut total_hits,
-                );
+        for (file, fields) in other.unknown {
+            let entry = self.unknown.entry(file).or_default();
+            for (field, occs) in fields {
+                entry.entry(field).or_default().extend(occs);
             }
         }
     }
 
-    out.stats.files_scanned = files_scanned;
-    out.stats.py_files = py_files;
-    out.stats.doctypes_detected = doctypes_detected.len();
-    out.stats.total_field_hits = total_hits;
+    /// Recompute hit/doctype counters after the output has been patched.
+    pub fn recount(&mut self) {
+        self.stats.doctypes_detected = self.doctypes.len();
+        self.stats.total_field_hits = self
+            .doctypes
+            .values()
+            .flat_map(|u| u.fields.values())
+            .map(|occs| occs.len())
+            .sum::<usize>()
+            + self
+                .unknown
+                .values()
+                .flat_map(|f| f.values())
+                .map(|occs| occs.len())
+                .sum::<usize>();
+    }
+}
+
+impl Default for Output {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Re-scan a single python or form script file; the result only contains hits from that file.
+///
+/// Returns an empty output when the file no longer exists.
+pub fn analyze_file_field_usage(path: &Path, index: &DocTypeIndex) -> Result<Output> {
+    let mut out = Output::new();
+    let is_py = path.extension().and_then(|e| e.to_str()) == Some("py");
+    if !path.is_file() || !(is_py || is_js_source(path)) {
+        return Ok(out);
+    }
+    let content = fs::read_to_string(path).with_context(|| format!("read {}", path.display()))?;
+    let mut doctypes_detected = BTreeSet::new();
+    let mut total_hits = 0usize;
+    let scan = if is_py { scan_py_file } else { scan_js_file };
+    scan(
+        path,
+        &content,
+        index,
+        &mut out,
+        &mut doctypes_detected,
+        &mut total_hits,
+    );
+    Ok(out)
+}
+
+/// Whether `path` is a hand written `.js` file (not a build artifact or a dependency).
+pub fn is_js_source(path: &Path) -> bool {
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    name.ends_with(".js")
+        && !name.ends_with(".min.js")
+        && !name.ends_with(".bundle.js")
+        && !path.iter().any(|p| p == "node_modules" || p == "dist")
+}
+
+/// Path string as it is stored in `Occurrence::file`.
+pub fn occurrence_path(path: &Path) -> String {
+    normalize_sep(path)
+}
+
+/// Scan every python and javascript file under `root`; child tables are resolved with `index`.
+///
+/// Files are parsed on rayon's worker pool and merged in path order.
+pub fn analyze_frappe_field_usage(root: &str, index: &DocTypeIndex) -> Result<Output> {
+    let mut stats = Stats::default();
+    let files = source_files(Path::new(root), &mut stats)?;
+
+    let parsed = files
+        .par_iter()
+        .map(|path| analyze_file_field_usage(path, index))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut out = Output::new();
+    for refs in parsed {
+        out.merge(refs);
+    }
+    out.sort();
+    out.stats = stats;
+    out.recount();
 
     // let json = serde_json::to_string_pretty(&out)?;
     // Ok(json)
     Ok(out)
 }
 
-fn collect_hits(
-    doctypes: &mut BTreeMap<String, DoctypeUsage>,
-    dt: &str,
-    file: &str,
-    line: usize,
-    var: &str,
-    kind: &str,
-    rx: &Regex,
-    text: &str,
+/// Python and javascript files under `root` that are scanned for field usage,
+/// in path order; the file counters of `stats` are increased along the way.
+pub fn source_files(root: &Path, stats: &mut Stats) -> Result<Vec<PathBuf>> {
+    if !root.exists() {
+        bail!("Root path does not exist: {}", root.display());
+    }
+
+    let mut files = Vec::new();
+    for entry in WalkDir::new(root)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|e| e.ok())
+    {
+        let path = entry.path();
+        if !path.is_file() {
+            continue;
+        }
+        stats.files_scanned += 1;
+
+        if path.extension().and_then(|e| e.to_str()) == Some("py") {
+            stats.py_files += 1;
+        } else if is_js_source(path) {
+            stats.js_files += 1;
+        } else {
+            continue;
+        }
+        files.push(entry.into_path());
+    }
+    Ok(files)
+}
+
+/// Collect field usages of a single python file into `out`.
+fn scan_py_file(
+    path: &Path,
+    content: &str,
+    index: &DocTypeIndex,
+    out: &mut Output,
+    doctypes_detected: &mut BTreeSet<String>,
     total_hits: &mut usize,
 ) {
-    for cap in rx.captures_iter(text) {
-        if let Some(fm) = cap.name("field") {
-            let field = fm.as_str().to_string();
-            let usage = doctypes.entry(dt.to_string()).or_default();
-            usage.fields.entry(field).or_default().push(Occurrence {
-                file: file.to_string(),
-                line,
-                var: var.to_string(),
-                kind: kind.to_string(),
-            });
-            *total_hits += 1;
+    let pstr = normalize_sep(path);
+    let primary_dt = infer_primary_doctype_from_path(path); // Some(dt) jika di dalam doctype/<dt>/<dt>.py
+
+    // Nama DocType asli dari file json-nya, mis. "Sales Invoice" untuk sales_invoice.py
+    let mut primary_name = None;
+    if primary_dt.is_some() {
+        if let Some(dt_name) = doctype_name_from_json(path, index) {
+            scan_type_hints_in_doctype_py(&pstr, &dt_name, content, out, total_hits);
+            primary_name = Some(dt_name);
         }
     }
+    let primary_name = primary_name.or(primary_dt);
+
+    // Telusuri syntax tree: tipe variabel dilacak per scope
+    for r in py_refs::scan_source(&pstr, content, primary_name.as_deref(), index) {
+        let usage = out.doctypes.entry(r.doctype.clone()).or_default();
+        usage.fields.entry(r.field).or_default().push(r.occurrence);
+        doctypes_detected.insert(r.doctype);
+        *total_hits += 1;
+    }
 }
 
-fn collect_unknown(
-    unknown: &mut BTreeMap<String, BTreeMap<String, Vec<Occurrence>>>,
-    file: &str,
-    line: usize,
-    var: &str,
-    kind: &str,
-    rx: &Regex,
-    text: &str,
+/// Collect field usages of a single form script into `out`.
+fn scan_js_file(
+    path: &Path,
+    content: &str,
+    index: &DocTypeIndex,
+    out: &mut Output,
+    doctypes_detected: &mut BTreeSet<String>,
     total_hits: &mut usize,
 ) {
-    for cap in rx.captures_iter(text) {
-        if let Some(fm) = cap.name("field") {
-            let field = fm.as_str().to_string();
-            unknown
-                .entry(file.to_string())
-                .or_default()
-                .entry(field)
-                .or_default()
-                .push(Occurrence {
-                    file: file.to_string(),
-                    line,
-                    var: var.to_string(),
-                    kind: kind.to_string(),
-                });
-            *total_hits += 1;
-        }
+    let pstr = normalize_sep(path);
+    let file_dt = infer_primary_doctype_from_path(path)
+        .map(|dt| doctype_name_from_json(path, index).unwrap_or(dt));
+
+    for r in js_refs::scan_source(&pstr, content, file_dt.as_deref(), index) {
+        let usage = out.doctypes.entry(r.doctype.clone()).or_default();
+        usage.fields.entry(r.field).or_default().push(r.occurrence);
+        doctypes_detected.insert(r.doctype);
+        *total_hits += 1;
+    }
+}
+
+/// DocType name from the JSON next to a controller, e.g. "Sales Invoice"
+/// for `sales_invoice.py`; indexed JSONs aren't read again.
+fn doctype_name_from_json(path: &Path, index: &DocTypeIndex) -> Option<String> {
+    let json_file = path.with_extension("json");
+    if let Some(name) = index.doctype_name(&json_file) {
+        return Some(name.to_string());
+    }
+    if !json_file.is_file() {
+        return None;
     }
+    // loking for pattern like: `"name": "Sales Invoice",`
+    let json_content = fs::read_to_string(&json_file).ok()?;
+    let caps = RX_DT_NAME.captures(&json_content)?;
+    caps.get(1).map(|m| m.as_str().to_string())
 }
 
 fn normalize_sep(path: &Path) -> String {
@@ -510,7 +313,7 @@ fn normalize_sep(path: &Path) -> String {
     }
 }
 
-/// Infer primary doctype from a path matching: .../doctype/<dt>/<dt>.py
+/// Infer primary doctype from a path matching: .../doctype/<dt>/<dt>.py (or <dt>.js)
 /// - Works with absolute or relative paths
 /// - Doesn't assume a fixed index for "doctype"
 /// - Picks the *nearest* (rightmost) doctype/<dt>/<dt>.py segment if multiple exist
@@ -530,8 +333,11 @@ pub fn infer_primary_doctype_from_path(path: &Path) -> Option<String> {
             let file = parts[i + 2];
 
             let file_path = Path::new(file);
-            // Only consider Python files (*.py); relax this if you want other extensions
-            if file_path.extension().and_then(|e| e.to_str()) != Some("py") {
+            // Only consider controllers (*.py) and form scripts (*.js)
+            if !matches!(
+                file_path.extension().and_then(|e| e.to_str()),
+                Some("py" | "js")
+            ) {
                 continue;
             }
             if let Some(stem) = file_path.file_stem() {
@@ -820,3 +626,46 @@ fn scan_type_hints_in_doctype_py(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn occ(file: &str, line: usize) -> Occurrence {
+        Occurrence {
+            file: file.to_string(),
+            line,
+            var: "doc".to_string(),
+            kind: "attr".to_string(),
+        }
+    }
+
+    fn output(occs: Vec<Occurrence>) -> Output {
+        let mut out = Output::new();
+        let usage = out.doctypes.entry("Anggota".to_string()).or_default();
+        usage.fields.insert("nama".to_string(), occs);
+        out
+    }
+
+    #[test]
+    fn test_patched_output_matches_fresh_one() {
+        let fresh = output(vec![occ("a.py", 3), occ("b.py", 1), occ("b.py", 7)]);
+
+        // b.py re-scanned: its hits are dropped and appended again out of order
+        let mut patched = output(vec![occ("b.py", 1), occ("a.py", 3), occ("b.py", 9)]);
+        patched.remove_files(&std::iter::once("b.py".to_string()).collect());
+        patched.merge(output(vec![occ("b.py", 7), occ("b.py", 1)]));
+        patched.sort();
+        patched.recount();
+
+        assert_eq!(
+            serde_json::to_string(&patched.doctypes).unwrap(),
+            serde_json::to_string(&fresh.doctypes).unwrap()
+        );
+        assert_eq!(patched.stats.total_field_hits, 3);
+
+        patched.remove_file("a.py");
+        patched.remove_file("b.py");
+        assert!(patched.doctypes.is_empty());
+    }
+}
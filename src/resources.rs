@@ -0,0 +1,572 @@
+// Copyright (C) 2025 Nuwaira
+// All Rights Reserved.
+//
+// NOTICE: All information contained herein is, and remains
+// the property of Nuwaira.
+// The intellectual and technical concepts contained
+// herein are proprietary to Nuwaira
+// and are protected by trade secret or copyright law.
+// Dissemination of this information or reproduction of this material
+// is strictly forbidden unless prior written permission is obtained
+// from Nuwaira.
+#![allow(dead_code)]
+
+//! MCP resources backed by the analyzed data and the embedded docs:
+//!
+//! - `frappe://doctype/{name}`: the DocType's meta JSON
+//! - `frappe://module/{name}`: a module and its DocTypes
+//! - `frappe://file/{relative_path}`: an app source file, relative to the bench's `apps/`
+//! - `frappe://docs/{id}`: an embedded Frappe documentation page
+//! - `frappe://run/{run_id}`: the full output of a `bench` run
+
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use rmcp::{model::*, service::Peer, ErrorData as McpError, RoleServer};
+use serde_json::{json, Value};
+
+use crate::analyze::{AnalyzedData, Module};
+use crate::config::Config;
+use crate::functools::{frappe_doc_content, frappe_doc_entries};
+use crate::runlog;
+use crate::stringutil::to_snakec_var;
+
+type McpResult<T> = Result<T, McpError>;
+
+pub const SCHEME: &str = "frappe://";
+
+/// Resources returned per `resources/list` page.
+pub const PAGE_SIZE: usize = 100;
+
+/// Source files larger than this are not served.
+const MAX_FILE_SIZE: u64 = 1024 * 1024;
+
+/// A parsed `frappe://` URI.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FrappeUri {
+    Doctype(String),
+    Module(String),
+    File(String),
+    Docs(String),
+    Run(String),
+}
+
+impl FrappeUri {
+    pub fn parse(uri: &str) -> Option<Self> {
+        let rest = uri.strip_prefix(SCHEME)?;
+        let (kind, value) = rest.split_once('/')?;
+        let value = percent_decode(value)?;
+        if value.is_empty() {
+            return None;
+        }
+        match kind {
+            "doctype" => Some(FrappeUri::Doctype(value)),
+            "module" => Some(FrappeUri::Module(value)),
+            "file" => Some(FrappeUri::File(value)),
+            "docs" => Some(FrappeUri::Docs(value)),
+            "run" => Some(FrappeUri::Run(value)),
+            _ => None,
+        }
+    }
+
+    pub fn to_uri(&self) -> String {
+        let (kind, value, keep_slash) = match self {
+            FrappeUri::Doctype(v) => ("doctype", v, false),
+            FrappeUri::Module(v) => ("module", v, false),
+            FrappeUri::File(v) => ("file", v, true),
+            FrappeUri::Docs(v) => ("docs", v, false),
+            FrappeUri::Run(v) => ("run", v, false),
+        };
+        format!("{}{}/{}", SCHEME, kind, percent_encode(value, keep_slash))
+    }
+}
+
+/// Something a client may have cached went stale.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ResourceChange {
+    /// `notifications/resources/updated`, sent to the subscribers of the URI
+    Updated(String),
+    /// `notifications/resources/list_changed`, sent to every session
+    ListChanged,
+}
+
+struct Session {
+    peer: Peer<RoleServer>,
+    runtime: tokio::runtime::Handle,
+    uris: BTreeSet<String>,
+}
+
+/// Resource subscriptions of every connected session, shared by all clones of
+/// the server and by the file watcher.
+#[derive(Clone, Default)]
+pub struct ResourceNotifier {
+    sessions: Arc<Mutex<HashMap<u64, Session>>>,
+}
+
+impl ResourceNotifier {
+    /// Remember `peer` so it gets `list_changed` notifications.
+    pub fn register(&self, session: u64, peer: Peer<RoleServer>) {
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        self.sessions
+            .lock()
+            .unwrap()
+            .entry(session)
+            .or_insert_with(|| Session {
+                peer,
+                runtime,
+                uris: BTreeSet::new(),
+            });
+    }
+
+    pub fn subscribe(&self, session: u64, peer: Peer<RoleServer>, uri: &str) {
+        self.register(session, peer);
+        if let Some(s) = self.sessions.lock().unwrap().get_mut(&session) {
+            s.uris.insert(uri.to_string());
+        }
+    }
+
+    pub fn unsubscribe(&self, session: u64, uri: &str) {
+        if let Some(s) = self.sessions.lock().unwrap().get_mut(&session) {
+            s.uris.remove(uri);
+        }
+    }
+
+    /// Notify the sessions concerned by `changes`; closed sessions are dropped.
+    pub fn publish(&self, changes: &[ResourceChange]) {
+        if changes.is_empty() {
+            return;
+        }
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.retain(|_, s| !s.peer.is_transport_closed());
+
+        for session in sessions.values() {
+            for change in changes {
+                let peer = session.peer.clone();
+                match change {
+                    ResourceChange::Updated(uri) if session.uris.contains(uri) => {
+                        let uri = uri.clone();
+                        session.runtime.spawn(async move {
+                            let param = ResourceUpdatedNotificationParam { uri };
+                            if let Err(e) = peer.notify_resource_updated(param).await {
+                                tracing::debug!("Failed to send resource update: {}", e);
+                            }
+                        });
+                    }
+                    ResourceChange::ListChanged => {
+                        session.runtime.spawn(async move {
+                            if let Err(e) = peer.notify_resource_list_changed().await {
+                                tracing::debug!("Failed to send resource list change: {}", e);
+                            }
+                        });
+                    }
+                    ResourceChange::Updated(_) => {}
+                }
+            }
+        }
+    }
+}
+
+/// `frappe://file/...` URI of `path`, a file of `app` rooted at `app_root`.
+pub fn file_uri(app: &str, app_root: &Path, path: &Path) -> Option<String> {
+    let rest = path.strip_prefix(app_root).ok()?;
+    let rest = rest.to_string_lossy().replace('\\', "/");
+    Some(FrappeUri::File(format!("{}/{}", app, rest)).to_uri())
+}
+
+/// Resource templates advertised through `resources/templates/list`.
+pub fn templates() -> Vec<ResourceTemplate> {
+    let template = |uri_template: &str, name: &str, description: &str, mime_type: &str| {
+        RawResourceTemplate {
+            uri_template: uri_template.to_string(),
+            name: name.to_string(),
+            title: None,
+            description: Some(description.to_string()),
+            mime_type: Some(mime_type.to_string()),
+        }
+        .no_annotation()
+    };
+    vec![
+        template(
+            "frappe://doctype/{name}",
+            "DocType",
+            "Meta JSON of an analyzed DocType, e.g. frappe://doctype/Sales%20Invoice",
+            "application/json",
+        ),
+        template(
+            "frappe://module/{name}",
+            "Module",
+            "A module of the analyzed apps and the DocTypes it contains",
+            "application/json",
+        ),
+        template(
+            "frappe://file/{relative_path}",
+            "App source file",
+            "Source file of an analyzed app, relative to the bench's apps directory, \
+             e.g. frappe://file/erpnext/erpnext/controllers/queries.py",
+            "text/plain",
+        ),
+        template(
+            "frappe://docs/{id}",
+            "Frappe documentation",
+            "Embedded Frappe documentation page, by the id returned from search_frappe_docs",
+            "text/markdown",
+        ),
+        template(
+            "frappe://run/{run_id}",
+            "Bench run output",
+            "Full stdout and stderr of a bench run, by the run_id returned with its result",
+            "text/plain",
+        ),
+    ]
+}
+
+/// One page of the concrete resources: DocTypes, modules, then docs.
+pub fn list(anal: &AnalyzedData, cursor: Option<&str>) -> McpResult<ListResourcesResult> {
+    let offset = match cursor {
+        Some(cursor) => cursor.parse::<usize>().map_err(|_| {
+            McpError::invalid_params("invalid cursor", Some(json!({ "cursor": cursor })))
+        })?,
+        None => 0,
+    };
+
+    let mut doctypes: Vec<_> = anal.doctypes.iter().collect();
+    doctypes.sort_by(|a, b| (&a.name, &a.app).cmp(&(&b.name, &b.app)));
+    let mut modules: Vec<_> = anal.modules.iter().collect();
+    modules.sort_by(|a, b| (&a.name, &a.app).cmp(&(&b.name, &b.app)));
+
+    let mut all = Vec::new();
+    for dt in doctypes {
+        let mut res = RawResource::new(
+            FrappeUri::Doctype(dt.name.clone()).to_uri(),
+            dt.name.clone(),
+        );
+        res.description = Some(format!("DocType of module {} ({})", dt.module, dt.app));
+        res.mime_type = Some("application/json".to_string());
+        all.push(res);
+    }
+    for module in modules {
+        let mut res = RawResource::new(
+            FrappeUri::Module(module.name.clone()).to_uri(),
+            module.name.clone(),
+        );
+        res.description = Some(format!("Module of app {}", module.app));
+        res.mime_type = Some("application/json".to_string());
+        all.push(res);
+    }
+    for doc in frappe_doc_entries() {
+        let mut res = RawResource::new(FrappeUri::Docs(doc.id).to_uri(), doc.title);
+        res.description = Some(format!("Frappe docs ({}): {}", doc.category, doc.path));
+        res.mime_type = Some("text/markdown".to_string());
+        all.push(res);
+    }
+
+    // Cursors are handed out by this function and never point past the end
+    if offset > all.len() {
+        return Err(McpError::invalid_params(
+            "invalid cursor",
+            Some(json!({ "cursor": offset.to_string() })),
+        ));
+    }
+    let end = offset.saturating_add(PAGE_SIZE).min(all.len());
+    let next_cursor = if end < all.len() {
+        Some(end.to_string())
+    } else {
+        None
+    };
+    let resources = all
+        .into_iter()
+        .skip(offset)
+        .take(PAGE_SIZE)
+        .map(|r| r.no_annotation())
+        .collect();
+    Ok(ListResourcesResult {
+        resources,
+        next_cursor,
+    })
+}
+
+/// A module by its name, in any case, or its snake_case directory name.
+fn find_module<'a>(anal: &'a AnalyzedData, name: &str) -> Option<&'a Module> {
+    anal.modules
+        .iter()
+        .find(|m| m.name.eq_ignore_ascii_case(name) || to_snakec_var(&m.name) == name)
+}
+
+/// The URI change notifications are published under, for any spelling of a
+/// resource `read` accepts; `None` when it isn't a `frappe://` resource.
+pub fn canonical_uri(anal: &AnalyzedData, uri: &str) -> Option<String> {
+    let parsed = match FrappeUri::parse(uri)? {
+        FrappeUri::Doctype(name) => FrappeUri::Doctype(
+            anal.find_doctype(&name, None)
+                .map_or(name, |dt| dt.name.clone()),
+        ),
+        FrappeUri::Module(name) => {
+            FrappeUri::Module(find_module(anal, &name).map_or(name, |m| m.name.clone()))
+        }
+        other => other,
+    };
+    Some(parsed.to_uri())
+}
+
+/// Contents of a `frappe://` resource.
+pub fn read(config: &Config, anal: &AnalyzedData, uri: &str) -> McpResult<ReadResourceResult> {
+    let not_found = |reason: &str| {
+        McpError::resource_not_found(format!("{}: {}", reason, uri), Some(json!({ "uri": uri })))
+    };
+    let parsed = FrappeUri::parse(uri).ok_or_else(|| not_found("Unknown resource"))?;
+
+    let (mime_type, text) = match &parsed {
+        FrappeUri::Doctype(name) => {
+            let dt = anal
+                .find_doctype(name, None)
+                .ok_or_else(|| not_found("DocType not found"))?;
+            let meta_file = dt
+                .meta_file
+                .as_ref()
+                .ok_or_else(|| not_found("DocType has no metadata file"))?;
+            let path = Path::new(&config.app_path(&dt.app)).join(meta_file);
+            let content = fs::read_to_string(&path).map_err(|e| {
+                McpError::internal_error(format!("read {}: {}", path.display(), e), None)
+            })?;
+            let meta: Value = serde_json::from_str(&content).map_err(|e| {
+                McpError::internal_error(format!("parse {}: {}", path.display(), e), None)
+            })?;
+            (
+                "application/json",
+                serde_json::to_string_pretty(&meta).unwrap_or(content),
+            )
+        }
+        FrappeUri::Module(name) => {
+            let module = find_module(anal, name).ok_or_else(|| not_found("Module not found"))?;
+            let mut doctypes: Vec<Value> = anal
+                .doctypes
+                .iter()
+                .filter(|dt| dt.module == module.name && dt.app == module.app)
+                .map(|dt| {
+                    json!({
+                        "name": dt.name,
+                        "uri": FrappeUri::Doctype(dt.name.clone()).to_uri(),
+                        "backend_file": dt.backend_file,
+                        "frontend_file": dt.frontend_file,
+                        "test_file": dt.test_file,
+                    })
+                })
+                .collect();
+            doctypes.sort_by(|a, b| a["name"].as_str().cmp(&b["name"].as_str()));
+            let body = json!({
+                "name": module.name,
+                "app": module.app,
+                "location": module.location,
+                "doctypes": doctypes,
+            });
+            (
+                "application/json",
+                serde_json::to_string_pretty(&body).unwrap_or_default(),
+            )
+        }
+        FrappeUri::File(relative_path) => {
+            let path = resolve_app_file(config, relative_path)
+                .ok_or_else(|| not_found("File not found"))?;
+            let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+            if size > MAX_FILE_SIZE {
+                return Err(McpError::invalid_params(
+                    format!("File is too large to serve ({} bytes): {}", size, uri),
+                    Some(json!({ "uri": uri, "size": size })),
+                ));
+            }
+            let content = fs::read_to_string(&path).map_err(|e| {
+                McpError::internal_error(format!("read {}: {}", path.display(), e), None)
+            })?;
+            (mime_type_of(&path), content)
+        }
+        FrappeUri::Docs(id) => {
+            let (_, content) =
+                frappe_doc_content(id).ok_or_else(|| not_found("Document not found"))?;
+            ("text/markdown", content)
+        }
+        FrappeUri::Run(run_id) => {
+            let path = runlog::log_path(&runlog::runs_dir(config), run_id)
+                .filter(|p| p.is_file())
+                .ok_or_else(|| not_found("Run not found"))?;
+            let content = fs::read(&path).map_err(|e| {
+                McpError::internal_error(format!("read {}: {}", path.display(), e), None)
+            })?;
+            // Serve the end of very long runs
+            let skipped = content.len().saturating_sub(MAX_FILE_SIZE as usize);
+            let mut text = String::from_utf8_lossy(&content[skipped..]).to_string();
+            if skipped > 0 {
+                text = format!("... (first {} bytes omitted)\n{}", skipped, text);
+            }
+            ("text/plain", text)
+        }
+    };
+
+    Ok(ReadResourceResult {
+        contents: vec![ResourceContents::TextResourceContents {
+            uri: uri.to_string(),
+            mime_type: Some(mime_type.to_string()),
+            text,
+            meta: None,
+        }],
+    })
+}
+
+/// Absolute path of `<app>/<path>` if the app is analyzed and the file lies inside it.
+fn resolve_app_file(config: &Config, relative_path: &str) -> Option<std::path::PathBuf> {
+    let (app, rest) = relative_path.trim_start_matches('/').split_once('/')?;
+    if !config.app_dirs.iter().any(|a| a == app) && app != config.app_relative_path {
+        return None;
+    }
+    let app_root = fs::canonicalize(config.app_path(app)).ok()?;
+    let path = fs::canonicalize(app_root.join(rest)).ok()?;
+    if !path.starts_with(&app_root) || !path.is_file() {
+        return None;
+    }
+    Some(path)
+}
+
+fn mime_type_of(path: &Path) -> &'static str {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("py") => "text/x-python",
+        Some("js") => "text/javascript",
+        Some("json") => "application/json",
+        Some("md") => "text/markdown",
+        Some("html") => "text/html",
+        Some("css") => "text/css",
+        _ => "text/plain",
+    }
+}
+
+fn percent_encode(value: &str, keep_slash: bool) -> String {
+    let mut out = String::with_capacity(value.len());
+    for b in value.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                out.push(b as char)
+            }
+            b'/' if keep_slash => out.push('/'),
+            _ => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    out
+}
+
+fn percent_decode(value: &str) -> Option<String> {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = value.get(i + 1..i + 3)?;
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyze::{DocType, Module};
+
+    #[test]
+    fn test_uri_roundtrip() {
+        let uri = FrappeUri::Doctype("Sales Invoice".to_string());
+        assert_eq!(uri.to_uri(), "frappe://doctype/Sales%20Invoice");
+        assert_eq!(FrappeUri::parse(&uri.to_uri()), Some(uri));
+
+        let file = FrappeUri::File("erpnext/erpnext/controllers/queries.py".to_string());
+        assert_eq!(
+            file.to_uri(),
+            "frappe://file/erpnext/erpnext/controllers/queries.py"
+        );
+        assert_eq!(FrappeUri::parse(&file.to_uri()), Some(file));
+
+        assert_eq!(FrappeUri::parse("frappe://doctype/"), None);
+        assert_eq!(FrappeUri::parse("frappe://report/X"), None);
+        assert_eq!(FrappeUri::parse("cwd:///"), None);
+    }
+
+    #[test]
+    fn test_canonical_uri() {
+        let mut anal = AnalyzedData::default();
+        anal.doctypes.push(DocType {
+            name: "Sales Invoice".to_string(),
+            backend_file: String::new(),
+            frontend_file: None,
+            meta_file: None,
+            test_file: None,
+            module: "Simpanan Pokok".to_string(),
+            app: "koperasi".to_string(),
+        });
+        anal.modules.push(Module {
+            name: "Simpanan Pokok".to_string(),
+            location: "koperasi/simpanan_pokok".to_string(),
+            app: "koperasi".to_string(),
+        });
+
+        // The spellings `read` accepts map to the URI the watcher publishes
+        let doctype = FrappeUri::Doctype("Sales Invoice".to_string()).to_uri();
+        let module = FrappeUri::Module("Simpanan Pokok".to_string()).to_uri();
+        let canonical = |uri: &str| canonical_uri(&anal, uri);
+        assert_eq!(
+            canonical("frappe://doctype/Sales Invoice"),
+            Some(doctype.clone())
+        );
+        assert_eq!(canonical(&doctype), Some(doctype));
+        assert_eq!(
+            canonical("frappe://module/simpanan_pokok"),
+            Some(module.clone())
+        );
+        assert_eq!(canonical("frappe://module/simpanan pokok"), Some(module));
+        assert_eq!(
+            canonical("frappe://doctype/Not Yet"),
+            Some("frappe://doctype/Not%20Yet".to_string())
+        );
+        assert_eq!(canonical("frappe://report/X"), None);
+    }
+
+    #[test]
+    fn test_list_paginates() {
+        let mut anal = AnalyzedData::default();
+        for i in 0..PAGE_SIZE + 5 {
+            anal.doctypes.push(DocType {
+                name: format!("DT {:03}", i),
+                backend_file: String::new(),
+                frontend_file: None,
+                meta_file: None,
+                test_file: None,
+                module: "Simpanan".to_string(),
+                app: "koperasi".to_string(),
+            });
+        }
+        anal.modules.push(Module {
+            name: "Simpanan".to_string(),
+            location: "koperasi/simpanan".to_string(),
+            app: "koperasi".to_string(),
+        });
+
+        let first = list(&anal, None).unwrap();
+        assert_eq!(first.resources.len(), PAGE_SIZE);
+        assert_eq!(first.resources[0].raw.uri, "frappe://doctype/DT%20000");
+        let cursor = first.next_cursor.expect("more pages");
+
+        let second = list(&anal, Some(&cursor)).unwrap();
+        assert_eq!(second.resources[0].raw.uri, "frappe://doctype/DT%20100");
+        assert!(second
+            .resources
+            .iter()
+            .any(|r| r.raw.uri == "frappe://module/Simpanan"));
+
+        assert!(list(&anal, Some("nope")).is_err());
+        assert!(list(&anal, Some(&usize::MAX.to_string())).is_err());
+    }
+}
@@ -0,0 +1,148 @@
+// Copyright (C) 2025 Nuwaira
+// All Rights Reserved.
+//
+// NOTICE: All information contained herein is, and remains
+// the property of Nuwaira.
+// The intellectual and technical concepts contained
+// herein are proprietary to Nuwaira
+// and are protected by trade secret or copyright law.
+// Dissemination of this information or reproduction of this material
+// is strictly forbidden unless prior written permission is obtained
+// from Nuwaira.
+#![allow(dead_code)]
+
+//! Full output of `bench` runs, kept as `<bench>/.frappe-mcp/runs/<run_id>.log`
+//! so a truncated result can point at the complete log.
+
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use chrono::Utc;
+use rmcp::schemars;
+use serde::Serialize;
+
+use crate::cache;
+use crate::config::Config;
+use crate::resources::FrappeUri;
+
+/// Logs kept per bench, older ones are removed when a run starts.
+const KEEP_RUNS: usize = 50;
+
+static NEXT_RUN: AtomicU64 = AtomicU64::new(1);
+
+/// Where the full output of a run can be fetched.
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct LogHandle {
+    pub run_id: String,
+    /// `frappe://run/{run_id}` resource with the complete output
+    pub uri: String,
+    pub lines: usize,
+    pub bytes: usize,
+}
+
+impl LogHandle {
+    /// Text pointer to the log, for the end of a tool's text result.
+    pub fn render(&self) -> String {
+        format!(
+            "Full output: {} lines in run {}, read it with get_command_output or {}",
+            self.lines, self.run_id, self.uri
+        )
+    }
+}
+
+/// `<bench>/.frappe-mcp/runs`
+pub fn runs_dir(config: &Config) -> PathBuf {
+    cache::state_dir(config).join("runs")
+}
+
+pub fn uri(run_id: &str) -> String {
+    FrappeUri::Run(run_id.to_string()).to_uri()
+}
+
+/// Start the log of a new run, returning its id and file.
+pub fn create(dir: &Path) -> io::Result<(String, File)> {
+    fs::create_dir_all(dir)?;
+    prune(dir, KEEP_RUNS.saturating_sub(1));
+    // Zero-padded so that ids sort by start time, as `prune` expects
+    let run_id = format!(
+        "{}-{:07}-{:06}",
+        Utc::now().format("%Y%m%d-%H%M%S"),
+        std::process::id(),
+        NEXT_RUN.fetch_add(1, Ordering::Relaxed)
+    );
+    let file = File::create(dir.join(format!("{}.log", run_id)))?;
+    Ok((run_id, file))
+}
+
+/// Path of a run's log; `None` for ids that could escape the runs directory.
+pub fn log_path(dir: &Path, run_id: &str) -> Option<PathBuf> {
+    let valid = !run_id.is_empty()
+        && run_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-');
+    valid.then(|| dir.join(format!("{}.log", run_id)))
+}
+
+/// Keep the `keep` newest logs (run ids start with their start time).
+fn prune(dir: &Path, keep: usize) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut logs: Vec<PathBuf> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|e| e == "log"))
+        .collect();
+    if logs.len() <= keep {
+        return;
+    }
+    logs.sort();
+    for old in &logs[..logs.len() - keep] {
+        let _ = fs::remove_file(old);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_create_and_prune() {
+        let dir =
+            std::env::temp_dir().join(format!("frappe-mcp-runlog-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let (run_id, mut file) = create(&dir).unwrap();
+        writeln!(file, "hello").unwrap();
+        let path = log_path(&dir, &run_id).unwrap();
+        assert_eq!(fs::read_to_string(path).unwrap(), "hello\n");
+        assert!(log_path(&dir, "../../etc/passwd").is_none());
+        assert!(log_path(&dir, "").is_none());
+
+        for name in [
+            "20200101-000000-0000001-000009",
+            "20200101-000000-0000001-000010",
+        ] {
+            File::create(dir.join(format!("{}.log", name))).unwrap();
+        }
+        prune(&dir, 2);
+        let mut left: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .flatten()
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            .collect();
+        left.sort();
+        assert_eq!(
+            left,
+            vec![
+                "20200101-000000-0000001-000010.log".to_string(),
+                format!("{}.log", run_id)
+            ]
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -10,23 +10,43 @@
 // is strictly forbidden unless prior written permission is obtained
 // from Nuwaira.
 #![allow(dead_code)]
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
+use crate::audit::{self, AuditEntry};
+use crate::cache::AnalysisCache;
 use crate::config::Config;
+use crate::coverage;
 use crate::functools;
+use crate::prompts;
+use crate::resources::{self, ResourceChange, ResourceNotifier};
+use crate::runlog;
+use crate::shellutil::{CommandOutput, ProgressReporter, RunOptions};
 use crate::{analyze::AnalyzedData, stringutil::to_snakec_var};
+use anyhow::Context;
+use rmcp::tool_router;
 use rmcp::{
-    handler::server::{router::prompt::PromptRouter, tool::ToolRouter, wrapper::Parameters},
+    handler::server::{
+        router::prompt::PromptRouter,
+        tool::{ToolCallContext, ToolRouter},
+        wrapper::Parameters,
+    },
     model::*,
-    prompt_handler, prompt_router, schemars,
-    service::RequestContext,
+    prompt, prompt_handler, prompt_router, schemars,
+    service::{NotificationContext, RequestContext},
     tool,
-    transport::stdio,
+    transport::{
+        sse_server::{SseServer, SseServerConfig},
+        stdio,
+        streamable_http_server::{session::local::LocalSessionManager, StreamableHttpService},
+    },
     ErrorData as McpError, RoleServer, ServerHandler, ServiceExt,
 };
-use rmcp::{tool_handler, tool_router};
 use serde::{Deserialize, Serialize};
-use serde_json::json;
+use tokio_util::sync::CancellationToken;
 use tracing_subscriber::EnvFilter;
 
 // -----------------------------
@@ -49,6 +69,10 @@ pub struct FindSymbolsArgs {
     /// Maximum number of matches to return (default 50)
     #[serde(default)]
     pub limit: Option<usize>,
+
+    /// Restrict to a single app of the bench (optional, default: all analyzed apps)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app: Option<String>,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
@@ -73,6 +97,10 @@ pub struct GetDoctypeArgs {
 
     /// When true, return only the JSON content of the DocType
     pub json_only: Option<bool>,
+
+    /// Restrict to a single app of the bench (optional, default: all analyzed apps)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app: Option<String>,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
@@ -157,16 +185,78 @@ pub struct RunTestsArgs {
     /// Specific test to run, e.g., "test_method_name" (optional)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub test: Option<String>,
+
+    /// Restrict to a single app of the bench (optional, default: all analyzed apps)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app: Option<String>,
+
+    /// Run only the tests that failed in the last run on this site, instead of module/doctype/test
+    #[serde(default)]
+    pub rerun_failed: bool,
+
+    /// Collect line coverage and report it per module, DocType controller and uncovered function
+    #[serde(default)]
+    pub coverage: bool,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct TestsForChangesArgs {
+    /// Changed files, absolute or relative to the bench, its `apps/` or the main app (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub files: Option<Vec<String>>,
+
+    /// Unified diff to take the changed files from, instead of `files` (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff: Option<String>,
+
+    /// Git ref the apps' work trees are compared with when neither `files` nor `diff` is given (default: HEAD)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base: Option<String>,
+
+    /// Hops followed back through Link, Table and Select fields to DocTypes using an affected one (default: 1)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub link_depth: Option<usize>,
+
+    /// Run the selected test modules, as run_tests does
+    #[serde(default)]
+    pub run: bool,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
 pub struct AnalyzeLinksArgs {
-    /// DocType name to analyze relationships for
-    pub doctype: String,
+    /// DocType name to analyze relationships for (either this or `module`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub doctype: Option<String>,
 
-    /// Maximum depth for relationship traversal (default: 2)
+    /// Module whose DocTypes to analyze relationships for (either this or `doctype`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub module: Option<String>,
+
+    /// Maximum depth for relationship traversal (default: 2 from a DocType, 0 from a module)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub depth: Option<usize>,
+
+    /// Restrict to a single app of the bench (optional, default: all analyzed apps)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app: Option<String>,
+
+    /// Text view: "text" (default), "mermaid" (erDiagram), "dot" (Graphviz) or "json" (node/edge list)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<functools::GraphFormat>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct FindReferencingDoctypesArgs {
+    /// DocType the references point at (e.g., "Customer")
+    pub doctype: String,
+
+    /// Also list the Dynamic Link fields whose Select options offer the DocType (default: false)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_dynamic: Option<bool>,
+
+    /// Only references from DocTypes of this app (optional, default: all analyzed apps)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app: Option<String>,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
@@ -215,12 +305,72 @@ pub struct GetFieldUsageArgs {
     /// Maximum number of occurrences to return (default: 10)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub limit: Option<usize>,
+
+    /// Restrict to a single app of the bench (optional, default: all analyzed apps)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, schemars::JsonSchema)]
-pub struct ExamplePromptArgs {
-    /// A message to put in the prompt
-    pub message: String,
+pub struct AddDoctypeFieldPromptArgs {
+    /// DocType to add the field to (e.g., "Sales Invoice")
+    pub doctype: String,
+
+    /// Field name in snake_case (e.g., "customer_group")
+    pub fieldname: String,
+
+    /// Field type, e.g., "Data", "Link", "Table", "Currency"
+    pub fieldtype: String,
+
+    /// Field options, e.g., the target DocType of a Link (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub options: Option<String>,
+
+    /// Restrict to a single app of the bench (optional, default: all analyzed apps)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct ScriptReportPromptArgs {
+    /// DocType the report is built over (e.g., "Sales Invoice")
+    pub doctype: String,
+
+    /// Report name (optional, default: "<DocType> Report")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub report_name: Option<String>,
+
+    /// Restrict to a single app of the bench (optional, default: all analyzed apps)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct DebugTestPromptArgs {
+    /// DocType whose test fails (e.g., "Sales Invoice")
+    pub doctype: String,
+
+    /// Failing test method, e.g., "test_submit" (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub test: Option<String>,
+
+    /// Restrict to a single app of the bench (optional, default: all analyzed apps)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct WhitelistedApiPromptArgs {
+    /// Python function name of the API method (e.g., "get_outstanding")
+    pub method: String,
+
+    /// DocType the method works on (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub doctype: Option<String>,
+
+    /// App to put the method in (optional, default: the main app)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, schemars::JsonSchema)]
@@ -233,6 +383,10 @@ pub struct RunBenchCommandArgs {
 pub struct RunMariadbCommandArgs {
     /// SQL query to execute via bench mariadb command
     pub sql: String,
+
+    /// Confirm running statements that change data or schema, when the policy asks for it
+    #[serde(default)]
+    pub confirm: bool,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
@@ -255,6 +409,24 @@ pub struct RunBenchExecuteArgs {
     pub kwargs: Option<String>,
 }
 
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct GetCommandOutputArgs {
+    /// `run_id` from the `log` of a bench, database or test run
+    pub run_id: String,
+
+    /// Lines to skip, counting only matching lines when `grep` is set (default 0)
+    #[serde(default)]
+    pub offset: Option<usize>,
+
+    /// Maximum number of lines to return (default 200)
+    #[serde(default)]
+    pub limit: Option<usize>,
+
+    /// Regular expression the returned lines must match (optional)
+    #[serde(default)]
+    pub grep: Option<String>,
+}
+
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
 pub struct CreateTestTemplateArgs {
     /// DocType name (e.g., "Sales Invoice")
@@ -270,6 +442,10 @@ pub struct ListDoctypesArgs {
     /// Optional module filter to list DocTypes only from a specific module
     #[serde(skip_serializing_if = "Option::is_none")]
     pub module: Option<String>,
+
+    /// Restrict to a single app of the bench (optional, default: all analyzed apps)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app: Option<String>,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
@@ -329,6 +505,11 @@ pub struct ReadFrappeDocArgs {
 // Server impl
 // -----------------------------
 
+/// JSON schema of a tool's `structured_content`.
+fn output_schema<T: schemars::JsonSchema + 'static>() -> Arc<JsonObject> {
+    rmcp::handler::server::common::cached_schema_for_type::<T>()
+}
+
 #[derive(Clone)]
 pub struct ProjectExplorer {
     _state_counter: Arc<Mutex<i32>>, // example state (unused but shows pattern)
@@ -336,23 +517,106 @@ pub struct ProjectExplorer {
     prompt_router: PromptRouter<ProjectExplorer>,
     config: Config,
     anal: Arc<Mutex<AnalyzedData>>,
+    /// Identifies the client session this clone serves
+    session: u64,
+    notifier: ResourceNotifier,
 }
 
+static NEXT_SESSION: AtomicU64 = AtomicU64::new(1);
+
 #[tool_router]
 #[prompt_router]
 impl ProjectExplorer {
     pub fn new(config: Config, anal: AnalyzedData) -> Self {
+        // Tools turned off by the policy are neither listed nor callable
+        let mut tool_router = Self::tool_router();
+        for tool in config.policy.disabled_tools() {
+            tool_router.remove_route(tool);
+        }
         Self {
             _state_counter: Arc::new(Mutex::new(0)),
-            tool_router: Self::tool_router(),
+            tool_router,
             prompt_router: Self::prompt_router(),
             config,
             anal: Arc::new(Mutex::new(anal)),
+            session: 0,
+            notifier: ResourceNotifier::default(),
         }
     }
 
-    fn create_resource_text(&self, uri: &str, name: &str) -> Resource {
-        RawResource::new(uri, name.to_string()).no_annotation()
+    /// A clone for a new client session, sharing the analyzed data.
+    pub fn for_session(&self) -> Self {
+        let mut explorer = self.clone();
+        explorer.session = NEXT_SESSION.fetch_add(1, Ordering::Relaxed);
+        explorer
+    }
+
+    /// Server instructions listing the tools the policy leaves enabled, the
+    /// prompts and the resource templates.
+    fn instructions(&self) -> String {
+        let mut tools: Vec<String> = self
+            .tool_router
+            .list_all()
+            .into_iter()
+            .map(|t| t.name.to_string())
+            .collect();
+        tools.sort();
+        let mut prompts: Vec<String> = self
+            .prompt_router
+            .list_all()
+            .into_iter()
+            .map(|p| p.name)
+            .collect();
+        prompts.sort();
+        let resources: Vec<String> = resources::templates()
+            .into_iter()
+            .map(|t| t.raw.uri_template)
+            .collect();
+        format!(
+            "Frappe Based Project Explorer server. Tools: {}. Prompts: {}. Resources: {}.",
+            tools.join(", "),
+            prompts.join(", "),
+            resources.join(", ")
+        )
+    }
+
+    /// Append a finished call to the audit log, when one is configured.
+    fn audit(
+        &self,
+        tool: &str,
+        arguments: Option<&JsonObject>,
+        result: &Result<CallToolResult, McpError>,
+        started: Instant,
+    ) {
+        let Some(path) = self.config.audit_log_path() else {
+            return;
+        };
+        let entry = AuditEntry::from_call(self.session, tool, arguments, result, started.elapsed());
+        if let Err(e) = audit::append(&path, &entry) {
+            tracing::warn!("Failed to write audit log {}: {}", path.display(), e);
+        }
+    }
+
+    /// Options of a `bench` run by `tool`: its configured timeout, the request's
+    /// cancellation and progress token, and a run log under the bench.
+    fn run_options(&self, tool: &str, context: &RequestContext<RoleServer>) -> RunOptions {
+        RunOptions {
+            timeout: self.config.tool_timeout(tool),
+            cancel: context.ct.clone(),
+            progress: ProgressReporter::from_context(context),
+            log_dir: Some(runlog::runs_dir(&self.config)),
+        }
+    }
+
+    /// Keep `anal` in sync with the app sources while the server is running.
+    pub fn watch_app(&self) {
+        if let Err(e) = crate::watcher::spawn(
+            self.config.clone(),
+            self.anal.clone(),
+            self.notifier.clone(),
+        ) {
+            tracing::warn!("File watcher disabled: {}", e);
+        }
     }
 
     // -------------------------
@@ -360,7 +624,10 @@ impl ProjectExplorer {
     // -------------------------
 
     /// find_symbols: search for a symbol across the project source files.
-    #[tool(description = "Search for symbols across the app source files")]
+    #[tool(
+        description = "Search for symbols across the app source files",
+        output_schema = output_schema::<functools::SymbolSearch>()
+    )]
     fn find_symbols(
         &self,
         Parameters(args): Parameters<FindSymbolsArgs>,
@@ -373,6 +640,7 @@ impl ProjectExplorer {
             args.search_in,
             args.fuzzy,
             args.limit,
+            args.app,
         )
     }
 
@@ -394,30 +662,53 @@ impl ProjectExplorer {
     //}
 
     /// get_doctype: get DocType information by name, eg: "Sales Invoice"
-    #[tool(description = "Search and get a DocType information (by name) in the app")]
-    fn get_doctype(
+    #[tool(
+        description = "Search and get a DocType information (by name) in the app",
+        output_schema = output_schema::<functools::DocTypeInfo>()
+    )]
+    async fn get_doctype(
         &self,
         Parameters(args): Parameters<GetDoctypeArgs>,
+        context: RequestContext<RoleServer>,
     ) -> Result<CallToolResult, McpError> {
-        let anal = self.anal.lock().unwrap();
-        functools::get_doctype(
-            &self.config,
-            &anal,
-            &args.name,
-            args.json_only.unwrap_or(false),
-        )
+        let json_only = args.json_only.unwrap_or(false);
+        let found = {
+            let anal = self.anal.lock().unwrap();
+            functools::get_doctype(
+                &self.config,
+                &anal,
+                &args.name,
+                json_only,
+                args.app.as_deref(),
+            )
+        };
+        if let Some(result) = found {
+            return result;
+        }
+
+        // Only the site knows it: this runs bench, so it is audited
+        let started = Instant::now();
+        let options = self.run_options("get_doctype", &context);
+        let result =
+            functools::get_doctype_from_database(&self.config, &args.name, json_only, &options)
+                .await;
+        let arguments = serde_json::json!({ "name": args.name, "json_only": json_only });
+        self.audit("get_doctype", arguments.as_object(), &result, started);
+        result
     }
 
     /// create_doctype: Generate boilerplate DocType structure
     #[tool(
-        description = "Generate boilerplate DocType structure with JSON metadata, Python controller, and JS form files"
+        description = "Generate boilerplate DocType structure with JSON metadata, Python controller, and JS form files",
+        output_schema = output_schema::<functools::GeneratedFiles>()
     )]
     fn create_doctype(
         &self,
         Parameters(args): Parameters<CreateDoctypeArgs>,
     ) -> Result<CallToolResult, McpError> {
         let mut anal = self.anal.lock().unwrap();
-        functools::create_doctype(
+        let doctypes_before = anal.doctypes.len();
+        let result = functools::create_doctype(
             &self.config,
             &mut anal,
             &args.name,
@@ -467,36 +758,167 @@ impl ProjectExplorer {
                 is_submittable: args.is_submittable.unwrap_or(false),
                 is_child_table: args.is_child_table.unwrap_or(false),
             }),
-        )
+        );
+
+        // Clients listing resources should pick up the new DocType
+        let added = anal.doctypes.len() != doctypes_before;
+        drop(anal);
+        if added {
+            self.notifier.publish(&[ResourceChange::ListChanged]);
+        }
+        result
     }
 
     /// run_tests: Execute unit tests for specific modules or doctypes
     #[tool(
-        description = "Execute unit tests for specific modules, DocTypes, or entire app using bench run-tests"
+        description = "Execute unit tests for specific modules, DocTypes, or entire app using bench run-tests. \
+            Failures are parsed into `results`; `rerun_failed: true` runs only the tests that failed last time.",
+        output_schema = output_schema::<functools::TestRun>()
     )]
-    fn run_tests(
+    async fn run_tests(
         &self,
         Parameters(args): Parameters<RunTestsArgs>,
+        context: RequestContext<RoleServer>,
     ) -> Result<CallToolResult, McpError> {
-        let anal = self.anal.lock().unwrap();
-        functools::run_tests(&self.config, &anal, args.module, args.doctype, args.test)
+        if let Err(reason) = self.config.policy.check_bench(&["run-tests"]) {
+            mcp_error!(format!("Denied by policy: {}", reason));
+        }
+        if args.rerun_failed
+            && (args.module.is_some()
+                || args.doctype.is_some()
+                || args.test.is_some()
+                || args.coverage)
+        {
+            mcp_error!(
+                "`rerun_failed` can't be combined with module, doctype, test or coverage"
+                    .to_string()
+            );
+        }
+        // The analyzed data is only needed to resolve the tests, not while they run
+        let (runs, coverage) = {
+            let anal = self.anal.lock().unwrap();
+            let runs = if args.rerun_failed {
+                functools::rerun_failed_args(&self.config)
+            } else {
+                functools::test_args(
+                    &self.config,
+                    &anal,
+                    args.module,
+                    args.doctype,
+                    args.test,
+                    args.app,
+                )
+                .map(|test_args| vec![test_args])
+            };
+            let coverage = args
+                .coverage
+                .then(|| coverage::targets(&self.config, &anal));
+            (runs, coverage)
+        };
+        let runs = match runs {
+            Ok(runs) => runs,
+            Err(e) => mcp_error!(e),
+        };
+        let options = self.run_options("run_tests", &context);
+        functools::run_tests(&self.config, runs, coverage.as_ref(), &options).await
+    }
+
+    /// tests_for_changes: Select the tests affected by changed files
+    #[tool(
+        description = "Select the DocType test modules affected by changed files: a list of files, a unified diff, \
+            or by default the apps' uncommitted git changes. Uses the DocTypes' directories, field usage of \
+            changed shared code and Link/Table fields pointing at affected DocTypes. `run: true` runs them.",
+        output_schema = output_schema::<functools::TestSelection>()
+    )]
+    async fn tests_for_changes(
+        &self,
+        Parameters(args): Parameters<TestsForChangesArgs>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        let files = match (args.files, args.diff) {
+            (Some(files), _) => files,
+            (None, Some(diff)) => functools::diff_files(&diff),
+            (None, None) => {
+                match functools::git_changed_files(&self.config, args.base.as_deref()).await {
+                    Ok(files) => files,
+                    Err(e) => mcp_error!(e),
+                }
+            }
+        };
+        if !args.run {
+            let anal = self.anal.lock().unwrap();
+            return functools::tests_for_changes(&self.config, &anal, &files, args.link_depth);
+        }
+        // Running goes through run_tests, so its policy applies
+        if !self.config.policy.tool_enabled("run_tests") {
+            mcp_error!("Denied by policy: run_tests is disabled".to_string());
+        }
+        if let Err(reason) = self.config.policy.check_bench(&["run-tests"]) {
+            mcp_error!(format!("Denied by policy: {}", reason));
+        }
+        if files.is_empty() {
+            mcp_error!("No changed files".to_string());
+        }
+        let selection = {
+            let anal = self.anal.lock().unwrap();
+            functools::select_tests(&self.config, &anal, &files, args.link_depth)
+        };
+        if selection.test_modules.is_empty() {
+            return Ok(selection.into_result(None));
+        }
+        let runs = selection.runs(&self.config.site);
+        let options = self.run_options("run_tests", &context);
+        let run = functools::run_tests(&self.config, runs, None, &options).await?;
+        Ok(selection.into_result(Some(run)))
     }
 
     /// analyze_links: Map relationships between DocTypes
     #[tool(
-        description = "Analyze and map relationships between DocTypes by examining Link, Table, and Select fields"
+        description = "Analyze and map relationships between DocTypes by examining Link, Table, Table MultiSelect, Select and Dynamic Link fields \
+            and fetch_from expressions, \
+            from a DocType or a whole module. `format` exports the graph as a Mermaid erDiagram, Graphviz DOT or a JSON node/edge list.",
+        output_schema = output_schema::<functools::LinkGraph>()
     )]
     fn analyze_links(
         &self,
         Parameters(args): Parameters<AnalyzeLinksArgs>,
     ) -> Result<CallToolResult, McpError> {
         let anal = self.anal.lock().unwrap();
-        functools::analyze_links(&self.config, &anal, &args.doctype, args.depth)
+        functools::analyze_links(
+            &self.config,
+            &anal,
+            args.doctype.as_deref(),
+            args.module.as_deref(),
+            args.depth,
+            args.app,
+            args.format,
+        )
+    }
+
+    /// find_referencing_doctypes: Incoming references of a DocType
+    #[tool(
+        description = "Find the DocTypes pointing at a DocType through Link, Table and Table MultiSelect fields, \
+            the incoming side of analyze_links. Check it before renaming or deleting a DocType.",
+        output_schema = output_schema::<functools::DocTypeReferences>()
+    )]
+    fn find_referencing_doctypes(
+        &self,
+        Parameters(args): Parameters<FindReferencingDoctypesArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let anal = self.anal.lock().unwrap();
+        functools::find_referencing_doctypes(
+            &self.config,
+            &anal,
+            &args.doctype,
+            args.include_dynamic,
+            args.app,
+        )
     }
 
     /// create_web_page: Generate boilerplate web page files with HTML, CSS, and JavaScript
     #[tool(
-        description = "Generate boilerplate web page files with HTML, CSS, and JavaScript structure"
+        description = "Generate boilerplate web page files with HTML, CSS, and JavaScript structure",
+        output_schema = output_schema::<functools::GeneratedFiles>()
     )]
     fn create_web_page(
         &self,
@@ -516,7 +938,8 @@ impl ProjectExplorer {
     /// create_custom_page: Generate Frappe custom page scaffolding with forms and API endpoints
     #[tool(
         description = "Generate Frappe custom page scaffolding with forms and backend API endpoints. \
-            Creates JSON, Python, and JavaScript files for a complete custom page structure."
+            Creates JSON, Python, and JavaScript files for a complete custom page structure.",
+        output_schema = output_schema::<functools::GeneratedFiles>()
     )]
     fn create_custom_page(
         &self,
@@ -535,7 +958,8 @@ impl ProjectExplorer {
 
     /// find_field_usage: Search for references to a specific field within a DocType
     #[tool(
-        description = "Search for references to a specific field of a DocType, this can help identify where a field is used in code"
+        description = "Search for references to a specific field of a DocType, this can help identify where a field is used in code",
+        output_schema = output_schema::<functools::FieldUsage>()
     )]
     fn find_field_usage(
         &self,
@@ -548,66 +972,112 @@ impl ProjectExplorer {
             &args.doctype,
             &args.field_name,
             args.limit,
+            args.app,
         )
     }
 
     /// run_bench_command: Run arbitrary `bench` command with arguments, e.g: `migrate`
     #[tool(
-        description = "Run arbitrary bench command with args, e.g: `migrate`, the `--site` is auto-added, no need to include it."
+        description = "Run arbitrary bench command with args, e.g: `migrate`, the `--site` is auto-added, no need to include it.",
+        output_schema = output_schema::<CommandOutput>()
     )]
-    fn run_bench_command(
+    async fn run_bench_command(
         &self,
         Parameters(args): Parameters<RunBenchCommandArgs>,
+        context: RequestContext<RoleServer>,
     ) -> Result<CallToolResult, McpError> {
+        if let Err(reason) = self.config.policy.check_bench(&args.args) {
+            mcp_error!(format!("Denied by policy: {}", reason));
+        }
         functools::run_bench_command(
             &self.config,
-            &self.anal.lock().unwrap(),
             &args.args.iter().map(|s| s.as_str()).collect::<Vec<&str>>(),
+            &self.run_options("run_bench_command", &context),
         )
+        .await
     }
 
     /// get_doctype_db_schema: Get the database table schema for a specific DocType
     #[tool(
-        description = "Get the database table schema for a specific DocType, this will execute SQL query into the database."
+        description = "Get the database table schema for a specific DocType, this will execute SQL query into the database.",
+        output_schema = output_schema::<functools::DbSchema>()
     )]
-    fn get_doctype_db_schema(
+    async fn get_doctype_db_schema(
         &self,
         Parameters(args): Parameters<GetDoctypeDbSchemaArgs>,
+        context: RequestContext<RoleServer>,
     ) -> Result<CallToolResult, McpError> {
-        functools::get_doctype_db_schema(&self.config, &self.anal.lock().unwrap(), &args.name)
+        let limits = self.run_options("get_doctype_db_schema", &context);
+        functools::get_doctype_db_schema(&self.config, &args.name, &limits).await
     }
 
     /// run_db_command: Execute SQL query via bench mariadb command
-    #[tool(description = "Execute SQL query via bench mariadb command")]
-    fn run_db_command(
+    #[tool(
+        description = "Execute SQL query via bench mariadb command. Statements are checked against the server policy \
+            (SELECT-only by default) and unbounded SELECTs over DocType tables get a LIMIT.",
+        output_schema = output_schema::<functools::DbQuery>()
+    )]
+    async fn run_db_command(
         &self,
         Parameters(args): Parameters<RunMariadbCommandArgs>,
+        context: RequestContext<RoleServer>,
     ) -> Result<CallToolResult, McpError> {
-        functools::run_db_command(&self.config, &self.anal.lock().unwrap(), &args.sql)
+        let limits = self.run_options("run_db_command", &context);
+        functools::run_db_command(&self.config, &args.sql, args.confirm, &limits).await
     }
 
     /// bench_execute: Execute Frappe function via bench execute command
     #[tool(
         description = "Execute Frappe function via bench execute command with optional args and kwargs.\n\
         You don't need to escape quotes inside args.\n\
-        Example: bench_execute(frappe.db.get_list, Invoice, {fields:[\"invoice_code\"]})"
+        Example: bench_execute(frappe.db.get_list, Invoice, {fields:[\"invoice_code\"]})",
+        output_schema = output_schema::<CommandOutput>()
     )]
-    fn bench_execute(
+    async fn bench_execute(
         &self,
         Parameters(args): Parameters<RunBenchExecuteArgs>,
+        context: RequestContext<RoleServer>,
     ) -> Result<CallToolResult, McpError> {
+        if let Err(reason) = self
+            .config
+            .policy
+            .check_bench(&["execute", args.frappe_function.as_str()])
+        {
+            mcp_error!(format!("Denied by policy: {}", reason));
+        }
         functools::bench_execute(
             &self.config,
-            &self.anal.lock().unwrap(),
             &args.frappe_function,
             args.args.as_deref(),
             args.kwargs.as_deref(),
+            &self.run_options("bench_execute", &context),
+        )
+        .await
+    }
+
+    /// get_command_output: Page through or search the full output of a bench run
+    #[tool(
+        description = "Read the full output of a bench, database or test run by the run_id in its result's `log`. \
+            Page with offset/limit or filter lines with a grep regex instead of running the command again.",
+        output_schema = output_schema::<functools::CommandLog>()
+    )]
+    fn get_command_output(
+        &self,
+        Parameters(args): Parameters<GetCommandOutputArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        functools::get_command_output(
+            &self.config,
+            &args.run_id,
+            args.offset,
+            args.limit,
+            args.grep.as_deref(),
         )
     }
 
     /// search_frappe_docs: Search embedded Frappe documentation
     #[tool(
-        description = "Search through Frappe framework documentation. Supports fuzzy and exact search, category filtering, and returns relevant snippets."
+        description = "Search through Frappe framework documentation. Supports fuzzy and exact search, category filtering, and returns relevant snippets.",
+        output_schema = output_schema::<functools::DocSearch>()
     )]
     fn search_frappe_docs(
         &self,
@@ -624,7 +1094,8 @@ impl ProjectExplorer {
 
     /// read_frappe_doc: Read a specific Frappe documentation file
     #[tool(
-        description = "Read the full content of a specific Frappe documentation by its ID (e.g., 'a7b9c3', 'd8f2e1'). Use search_frappe_docs to find document IDs."
+        description = "Read the full content of a specific Frappe documentation by its ID (e.g., 'a7b9c3', 'd8f2e1'). Use search_frappe_docs to find document IDs.",
+        output_schema = output_schema::<functools::FrappeDocPage>()
     )]
     fn read_frappe_doc(
         &self,
@@ -634,8 +1105,11 @@ impl ProjectExplorer {
     }
 
     /// create_test_template: Create test template files for a Frappe DocType
-    #[tool(description = "Create test template files for a Frappe DocType. \
-            The function creates comprehensive test scaffolding, proper imports, FrappeTestCase inheritance, setUp/tearDown methods, and dependency declarations.")]
+    #[tool(
+        description = "Create test template files for a Frappe DocType. \
+            The function creates comprehensive test scaffolding, proper imports, FrappeTestCase inheritance, setUp/tearDown methods, and dependency declarations.",
+        output_schema = output_schema::<functools::GeneratedFiles>()
+    )]
     fn create_test_template(
         &self,
         Parameters(args): Parameters<CreateTestTemplateArgs>,
@@ -652,7 +1126,8 @@ impl ProjectExplorer {
     /// create_report: Create report template files for a Frappe Report
     #[tool(
         description = "Create report template files for starting with Frappe Report including Python logic file (.py), JavaScript filters (.js), JSON metadata (.json). \
-            Creates a complete report structure with sample filters, columns, and data processing logic."
+            Creates a complete report structure with sample filters, columns, and data processing logic.",
+        output_schema = output_schema::<functools::GeneratedFiles>()
     )]
     fn create_report(
         &self,
@@ -671,14 +1146,97 @@ impl ProjectExplorer {
 
     /// list_doctypes: List all available DocTypes in the current Frappe app
     #[tool(
-        description = "List all available DocTypes in the current Frappe app, optionally filtered by module"
+        description = "List all available DocTypes in the current Frappe app, optionally filtered by module",
+        output_schema = output_schema::<functools::DocTypeList>()
     )]
     fn list_doctypes(
         &self,
         Parameters(args): Parameters<ListDoctypesArgs>,
     ) -> Result<CallToolResult, McpError> {
         let anal = self.anal.lock().unwrap();
-        functools::list_doctypes(&self.config, &anal, args.module)
+        functools::list_doctypes(&self.config, &anal, args.module, args.app)
+    }
+
+    // -------------------------
+    // Prompts
+    // -------------------------
+
+    /// add_doctype_field: plan for adding a field to a DocType end to end.
+    #[prompt(
+        name = "add_doctype_field",
+        description = "Add a field to a DocType end to end: metadata, controller, form script, migrate and tests"
+    )]
+    fn add_doctype_field(
+        &self,
+        Parameters(args): Parameters<AddDoctypeFieldPromptArgs>,
+    ) -> Result<GetPromptResult, McpError> {
+        let anal = self.anal.lock().unwrap();
+        prompts::add_doctype_field(
+            &self.config,
+            &anal,
+            &args.doctype,
+            &args.fieldname,
+            &args.fieldtype,
+            args.options.as_deref(),
+            args.app.as_deref(),
+        )
+    }
+
+    /// write_script_report: plan for a Script Report over a DocType.
+    #[prompt(
+        name = "write_script_report",
+        description = "Write a Script Report over a DocType, grounded in its fields and links"
+    )]
+    fn write_script_report(
+        &self,
+        Parameters(args): Parameters<ScriptReportPromptArgs>,
+    ) -> Result<GetPromptResult, McpError> {
+        let anal = self.anal.lock().unwrap();
+        prompts::write_script_report(
+            &self.config,
+            &anal,
+            &args.doctype,
+            args.report_name.as_deref(),
+            args.app.as_deref(),
+        )
+    }
+
+    /// debug_doctype_test: plan for debugging a failing DocType test.
+    #[prompt(
+        name = "debug_doctype_test",
+        description = "Debug a failing test of a DocType: reproduce, check fixtures and controller hooks"
+    )]
+    fn debug_doctype_test(
+        &self,
+        Parameters(args): Parameters<DebugTestPromptArgs>,
+    ) -> Result<GetPromptResult, McpError> {
+        let anal = self.anal.lock().unwrap();
+        prompts::debug_doctype_test(
+            &self.config,
+            &anal,
+            &args.doctype,
+            args.test.as_deref(),
+            args.app.as_deref(),
+        )
+    }
+
+    /// add_whitelisted_api: plan for a new `@frappe.whitelist()` method.
+    #[prompt(
+        name = "add_whitelisted_api",
+        description = "Add a whitelisted API method with argument validation and permission checks"
+    )]
+    fn add_whitelisted_api(
+        &self,
+        Parameters(args): Parameters<WhitelistedApiPromptArgs>,
+    ) -> Result<GetPromptResult, McpError> {
+        let anal = self.anal.lock().unwrap();
+        prompts::add_whitelisted_api(
+            &self.config,
+            &anal,
+            &args.method,
+            args.doctype.as_deref(),
+            args.app.as_deref(),
+        )
     }
 }
 
@@ -686,38 +1244,58 @@ impl ProjectExplorer {
 // ServerHandler impl
 // -----------------------------
 
-#[tool_handler]
 #[prompt_handler]
 impl ServerHandler for ProjectExplorer {
+    async fn call_tool(
+        &self,
+        request: CallToolRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        if self.config.audit_log_path().is_none() || !audit::is_audited(&request.name) {
+            let tcc = ToolCallContext::new(self, request, context);
+            return self.tool_router.call(tcc).await;
+        }
+
+        let tool = request.name.to_string();
+        let arguments = request.arguments.clone();
+        let started = Instant::now();
+        let tcc = ToolCallContext::new(self, request, context);
+        let result = self.tool_router.call(tcc).await;
+        self.audit(&tool, arguments.as_ref(), &result, started);
+        result
+    }
+
+    async fn list_tools(
+        &self,
+        _request: Option<PaginatedRequestParam>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListToolsResult, McpError> {
+        Ok(ListToolsResult::with_all_items(self.tool_router.list_all()))
+    }
+
     fn get_info(&self) -> ServerInfo {
         ServerInfo {
             protocol_version: ProtocolVersion::V_2024_11_05,
             capabilities: ServerCapabilities::builder()
                 .enable_prompts()
                 .enable_resources()
+                .enable_resources_subscribe()
+                .enable_resources_list_changed()
                 .enable_tools()
                 .build(),
             server_info: Implementation::from_build_env(),
-            instructions: Some(
-                "Frappe Based Project Explorer server. Tools: find_symbols, get_function_signature, get_doctype, list_doctypes, create_doctype_template, create_report_template, create_test_template, create_web_page, run_tests, analyze_links, find_field_usage, echo. Prompt: example_prompt."
-                    .to_string(),
-            ),
+            instructions: Some(self.instructions()),
         }
     }
 
     async fn list_resources(
         &self,
-        _request: Option<PaginatedRequestParam>,
+        request: Option<PaginatedRequestParam>,
         _: RequestContext<RoleServer>,
     ) -> Result<ListResourcesResult, McpError> {
-        // Advertise CWD and a sample memo
-        Ok(ListResourcesResult {
-            resources: vec![
-                self.create_resource_text("cwd:///", "Current Working Directory"),
-                self.create_resource_text("memo://explorer-notes", "Explorer Notes"),
-            ],
-            next_cursor: None,
-        })
+        let cursor = request.and_then(|r| r.cursor);
+        let anal = self.anal.lock().unwrap();
+        resources::list(&anal, cursor.as_deref())
     }
 
     async fn read_resource(
@@ -725,40 +1303,42 @@ impl ServerHandler for ProjectExplorer {
         ReadResourceRequestParam { uri }: ReadResourceRequestParam,
         _: RequestContext<RoleServer>,
     ) -> Result<ReadResourceResult, McpError> {
-        match uri.as_str() {
-            "cwd:///" => {
-                let cwd = std::env::current_dir()
-                    .map(|p| p.display().to_string())
-                    .unwrap_or_else(|_| "<unknown>".to_string());
-                Ok(ReadResourceResult {
-                    contents: vec![ResourceContents::text(&cwd, uri)],
-                })
-            }
-            "memo://explorer-notes" => {
-                let memo = "\
-                    Explorer Notes\n\n\
-                    Use tools:\n\
-                    - find_symbols { name, search_in?, fuzzy?, limit? }\n\
-                    - get_function_signature { name, module?, builtin? }\n\
-                    - get_doctype { name, json_only? }\n\
-                    - list_doctypes { module? }\n\
-                    - create_doctype_template { name, module, fields? }\n\
-                    - create_report_template { report_name, module, report_type?, ref_doctype? }\n\
-                    - create_test_template { doctype, doctype_dependencies? }\n\
-                    - create_web_page { path, title?, include_css?, include_js? }\n\
-                    - run_tests { module?, doctype?, test_type? }\n\
-                    - analyze_links { doctype, depth? }\n\
-                    - find_field_usage { doctype, field_name, limit? }
-                ";
-                Ok(ReadResourceResult {
-                    contents: vec![ResourceContents::text(memo, uri)],
-                })
-            }
-            _ => Err(McpError::resource_not_found(
-                "resource_not_found",
-                Some(json!({ "uri": uri })),
-            )),
-        }
+        let anal = self.anal.lock().unwrap();
+        resources::read(&self.config, &anal, &uri)
+    }
+
+    async fn subscribe(
+        &self,
+        SubscribeRequestParam { uri }: SubscribeRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<(), McpError> {
+        // Updates are published under the canonical URI, whatever the client sent
+        let canonical = resources::canonical_uri(&self.anal.lock().unwrap(), &uri);
+        let Some(canonical) = canonical else {
+            return Err(McpError::resource_not_found(
+                format!("Unknown resource: {}", uri),
+                Some(serde_json::json!({ "uri": uri })),
+            ));
+        };
+        self.notifier
+            .subscribe(self.session, context.peer, &canonical);
+        Ok(())
+    }
+
+    async fn unsubscribe(
+        &self,
+        UnsubscribeRequestParam { uri }: UnsubscribeRequestParam,
+        _: RequestContext<RoleServer>,
+    ) -> Result<(), McpError> {
+        let canonical = resources::canonical_uri(&self.anal.lock().unwrap(), &uri);
+        self.notifier
+            .unsubscribe(self.session, canonical.as_deref().unwrap_or(&uri));
+        Ok(())
+    }
+
+    async fn on_initialized(&self, context: NotificationContext<RoleServer>) {
+        tracing::info!("client initialized");
+        self.notifier.register(self.session, context.peer);
     }
 
     async fn list_resource_templates(
@@ -768,7 +1348,7 @@ impl ServerHandler for ProjectExplorer {
     ) -> Result<ListResourceTemplatesResult, McpError> {
         Ok(ListResourceTemplatesResult {
             next_cursor: None,
-            resource_templates: Vec::new(),
+            resource_templates: resources::templates(),
         })
     }
 
@@ -790,14 +1370,12 @@ impl ServerHandler for ProjectExplorer {
 // Helper functions
 // -----------------------------
 
-fn should_run_analysis(config: &Config, analysis_file: &str) -> bool {
-    use std::path::Path;
-
+fn should_run_analysis(config: &Config, analysis_file: &Path) -> bool {
     // Always analyze if file doesn't exist
-    if !Path::new(analysis_file).exists() {
+    if !analysis_file.exists() {
         tracing::info!(
             "Analysis file '{}' doesn't exist, will run analysis",
-            analysis_file
+            analysis_file.display()
         );
         return true;
     }
@@ -818,35 +1396,47 @@ fn should_run_analysis(config: &Config, analysis_file: &str) -> bool {
         }
     };
 
-    // Check if modules.txt is newer
-    let modules_txt = app_path.join(&config.app_relative_path).join("modules.txt");
-    if let Ok(metadata) = std::fs::metadata(&modules_txt) {
-        if let Ok(mtime) = metadata.modified() {
-            if mtime > analysis_mtime {
-                tracing::info!("modules.txt is newer than analysis file, will run analysis");
-                return true;
+    for app in &config.app_dirs {
+        // Check if modules.txt is newer
+        let modules_txt = Path::new(&config.app_path(app))
+            .join(app)
+            .join("modules.txt");
+        if let Ok(metadata) = std::fs::metadata(&modules_txt) {
+            if let Ok(mtime) = metadata.modified() {
+                if mtime > analysis_mtime {
+                    tracing::info!(
+                        "modules.txt of '{}' is newer than analysis file, will run analysis",
+                        app
+                    );
+                    return true;
+                }
             }
         }
-    }
 
-    // Check if any doctype files are newer than analysis
-    if check_doctype_files_newer(&config, analysis_mtime) {
-        tracing::info!("Found doctype files newer than analysis file, will run analysis");
-        return true;
+        // Check if any doctype files are newer than analysis
+        if check_doctype_files_newer(config, app, analysis_mtime) {
+            tracing::info!("Found doctype files newer than analysis file, will run analysis");
+            return true;
+        }
     }
 
-    tracing::debug!("Analysis file '{}' is up to date", analysis_file);
+    tracing::debug!("Analysis file '{}' is up to date", analysis_file.display());
     false
 }
 
-fn check_doctype_files_newer(config: &Config, analysis_mtime: std::time::SystemTime) -> bool {
+fn check_doctype_files_newer(
+    config: &Config,
+    app: &str,
+    analysis_mtime: std::time::SystemTime,
+) -> bool {
     use std::fs;
     use std::path::Path;
 
-    let app_path = Path::new(&config.app_absolute_path);
-    let modules_txt = app_path.join(&config.app_relative_path).join("modules.txt");
+    let app_absolute_path = config.app_path(app);
+    let app_path = Path::new(&app_absolute_path);
+    let modules_txt = app_path.join(app).join("modules.txt");
 
-    println!("Checking doctype files in app path: {:?}", app_path);
+    tracing::debug!("Checking doctype files in app path: {:?}", app_path);
 
     // Read modules.txt to get module list
     let modules_content = match fs::read_to_string(&modules_txt) {
@@ -861,11 +1451,9 @@ fn check_doctype_files_newer(config: &Config, analysis_mtime: std::time::SystemT
         }
 
         let module_dir = to_snakec_var(module_title);
-        let module_path = app_path.join(&config.app_relative_path).join(&module_dir);
+        let module_path = app_path.join(app).join(&module_dir);
 
-        println!("Checking module: {}", module_title);
-        println!("Module path: {:?}", module_path);
-        println!("Module dir: {}", module_dir);
+        tracing::trace!("Checking module: {} ({:?})", module_title, module_path);
 
         // Check doctype directory
         let doctype_path = module_path.join("doctype");
@@ -877,7 +1465,6 @@ fn check_doctype_files_newer(config: &Config, analysis_mtime: std::time::SystemT
         // Check each doctype directory
         if let Ok(entries) = fs::read_dir(&doctype_path) {
             for entry in entries.flatten() {
-                println!("reading entry: {:?}", entry.path());
                 if !entry.file_type().map_or(false, |ft| ft.is_dir()) {
                     continue;
                 }
@@ -918,11 +1505,10 @@ fn check_doctype_files_newer(config: &Config, analysis_mtime: std::time::SystemT
 }
 
 // -----------------------------
-// Main: run over stdio
+// Main: run over stdio / HTTP
 // -----------------------------
 
-// #[tokio::main]
-pub async fn run(config: Config) -> anyhow::Result<()> {
+fn init_tracing() {
     // Pretty logs help when debugging with a local MCP client
     if std::env::var("RUST_LOG").is_err() {
         std::env::set_var("RUST_LOG", "info");
@@ -934,26 +1520,24 @@ pub async fn run(config: Config) -> anyhow::Result<()> {
         .with_writer(std::io::stderr)
         .with_ansi(false)
         .init();
+}
 
-    // Auto-run analysis if needed
-    let analysis_file = "analyzed_output.dat";
-    let should_analyze = should_run_analysis(&config, analysis_file);
-
-    if should_analyze {
-        tracing::info!("Running automatic analysis...");
-        let app_dir = &config.app_absolute_path;
-        let relative_path = format!("{}", config.app_relative_path);
+fn load_analysis(config: &Config) -> AnalyzedData {
+    let cache = AnalysisCache::for_config(config);
 
-        if let Err(e) = crate::analyze::analyze_frappe_app(app_dir, &relative_path, analysis_file) {
-            tracing::error!("Failed to run automatic analysis: {}", e);
-        } else {
-            tracing::info!("Automatic analysis completed");
+    tracing::debug!("Load analyzed data: {}", cache.path().display());
+    let previous = match cache.load() {
+        Ok(data) => Some(data),
+        Err(e) => {
+            if cache.exists() {
+                tracing::info!("Analysis cache can't be used ({:#}), will run analysis", e);
+            }
+            None
         }
-    }
+    };
 
-    tracing::debug!("Load analyzed data: {}", analysis_file);
-    let anal = AnalyzedData::from_file(analysis_file)
-        .map(|data| {
+    if let Some(data) = previous.as_ref() {
+        if !should_run_analysis(config, cache.path()) {
             tracing::debug!(
                 "Analyzed Data:\n\
                  + {} modules\n\
@@ -962,35 +1546,105 @@ pub async fn run(config: Config) -> anyhow::Result<()> {
                 data.modules.len(),
                 data.doctypes.len()
             );
-            data
-        })
-        .unwrap_or_else(|e| {
-            tracing::warn!(
-                "Failed to load analyzed data from '{}': {}. Using empty analysis.",
-                analysis_file,
-                e
-            );
-            AnalyzedData {
-                doctypes: Vec::new(),
-                modules: Vec::new(),
-                symbol_refs: None,
+            return previous.unwrap();
+        }
+    }
+
+    // Auto-run analysis if needed, re-parsing only the files that changed
+    tracing::info!("Running automatic analysis...");
+    match crate::analyze::analyze_bench(config, previous.as_ref()) {
+        Ok(data) => {
+            if let Err(e) = cache.save(&data) {
+                tracing::warn!("Failed to save analysis cache: {:#}", e);
             }
-        });
+            tracing::info!("Automatic analysis completed");
+            data
+        }
+        Err(e) => {
+            let kept = if previous.is_some() {
+                "Keeping the previous analysis"
+            } else {
+                "Using empty analysis"
+            };
+            tracing::error!("Failed to run automatic analysis: {}. {}.", e, kept);
+            previous.unwrap_or_default()
+        }
+    }
+}
+
+// #[tokio::main]
+pub async fn run(config: Config) -> anyhow::Result<()> {
+    init_tracing();
+
+    let anal = load_analysis(&config);
     tracing::info!("Starting MCP server");
 
     // Create an instance of our counter router
-    let service = ProjectExplorer::new(config, anal)
-        .serve(stdio())
-        .await
-        .inspect_err(|e| {
-            tracing::error!("serving error: {:?}", e);
-        })?;
+    let explorer = ProjectExplorer::new(config, anal);
+    explorer.watch_app();
+
+    let service = explorer.serve(stdio()).await.inspect_err(|e| {
+        tracing::error!("serving error: {:?}", e);
+    })?;
 
     service.waiting().await?;
 
     Ok(())
 }
 
+/// Serve over HTTP so several agents/editors can share one server per bench.
+///
+/// - `/mcp`: streamable HTTP transport (stateful, one session per client)
+/// - `/sse` + `/message`: legacy SSE transport for older clients
+///
+/// Every session gets a clone of the same `ProjectExplorer`, so they all
+/// share a single `AnalyzedData` and resource subscriptions are tracked per
+/// session.
+pub async fn run_http(config: Config, addr: &str) -> anyhow::Result<()> {
+    init_tracing();
+
+    let bind: SocketAddr = addr
+        .parse()
+        .with_context(|| format!("Invalid HTTP bind address '{}'", addr))?;
+
+    let anal = load_analysis(&config);
+    let explorer = ProjectExplorer::new(config, anal);
+    explorer.watch_app();
+    let ct = CancellationToken::new();
+
+    let (sse_server, sse_router) = SseServer::new(SseServerConfig {
+        bind,
+        sse_path: "/sse".to_string(),
+        post_path: "/message".to_string(),
+        ct: ct.clone(),
+        sse_keep_alive: None,
+    });
+    let sse_explorer = explorer.clone();
+    sse_server.with_service(move || sse_explorer.for_session());
+
+    let streamable = StreamableHttpService::new(
+        move || Ok(explorer.for_session()),
+        LocalSessionManager::default().into(),
+        Default::default(),
+    );
+    let router = sse_router.nest_service("/mcp", streamable);
+
+    let listener = tokio::net::TcpListener::bind(bind).await?;
+    tracing::info!(
+        "Starting MCP server on http://{} (streamable: /mcp, SSE: /sse)",
+        bind
+    );
+
+    axum::serve(listener, router)
+        .with_graceful_shutdown(async move {
+            let _ = tokio::signal::ctrl_c().await;
+            ct.cancel();
+        })
+        .await?;
+
+    Ok(())
+}
+
 // -----------------------------
 // Tests (quick sanity)
 // -----------------------------
@@ -1008,7 +1662,9 @@ mod tests {
         assert!(r.has_route("create_web_page"));
         assert!(r.has_route("create_custom_page"));
         assert!(r.has_route("run_tests"));
+        assert!(r.has_route("tests_for_changes"));
         assert!(r.has_route("analyze_links"));
+        assert!(r.has_route("find_referencing_doctypes"));
         assert!(r.has_route("find_field_usage"));
         assert!(r.has_route("run_bench_command"));
         assert!(r.has_route("bench_execute"));
@@ -1018,11 +1674,59 @@ mod tests {
         assert!(r.has_route("create_report"));
     }
 
-    // #[tokio::test]
-    // async fn prompt_has_route() {
-    //     let r = ProjectExplorer::prompt_router();
-    //     assert!(r.has_route("example_prompt"));
-    //     let attr = ProjectExplorer::example_prompt_prompt_attr();
-    //     assert_eq!(attr.name, "example_prompt");
-    // }
+    #[test]
+    fn tools_declare_output_schema() {
+        for tool in ProjectExplorer::tool_router().list_all() {
+            let schema = tool
+                .output_schema
+                .as_ref()
+                .unwrap_or_else(|| panic!("{} has no output schema", tool.name));
+            assert_eq!(schema.get("type"), Some(&serde_json::json!("object")));
+        }
+    }
+
+    #[test]
+    fn policy_removes_tools() {
+        let mut config = Config::default();
+        config.policy.read_only = true;
+        config.policy.disabled_groups = vec![crate::policy::ToolGroup::Db];
+        let explorer = ProjectExplorer::new(config, AnalyzedData::default());
+        let r = &explorer.tool_router;
+        assert!(r.has_route("get_doctype"));
+        assert!(r.has_route("run_bench_command"));
+        assert!(!r.has_route("create_doctype"));
+        assert!(!r.has_route("bench_execute"));
+        assert!(!r.has_route("run_db_command"));
+    }
+
+    #[test]
+    fn instructions_list_enabled_tools() {
+        let mut config = Config::default();
+        config.policy.disabled_groups = vec![crate::policy::ToolGroup::Db];
+        let explorer = ProjectExplorer::new(config, AnalyzedData::default());
+        let instructions = explorer.instructions();
+        for tool in explorer.tool_router.list_all() {
+            assert!(instructions.contains(tool.name.as_ref()), "{}", tool.name);
+        }
+        assert!(!instructions.contains("get_doctype_db_schema"));
+        assert!(!instructions.contains("run_db_command"));
+        assert!(instructions.contains("add_whitelisted_api"));
+        assert!(instructions.contains("frappe://run/{run_id}"));
+    }
+
+    #[test]
+    fn routers_have_prompts() {
+        let r = ProjectExplorer::prompt_router();
+        assert!(r.has_route("add_doctype_field"));
+        assert!(r.has_route("write_script_report"));
+        assert!(r.has_route("debug_doctype_test"));
+        assert!(r.has_route("add_whitelisted_api"));
+        let attr = ProjectExplorer::add_doctype_field_prompt_attr();
+        assert_eq!(attr.name, "add_doctype_field");
+        assert!(attr
+            .arguments
+            .unwrap_or_default()
+            .iter()
+            .any(|a| a.name == "fieldtype" && a.required == Some(true)));
+    }
 }
@@ -11,19 +11,411 @@
 // from Nuwaira.
 #![allow(dead_code)]
 use std::env;
-use std::ffi::OsStr;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::fs::File;
+use std::io::Write;
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Command, ExitStatus, Stdio};
+use std::time::{Duration, Instant};
 
 use crate::config::Config;
+use crate::runlog::{self, LogHandle};
+use rmcp::model::{ProgressNotificationParam, ProgressToken};
+use rmcp::schemars;
+use rmcp::service::{Peer, RequestContext, RoleServer};
+use serde::Serialize;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio_util::sync::CancellationToken;
 
-use anyhow::{bail, Context, Result};
+use anyhow::{Context, Result};
 
-pub fn run_bench_command<I, S>(config: &Config, args: I, max_chars: usize) -> Result<String>
-where
-    I: IntoIterator<Item = S>,
-    S: AsRef<OsStr>,
-{
+/// Why a `bench` process was killed before it finished.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum KillReason {
+    /// It ran longer than the tool's timeout
+    Timeout,
+    /// The client cancelled the request
+    Cancelled,
+}
+
+/// How an async `bench` run is bounded and observed.
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    pub timeout: Option<Duration>,
+    /// The request's cancellation
+    pub cancel: CancellationToken,
+    /// Receives the output lines while the command runs
+    pub progress: Option<ProgressReporter>,
+    /// Keep the full output under this directory, see [`crate::runlog`]
+    pub log_dir: Option<PathBuf>,
+}
+
+/// Minimum time between two progress notifications; lines in between are batched.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Bytes of each stream kept in memory when the full output goes to a run log
+/// and the caller asked for all of it.
+const TAIL_LIMIT: usize = 1 << 20;
+
+/// Sends output lines as `notifications/progress` for a request's progress token.
+#[derive(Clone)]
+pub struct ProgressReporter {
+    peer: Peer<RoleServer>,
+    token: ProgressToken,
+}
+
+impl fmt::Debug for ProgressReporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProgressReporter")
+            .field("token", &self.token)
+            .finish()
+    }
+}
+
+impl ProgressReporter {
+    /// `None` when the client didn't send a progress token with the request.
+    pub fn from_context(context: &RequestContext<RoleServer>) -> Option<Self> {
+        context
+            .meta
+            .get_progress_token()
+            .map(|token| ProgressReporter {
+                peer: context.peer.clone(),
+                token,
+            })
+    }
+
+    /// `progress` is the number of lines printed so far.
+    async fn report(&self, progress: usize, message: String) {
+        let param = ProgressNotificationParam {
+            progress_token: self.token.clone(),
+            progress: progress as f64,
+            total: None,
+            message: Some(message),
+        };
+        if let Err(e) = self.peer.notify_progress(param).await {
+            tracing::debug!("Failed to send progress: {}", e);
+        }
+    }
+}
+
+/// Output of a finished `bench` invocation.
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct CommandOutput {
+    /// The command line that was run
+    pub command: String,
+    /// Process exit code (`null` when killed by a signal)
+    pub exit_code: Option<i32>,
+    pub success: bool,
+    /// Trimmed stdout, truncated to the requested size
+    pub stdout: String,
+    /// Trimmed stderr, truncated to the requested size
+    pub stderr: String,
+    /// Set when the process group was killed; the output is what it printed until then
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub killed: Option<KillReason>,
+    /// Full output of the run, when it was kept
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log: Option<LogHandle>,
+}
+
+impl CommandOutput {
+    /// Plain text view: stdout and stderr, or the failure report.
+    pub fn render(&self) -> String {
+        let mut text = self.render_output();
+        if let Some(log) = &self.log {
+            text.push_str("\n\n");
+            text.push_str(&log.render());
+        }
+        text
+    }
+
+    fn render_output(&self) -> String {
+        if let Some(reason) = self.killed {
+            let why = match reason {
+                KillReason::Timeout => "the tool's timeout ran out",
+                KillReason::Cancelled => "the request was cancelled",
+            };
+            return format!(
+                "bench was killed, {}\nSTDOUT:\n{}\n\nSTDERR:\n{}",
+                why, self.stdout, self.stderr
+            );
+        }
+        if self.success {
+            format!("{}\n{}", self.stdout, self.stderr)
+        } else {
+            format!(
+                "bench exited with code {:?}\nSTDOUT:\n{}\n\nSTDERR:\n{}",
+                self.exit_code, self.stdout, self.stderr
+            )
+        }
+    }
+}
+
+/// Async `bench --site <site> <args>`, killed on timeout or cancellation.
+pub async fn run_bench_async<S: AsRef<OsStr>>(
+    config: &Config,
+    args: &[S],
+    max_chars: usize,
+    options: &RunOptions,
+) -> Result<CommandOutput> {
+    let command = bench_command_line(config, args.iter().map(|a| a.as_ref()));
+    let mut cmd = Command::new("bench");
+    set_bench_env(&mut cmd, config)?
+        .arg("--site")
+        .arg(&config.site)
+        .args(args);
+    run_command(cmd.into(), command, max_chars, options).await
+}
+
+/// Run `cmd` in its own process group, with the whole group killed when
+/// the timeout runs out or the request is cancelled; stdin is closed.
+///
+/// Output is read line by line and forwarded to `options.progress`. When the
+/// full output is kept in a run log, stdout and stderr keep their last
+/// `max_chars` instead of the first, and only that much stays in memory.
+/// Processes of the group still running a second after `cmd` exited are
+/// killed.
+pub async fn run_command(
+    mut cmd: tokio::process::Command,
+    command: String,
+    max_chars: usize,
+    options: &RunOptions,
+) -> Result<CommandOutput> {
+    cmd.stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    #[cfg(unix)]
+    cmd.process_group(0);
+
+    let mut child = cmd
+        .spawn()
+        .with_context(|| format!("Failed to spawn `{}`", command))?;
+    let pid = child.id();
+    let mut stdout = BufReader::new(child.stdout.take().context("No stdout pipe")?);
+    let mut stderr = BufReader::new(child.stderr.take().context("No stderr pipe")?);
+    let mut collector = OutputCollector::new(&command, max_chars, options);
+
+    let timeout = async {
+        match options.timeout {
+            Some(t) => tokio::time::sleep(t).await,
+            None => std::future::pending().await,
+        }
+    };
+    tokio::pin!(timeout);
+
+    // `read_until` keeps partial lines in the buffers when another branch wins
+    let (mut out_line, mut err_line) = (Vec::new(), Vec::new());
+    let (mut out_open, mut err_open) = (true, true);
+    let mut status: Option<ExitStatus> = None;
+    // Children left running with the pipes open get a moment to finish writing
+    let mut linger: Option<tokio::time::Instant> = None;
+    let killed = loop {
+        if status.is_some() && !out_open && !err_open {
+            break None;
+        }
+        tokio::select! {
+            read = stdout.read_until(b'\n', &mut out_line), if out_open => {
+                if !matches!(read, Ok(n) if n > 0) {
+                    out_open = false;
+                }
+                if !out_line.is_empty() {
+                    collector.push(false, &out_line).await;
+                    out_line.clear();
+                }
+            }
+            read = stderr.read_until(b'\n', &mut err_line), if err_open => {
+                if !matches!(read, Ok(n) if n > 0) {
+                    err_open = false;
+                }
+                if !err_line.is_empty() {
+                    collector.push(true, &err_line).await;
+                    err_line.clear();
+                }
+            }
+            exit = child.wait(), if status.is_none() => {
+                status = Some(exit.context("Failed to wait for bench")?);
+                linger = Some(tokio::time::Instant::now() + Duration::from_secs(1));
+            }
+            _ = tokio::time::sleep(PROGRESS_INTERVAL), if collector.has_pending() => {
+                collector.report().await;
+            }
+            _ = tokio::time::sleep_until(linger.unwrap_or_else(tokio::time::Instant::now)), if linger.is_some() => {
+                // Whatever `cmd` left behind doesn't outlive the tool call
+                kill_process_group(pid);
+                break None;
+            }
+            _ = &mut timeout => break Some(KillReason::Timeout),
+            _ = options.cancel.cancelled() => break Some(KillReason::Cancelled),
+        }
+    };
+
+    if let Some(reason) = killed {
+        tracing::warn!("Killing `{}`: {:?}", command, reason);
+        kill_process_group(pid);
+        status = tokio::time::timeout(Duration::from_secs(5), child.wait())
+            .await
+            .ok()
+            .and_then(|s| s.ok());
+    }
+    Ok(collector.finish(command, status, max_chars, killed).await)
+}
+
+/// Output lines read so far, mirrored to the run log and the progress notifications.
+struct OutputCollector {
+    stdout: String,
+    stderr: String,
+    /// Bytes dropped from the front of `stdout` and `stderr`
+    omitted: (usize, usize),
+    /// Bytes of each stream to keep, when the run log has all of it
+    tail_limit: Option<usize>,
+    log: Option<(String, File)>,
+    progress: Option<ProgressReporter>,
+    pending: Vec<String>,
+    last_report: Instant,
+    lines: usize,
+    bytes: usize,
+}
+
+impl OutputCollector {
+    fn new(command: &str, max_chars: usize, options: &RunOptions) -> Self {
+        let log = options.log_dir.as_ref().and_then(|dir| {
+            let (run_id, mut file) = runlog::create(dir)
+                .map_err(|e| tracing::warn!("Failed to create run log in {:?}: {}", dir, e))
+                .ok()?;
+            let _ = writeln!(file, "$ {}", command);
+            Some((run_id, file))
+        });
+        let tail_limit = log.as_ref().map(|_| match max_chars {
+            0 => TAIL_LIMIT,
+            n => n,
+        });
+        OutputCollector {
+            stdout: String::new(),
+            stderr: String::new(),
+            omitted: (0, 0),
+            tail_limit,
+            log,
+            progress: options.progress.clone(),
+            pending: Vec::new(),
+            last_report: Instant::now(),
+            lines: 0,
+            bytes: 0,
+        }
+    }
+
+    async fn push(&mut self, is_stderr: bool, raw: &[u8]) {
+        let line = String::from_utf8_lossy(raw);
+        self.lines += 1;
+        self.bytes += raw.len();
+        let (buffer, omitted) = if is_stderr {
+            (&mut self.stderr, &mut self.omitted.1)
+        } else {
+            (&mut self.stdout, &mut self.omitted.0)
+        };
+        buffer.push_str(&line);
+        if let Some(limit) = self.tail_limit {
+            // Trimmed once it holds twice the limit, so it isn't moved on every line
+            if buffer.len() > limit * 2 {
+                let mut start = buffer.len() - limit;
+                while !buffer.is_char_boundary(start) {
+                    start += 1;
+                }
+                buffer.drain(..start);
+                *omitted += start;
+            }
+        }
+        if let Some((_, file)) = &mut self.log {
+            let newline: &[u8] = if raw.ends_with(b"\n") { b"" } else { b"\n" };
+            let _ = file.write_all(raw).and_then(|_| file.write_all(newline));
+        }
+        if self.progress.is_some() {
+            self.pending.push(line.trim_end().to_string());
+            if self.last_report.elapsed() >= PROGRESS_INTERVAL {
+                self.report().await;
+            }
+        }
+    }
+
+    fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Send the batched lines as one notification.
+    async fn report(&mut self) {
+        self.last_report = Instant::now();
+        if let Some(progress) = &self.progress {
+            if !self.pending.is_empty() {
+                let message = self.pending.join("\n");
+                self.pending.clear();
+                progress.report(self.lines, message).await;
+            }
+        }
+    }
+
+    async fn finish(
+        mut self,
+        command: String,
+        status: Option<ExitStatus>,
+        max_chars: usize,
+        killed: Option<KillReason>,
+    ) -> CommandOutput {
+        self.report().await;
+        let log = self.log.take().map(|(run_id, _)| LogHandle {
+            uri: runlog::uri(&run_id),
+            run_id,
+            lines: self.lines,
+            bytes: self.bytes,
+        });
+        // With the full log kept, the end of the output is the useful part
+        let truncate = |output: &str, omitted: usize| {
+            if log.is_some() {
+                truncate_output_tail(output.trim(), max_chars, omitted)
+            } else {
+                truncate_output(output.trim(), max_chars)
+            }
+        };
+        CommandOutput {
+            command,
+            exit_code: status.and_then(|s| s.code()),
+            success: killed.is_none() && status.is_some_and(|s| s.success()),
+            stdout: truncate(&self.stdout, self.omitted.0),
+            stderr: truncate(&self.stderr, self.omitted.1),
+            killed,
+            log,
+        }
+    }
+}
+
+#[cfg(unix)]
+fn kill_process_group(pid: Option<u32>) {
+    if let Some(pid) = pid {
+        // SAFETY: plain syscall; the child leads its own group (`process_group(0)`)
+        unsafe {
+            libc::killpg(pid as libc::pid_t, libc::SIGKILL);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn kill_process_group(_pid: Option<u32>) {
+    // `kill_on_drop` kills the direct child
+}
+
+fn bench_command_line<'a>(config: &Config, args: impl Iterator<Item = &'a OsStr>) -> String {
+    command_line(
+        "bench",
+        ["--site", config.site.as_str()]
+            .iter()
+            .map(|a| a.to_string())
+            .chain(args.map(|a| a.to_string_lossy().to_string())),
+    )
+}
+
+/// Run in the bench directory with its virtualenv first on `PATH`.
+fn set_bench_env<'a>(cmd: &'a mut Command, config: &Config) -> Result<&'a mut Command> {
     let bench_dir = Path::new(&config.frappe_bench_dir);
 
     // Tentukan folder bin venv
@@ -38,43 +430,55 @@ where
     let old_path = env::var_os("PATH").unwrap_or_default();
     let mut paths: Vec<PathBuf> = env::split_paths(&old_path).collect();
     // prepend (di depan)
-    paths.insert(0, venv_bin.clone());
-    let new_path = env::join_paths(paths).context("join PATH failed")?;
+    paths.insert(0, venv_bin);
+    let new_path: OsString = env::join_paths(paths).context("join PATH failed")?;
 
     // Bangun perintah bench; biarkan resolve dari PATH global (/usr/local/bin/bench)
-    let mut cmd = Command::new("bench");
-    cmd.current_dir(bench_dir)
+    Ok(cmd
+        .current_dir(bench_dir)
         .env("PATH", &new_path)
         .env("VIRTUAL_ENV", &venv_dir)
         // opsional supaya pip tidak nulis ke user site
-        .env("PIP_USER", "0")
-        .arg("--site")
-        .arg(&config.site)
-        .args(args);
-
-    let output = cmd.output().with_context(|| "Failed to spawn bench")?;
-    let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
-    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
-
-    if !output.status.success() {
-        let truncated_stdout = truncate_output(&stdout, max_chars);
-        let truncated_stderr = truncate_output(&stderr, max_chars);
-        bail!(format!(
-            "bench exited with code {:?}\nSTDOUT:\n{}\n\nSTDERR:\n{}",
-            output.status.code(),
-            truncated_stdout,
-            truncated_stderr
-        ));
-    }
+        .env("PIP_USER", "0"))
+}
 
-    let truncated_stdout = truncate_output(&stdout, max_chars);
-    let truncated_stderr = truncate_output(&stderr, max_chars);
+pub async fn run_db_command(
+    config: &Config,
+    sql: &str,
+    options: &RunOptions,
+) -> Result<CommandOutput> {
+    // --binary-mode stops the client from running `\` commands of its own
+    run_bench_async(
+        config,
+        &["mariadb", "--binary-mode", "-e", sql],
+        5000,
+        options,
+    )
+    .await
+}
 
-    Ok(format!("{}\n{}", truncated_stdout, truncated_stderr))
+/// The command line as it would be typed in a shell, quoting arguments when needed.
+pub fn command_line<I, S>(program: &str, args: I) -> String
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    std::iter::once(program.to_string())
+        .chain(args.into_iter().map(|a| shell_quote(a.as_ref())))
+        .collect::<Vec<_>>()
+        .join(" ")
 }
 
-pub fn run_db_command(config: &Config, sql: &str) -> Result<String> {
-    run_bench_command(config, &["mariadb", "-e", sql], 5000)
+fn shell_quote(arg: &str) -> String {
+    let plain = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-./:=,@%+".contains(c));
+    if plain {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
 }
 
 fn truncate_output(output: &str, max_chars: usize) -> String {
@@ -105,6 +509,26 @@ fn truncate_output(output: &str, max_chars: usize) -> String {
     result
 }
 
+/// Like [`truncate_output`], keeping the last `max_chars` instead; `omitted`
+/// bytes were already dropped from the front of `output`.
+fn truncate_output_tail(output: &str, max_chars: usize, omitted: usize) -> String {
+    let mut start = 0;
+    if max_chars > 0 && output.len() > max_chars {
+        start = output.len() - max_chars;
+        while !output.is_char_boundary(start) {
+            start += 1;
+        }
+    }
+    if start + omitted == 0 {
+        return output.to_string();
+    }
+    format!(
+        "... (truncated {} chars)\n{}",
+        start + omitted,
+        &output[start..]
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -155,6 +579,124 @@ mod tests {
         assert_eq!(result, "ab");
     }
 
+    #[test]
+    fn test_command_line_quoting() {
+        assert_eq!(
+            command_line(
+                "bench",
+                ["--site", "dev.local", "mariadb", "-e", "SELECT 'x'"]
+            ),
+            "bench --site dev.local mariadb -e 'SELECT '\\''x'\\'''"
+        );
+        assert_eq!(command_line("bench", ["migrate", ""]), "bench migrate ''");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_run_command_limits() {
+        let started = std::time::Instant::now();
+        let mut cmd = tokio::process::Command::new("sh");
+        cmd.args(["-c", "echo started; sleep 30 & sleep 30"]);
+        let limits = RunOptions {
+            timeout: Some(Duration::from_millis(300)),
+            ..Default::default()
+        };
+        let output = run_command(cmd, "sh".to_string(), 0, &limits)
+            .await
+            .unwrap();
+        assert_eq!(output.killed, Some(KillReason::Timeout));
+        assert!(!output.success);
+        assert_eq!(output.stdout, "started");
+        // The background `sleep` holding stdout was killed with the group
+        assert!(started.elapsed() < Duration::from_secs(10));
+
+        let limits = RunOptions::default();
+        limits.cancel.cancel();
+        let mut cmd = tokio::process::Command::new("sleep");
+        cmd.arg("30");
+        let output = run_command(cmd, "sleep 30".to_string(), 0, &limits)
+            .await
+            .unwrap();
+        assert_eq!(output.killed, Some(KillReason::Cancelled));
+
+        let mut cmd = tokio::process::Command::new("sh");
+        cmd.args(["-c", "echo done; exit 3"]);
+        let output = run_command(cmd, "sh".to_string(), 0, &RunOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(output.killed, None);
+        assert_eq!(output.exit_code, Some(3));
+        assert_eq!(output.stdout, "done");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_run_command_kills_leftovers() {
+        // The background `sleep` keeps stdout open after `sh` exited
+        let mut cmd = tokio::process::Command::new("sh");
+        cmd.args(["-c", "sleep 30 & echo $!"]);
+        let output = run_command(cmd, "sh".to_string(), 0, &RunOptions::default())
+            .await
+            .unwrap();
+        assert!(output.success);
+        assert_eq!(output.killed, None);
+
+        // Gone, or a zombie left for init to reap
+        let stat = format!("/proc/{}/stat", output.stdout);
+        let dead = || std::fs::read_to_string(&stat).map_or(true, |s| s.contains(") Z "));
+        for _ in 0..20 {
+            if dead() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert!(dead());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_run_command_log() {
+        let dir = std::env::temp_dir().join(format!("frappe-mcp-runs-test-{}", std::process::id()));
+        let options = RunOptions {
+            log_dir: Some(dir.clone()),
+            ..Default::default()
+        };
+        let mut cmd = tokio::process::Command::new("sh");
+        cmd.args(["-c", "echo one; echo two >&2; printf three"]);
+        let output = run_command(cmd, "sh".to_string(), 4, &options)
+            .await
+            .unwrap();
+        assert!(output.success);
+        assert_eq!(output.stdout, "... (truncated 5 chars)\nhree");
+        assert_eq!(output.stderr, "two");
+
+        let log = output.log.unwrap();
+        assert_eq!(log.lines, 3);
+        assert_eq!(log.uri, format!("frappe://run/{}", log.run_id));
+        let path = runlog::log_path(&dir, &log.run_id).unwrap();
+        let content = std::fs::read_to_string(path).unwrap();
+        // stdout and stderr lines are logged in the order they are read
+        let mut lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.remove(0), "$ sh");
+        lines.sort();
+        assert_eq!(lines, vec!["one", "three", "two"]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_truncate_output_tail() {
+        assert_eq!(truncate_output_tail("Hello world", 20, 0), "Hello world");
+        assert_eq!(
+            truncate_output_tail("Hello 🌍 world", 7, 0),
+            "... (truncated 10 chars)\n world"
+        );
+        // Bytes already dropped from the buffer count as truncated
+        assert_eq!(
+            truncate_output_tail("world", 0, 6),
+            "... (truncated 6 chars)\nworld"
+        );
+    }
+
     #[test]
     fn test_truncate_output_newlines_preserved() {
         let input = "Line 1\nLine 2\nLine 3";
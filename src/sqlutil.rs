@@ -0,0 +1,441 @@
+// Copyright (C) 2025 Nuwaira
+// All Rights Reserved.
+//
+// NOTICE: All information contained herein is, and remains
+// the property of Nuwaira.
+// The intellectual and technical concepts contained
+// herein are proprietary to Nuwaira
+// and are protected by trade secret or copyright law.
+// Dissemination of this information or reproduction of this material
+// is strictly forbidden unless prior written permission is obtained
+// from Nuwaira.
+#![allow(dead_code)]
+
+//! A small MariaDB tokenizer, enough to split a query into statements and
+//! tell reads from writes before it is sent to `bench mariadb`.
+
+use rmcp::schemars;
+use serde::Serialize;
+
+/// What a statement does to the database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum StatementClass {
+    /// `SELECT`, `SHOW`, `DESCRIBE`, `EXPLAIN`
+    Read,
+    /// Data changes: `INSERT`, `UPDATE`, `DELETE`, `REPLACE`, ...
+    Dml,
+    /// Schema changes: `CREATE`, `ALTER`, `DROP`, `TRUNCATE`, ...
+    Ddl,
+    /// Anything else (`SET`, `LOCK`, `COMMIT`, ...), treated as a write
+    Other,
+}
+
+impl StatementClass {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            StatementClass::Read => "read",
+            StatementClass::Dml => "dml",
+            StatementClass::Ddl => "ddl",
+            StatementClass::Other => "other",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// Keyword, unquoted identifier, number or `@variable`
+    Word(String),
+    /// Backtick-quoted identifier, without the quotes
+    Ident(String),
+    /// String literal
+    Literal,
+    /// Unquoted `\` and the character after it, a command of the `mysql` client
+    ClientCommand(String),
+    Symbol(char),
+}
+
+/// A single statement of a query.
+#[derive(Debug, Clone)]
+pub struct Statement {
+    /// Statement text without the terminating `;`
+    pub text: String,
+    /// Leading keyword, lowercased; for `WITH` the keyword of the main statement
+    pub kind: String,
+    pub class: StatementClass,
+    tokens: Vec<Token>,
+    /// Byte offset in `text` right after the last token, before any trailing comment
+    code_end: usize,
+}
+
+const DML: &[&str] = &[
+    "insert", "update", "delete", "replace", "load", "call", "do", "handler",
+];
+const DDL: &[&str] = &[
+    "create", "alter", "drop", "truncate", "rename", "grant", "revoke",
+];
+
+/// Split `sql` into its statements, skipping empty ones and comments.
+///
+/// `/*! ... */` and `/*M! ... */` are executed by MariaDB, so their content
+/// is tokenized like the rest of the query. An unquoted `\` starts a client
+/// command (`\!` runs a shell command), kept as its own token.
+pub fn split_statements(sql: &str) -> Vec<Statement> {
+    let b = sql.as_bytes();
+    let mut statements = Vec::new();
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut code_end = 0;
+    let mut exec_comment = false;
+    let mut i = 0;
+
+    while i < b.len() {
+        let c = b[i];
+        let next = b.get(i + 1).copied();
+        match c {
+            b';' => {
+                push_statement(sql, start, i, code_end, &mut tokens, &mut statements);
+                i += 1;
+                start = i;
+            }
+            b'\'' | b'"' | b'`' => {
+                let end = skip_quoted(b, i);
+                if c == b'`' {
+                    let close = if end > i + 1 && b[end - 1] == b'`' {
+                        end - 1
+                    } else {
+                        end
+                    };
+                    tokens.push(Token::Ident(sql[i + 1..close].replace("``", "`")));
+                } else {
+                    tokens.push(Token::Literal);
+                }
+                i = end;
+                code_end = i;
+            }
+            b'-' if next == Some(b'-') && b.get(i + 2).is_none_or(|c| c.is_ascii_whitespace()) => {
+                i = skip_line(b, i);
+            }
+            b'#' => i = skip_line(b, i),
+            b'/' if next == Some(b'*') => {
+                let exec_prefix = if b.get(i + 2) == Some(&b'!') {
+                    3
+                } else if b[i + 2..].starts_with(b"M!") {
+                    4
+                } else {
+                    0
+                };
+                if exec_prefix > 0 {
+                    i += exec_prefix;
+                    while i < b.len() && b[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                    exec_comment = true;
+                } else {
+                    i = sql[i + 2..]
+                        .find("*/")
+                        .map_or(b.len(), |pos| i + 2 + pos + 2);
+                }
+            }
+            b'\\' => {
+                let command = sql[i + 1..].chars().next().map_or(0, char::len_utf8);
+                tokens.push(Token::ClientCommand(sql[i..i + 1 + command].to_string()));
+                i += 1 + command;
+                code_end = i;
+            }
+            b'*' if exec_comment && next == Some(b'/') => {
+                exec_comment = false;
+                i += 2;
+            }
+            c if is_word_byte(c) => {
+                let s = i;
+                while i < b.len() && is_word_byte(b[i]) {
+                    i += 1;
+                }
+                tokens.push(Token::Word(sql[s..i].to_string()));
+                code_end = i;
+            }
+            c if c.is_ascii_whitespace() => i += 1,
+            _ => {
+                tokens.push(Token::Symbol(c as char));
+                i += 1;
+                code_end = i;
+            }
+        }
+    }
+    push_statement(sql, start, b.len(), code_end, &mut tokens, &mut statements);
+    statements
+}
+
+fn is_word_byte(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_' || c == b'$' || c == b'@' || c >= 0x80
+}
+
+/// Index right after the quoted string starting at `start`.
+fn skip_quoted(b: &[u8], start: usize) -> usize {
+    let quote = b[start];
+    let mut i = start + 1;
+    while i < b.len() {
+        if b[i] == b'\\' && quote != b'`' {
+            i += 2;
+        } else if b[i] == quote {
+            // A doubled quote is an escaped quote
+            if b.get(i + 1) == Some(&quote) {
+                i += 2;
+            } else {
+                return i + 1;
+            }
+        } else {
+            i += 1;
+        }
+    }
+    b.len()
+}
+
+fn skip_line(b: &[u8], start: usize) -> usize {
+    b[start..]
+        .iter()
+        .position(|c| *c == b'\n')
+        .map_or(b.len(), |pos| start + pos + 1)
+}
+
+fn push_statement(
+    sql: &str,
+    start: usize,
+    end: usize,
+    code_end: usize,
+    tokens: &mut Vec<Token>,
+    statements: &mut Vec<Statement>,
+) {
+    if tokens.is_empty() {
+        return;
+    }
+    let raw = &sql[start..end];
+    let text_start = start + (raw.len() - raw.trim_start().len());
+    let text = raw.trim().to_string();
+    let tokens = std::mem::take(tokens);
+    let (kind, class) = classify(&tokens);
+    statements.push(Statement {
+        code_end: (code_end - text_start).min(text.len()),
+        text,
+        kind,
+        class,
+        tokens,
+    });
+}
+
+/// Lowercased words outside of any parentheses.
+fn top_level_words(tokens: &[Token]) -> Vec<String> {
+    let mut depth = 0usize;
+    let mut words = Vec::new();
+    for token in tokens {
+        match token {
+            Token::Symbol('(') => depth += 1,
+            Token::Symbol(')') => depth = depth.saturating_sub(1),
+            Token::Word(w) if depth == 0 => words.push(w.to_lowercase()),
+            _ => {}
+        }
+    }
+    words
+}
+
+fn classify(tokens: &[Token]) -> (String, StatementClass) {
+    let first = tokens.iter().find_map(|t| match t {
+        Token::Word(w) => Some(w.to_lowercase()),
+        _ => None,
+    });
+    let Some(first) = first else {
+        return (String::new(), StatementClass::Other);
+    };
+    let words = top_level_words(tokens);
+    let has_dml = words.iter().any(|w| DML.contains(&w.as_str()));
+
+    let kind = if first == "with" {
+        words
+            .iter()
+            .find(|w| *w == "select" || DML.contains(&w.as_str()))
+            .cloned()
+            .unwrap_or(first)
+    } else {
+        first
+    };
+    let class = match kind.as_str() {
+        // SELECT ... INTO OUTFILE writes to the server's disk
+        "select" if words.iter().any(|w| w == "into") => StatementClass::Dml,
+        "select" => StatementClass::Read,
+        "describe" | "desc" | "explain" if has_dml => StatementClass::Dml,
+        "show" | "describe" | "desc" | "explain" => StatementClass::Read,
+        k if DML.contains(&k) => StatementClass::Dml,
+        k if DDL.contains(&k) => StatementClass::Ddl,
+        _ => StatementClass::Other,
+    };
+    (kind, class)
+}
+
+impl Statement {
+    pub fn is_read(&self) -> bool {
+        self.class == StatementClass::Read
+    }
+
+    /// The first `mysql` client command (`\!`, `\T`, `\.`, ...) in the
+    /// statement, which the client runs itself rather than sending it to the
+    /// server.
+    pub fn client_command(&self) -> Option<&str> {
+        self.tokens.iter().find_map(|t| match t {
+            Token::ClientCommand(command) => Some(command.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Tables read or written by the statement, as written in the query.
+    pub fn tables(&self) -> Vec<String> {
+        let mut tables = Vec::new();
+        for (i, token) in self.tokens.iter().enumerate() {
+            let Token::Word(w) = token else {
+                continue;
+            };
+            let w = w.to_lowercase();
+            if !matches!(w.as_str(), "from" | "join" | "into" | "update" | "table") {
+                continue;
+            }
+            let mut j = i + 1;
+            let mut name = None;
+            // `db.table`, `db`.`table`
+            while let Some(Token::Word(n)) | Some(Token::Ident(n)) = self.tokens.get(j) {
+                name = Some(n.clone());
+                if self.tokens.get(j + 1) != Some(&Token::Symbol('.')) {
+                    break;
+                }
+                j += 2;
+            }
+            if let Some(name) = name {
+                tables.push(name);
+            }
+        }
+        tables
+    }
+
+    /// The statement with ` LIMIT <limit>` added, when it is a `SELECT` over a
+    /// DocType table (`tab*`) without a limit of its own.
+    pub fn with_limit(&self, limit: usize) -> Option<String> {
+        if limit == 0 || self.kind != "select" || !self.is_read() {
+            return None;
+        }
+        let words = top_level_words(&self.tokens);
+        // LIMIT must come before FOR UPDATE / LOCK IN SHARE MODE
+        if words
+            .iter()
+            .any(|w| matches!(w.as_str(), "limit" | "for" | "lock" | "procedure"))
+        {
+            return None;
+        }
+        if !self.tables().iter().any(|t| is_doctype_table(t)) {
+            return None;
+        }
+        Some(format!(
+            "{} LIMIT {}{}",
+            &self.text[..self.code_end],
+            limit,
+            &self.text[self.code_end..]
+        ))
+    }
+}
+
+/// `tabSales Invoice`, but not `tables` or `tabs`.
+fn is_doctype_table(name: &str) -> bool {
+    name.strip_prefix("tab")
+        .and_then(|rest| rest.chars().next())
+        .is_some_and(|c| c.is_uppercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(sql: &str) -> Vec<(String, StatementClass)> {
+        split_statements(sql)
+            .into_iter()
+            .map(|s| (s.kind, s.class))
+            .collect()
+    }
+
+    #[test]
+    fn test_split_and_classify() {
+        let statements = split_statements(
+            "SELECT name FROM `tabUser` WHERE bio = 'a; DROP TABLE x';\n\
+             -- comment; still a comment\n\
+             update tabUser set enabled = 0;  ; DROP TABLE `tabNote`",
+        );
+        assert_eq!(statements.len(), 3);
+        assert_eq!(
+            statements[0].text,
+            "SELECT name FROM `tabUser` WHERE bio = 'a; DROP TABLE x'"
+        );
+        assert_eq!(statements[0].class, StatementClass::Read);
+        assert_eq!(statements[1].kind, "update");
+        assert_eq!(statements[1].class, StatementClass::Dml);
+        assert_eq!(statements[2].class, StatementClass::Ddl);
+
+        assert_eq!(
+            kinds("WITH t AS (SELECT 1) DELETE FROM tabUser"),
+            vec![("delete".to_string(), StatementClass::Dml)]
+        );
+        assert_eq!(
+            kinds("(SELECT 1) UNION (SELECT 2)"),
+            vec![("select".to_string(), StatementClass::Read)]
+        );
+        assert_eq!(
+            kinds("SELECT * FROM tabUser INTO OUTFILE '/tmp/x'"),
+            vec![("select".to_string(), StatementClass::Dml)]
+        );
+        assert_eq!(
+            kinds("SET GLOBAL general_log = 1"),
+            vec![("set".to_string(), StatementClass::Other)]
+        );
+        // Executable comments are run by MariaDB
+        assert_eq!(
+            kinds("/*!50000 DROP TABLE tabUser */"),
+            vec![("drop".to_string(), StatementClass::Ddl)]
+        );
+        assert!(split_statements("/* nothing */ ; -- here").is_empty());
+    }
+
+    #[test]
+    fn test_client_commands() {
+        let statements = split_statements("select 1 \\! id");
+        assert_eq!(statements.len(), 1);
+        assert_eq!(statements[0].client_command(), Some("\\!"));
+        assert_eq!(
+            split_statements("\\T /tmp/out\nselect 1")[0].client_command(),
+            Some("\\T")
+        );
+        // Escapes inside literals are the server's
+        assert_eq!(
+            split_statements("select 'a\\'b', `x\\y`")[0].client_command(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_with_limit() {
+        let limited = |sql: &str| split_statements(sql)[0].with_limit(100);
+        assert_eq!(
+            limited("select name from tabUser -- all users").as_deref(),
+            Some("select name from tabUser LIMIT 100 -- all users")
+        );
+        assert_eq!(
+            limited("SELECT * FROM `tabSales Invoice` si JOIN tabCustomer c ON c.name = si.customer")
+                .as_deref(),
+            Some("SELECT * FROM `tabSales Invoice` si JOIN tabCustomer c ON c.name = si.customer LIMIT 100")
+        );
+        assert!(limited("SELECT * FROM tabUser LIMIT 5").is_none());
+        assert!(limited("SELECT * FROM tabUser FOR UPDATE").is_none());
+        assert!(limited("SELECT * FROM information_schema.tables").is_none());
+        assert!(limited("SELECT 1").is_none());
+        assert!(limited("DELETE FROM tabUser").is_none());
+        assert_eq!(
+            limited("SELECT * FROM (SELECT name FROM tabUser LIMIT 5) u").as_deref(),
+            Some("SELECT * FROM (SELECT name FROM tabUser LIMIT 5) u LIMIT 100")
+        );
+    }
+}
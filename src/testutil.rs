@@ -0,0 +1,535 @@
+// Copyright (C) 2025 Nuwaira
+// All Rights Reserved.
+//
+// NOTICE: All information contained herein is, and remains
+// the property of Nuwaira.
+// The intellectual and technical concepts contained
+// herein are proprietary to Nuwaira
+// and are protected by trade secret or copyright law.
+// Dissemination of this information or reproduction of this material
+// is strictly forbidden unless prior written permission is obtained
+// from Nuwaira.
+#![allow(dead_code)]
+
+//! Parser for the unittest report printed by `bench run-tests`: the
+//! `FAIL:`/`ERROR:` blocks with their tracebacks and the closing
+//! `Ran N tests` / `FAILED (failures=1)` summary.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::sync::Mutex;
+
+use regex::Regex;
+use rmcp::schemars;
+use serde::{Deserialize, Serialize};
+
+use crate::cache;
+use crate::config::Config;
+
+/// Last failing tests per site, under the bench's state directory.
+const FAILED_TESTS_FILE: &str = "failed_tests.json";
+
+lazy_static::lazy_static! {
+    /// Serializes updates of the failing tests from concurrent sessions
+    static ref FAILED_LOCK: Mutex<()> = Mutex::new(());
+    static ref RX_FAILURE: Regex =
+        Regex::new(r"^(FAIL|ERROR|UNEXPECTED SUCCESS): (\S+) \(([\w.]+)\)").expect("rx_failure bad");
+    static ref RX_FRAME: Regex =
+        Regex::new(r#"^\s*File "([^"]+)", line (\d+), in (\S+)"#).expect("rx_frame bad");
+    static ref RX_RAN: Regex =
+        Regex::new(r"^Ran (\d+) tests? in ([\d.]+)s").expect("rx_ran bad");
+    static ref RX_STATUS: Regex =
+        Regex::new(r"^(OK|FAILED)(?: \((.*)\))?\s*$").expect("rx_status bad");
+}
+
+/// The separators unittest prints around failure blocks.
+const DOUBLE_RULE: &str = "======================================================================";
+const SINGLE_RULE: &str = "----------------------------------------------------------------------";
+
+/// Counts and failures of a test run.
+#[derive(Debug, Clone, Default, Serialize, schemars::JsonSchema)]
+pub struct TestResults {
+    /// Tests run, including the failed and skipped ones
+    pub ran: usize,
+    pub passed: usize,
+    /// Failed assertions, and unexpected successes
+    pub failed: usize,
+    /// Tests that raised an exception other than an assertion
+    pub errors: usize,
+    pub skipped: usize,
+    /// Wall time reported by the runner, in seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_secs: Option<f64>,
+    pub failures: Vec<TestFailure>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    /// An assertion failed
+    Failure,
+    /// The test raised an exception
+    Error,
+    /// An `expectedFailure` test passed
+    UnexpectedSuccess,
+}
+
+/// A failing test and where it failed.
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct TestFailure {
+    pub kind: FailureKind,
+    /// Dotted module of the test case, e.g. `koperasi.koperasi.doctype.anggota.test_anggota`
+    pub module: String,
+    pub class: String,
+    /// Test method, or the fixture that failed (`setUpClass`)
+    pub method: String,
+    /// The exception line(s), e.g. `AssertionError: 1 != 2`
+    pub message: String,
+    /// `file:line` of the innermost frame in app code, the line to look at first
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    /// Traceback frames, outermost first
+    pub traceback: Vec<TraceFrame>,
+}
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct TraceFrame {
+    /// Path relative to the bench's `apps/` when the file belongs to an app, as printed otherwise
+    pub file: String,
+    pub line: usize,
+    pub function: String,
+    /// The source line, when Python printed it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    /// Whether the file lies in an app of the bench
+    pub in_app: bool,
+}
+
+/// A test that failed, enough to run it again.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct TestCase {
+    pub module: String,
+    pub class: String,
+    pub method: String,
+}
+
+impl TestFailure {
+    /// `module.Class.method`, as unittest names the test.
+    pub fn test_id(&self) -> String {
+        format!("{}.{}.{}", self.module, self.class, self.method)
+    }
+
+    pub fn test_case(&self) -> TestCase {
+        TestCase {
+            module: self.module.clone(),
+            class: self.class.clone(),
+            method: self.method.clone(),
+        }
+    }
+}
+
+impl TestResults {
+    /// Add the counts and failures of another run.
+    pub fn merge(&mut self, other: TestResults) {
+        self.ran += other.ran;
+        self.passed += other.passed;
+        self.failed += other.failed;
+        self.errors += other.errors;
+        self.skipped += other.skipped;
+        self.duration_secs = match (self.duration_secs, other.duration_secs) {
+            (Some(a), Some(b)) => Some(a + b),
+            (a, b) => a.or(b),
+        };
+        self.failures.extend(other.failures);
+    }
+
+    /// Counts on one line, e.g. `Ran 12 tests in 3.2s: 10 passed, 1 failed, 1 errors, 0 skipped`.
+    pub fn summary(&self) -> String {
+        let duration = self
+            .duration_secs
+            .map(|d| format!(" in {}s", d))
+            .unwrap_or_default();
+        format!(
+            "Ran {} tests{}: {} passed, {} failed, {} errors, {} skipped",
+            self.ran, duration, self.passed, self.failed, self.errors, self.skipped
+        )
+    }
+}
+
+/// Parse the runner's report out of `output`; `None` when no test ran and nothing failed,
+/// e.g. when the site couldn't be set up.
+pub fn parse_test_output(output: &str, bench_dir: &str) -> Option<TestResults> {
+    let lines: Vec<&str> = output.lines().collect();
+    let mut results = TestResults::default();
+    let mut seen_summary = false;
+    let mut unexpected_successes = 0;
+
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i].trim_end();
+        if line == DOUBLE_RULE {
+            if let Some(caps) = lines.get(i + 1).and_then(|l| RX_FAILURE.captures(l)) {
+                let end = block_end(&lines, i + 2);
+                results
+                    .failures
+                    .push(parse_failure(&caps, &lines[i + 2..end], bench_dir));
+                i = end;
+                continue;
+            }
+        } else if let Some(caps) = RX_RAN.captures(line) {
+            seen_summary = true;
+            results.ran += caps[1].parse::<usize>().unwrap_or(0);
+            let secs: f64 = caps[2].parse().unwrap_or(0.0);
+            results.duration_secs = Some(results.duration_secs.unwrap_or(0.0) + secs);
+        } else if let Some(caps) = RX_STATUS.captures(line) {
+            for (key, count) in caps
+                .get(2)
+                .map_or("", |m| m.as_str())
+                .split(',')
+                .filter_map(|pair| pair.trim().split_once('='))
+            {
+                let count: usize = count.trim().parse().unwrap_or(0);
+                match key.trim() {
+                    "failures" => results.failed += count,
+                    "errors" => results.errors += count,
+                    "skipped" => results.skipped += count,
+                    // Expected failures count as passed
+                    "unexpected successes" => unexpected_successes += count,
+                    _ => {}
+                }
+            }
+        }
+        i += 1;
+    }
+
+    if !seen_summary && results.failures.is_empty() {
+        return None;
+    }
+    if !seen_summary {
+        // The run was cut short, count what was reported
+        let count = |kind| results.failures.iter().filter(|f| f.kind == kind).count();
+        let (failed, errors) = (count(FailureKind::Failure), count(FailureKind::Error));
+        results.failed = failed;
+        results.errors = errors;
+    }
+    results.failed += unexpected_successes;
+    results.passed = results
+        .ran
+        .saturating_sub(results.failed + results.errors + results.skipped);
+    Some(results)
+}
+
+/// End of a failure block: the next block or the rule before `Ran N tests`.
+fn block_end(lines: &[&str], start: usize) -> usize {
+    // The block's own header rule comes first, skip it
+    let body_start = lines[start..]
+        .iter()
+        .position(|l| l.trim_end() == SINGLE_RULE)
+        .map_or(start, |p| start + p + 1);
+    lines[body_start..]
+        .iter()
+        .position(|l| {
+            let l = l.trim_end();
+            l == DOUBLE_RULE || l == SINGLE_RULE
+        })
+        .map_or(lines.len(), |p| body_start + p)
+}
+
+fn parse_failure(caps: &regex::Captures, body: &[&str], bench_dir: &str) -> TestFailure {
+    let kind = match &caps[1] {
+        "FAIL" => FailureKind::Failure,
+        "ERROR" => FailureKind::Error,
+        _ => FailureKind::UnexpectedSuccess,
+    };
+    let method = caps[2].to_string();
+    // Python 3.11+ prints `module.Class.method`, older versions `module.Class`
+    let mut path: Vec<&str> = caps[3].split('.').collect();
+    if path.len() > 1 && path.last() == Some(&method.as_str()) {
+        path.pop();
+    }
+    let class = path.pop().unwrap_or_default().to_string();
+    let module = path.join(".");
+
+    // Skip the docstring line and the rule under the header
+    let body = body
+        .iter()
+        .position(|l| l.trim_end() == SINGLE_RULE)
+        .map_or(body, |p| &body[p + 1..]);
+
+    let mut traceback = Vec::new();
+    let mut message_start = 0;
+    for (i, line) in body.iter().enumerate() {
+        if line.starts_with("Traceback (most recent call last)") {
+            // Chained exceptions print one traceback each; the last one raised
+            traceback.clear();
+        }
+        if let Some(frame) = RX_FRAME.captures(line) {
+            let code = body
+                .get(i + 1)
+                .filter(|next| next.starts_with("    ") && !RX_FRAME.is_match(next))
+                .map(|next| next.trim().to_string());
+            let (file, in_app) = app_relative(&frame[1], bench_dir);
+            traceback.push(TraceFrame {
+                file,
+                line: frame[2].parse().unwrap_or(0),
+                function: frame[3].to_string(),
+                code,
+                in_app,
+            });
+            message_start = i + 1;
+        }
+    }
+    // The exception follows the last frame: unindented lines, after the
+    // source line and any `^^^^` markers
+    let message = body[message_start.min(body.len())..]
+        .iter()
+        .skip_while(|l| l.starts_with(' ') || l.is_empty())
+        .map(|l| l.trim_end())
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string();
+
+    let app = module.split('.').next().unwrap_or_default();
+    let location = innermost_app_frame(&traceback, app).map(|f| format!("{}:{}", f.file, f.line));
+    TestFailure {
+        kind,
+        module,
+        class,
+        method,
+        message,
+        location,
+        traceback,
+    }
+}
+
+/// The innermost frame in app code, preferring apps other than the framework
+/// unless the test belongs to it.
+fn innermost_app_frame<'a>(traceback: &'a [TraceFrame], test_app: &str) -> Option<&'a TraceFrame> {
+    let framework = |f: &TraceFrame| f.file.starts_with("frappe/") && test_app != "frappe";
+    traceback
+        .iter()
+        .rev()
+        .find(|f| f.in_app && !framework(f))
+        .or_else(|| traceback.iter().rev().find(|f| f.in_app))
+}
+
+/// `path` relative to the bench's `apps/`, when it lies inside an app.
+pub fn app_relative(path: &str, bench_dir: &str) -> (String, bool) {
+    let bench_apps = format!("{}/apps/", bench_dir.trim_end_matches('/'));
+    let relative = path
+        .strip_prefix(&bench_apps)
+        .or_else(|| path.split_once("/apps/").map(|(_, rest)| rest))
+        .or_else(|| path.strip_prefix("apps/"));
+    match relative {
+        Some(rest) => (rest.to_string(), true),
+        None => (path.to_string(), false),
+    }
+}
+
+/// The failing tests remembered for the configured site.
+pub fn load_failed(config: &Config) -> Vec<TestCase> {
+    let _guard = FAILED_LOCK.lock().unwrap();
+    read_failed(config).remove(&config.site).unwrap_or_default()
+}
+
+/// Replace the failing tests remembered for the configured site.
+pub fn save_failed(config: &Config, cases: &[TestCase]) -> std::io::Result<()> {
+    let _guard = FAILED_LOCK.lock().unwrap();
+    let mut all = read_failed(config);
+    if cases.is_empty() {
+        all.remove(&config.site);
+    } else {
+        all.insert(config.site.clone(), cases.to_vec());
+    }
+    let dir = cache::state_dir(config);
+    fs::create_dir_all(&dir)?;
+    fs::write(
+        dir.join(FAILED_TESTS_FILE),
+        serde_json::to_string_pretty(&all)?,
+    )
+}
+
+fn read_failed(config: &Config) -> BTreeMap<String, Vec<TestCase>> {
+    fs::read_to_string(cache::state_dir(config).join(FAILED_TESTS_FILE))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// `bench` arguments re-running `cases`: one `run-tests --module` per module,
+/// with a `--test` per method unless a fixture like `setUpClass` failed.
+///
+/// Import errors reported by unittest's loader name no module to run and are skipped.
+pub fn rerun_args(site: &str, cases: &[TestCase]) -> Vec<Vec<String>> {
+    let mut modules: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for case in cases.iter().filter(|c| !c.module.starts_with("unittest.")) {
+        let methods = modules.entry(case.module.as_str()).or_default();
+        if !methods.contains(&case.method.as_str()) {
+            methods.push(case.method.as_str());
+        }
+    }
+    modules
+        .into_iter()
+        .map(|(module, methods)| {
+            let mut args: Vec<String> = ["--site", site, "run-tests", "--module", module]
+                .iter()
+                .map(|a| a.to_string())
+                .collect();
+            if methods.iter().all(|m| m.starts_with("test")) {
+                for method in methods {
+                    args.push("--test".to_string());
+                    args.push(method.to_string());
+                }
+            }
+            args
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OUTPUT: &str = r#"test_join (koperasi.koperasi.doctype.anggota.test_anggota.TestAnggota.test_join) ... ok
+test_limit (koperasi.koperasi.doctype.anggota.test_anggota.TestAnggota.test_limit) ... FAIL
+test_saldo (koperasi.koperasi.doctype.simpanan.test_simpanan.TestSimpanan.test_saldo) ... ERROR
+test_old (koperasi.koperasi.doctype.simpanan.test_simpanan.TestSimpanan.test_old) ... skipped 'legacy'
+
+======================================================================
+FAIL: test_limit (koperasi.koperasi.doctype.anggota.test_anggota.TestAnggota.test_limit)
+Members over the limit are rejected
+----------------------------------------------------------------------
+Traceback (most recent call last):
+  File "/home/frappe/frappe-bench/apps/koperasi/koperasi/koperasi/doctype/anggota/test_anggota.py", line 24, in test_limit
+    self.assertEqual(doc.status, "Rejected")
+AssertionError: 'Active' != 'Rejected'
+- Active
++ Rejected
+
+
+======================================================================
+ERROR: test_saldo (koperasi.koperasi.doctype.simpanan.test_simpanan.TestSimpanan)
+----------------------------------------------------------------------
+Traceback (most recent call last):
+  File "/home/frappe/frappe-bench/apps/koperasi/koperasi/koperasi/doctype/simpanan/test_simpanan.py", line 15, in test_saldo
+    doc.insert()
+  File "/home/frappe/frappe-bench/apps/frappe/frappe/model/document.py", line 286, in insert
+    self.run_method("validate")
+  File "/home/frappe/frappe-bench/apps/koperasi/koperasi/koperasi/doctype/simpanan/simpanan.py", line 42, in validate
+    total = self.jumlah / self.tenor
+            ~~~~~~~~~~~~^~~~~~~~~~~~
+  File "/home/frappe/frappe-bench/env/lib/python3.11/site-packages/some/lib.py", line 9, in helper
+    raise
+ZeroDivisionError: division by zero
+
+----------------------------------------------------------------------
+Ran 4 tests in 1.532s
+
+FAILED (failures=1, errors=1, skipped=1)
+"#;
+
+    #[test]
+    fn test_parse_counts_and_failures() {
+        let results = parse_test_output(OUTPUT, "/home/frappe/frappe-bench").unwrap();
+        assert_eq!(results.ran, 4);
+        assert_eq!(results.passed, 1);
+        assert_eq!(results.failed, 1);
+        assert_eq!(results.errors, 1);
+        assert_eq!(results.skipped, 1);
+        assert_eq!(results.duration_secs, Some(1.532));
+        assert_eq!(results.failures.len(), 2);
+
+        let fail = &results.failures[0];
+        assert_eq!(fail.kind, FailureKind::Failure);
+        assert_eq!(
+            fail.module,
+            "koperasi.koperasi.doctype.anggota.test_anggota"
+        );
+        assert_eq!(fail.class, "TestAnggota");
+        assert_eq!(fail.method, "test_limit");
+        assert_eq!(
+            fail.message,
+            "AssertionError: 'Active' != 'Rejected'\n- Active\n+ Rejected"
+        );
+        assert_eq!(
+            fail.location.as_deref(),
+            Some("koperasi/koperasi/koperasi/doctype/anggota/test_anggota.py:24")
+        );
+        assert_eq!(
+            fail.traceback[0].code.as_deref(),
+            Some("self.assertEqual(doc.status, \"Rejected\")")
+        );
+
+        // Pre-3.11 header without the method, error raised outside the app
+        let error = &results.failures[1];
+        assert_eq!(error.kind, FailureKind::Error);
+        assert_eq!(error.class, "TestSimpanan");
+        assert_eq!(error.method, "test_saldo");
+        assert_eq!(error.message, "ZeroDivisionError: division by zero");
+        assert_eq!(error.traceback.len(), 4);
+        assert!(!error.traceback[3].in_app);
+        assert_eq!(
+            error.location.as_deref(),
+            Some("koperasi/koperasi/koperasi/doctype/simpanan/simpanan.py:42")
+        );
+        assert_eq!(
+            error.test_id(),
+            "koperasi.koperasi.doctype.simpanan.test_simpanan.TestSimpanan.test_saldo"
+        );
+    }
+
+    #[test]
+    fn test_parse_ok_and_missing_report() {
+        let results =
+            parse_test_output("...\n----\nRan 3 tests in 0.1s\n\nOK (skipped=1)\n", "/b").unwrap();
+        assert_eq!((results.ran, results.passed, results.skipped), (3, 2, 1));
+        assert!(results.failures.is_empty());
+
+        assert!(parse_test_output("Site dev.local does not exist", "/b").is_none());
+    }
+
+    #[test]
+    fn test_rerun_args_and_store() {
+        let case = |module: &str, class: &str, method: &str| TestCase {
+            module: module.to_string(),
+            class: class.to_string(),
+            method: method.to_string(),
+        };
+        let cases = vec![
+            case("koperasi.a.test_a", "TestA", "test_one"),
+            case("koperasi.b.test_b", "TestB", "setUpClass"),
+            case("koperasi.a.test_a", "TestA", "test_two"),
+            case("koperasi.b.test_b", "TestB", "test_three"),
+            case("unittest.loader", "_FailedTest", "test_c"),
+        ];
+        let runs = rerun_args("dev.local", &cases);
+        assert_eq!(
+            runs,
+            [
+                "--site dev.local run-tests --module koperasi.a.test_a --test test_one --test test_two",
+                "--site dev.local run-tests --module koperasi.b.test_b",
+            ]
+            .iter()
+            .map(|r| r.split(' ').map(|a| a.to_string()).collect::<Vec<_>>())
+            .collect::<Vec<_>>()
+        );
+
+        let bench =
+            std::env::temp_dir().join(format!("frappe-mcp-failed-test-{}", std::process::id()));
+        let mut config = Config {
+            frappe_bench_dir: bench.to_string_lossy().to_string(),
+            site: "one.local".to_string(),
+            ..Default::default()
+        };
+        save_failed(&config, &cases[..2]).unwrap();
+        config.site = "two.local".to_string();
+        save_failed(&config, &cases[2..3]).unwrap();
+        assert_eq!(load_failed(&config), cases[2..3].to_vec());
+        save_failed(&config, &[]).unwrap();
+        assert!(load_failed(&config).is_empty());
+        config.site = "one.local".to_string();
+        assert_eq!(load_failed(&config), cases[..2].to_vec());
+        let _ = fs::remove_dir_all(&bench);
+    }
+}
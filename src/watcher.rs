@@ -0,0 +1,589 @@
+// Copyright (C) 2025 Nuwaira
+// All Rights Reserved.
+//
+// NOTICE: All information contained herein is, and remains
+// the property of Nuwaira.
+// The intellectual and technical concepts contained
+// herein are proprietary to Nuwaira
+// and are protected by trade secret or copyright law.
+// Dissemination of this information or reproduction of this material
+// is strictly forbidden unless prior written permission is obtained
+// from Nuwaira.
+#![allow(dead_code)]
+use std::collections::BTreeSet;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use notify::event::ModifyKind;
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use rayon::prelude::*;
+use walkdir::WalkDir;
+
+use crate::analyze::{self, AnalyzedData, DocType};
+use crate::cache::{self, AnalysisCache, FileRecord};
+use crate::config::Config;
+use crate::py_refs::DocTypeIndex;
+use crate::refs_finder::{self, Output as RefsFinderOutput};
+use crate::resources::{self, FrappeUri, ResourceChange, ResourceNotifier};
+use crate::stringutil::to_snakec_var;
+
+/// Quiet period used to batch bursts of events (editor saves, git checkouts).
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Directories that never contain anything worth re-analyzing.
+const IGNORED_DIRS: [&str; 3] = ["__pycache__", ".git", "node_modules"];
+
+/// What a batch of file events requires us to refresh.
+#[derive(Debug, Default, PartialEq)]
+pub struct ChangeSet {
+    /// `modules.txt` changed: rescan every module and DocType.
+    pub modules: bool,
+    /// `.../<module>/doctype/<dt>` directories touched.
+    pub doctype_dirs: BTreeSet<PathBuf>,
+    /// Python and JS files whose field references must be re-scanned.
+    pub source_files: BTreeSet<PathBuf>,
+}
+
+impl ChangeSet {
+    pub fn is_empty(&self) -> bool {
+        !self.modules && self.doctype_dirs.is_empty() && self.source_files.is_empty()
+    }
+}
+
+/// Watch every analyzed app in a background thread and patch `anal`
+/// whenever DocTypes, `modules.txt` or python files change on disk; clients
+/// are told through `notifier` which resources went stale.
+pub fn spawn(
+    config: Config,
+    anal: Arc<Mutex<AnalyzedData>>,
+    notifier: ResourceNotifier,
+) -> Result<()> {
+    let app_path = PathBuf::from(&config.app_absolute_path);
+    if !app_path.is_dir() {
+        bail!("App directory '{}' doesn't exist", app_path.display());
+    }
+
+    let (tx, rx) = mpsc::channel::<notify::Result<Event>>();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    let mut apps = Vec::new();
+    for app in &config.app_dirs {
+        let path = PathBuf::from(config.app_path(app));
+        if !path.is_dir() {
+            tracing::warn!("Not watching '{}': directory doesn't exist", path.display());
+            continue;
+        }
+        // One app out of watches doesn't stop the others from being watched
+        match watch_dirs(&mut watcher, &path) {
+            Ok(count) => {
+                tracing::debug!("Watching {} directories of '{}'", count, app);
+                apps.push((app.clone(), path));
+            }
+            Err(e) => tracing::warn!("Not watching '{}': {}", path.display(), watch_error(&e)),
+        }
+    }
+    if apps.is_empty() {
+        watch_dirs(&mut watcher, &app_path).map_err(|e| anyhow::anyhow!(watch_error(&e)))?;
+        apps.push((config.app_relative_path.clone(), app_path));
+    }
+    let watched = apps
+        .iter()
+        .map(|(_, p)| p.display().to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    std::thread::Builder::new()
+        .name("frappe-mcp-watcher".to_string())
+        .spawn(move || {
+            // Keep the watcher alive for as long as the thread runs
+            let mut watcher = watcher;
+
+            while let Ok(first) = rx.recv() {
+                let mut paths = BTreeSet::new();
+                let mut created = BTreeSet::new();
+                collect_paths(first, &mut paths, &mut created);
+                while let Ok(event) = rx.recv_timeout(DEBOUNCE) {
+                    collect_paths(event, &mut paths, &mut created);
+                }
+                watch_new_dirs(&mut watcher, &created, &mut paths);
+
+                for (app, root) in &apps {
+                    let app_paths: BTreeSet<PathBuf> = paths
+                        .iter()
+                        .filter(|p| p.starts_with(root))
+                        .cloned()
+                        .collect();
+                    let changes = classify(&app_paths);
+                    if changes.is_empty() {
+                        continue;
+                    }
+                    tracing::debug!("File changes detected in '{}': {:?}", app, changes);
+                    match apply(&config, app, &anal, &changes) {
+                        Ok(stale) => notifier.publish(&stale),
+                        Err(e) => {
+                            tracing::warn!("Failed to refresh analyzed data for '{}': {}", app, e)
+                        }
+                    }
+                }
+            }
+            tracing::debug!("File watcher stopped");
+        })?;
+
+    tracing::info!("Watching {} for changes", watched);
+    Ok(())
+}
+
+/// Watch `root` and every directory below it one by one, skipping
+/// `IGNORED_DIRS`: a recursive watch would cover `node_modules` too and can use
+/// up the inotify watch limit. Returns how many directories are watched.
+fn watch_dirs<W: Watcher>(watcher: &mut W, root: &Path) -> Result<usize> {
+    let mut paths = watcher.paths_mut();
+    let mut count = 0;
+    for entry in WalkDir::new(root)
+        .into_iter()
+        .filter_entry(|e| e.file_type().is_dir() && !is_ignored(e.file_name()))
+        .filter_map(|e| e.ok())
+    {
+        paths.add(entry.path(), RecursiveMode::NonRecursive)?;
+        count += 1;
+    }
+    paths.commit()?;
+    Ok(count)
+}
+
+/// Watch the directories among the `created` paths, and add the files
+/// already in them, whose events came before the watch, to `paths`.
+fn watch_new_dirs<W: Watcher>(
+    watcher: &mut W,
+    created: &BTreeSet<PathBuf>,
+    paths: &mut BTreeSet<PathBuf>,
+) {
+    for dir in created {
+        if !dir.is_dir() || dir.iter().any(is_ignored) {
+            continue;
+        }
+        if let Err(e) = watch_dirs(watcher, dir) {
+            tracing::warn!("Not watching '{}': {}", dir.display(), watch_error(&e));
+            continue;
+        }
+        paths.extend(
+            WalkDir::new(dir)
+                .into_iter()
+                .filter_entry(|e| !is_ignored(e.file_name()))
+                .filter_map(|e| e.ok())
+                .filter(|e| e.file_type().is_file())
+                .map(|e| e.into_path()),
+        );
+    }
+}
+
+/// `e`, with a hint when it is the OS watch limit.
+fn watch_error(e: &anyhow::Error) -> String {
+    let limit = e
+        .downcast_ref::<notify::Error>()
+        .is_some_and(|e| matches!(e.kind, notify::ErrorKind::MaxFilesWatch));
+    if limit {
+        format!("{} (raise fs.inotify.max_user_watches)", e)
+    } else {
+        e.to_string()
+    }
+}
+
+fn is_ignored(name: &OsStr) -> bool {
+    IGNORED_DIRS.iter().any(|d| name == *d)
+}
+
+/// Add the paths of `event` to `paths`, and to `created` when they appeared
+/// (created or moved in).
+fn collect_paths(
+    event: notify::Result<Event>,
+    paths: &mut BTreeSet<PathBuf>,
+    created: &mut BTreeSet<PathBuf>,
+) {
+    match event {
+        Ok(event) => {
+            if matches!(event.kind, EventKind::Access(_)) {
+                return;
+            }
+            if matches!(
+                event.kind,
+                EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(_))
+            ) {
+                created.extend(event.paths.iter().cloned());
+            }
+            paths.extend(event.paths);
+        }
+        Err(e) => tracing::warn!("File watcher error: {}", e),
+    }
+}
+
+/// Sort raw changed paths into the parts of the analysis they affect.
+pub fn classify(paths: &BTreeSet<PathBuf>) -> ChangeSet {
+    let mut changes = ChangeSet::default();
+
+    for path in paths {
+        let parts: Vec<_> = path.iter().collect();
+        if parts.iter().any(|p| is_ignored(p)) {
+            continue;
+        }
+
+        if path.file_name().is_some_and(|f| f == "modules.txt") {
+            changes.modules = true;
+            continue;
+        }
+
+        // Nearest `doctype/<dt>` ancestor (or the directory itself)
+        if let Some(i) = parts.iter().rposition(|p| *p == "doctype") {
+            if i + 1 < parts.len() {
+                let dir: PathBuf = parts[..=i + 1].iter().collect();
+                let dt_dir_name = parts[i + 1].to_string_lossy();
+                // The controller's type hints depend on the name inside the JSON
+                for ext in ["py", "js"] {
+                    changes
+                        .source_files
+                        .insert(dir.join(format!("{}.{}", dt_dir_name, ext)));
+                }
+                changes.doctype_dirs.insert(dir);
+            }
+        }
+
+        if path.extension().and_then(|e| e.to_str()) == Some("py")
+            || refs_finder::is_js_source(path)
+        {
+            changes.source_files.insert(path.clone());
+        }
+    }
+
+    changes
+}
+
+/// Re-scan what changed in `app` (without holding the lock) and patch `anal` in place.
+///
+/// Returns the resources that changed as a result.
+fn apply(
+    config: &Config,
+    app: &str,
+    anal: &Arc<Mutex<AnalyzedData>>,
+    changes: &ChangeSet,
+) -> Result<Vec<ResourceChange>> {
+    let app_path = config.app_path(app);
+    let app_root = Path::new(&app_path);
+    let root_sub_path = analyze::app_package_path(app_root);
+    let relative_path = &app.to_string();
+
+    // Hashes of the files about to be re-scanned, taken before reading them so a
+    // later edit still looks changed to the next analysis
+    let json_files: Vec<PathBuf> = changes
+        .doctype_dirs
+        .iter()
+        .filter_map(|dir| {
+            dir.file_name()
+                .map(|name| dir.join(name).with_extension("json"))
+        })
+        .collect();
+    let records: Vec<(String, Option<FileRecord>)> = changes
+        .source_files
+        .iter()
+        .chain(&json_files)
+        .map(|file| (file.to_string_lossy().to_string(), cache::file_record(file)))
+        .collect();
+
+    // Table fields of every DocType; a cache without one (e.g. migrated) gets it rebuilt
+    let mut index = anal.lock().unwrap().doctype_index.clone();
+    if index.files().next().is_none() {
+        let app_paths: Vec<String> = config.app_dirs.iter().map(|a| config.app_path(a)).collect();
+        index = DocTypeIndex::scan(&app_paths);
+    }
+    for file in &json_files {
+        index.add_file(file);
+    }
+
+    let rescanned_app = if changes.modules {
+        Some(analyze::scan_app(app_root, relative_path, &index)?)
+    } else {
+        None
+    };
+
+    // Relative path prefixes of the touched DocType directories
+    let prefixes: Vec<String> = changes
+        .doctype_dirs
+        .iter()
+        .map(|dir| {
+            format!(
+                "{}/",
+                analyze::to_relative_path(
+                    &dir.to_string_lossy(),
+                    &root_sub_path.to_string_lossy(),
+                    relative_path,
+                )
+            )
+        })
+        .collect();
+
+    // (relative dir prefix, fresh DocType if the directory still holds one)
+    let mut doctype_updates: Vec<(String, Option<DocType>)> = Vec::new();
+    if rescanned_app.is_none() && !changes.doctype_dirs.is_empty() {
+        let modules = anal.lock().unwrap().modules.clone();
+        for (dir, prefix) in changes.doctype_dirs.iter().zip(&prefixes) {
+            let prefix = prefix.clone();
+            let module_dir = dir
+                .parent()
+                .and_then(|p| p.parent())
+                .and_then(|p| p.file_name())
+                .map(|f| f.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let module = modules.iter().find(|m| {
+                (m.app.is_empty() || m.app == *relative_path)
+                    && to_snakec_var(&m.name) == module_dir
+            });
+
+            let doctype = match module {
+                Some(module) if dir.is_dir() => analyze::scan_doctype_dir(
+                    dir,
+                    &module.name,
+                    &root_sub_path,
+                    relative_path,
+                    &index,
+                )?,
+                _ => None,
+            };
+            doctype_updates.push((prefix, doctype));
+        }
+    }
+
+    let ref_updates = changes
+        .source_files
+        .par_iter()
+        .map(|file| {
+            let refs = refs_finder::analyze_file_field_usage(file, &index)?;
+            Ok((refs_finder::occurrence_path(file), refs))
+        })
+        .collect::<Result<Vec<(String, RefsFinderOutput)>>>()?;
+
+    let mut anal = anal.lock().unwrap();
+    anal.doctype_index = index;
+    let before = anal.doctypes.clone();
+
+    if let Some((modules, doctypes)) = rescanned_app {
+        let is_other_app = |other: &str| !other.is_empty() && other != relative_path;
+        anal.modules.retain(|m| is_other_app(&m.app));
+        anal.modules.extend(modules);
+        anal.doctypes.retain(|dt| is_other_app(&dt.app));
+        anal.doctypes.extend(doctypes);
+    }
+
+    for (prefix, doctype) in doctype_updates {
+        anal.doctypes.retain(|dt| {
+            !dt.backend_file.starts_with(&prefix)
+                && !dt
+                    .meta_file
+                    .as_ref()
+                    .is_some_and(|f| f.starts_with(&prefix))
+        });
+        if let Some(doctype) = doctype {
+            tracing::info!("Refreshed DocType '{}' ({})", doctype.name, app);
+            anal.doctypes.push(doctype);
+        }
+    }
+
+    for (file, record) in records {
+        match record {
+            Some(record) => anal.files.insert(file, record),
+            None => anal.files.remove(&file),
+        };
+    }
+
+    if !ref_updates.is_empty() {
+        let symbol_refs = anal.symbol_refs.get_or_insert_with(RefsFinderOutput::new);
+        for (file, refs) in ref_updates {
+            symbol_refs.remove_file(&file);
+            symbol_refs.merge(refs);
+        }
+        symbol_refs.sort();
+        symbol_refs.recount();
+    }
+
+    tracing::info!(
+        "Analyzed data refreshed: {} modules, {} doctypes",
+        anal.modules.len(),
+        anal.doctypes.len()
+    );
+
+    let mut stale = resource_changes(&before, &anal.doctypes, app, &prefixes);
+    for file in changes.source_files.iter().chain(&json_files) {
+        stale.extend(resources::file_uri(app, app_root, file).map(ResourceChange::Updated));
+    }
+
+    // Keep the cache in sync so the next start doesn't redo the work
+    let snapshot = anal.clone();
+    drop(anal);
+    AnalysisCache::for_config(config).save(&snapshot)?;
+
+    Ok(stale)
+}
+
+/// DocType and module resources of `app` affected by a refresh: the ones
+/// whose directory (one of `prefixes`) was touched, plus `list_changed` when
+/// DocTypes were added or removed.
+fn resource_changes(
+    before: &[DocType],
+    after: &[DocType],
+    app: &str,
+    prefixes: &[String],
+) -> Vec<ResourceChange> {
+    let mut stale = BTreeSet::new();
+
+    let names = |doctypes: &[DocType]| -> BTreeSet<(String, String)> {
+        doctypes
+            .iter()
+            .map(|dt| (dt.app.clone(), dt.name.clone()))
+            .collect()
+    };
+    if names(before) != names(after) {
+        stale.insert(ResourceChange::ListChanged);
+    }
+
+    for dt in before.iter().chain(after) {
+        let in_app = dt.app.is_empty() || dt.app == app;
+        let touched = prefixes.iter().any(|prefix| {
+            dt.backend_file.starts_with(prefix)
+                || dt.meta_file.as_ref().is_some_and(|f| f.starts_with(prefix))
+        });
+        if in_app && touched {
+            stale.insert(ResourceChange::Updated(
+                FrappeUri::Doctype(dt.name.clone()).to_uri(),
+            ));
+            stale.insert(ResourceChange::Updated(
+                FrappeUri::Module(dt.module.clone()).to_uri(),
+            ));
+        }
+    }
+
+    stale.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(items: &[&str]) -> BTreeSet<PathBuf> {
+        items.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn test_classify_doctype_json() {
+        let changes = classify(&paths(&[
+            "/bench/apps/koperasi/koperasi/simpanan/doctype/anggota/anggota.json",
+        ]));
+        assert!(!changes.modules);
+        assert_eq!(
+            changes.doctype_dirs,
+            paths(&["/bench/apps/koperasi/koperasi/simpanan/doctype/anggota"])
+        );
+        assert_eq!(
+            changes.source_files,
+            paths(&[
+                "/bench/apps/koperasi/koperasi/simpanan/doctype/anggota/anggota.js",
+                "/bench/apps/koperasi/koperasi/simpanan/doctype/anggota/anggota.py",
+            ])
+        );
+    }
+
+    #[test]
+    fn test_classify_modules_and_plain_sources() {
+        let changes = classify(&paths(&[
+            "/bench/apps/koperasi/koperasi/modules.txt",
+            "/bench/apps/koperasi/koperasi/api.py",
+            "/bench/apps/koperasi/koperasi/public/js/anggota.js",
+            "/bench/apps/koperasi/koperasi/public/dist/app.bundle.js",
+        ]));
+        assert!(changes.modules);
+        assert!(changes.doctype_dirs.is_empty());
+        assert_eq!(
+            changes.source_files,
+            paths(&[
+                "/bench/apps/koperasi/koperasi/api.py",
+                "/bench/apps/koperasi/koperasi/public/js/anggota.js",
+            ])
+        );
+    }
+
+    fn doctype(name: &str, dir: &str) -> DocType {
+        DocType {
+            name: name.to_string(),
+            backend_file: format!("koperasi/simpanan/doctype/{0}/{0}.py", dir),
+            frontend_file: None,
+            meta_file: Some(format!("koperasi/simpanan/doctype/{0}/{0}.json", dir)),
+            test_file: None,
+            module: "Simpanan".to_string(),
+            app: "koperasi".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_resource_changes() {
+        let anggota = doctype("Anggota", "anggota");
+        let item = doctype("Simpanan Item", "simpanan_item");
+        let prefix = vec!["koperasi/simpanan/doctype/anggota/".to_string()];
+
+        // Controller or JSON edited: only its resources are stale
+        let stale = resource_changes(
+            &[anggota.clone(), item.clone()],
+            &[anggota.clone(), item.clone()],
+            "koperasi",
+            &prefix,
+        );
+        assert_eq!(
+            stale,
+            vec![
+                ResourceChange::Updated("frappe://doctype/Anggota".to_string()),
+                ResourceChange::Updated("frappe://module/Simpanan".to_string()),
+            ]
+        );
+
+        // DocType removed
+        let stale = resource_changes(&[anggota, item.clone()], &[item], "koperasi", &prefix);
+        assert!(stale.contains(&ResourceChange::ListChanged));
+        assert!(stale.contains(&ResourceChange::Updated(
+            "frappe://doctype/Anggota".to_string()
+        )));
+    }
+
+    #[test]
+    fn test_watch_dirs_skips_ignored() {
+        let root = std::env::temp_dir().join(format!("frappe-mcp-watch-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        for dir in [
+            "koperasi/simpanan/doctype/anggota",
+            "node_modules/x/y",
+            ".git/objects",
+        ] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        let (tx, _rx) = mpsc::channel::<notify::Result<Event>>();
+        let mut watcher = notify::recommended_watcher(tx).unwrap();
+        // root, koperasi, simpanan, doctype, anggota
+        assert_eq!(watch_dirs(&mut watcher, &root).unwrap(), 5);
+
+        std::fs::create_dir_all(root.join("koperasi/api")).unwrap();
+        std::fs::write(root.join("koperasi/api/v1.py"), "").unwrap();
+        let created = BTreeSet::from([root.join("koperasi/api")]);
+        let mut changed = created.clone();
+        watch_new_dirs(&mut watcher, &created, &mut changed);
+        assert!(changed.contains(&root.join("koperasi/api/v1.py")));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_classify_ignores_noise() {
+        let changes = classify(&paths(&[
+            "/bench/apps/koperasi/koperasi/__pycache__/api.cpython-311.pyc",
+            "/bench/apps/koperasi/.git/index",
+            "/bench/apps/koperasi/README.md",
+        ]));
+        assert!(changes.is_empty());
+    }
+}